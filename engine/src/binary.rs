@@ -0,0 +1,267 @@
+//! Binary files in patches: telling them apart from text the way git does,
+//! and writing and applying git's `GIT binary patch` format (`git diff
+//! --binary`).
+
+#[cfg(feature = "repository")]
+use gix::bstr::BStr;
+#[cfg(feature = "repository")]
+use gix::objs::FindExt;
+use gix_object::bstr::ByteSlice;
+
+use std::io::Read;
+#[cfg(feature = "repository")]
+use std::io::Write;
+
+/// The digits of git's base85, from 0 to 84.
+const ALPHABET: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// Whether `data` looks binary to git, which is if there is a NUL in its
+/// first few bytes.
+pub fn looks_binary(data: &[u8]) -> bool {
+    data[..data.len().min(8000)].contains(&0)
+}
+
+/// Whether git diffs the change from `older_id` to `newer_id` as binary.
+///
+/// That's the case if either side is binary by its `diff` attribute (`-diff`,
+/// `binary`, or a driver with `binary = true`) or by a NUL in its first 8000
+/// bytes, unless the `diff` attribute is set, which forces a text diff. The
+/// blobs are left set in `resource_cache`.
+#[cfg(feature = "repository")]
+pub fn is_binary(
+    resource_cache: &mut gix::diff::blob::Platform,
+    objects: &gix::OdbHandle,
+    older_id: &gix::oid,
+    older_location: &BStr,
+    newer_id: &gix::oid,
+    newer_location: &BStr,
+) -> bool {
+    for (id, location, kind) in [
+        (
+            older_id,
+            older_location,
+            gix::diff::blob::ResourceKind::OldOrSource,
+        ),
+        (
+            newer_id,
+            newer_location,
+            gix::diff::blob::ResourceKind::NewOrDestination,
+        ),
+    ] {
+        resource_cache
+            .set_resource(
+                id.into(),
+                gix::object::tree::EntryKind::Blob,
+                location,
+                kind,
+                objects,
+            )
+            .unwrap();
+    }
+    let outcome = resource_cache.prepare_diff().unwrap();
+    let binary = [outcome.old.data, outcome.new.data].iter().any(|data| {
+        matches!(
+            data,
+            gix::diff::blob::platform::resource::Data::Binary { .. }
+        )
+    });
+    binary
+        && ![older_location, newer_location]
+            .into_iter()
+            .any(|location| is_text(resource_cache, objects, location))
+}
+
+/// Whether the `diff` attribute is set for `location` (without naming a
+/// driver), which makes git diff it as text whatever its content.
+#[cfg(feature = "repository")]
+fn is_text(
+    resource_cache: &mut gix::diff::blob::Platform,
+    objects: &gix::OdbHandle,
+    location: &BStr,
+) -> bool {
+    let stack = &mut resource_cache.attr_stack;
+    let mut outcome = stack.selected_attribute_matches(["diff"]);
+    stack
+        .at_entry(location, None, objects)
+        .is_ok_and(|entry| entry.matching_attributes(&mut outcome))
+        && outcome
+            .iter_selected()
+            .next()
+            .is_some_and(|diff| matches!(diff.assignment.state, gix::attrs::StateRef::Set))
+}
+
+/// The content of the blob `id` as stored, empty for the null ID of a side
+/// that doesn't exist.
+#[cfg(feature = "repository")]
+pub fn read<'a>(objects: &gix::OdbHandle, id: &gix::oid, buffer: &'a mut Vec<u8>) -> &'a [u8] {
+    if id.is_null() {
+        return &[];
+    }
+    objects.find_blob(id, buffer).unwrap().data
+}
+
+/// Append git's `GIT binary patch` for a change from `older_id` to
+/// `newer_id`: the new content followed by the old one to reverse it, both
+/// deflated and in base85.
+///
+/// git sends a delta against the other side where that's smaller, here the
+/// content is always sent whole (a `literal`), which `git apply` takes all
+/// the same.
+#[cfg(feature = "repository")]
+pub fn write_patch(
+    out: &mut Vec<u8>,
+    objects: &gix::OdbHandle,
+    older_id: &gix::oid,
+    newer_id: &gix::oid,
+) {
+    let (mut older_buffer, mut newer_buffer) = (Vec::new(), Vec::new());
+    out.extend_from_slice(b"GIT binary patch\n");
+    write_literal(out, read(objects, newer_id, &mut newer_buffer));
+    write_literal(out, read(objects, older_id, &mut older_buffer));
+}
+
+#[cfg(feature = "repository")]
+fn write_literal(out: &mut Vec<u8>, data: &[u8]) {
+    // git's default `core.compression`.
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(data).unwrap();
+    let deflated = encoder.finish().unwrap();
+
+    writeln!(out, "literal {}", data.len()).unwrap();
+    // Every line starts with the number of bytes it encodes, `A`-`Z` for 1
+    // to 26 and `a`-`z` for 27 to 52.
+    for chunk in deflated.chunks(52) {
+        let len = chunk.len() as u8;
+        out.push(if len <= 26 {
+            b'A' + len - 1
+        } else {
+            b'a' + len - 27
+        });
+        encode_85(out, chunk);
+        out.push(b'\n');
+    }
+    out.push(b'\n');
+}
+
+/// Append `data` in git's base85, five characters for every (zero-padded)
+/// four bytes.
+#[cfg(feature = "repository")]
+fn encode_85(out: &mut Vec<u8>, data: &[u8]) {
+    for group in data.chunks(4) {
+        let mut bytes = [0; 4];
+        bytes[..group.len()].copy_from_slice(group);
+        let mut value = u32::from_be_bytes(bytes);
+        let mut encoded = [0; 5];
+        for digit in encoded.iter_mut().rev() {
+            *digit = ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        out.extend_from_slice(&encoded);
+    }
+}
+
+/// Decode git's base85, `None` if `encoded` isn't valid.
+fn decode_85(encoded: &[u8]) -> Option<Vec<u8>> {
+    if !encoded.len().is_multiple_of(5) {
+        return None;
+    }
+    let mut data = Vec::with_capacity(encoded.len() / 5 * 4);
+    for group in encoded.chunks(5) {
+        let mut value = 0u32;
+        for character in group {
+            let digit = ALPHABET.iter().position(|digit| digit == character)?;
+            value = value.checked_mul(85)?.checked_add(digit as u32)?;
+        }
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+    Some(data)
+}
+
+/// The new content of the forward side of the `GIT binary patch` in `patch`
+/// applied to `old`, `None` if it has none or it doesn't apply.
+pub fn apply_patch(patch: &[u8], old: &[u8]) -> Option<Vec<u8>> {
+    let start = patch.find(b"GIT binary patch\n")?;
+    let mut lines = patch[start..].lines().skip(1);
+    let (method, size) = lines.next()?.to_str().ok()?.split_once(' ')?;
+    let size: usize = size.parse().ok()?;
+
+    let mut deflated = Vec::new();
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let (&len, encoded) = line.split_first()?;
+        let len = match len {
+            b'A'..=b'Z' => len - b'A' + 1,
+            b'a'..=b'z' => len - b'a' + 27,
+            _ => return None,
+        };
+        deflated.extend_from_slice(decode_85(encoded)?.get(..usize::from(len))?);
+    }
+    let mut data = Vec::new();
+    flate2::read::ZlibDecoder::new(deflated.as_slice())
+        .read_to_end(&mut data)
+        .ok()?;
+    if data.len() != size {
+        return None;
+    }
+    match method {
+        "literal" => Some(data),
+        "delta" => apply_delta(old, &data),
+        _ => None,
+    }
+}
+
+/// Apply a delta in git's pack format to `base`, `None` if it doesn't fit.
+///
+/// The delta starts with the sizes of the base and the result, followed by
+/// instructions to either copy a range of the base or insert new data.
+fn apply_delta(base: &[u8], mut delta: &[u8]) -> Option<Vec<u8>> {
+    fn size(delta: &mut &[u8]) -> Option<usize> {
+        let mut size = 0;
+        for shift in (0..usize::BITS).step_by(7) {
+            let (&byte, rest) = delta.split_first()?;
+            *delta = rest;
+            size |= usize::from(byte & 0x7f).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(size);
+            }
+        }
+        None
+    }
+
+    if size(&mut delta)? != base.len() {
+        return None;
+    }
+    let size = size(&mut delta)?;
+    let mut data = Vec::with_capacity(size);
+    while let Some((&instruction, rest)) = delta.split_first() {
+        delta = rest;
+        if instruction & 0x80 != 0 {
+            // The low four bits say which bytes of the offset follow, the
+            // next three those of the length.
+            let (mut offset, mut len) = (0, 0);
+            for bit in 0..7 {
+                if instruction & (1 << bit) == 0 {
+                    continue;
+                }
+                let (&byte, rest) = delta.split_first()?;
+                delta = rest;
+                if bit < 4 {
+                    offset |= usize::from(byte) << (8 * bit);
+                } else {
+                    len |= usize::from(byte) << (8 * (bit - 4));
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            data.extend_from_slice(base.get(offset..offset.checked_add(len)?)?);
+        } else if instruction != 0 {
+            let (inserted, rest) = delta.split_at_checked(usize::from(instruction))?;
+            data.extend_from_slice(inserted);
+            delta = rest;
+        } else {
+            return None;
+        }
+    }
+    (data.len() == size).then_some(data)
+}
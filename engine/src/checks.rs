@@ -0,0 +1,516 @@
+//! Commit checks implemented natively and run in parallel, without the GIL
+//! when run from Python.
+//!
+//! Checks compiled in implement [`Check`] and are added with
+//! [`register_check`], checks in shared libraries implement the C interface
+//! of `include/gitch_check.h` and are registered with
+//! [`register_native_check`]. [`explain_checks`] also explains the checks of
+//! gitch's configuration file, which `gitch check` runs.
+
+use gix::bstr::BString;
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::sync::{Arc, RwLock};
+
+use crate::{Error, diff, message, paths, unified_diff};
+
+/// The version of `gitch_check_commit` this module passes to native checks.
+const ABI_VERSION: u32 = 1;
+
+/// A commit as seen by a check.
+pub struct CheckedCommit<'a> {
+    commit: gix::Commit<'a>,
+    message: OnceCell<message::Message>,
+    patch: OnceCell<Vec<u8>>,
+    changed_paths: OnceCell<Vec<BString>>,
+}
+
+impl<'a> CheckedCommit<'a> {
+    pub fn new(commit: gix::Commit<'a>) -> Self {
+        Self {
+            commit,
+            message: OnceCell::new(),
+            patch: OnceCell::new(),
+            changed_paths: OnceCell::new(),
+        }
+    }
+
+    pub fn id(&self) -> gix::ObjectId {
+        self.commit.id
+    }
+
+    /// The message as stored in the commit object.
+    pub fn raw_message(&self) -> &[u8] {
+        gix::objs::CommitRef::from_bytes(&self.commit.data)
+            .map(|commit| commit.message.as_ref())
+            .unwrap_or_default()
+    }
+
+    /// The decoded message split into its parts, only parsed on first use.
+    pub fn message(&self) -> &message::Message {
+        self.message
+            .get_or_init(|| message::parse(&message::decode(&self.commit.data)))
+    }
+
+    /// The patch against the first parent, only computed on first use.
+    pub fn patch(&self) -> &[u8] {
+        self.patch.get_or_init(|| {
+            diff::diff_with_parent(
+                &self.commit,
+                gix::diff::blob::Algorithm::Myers,
+                &unified_diff::PatchFormat::default(),
+            )
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+        })
+    }
+}
+
+impl CheckedCommit<'_> {
+    /// The paths changed against the first parent, only computed on first
+    /// use.
+    pub fn changed_paths(&self) -> &[BString] {
+        self.changed_paths.get_or_init(|| {
+            diff::changes_with_parent(&self.commit)
+                .iter()
+                .map(|change| change.location().to_owned())
+                .collect()
+        })
+    }
+}
+
+/// A check run on every commit, concurrently from several threads.
+pub trait Check: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The settings the check runs with as `(name, value)`, for explaining
+    /// what it does.
+    fn settings(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Why the check doesn't apply to `commit` (e.g. because it only looks
+    /// at some paths), `None` if it does.
+    fn skip_reason(&self, _commit: &CheckedCommit<'_>) -> Option<String> {
+        None
+    }
+
+    /// The violations of `commit`, empty if there are none.
+    fn check(&self, commit: &CheckedCommit<'_>) -> Vec<String>;
+}
+
+/// Whether and how a check would run on a commit.
+pub struct CheckExplanation {
+    pub name: String,
+    pub runs: bool,
+    /// Why the check wouldn't run, `None` if it would.
+    pub reason: Option<String>,
+    pub settings: Vec<(String, String)>,
+}
+
+static CHECKS: RwLock<Vec<Arc<dyn Check>>> = RwLock::new(Vec::new());
+
+/// Checks and whether each was selected.
+type Selection = Vec<(Arc<dyn Check>, bool)>;
+
+/// Add a check, replacing one that was registered under the same name.
+pub fn register_check(check: Arc<dyn Check>) {
+    let mut checks = CHECKS.write().unwrap();
+    checks.retain(|registered| registered.name() != check.name());
+    checks.push(check);
+}
+
+/// `gitch_check_commit` of `include/gitch_check.h`.
+#[repr(C)]
+pub struct NativeCommit {
+    pub abi_version: u32,
+    pub id: *const c_char,
+    pub summary: *const c_char,
+    pub message: *const c_char,
+    pub patch: *const u8,
+    pub patch_len: usize,
+}
+
+/// `gitch_check_report` of `include/gitch_check.h`.
+pub type Report = unsafe extern "C" fn(context: *mut c_void, error: *const c_char);
+/// `gitch_check_fn` of `include/gitch_check.h`.
+pub type NativeFn =
+    unsafe extern "C" fn(commit: *const NativeCommit, report: Report, context: *mut c_void);
+
+/// A check from a shared library, see `include/gitch_check.h`.
+struct NativeCheck {
+    name: String,
+    function: NativeFn,
+    needs_patch: bool,
+    /// Patterns of `paths::matches` the check is restricted to, all paths if
+    /// empty.
+    paths: Vec<String>,
+}
+
+unsafe extern "C" fn report(context: *mut c_void, error: *const c_char) {
+    if error.is_null() {
+        return;
+    }
+    // SAFETY: `context` is the `Vec` passed by `NativeCheck::check` and
+    // `error` a NUL-terminated string per the interface.
+    let errors = unsafe { &mut *context.cast::<Vec<String>>() };
+    errors.push(
+        unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned(),
+    );
+}
+
+/// Interior NULs can't be passed on, they are dropped.
+fn c_string(value: &[u8]) -> CString {
+    CString::new(
+        value
+            .iter()
+            .copied()
+            .filter(|&byte| byte != 0)
+            .collect::<Vec<_>>(),
+    )
+    .unwrap()
+}
+
+impl Check for NativeCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn settings(&self) -> Vec<(String, String)> {
+        let mut settings = vec![
+            ("kind".into(), "native".into()),
+            ("address".into(), format!("{:#x}", self.function as usize)),
+            ("needs_patch".into(), self.needs_patch.to_string()),
+        ];
+        if !self.paths.is_empty() {
+            settings.push(("paths".into(), self.paths.join(", ")));
+        }
+        settings
+    }
+
+    fn skip_reason(&self, commit: &CheckedCommit<'_>) -> Option<String> {
+        if self.paths.is_empty() {
+            return None;
+        }
+        let changed = commit
+            .changed_paths()
+            .iter()
+            .any(|path| paths::matches(&self.paths, path.as_ref()));
+        (!changed).then(|| {
+            format!(
+                "path-filtered: changes nothing in {}",
+                self.paths.join(", ")
+            )
+        })
+    }
+
+    fn check(&self, commit: &CheckedCommit<'_>) -> Vec<String> {
+        let id = c_string(commit.id().to_string().as_bytes());
+        let summary = c_string(commit.message().summary.as_bytes());
+        let message = c_string(commit.raw_message());
+        let patch = self.needs_patch.then(|| commit.patch());
+        let native = NativeCommit {
+            abi_version: ABI_VERSION,
+            id: id.as_ptr(),
+            summary: summary.as_ptr(),
+            message: message.as_ptr(),
+            patch: patch.map_or(std::ptr::null(), <[u8]>::as_ptr),
+            patch_len: patch.map_or(0, <[u8]>::len),
+        };
+        let mut errors = Vec::<String>::new();
+        // SAFETY: the function was registered as a `gitch_check_fn` and all
+        // pointers stay valid for the duration of the call.
+        unsafe {
+            (self.function)(&native, report, (&mut errors as *mut Vec<String>).cast());
+        }
+        errors
+    }
+}
+
+/// Register the native check `function` as the check `name`, replacing one
+/// of the same name. With `needs_patch` it gets each commit's patch as well.
+/// With `paths` (patterns as `paths::matches` takes them) it only runs on
+/// commits changing a path they match.
+///
+/// # Safety
+///
+/// `function` has to stay loaded and be safe to call from several threads
+/// at once for the lifetime of the process.
+pub unsafe fn register_native_check(
+    name: String,
+    function: NativeFn,
+    needs_patch: bool,
+    paths: Vec<String>,
+) {
+    register_check(Arc::new(NativeCheck {
+        name,
+        function,
+        needs_patch,
+        paths,
+    }));
+}
+
+/// The names of all registered checks in registration order.
+pub fn registered_checks() -> Vec<String> {
+    CHECKS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|check| check.name().to_string())
+        .collect()
+}
+
+/// The registered checks and whether each was selected by `names` (all are
+/// without), failing for names that aren't registered.
+fn select(names: Option<&[String]>) -> Result<Selection, Error> {
+    let checks = CHECKS.read().unwrap().clone();
+    if let Some(names) = names
+        && let Some(unknown) = names
+            .iter()
+            .find(|name| !checks.iter().any(|check| check.name() == name.as_str()))
+    {
+        return Err(Error::Value(format!(
+            "no check named {unknown:?} is registered"
+        )));
+    }
+    Ok(checks
+        .into_iter()
+        .map(|check| {
+            let selected = names.is_none_or(|names| names.iter().any(|name| name == check.name()));
+            (check, selected)
+        })
+        .collect())
+}
+
+/// The checks of gitch's configuration file (`.check-commits.yaml`), which
+/// `gitch check` runs on the commits reachable from `HEAD`.
+#[derive(Default)]
+pub struct Configuration {
+    /// `starting-from`: commits before it (in the order of `Repository.commits`)
+    /// aren't checked.
+    pub starting_from: Option<gix::ObjectId>,
+    /// `first-commit-is-empty`.
+    pub first_commit_is_empty: bool,
+    /// `summary.first-word-is-simple-verb`.
+    pub first_word_is_simple_verb: bool,
+    /// `summary.first-word-capitalization`, `lower` or `upper`.
+    pub first_word_capitalization: Option<String>,
+    /// `trailers` in their order.
+    pub trailers: Vec<(String, message::TrailerRule)>,
+}
+
+/// Whether the commit `id` is one of those `gitch check` checks, and its
+/// first commit: the commits reachable from `HEAD` newest first, up to and
+/// including `starting_from`.
+fn checked_by_configuration(
+    repository: &gix::Repository,
+    id: gix::ObjectId,
+    starting_from: Option<gix::ObjectId>,
+) -> Result<(Option<String>, gix::ObjectId), Error> {
+    let head = repository.head_id().map_err(Error::repository)?;
+    let mut reason = Some("suppressed: not reachable from HEAD".to_string());
+    let mut first = head.detach();
+    let mut cut = false;
+    for info in head
+        .ancestors()
+        .sorting(gix::revision::walk::Sorting::ByCommitTime(
+            gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+        ))
+        .all()
+        .map_err(Error::repository)?
+    {
+        let info = info.map_err(Error::repository)?;
+        if info.id == id {
+            reason = if cut {
+                starting_from.map(|cutoff| format!("suppressed: before starting-from {cutoff}"))
+            } else {
+                None
+            };
+        }
+        cut |= Some(info.id) == starting_from;
+        first = info.id;
+    }
+    Ok((reason, first))
+}
+
+/// Explain the checks of `configuration` for the commit `id` under the
+/// names of their settings.
+fn explain_configuration(
+    repository: &gix::Repository,
+    id: gix::ObjectId,
+    configuration: &Configuration,
+) -> Result<Vec<CheckExplanation>, Error> {
+    let (suppressed, first) =
+        checked_by_configuration(repository, id, configuration.starting_from)?;
+    let starting_from = configuration
+        .starting_from
+        .map(|cutoff| ("starting-from".to_string(), cutoff.to_string()));
+    let explain = |name: String, on: bool, settings: Vec<(String, String)>| {
+        let reason = if on {
+            suppressed.clone()
+        } else {
+            Some("off in the configuration".into())
+        };
+        CheckExplanation {
+            name,
+            runs: reason.is_none(),
+            reason,
+            settings: settings.into_iter().chain(starting_from.clone()).collect(),
+        }
+    };
+
+    let mut explanations = Vec::new();
+    // Like `gitch check`, the first commit is checked regardless of
+    // `starting-from`.
+    let reason = if !configuration.first_commit_is_empty {
+        Some("off in the configuration".to_string())
+    } else if id != first {
+        Some("only checks the first commit".to_string())
+    } else {
+        None
+    };
+    explanations.push(CheckExplanation {
+        name: "first-commit-is-empty".into(),
+        runs: reason.is_none(),
+        reason,
+        settings: vec![(
+            "first-commit-is-empty".into(),
+            configuration.first_commit_is_empty.to_string(),
+        )],
+    });
+    explanations.push(explain(
+        "summary.first-word-is-simple-verb".into(),
+        configuration.first_word_is_simple_verb,
+        vec![(
+            "first-word-is-simple-verb".into(),
+            configuration.first_word_is_simple_verb.to_string(),
+        )],
+    ));
+    explanations.push(explain(
+        "summary.first-word-capitalization".into(),
+        configuration.first_word_capitalization.is_some(),
+        configuration
+            .first_word_capitalization
+            .iter()
+            .map(|capitalization| {
+                (
+                    "first-word-capitalization".to_string(),
+                    capitalization.clone(),
+                )
+            })
+            .collect(),
+    ));
+    for (token, rule) in &configuration.trailers {
+        explanations.push(explain(
+            format!("trailers.{token}"),
+            true,
+            vec![
+                ("mandatory".into(), rule.mandatory.to_string()),
+                ("singular".into(), rule.singular.to_string()),
+                (
+                    "values".into(),
+                    rule.values.iter().cloned().collect::<Vec<_>>().join(", "),
+                ),
+            ],
+        ));
+    }
+    Ok(explanations)
+}
+
+/// Explain for every registered check whether it would run on the commit
+/// `id` with the selection `names`, without running any. With
+/// `configuration` its checks follow, which `names` doesn't select.
+///
+/// The reasons for a check not to run start with what kind of reason they
+/// are: `not selected`, `suppressed` (e.g. for commits before
+/// `starting-from`), `path-filtered` (for checks of some paths only), `off
+/// in the configuration`, or `only checks the first commit`.
+pub fn explain_checks(
+    repository: &gix::Repository,
+    id: gix::ObjectId,
+    names: Option<&[String]>,
+    configuration: Option<&Configuration>,
+) -> Result<Vec<CheckExplanation>, Error> {
+    let checks = select(names)?;
+    let checked = CheckedCommit::new(repository.find_commit(id).map_err(Error::repository)?);
+    let mut explanations = checks
+        .into_iter()
+        .map(|(check, selected)| {
+            let reason = if selected {
+                check.skip_reason(&checked)
+            } else {
+                Some("not selected".into())
+            };
+            CheckExplanation {
+                name: check.name().to_string(),
+                runs: reason.is_none(),
+                reason,
+                settings: check.settings(),
+            }
+        })
+        .collect::<Vec<_>>();
+    if let Some(configuration) = configuration {
+        explanations.extend(explain_configuration(repository, id, configuration)?);
+    }
+    Ok(explanations)
+}
+
+/// Run the registered checks (or those named in `names`) on the commits
+/// `ids` spread over all available cores, returning the violations as
+/// `<check>: <error>` by commit ID for commits that have any.
+pub fn run_checks(
+    repository: &gix::ThreadSafeRepository,
+    ids: &[gix::ObjectId],
+    names: Option<&[String]>,
+) -> Result<HashMap<String, Vec<String>>, Error> {
+    let checks = select(names)?
+        .into_iter()
+        .filter_map(|(check, selected)| selected.then_some(check))
+        .collect::<Vec<_>>();
+
+    let workers = std::thread::available_parallelism()
+        .map(usize::from)
+        .unwrap_or(1);
+    let chunk_size = ids.len().div_ceil(workers).max(1);
+    std::thread::scope(|scope| {
+        let handles = ids
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let checks = &checks;
+                scope.spawn(move || {
+                    let repository = repository.to_thread_local();
+                    let mut results = Vec::new();
+                    for &id in chunk {
+                        let checked = CheckedCommit::new(
+                            repository.find_commit(id).map_err(Error::repository)?,
+                        );
+                        let errors = checks
+                            .iter()
+                            .filter(|check| check.skip_reason(&checked).is_none())
+                            .flat_map(|check| {
+                                check
+                                    .check(&checked)
+                                    .into_iter()
+                                    .map(|error| format!("{}: {error}", check.name()))
+                            })
+                            .collect::<Vec<_>>();
+                        if !errors.is_empty() {
+                            results.push((id.to_string(), errors));
+                        }
+                    }
+                    Ok(results)
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut results = HashMap::new();
+        for handle in handles {
+            results.extend(handle.join().unwrap()?);
+        }
+        Ok(results)
+    })
+}
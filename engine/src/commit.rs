@@ -0,0 +1,47 @@
+//! Commits read straight from their raw objects.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{message, time};
+
+/// The metadata of the commit `id` with the raw object `data` as JSON, how
+/// review bundles and the C ABI describe commits.
+///
+/// Like git, the message and identities are decoded from the encoding the
+/// commit declares. Trailers are by token, each with its distinct values
+/// sorted.
+pub fn metadata(id: &gix::oid, data: &[u8]) -> serde_json::Value {
+    let Ok(commit) = gix::objs::CommitRef::from_bytes(data) else {
+        return serde_json::json!({ "id": id.to_string() });
+    };
+    let encoding = commit.encoding.map(|encoding| encoding.to_string());
+    let decoder = message::decoder(encoding.as_deref());
+    let decode = |bytes: &[u8]| decoder.decode_without_bom_handling(bytes).0.into_owned();
+    let identity = |signature: gix::actor::SignatureRef<'_>, time: &jiff::Zoned| {
+        serde_json::json!({
+            "name": decode(signature.name),
+            "email": decode(signature.email),
+            "utc_offset": time.offset().seconds(),
+        })
+    };
+
+    let author_time = time::signature_zoned(data, b"author");
+    let commit_time = time::signature_zoned(data, b"committer");
+    let parsed = message::parse(&message::decode(data));
+    let mut trailers = BTreeMap::<_, BTreeSet<_>>::new();
+    for (token, value) in &parsed.trailer_list {
+        trailers.entry(token).or_default().insert(value);
+    }
+
+    serde_json::json!({
+        "id": id.to_string(),
+        "parent_ids": commit.parents().map(|id| id.to_string()).collect::<Vec<_>>(),
+        "summary": parsed.summary,
+        "body": parsed.body,
+        "time": time::local_time_string(&commit_time),
+        "author_time": time::local_time_string(&author_time),
+        "author": identity(commit.author(), &author_time),
+        "committer": identity(commit.committer(), &commit_time),
+        "trailers": trailers,
+    })
+}
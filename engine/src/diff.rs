@@ -0,0 +1,563 @@
+//! Patches of the changes between trees in git's format, the same as `git
+//! diff` writes them.
+
+use gix::objs::FindHeader;
+
+use std::io::Write;
+
+use crate::{Error, binary, paths, renames, submodule, unified_diff};
+
+/// The hunks between two blobs with the context of `format`, `None` if
+/// either of them is binary. Fails if a converter of `format` does.
+#[allow(clippy::too_many_arguments)]
+pub fn unified_diff_files(
+    resource_cache: &mut gix::diff::blob::Platform,
+    objects: &gix::OdbHandle,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+    older_id: &gix::oid,
+    older_location: &gix::diff::object::bstr::BStr,
+    newer_id: &gix::oid,
+    newer_location: &gix::diff::object::bstr::BStr,
+) -> Result<Option<Vec<u8>>, Error> {
+    let funcname =
+        format
+            .funcnames
+            .patterns(resource_cache, objects, older_location, newer_location);
+    if let Some((older, newer)) = format.textconv.convert(
+        resource_cache,
+        objects,
+        older_id,
+        older_location,
+        newer_id,
+        newer_location,
+    )? {
+        return Ok(Some(unified_diff::unified_diff(
+            &older,
+            &newer,
+            algorithm,
+            format.context_lines,
+            format.inter_hunk_context,
+            format.ignore_whitespace,
+            funcname,
+        )));
+    }
+    if binary::is_binary(
+        resource_cache,
+        objects,
+        older_id,
+        older_location,
+        newer_id,
+        newer_location,
+    ) {
+        return Ok(None);
+    }
+    let outcome = resource_cache.prepare_diff().unwrap();
+    // Files the `diff` attribute makes text may not have been loaded.
+    let (mut older_buffer, mut newer_buffer) = (Vec::new(), Vec::new());
+    let older = outcome
+        .old
+        .data
+        .as_slice()
+        .unwrap_or_else(|| binary::read(objects, older_id, &mut older_buffer));
+    let newer = outcome
+        .new
+        .data
+        .as_slice()
+        .unwrap_or_else(|| binary::read(objects, newer_id, &mut newer_buffer));
+
+    Ok(Some(unified_diff::unified_diff(
+        older,
+        newer,
+        algorithm,
+        format.context_lines,
+        format.inter_hunk_context,
+        format.ignore_whitespace,
+        funcname,
+    )))
+}
+
+/// The changes of `commit` against its first parent (or the empty tree).
+pub fn changes_with_parent(
+    commit: &gix::Commit<'_>,
+) -> Vec<gix::object::tree::diff::ChangeDetached> {
+    changes_with_nth_parent(commit, 0, &Default::default())
+}
+
+/// The changes against the parent at index `parent` of the commit's
+/// parents, against the empty tree if there is no such parent.
+pub fn changes_with_nth_parent(
+    commit: &gix::Commit<'_>,
+    parent: usize,
+    renames: &renames::RenameDetection,
+) -> Vec<gix::object::tree::diff::ChangeDetached> {
+    let tree = commit.tree().unwrap();
+    let parent_tree = if let Some(parent_id) = commit.parent_ids().nth(parent) {
+        parent_id.object().unwrap().peel_to_tree().unwrap()
+    } else {
+        tree.repo.empty_tree()
+    };
+
+    renames.changes(commit.repo, &parent_tree, &tree)
+}
+
+/// The cache blobs are loaded into for diffing them, converted to what git
+/// diffs (e.g. without CRLF line endings if configured).
+pub fn resource_cache(repository: &gix::Repository) -> gix::diff::blob::Platform {
+    repository
+        .diff_resource_cache(
+            gix::diff::blob::pipeline::Mode::ToGitUnlessBinaryToTextIsPresent,
+            Default::default(),
+        )
+        .unwrap()
+}
+
+/// Append the patch of a single changed file to `diff`, nothing is written
+/// for changes that are neither of files, symlinks, nor submodules.
+pub fn write_change(
+    diff: &mut Vec<u8>,
+    resource_cache: &mut gix::diff::blob::Platform,
+    repository: &gix::Repository,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+    delta: &gix::object::tree::diff::ChangeDetached,
+) -> Result<(), Error> {
+    use gix::object::tree::diff::ChangeDetached;
+
+    if let ChangeDetached::Modification {
+        location,
+        previous_entry_mode,
+        entry_mode,
+        previous_id,
+        id,
+    } = delta
+        && (previous_entry_mode.is_blob() != entry_mode.is_blob()
+            || previous_entry_mode.is_link() != entry_mode.is_link())
+    {
+        // Like git, a file replaced by a symlink or a submodule (or any
+        // other change of the type) is deleted and added instead.
+        for change in [
+            ChangeDetached::Deletion {
+                location: location.clone(),
+                relation: None,
+                entry_mode: *previous_entry_mode,
+                id: *previous_id,
+            },
+            ChangeDetached::Addition {
+                location: location.clone(),
+                relation: None,
+                entry_mode: *entry_mode,
+                id: *id,
+            },
+        ] {
+            write_change(diff, resource_cache, repository, algorithm, format, &change)?;
+        }
+        return Ok(());
+    }
+    if format.submodule == submodule::Format::Log
+        && let Some((location, older_id, newer_id)) = submodule::change(delta)
+    {
+        submodule::write_log(diff, repository, location, older_id, newer_id);
+        return Ok(());
+    }
+
+    let objects = &repository.objects;
+    let (src_prefix, dst_prefix) = (&format.src_prefix, &format.dst_prefix);
+    // Everything written before the `---`/`+++` lines is git's extended
+    // header which can be left out.
+    let header_start = diff.len();
+    let ((source_mode, source_id), (mode, id)) =
+        (delta.source_entry_mode_and_id(), delta.entry_mode_and_id());
+    // Only the headers of the blobs are read to tell.
+    let size = |id: &gix::oid| {
+        objects
+            .try_header(id)
+            .ok()
+            .flatten()
+            .map_or(0, |header| header.size)
+    };
+    let suppressed = format
+        .max_file_size
+        .filter(|_| source_mode.is_blob() && mode.is_blob())
+        .and_then(|max| Some(size(source_id).max(size(id))).filter(|&size| size > max));
+    // Like git, binary patches name the blobs by their full IDs.
+    let abbrev = if format.binary
+        && suppressed.is_none()
+        && source_mode.is_blob()
+        && mode.is_blob()
+        && binary::is_binary(
+            resource_cache,
+            objects,
+            source_id,
+            delta.source_location(),
+            id,
+            delta.location(),
+        ) {
+        id.kind().len_in_hex()
+    } else {
+        7
+    };
+    let (older_location, newer_location, older_id, newer_id, older_label, newer_label) = match delta
+    {
+        ChangeDetached::Addition {
+            location,
+            entry_mode,
+            id,
+            ..
+        } => {
+            // Skip anything that's neither a file, a symlink, nor a
+            // submodule. This includes the addition of new directories
+            // that git will not normally show.
+            if !(entry_mode.is_blob_or_symlink() || entry_mode.is_commit()) {
+                return Ok(());
+            }
+            // older is nothing
+            // newer is everything
+            let previous_id = gix::index::hash::Kind::Sha1.null();
+            let backing = &mut [0; 6];
+            writeln!(
+                diff,
+                "diff --git {src_prefix}{location} {dst_prefix}{location}"
+            )
+            .unwrap();
+            writeln!(diff, "new file mode {}", entry_mode.as_bytes(backing)).unwrap();
+            writeln!(
+                diff,
+                "index {}..{}",
+                &previous_id.to_string()[..abbrev],
+                &id.to_string()[..abbrev],
+            )
+            .unwrap();
+            (
+                location.as_ref(),
+                location.as_ref(),
+                &gix::index::hash::Kind::Sha1.null(),
+                id,
+                "/dev/null".to_string(),
+                format!("{dst_prefix}{location}"),
+            )
+        }
+        ChangeDetached::Deletion {
+            location,
+            entry_mode,
+            id,
+            ..
+        } => {
+            // Skip anything that's neither a file, a symlink, nor a
+            // submodule. This includes the addition of new directories
+            // that git will not normally show.
+            if !(entry_mode.is_blob_or_symlink() || entry_mode.is_commit()) {
+                return Ok(());
+            }
+            // newer is nothing
+            // older is everything
+            let newer_id = gix::index::hash::Kind::Sha1.null();
+            let backing = &mut [0; 6];
+            writeln!(
+                diff,
+                "diff --git {src_prefix}{location} {dst_prefix}{location}"
+            )
+            .unwrap();
+            writeln!(diff, "deleted file mode {}", entry_mode.as_bytes(backing)).unwrap();
+
+            writeln!(
+                diff,
+                "index {}..{}",
+                &id.to_string()[..abbrev],
+                &newer_id.to_string()[..abbrev],
+            )
+            .unwrap();
+            (
+                location.as_ref(),
+                location.as_ref(),
+                id,
+                &gix::index::hash::Kind::Sha1.null(),
+                format!("{src_prefix}{location}"),
+                "/dev/null".to_string(),
+            )
+        }
+        ChangeDetached::Modification {
+            location,
+            previous_entry_mode,
+            entry_mode,
+            previous_id,
+            id,
+        } => {
+            // Skip anything that's neither a file, a symlink, nor a
+            // submodule. This includes the addition of new directories
+            // that git will not normally show.
+            if !(entry_mode.is_blob_or_symlink() || entry_mode.is_commit()) {
+                return Ok(());
+            }
+
+            writeln!(
+                diff,
+                "diff --git {src_prefix}{location} {dst_prefix}{location}"
+            )
+            .unwrap();
+            let backing = &mut [0; 6];
+            if previous_entry_mode != entry_mode {
+                // Without these `git apply` would silently drop the
+                // mode change.
+                writeln!(diff, "old mode {}", previous_entry_mode.as_bytes(backing)).unwrap();
+                writeln!(diff, "new mode {}", entry_mode.as_bytes(backing)).unwrap();
+                if previous_id != id {
+                    writeln!(
+                        diff,
+                        "index {}..{}",
+                        &previous_id.to_string()[..abbrev],
+                        &id.to_string()[..abbrev],
+                    )
+                    .unwrap();
+                }
+            } else {
+                writeln!(
+                    diff,
+                    "index {}..{} {}",
+                    &previous_id.to_string()[..abbrev],
+                    &id.to_string()[..abbrev],
+                    entry_mode.as_bytes(backing)
+                )
+                .unwrap();
+            }
+
+            (
+                location.as_ref(),
+                location.as_ref(),
+                previous_id,
+                id,
+                format!("{src_prefix}{location}"),
+                format!("{dst_prefix}{location}"),
+            )
+        }
+        ChangeDetached::Rewrite {
+            source_location,
+            location,
+            source_entry_mode,
+            entry_mode,
+            source_id,
+            id,
+            copy,
+            ..
+        } => {
+            if !(source_entry_mode.is_blob_or_symlink() && entry_mode.is_blob_or_symlink()) {
+                return Ok(());
+            }
+
+            let (from, to) = if *copy {
+                ("copy from", "copy to")
+            } else {
+                ("rename from", "rename to")
+            };
+
+            writeln!(
+                diff,
+                "diff --git {src_prefix}{source_location} {dst_prefix}{location}"
+            )
+            .unwrap();
+            let backing = &mut [0; 6];
+            if source_entry_mode != entry_mode {
+                writeln!(diff, "old mode {}", source_entry_mode.as_bytes(backing)).unwrap();
+                writeln!(diff, "new mode {}", entry_mode.as_bytes(backing)).unwrap();
+            }
+            if id == source_id {
+                // This is a perfect copy.
+                writeln!(diff, "similarity index 100%").unwrap();
+                writeln!(diff, "{from} {source_location}").unwrap();
+                writeln!(diff, "{to} {location}").unwrap();
+                if !format.git_header {
+                    diff.truncate(header_start);
+                }
+                return Ok(());
+            } else {
+                if let Some(similarity) = renames::similarity(objects, source_id, id) {
+                    writeln!(diff, "similarity index {similarity}%").unwrap();
+                }
+                writeln!(diff, "{from} {source_location}").unwrap();
+                writeln!(diff, "{to} {location}").unwrap();
+                if source_entry_mode != entry_mode {
+                    writeln!(
+                        diff,
+                        "index {}..{}",
+                        &source_id.to_string()[..abbrev],
+                        &id.to_string()[..abbrev],
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(
+                        diff,
+                        "index {}..{} {}",
+                        &source_id.to_string()[..abbrev],
+                        &id.to_string()[..abbrev],
+                        entry_mode.as_bytes(backing)
+                    )
+                    .unwrap();
+                }
+            }
+            (
+                source_location.as_ref(),
+                location.as_ref(),
+                source_id,
+                id,
+                format!("{src_prefix}{source_location}"),
+                format!("{dst_prefix}{location}"),
+            )
+        }
+    };
+    if !format.git_header {
+        diff.truncate(header_start);
+    }
+    if let Some(size) = suppressed.filter(|_| older_id != newer_id) {
+        if format.git_header {
+            writeln!(diff, "diff suppressed ({size} bytes)").unwrap();
+        }
+        return Ok(());
+    }
+
+    let hunks = if source_mode.is_commit() || mode.is_commit() {
+        Some(submodule::hunks(older_id, newer_id, format))
+    } else {
+        unified_diff_files(
+            resource_cache,
+            objects,
+            algorithm,
+            format,
+            older_id,
+            older_location,
+            newer_id,
+            newer_location,
+        )?
+    };
+
+    match hunks {
+        // Like git, only emit the file markers when there are hunks to
+        // follow them (e.g. not for empty files or pure mode changes).
+        Some(hunks) if !hunks.is_empty() => {
+            writeln!(diff, "--- {older_label}").unwrap();
+            writeln!(diff, "+++ {newer_label}").unwrap();
+            diff.extend_from_slice(&hunks);
+        }
+        // Unless there's more to tell than the content, like git leave
+        // out files whose changes were all ignored whitespace.
+        Some(_)
+            if format.ignore_whitespace.is_some()
+                && matches!(
+                    delta,
+                    ChangeDetached::Modification {
+                        previous_entry_mode,
+                        entry_mode,
+                        ..
+                    } if previous_entry_mode == entry_mode
+                ) =>
+        {
+            diff.truncate(header_start);
+        }
+        Some(_) => {}
+        // Nothing changed but the mode.
+        None if older_id == newer_id => {}
+        None if format.git_header && format.binary => {
+            binary::write_patch(diff, objects, older_id, newer_id);
+        }
+        None if format.git_header => {
+            writeln!(diff, "Binary files {older_label} and {newer_label} differ").unwrap();
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+pub fn diff_with_parent(
+    commit: &gix::Commit<'_>,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+) -> Result<Option<Vec<u8>>, Error> {
+    diff_with_nth_parent(commit, 0, algorithm, format, &[])
+}
+
+/// The patch against the parent at index `parent`, restricted to the
+/// files selected by `paths` (see `paths::matches`).
+pub fn diff_with_nth_parent(
+    commit: &gix::Commit<'_>,
+    parent: usize,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+    paths: &[String],
+) -> Result<Option<Vec<u8>>, Error> {
+    write_changes(
+        commit.repo,
+        changes_with_nth_parent(commit, parent, &format.renames),
+        algorithm,
+        format,
+        paths,
+    )
+}
+
+/// The patch of `changes`, restricted to the files selected by `paths`
+/// (see `paths::matches`).
+pub fn write_changes(
+    repository: &gix::Repository,
+    changes: Vec<gix::object::tree::diff::ChangeDetached>,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+    paths: &[String],
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut diff = Vec::new();
+    stream_changes(
+        repository,
+        &mut resource_cache(repository),
+        changes,
+        algorithm,
+        format,
+        paths,
+        |patch| {
+            diff.extend_from_slice(patch);
+            Ok(())
+        },
+    )?;
+    if diff.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(diff))
+    }
+}
+
+/// Pass the patch of `changes` to `sink` file by file as it is
+/// produced, see `write_changes`.
+pub fn stream_changes(
+    repository: &gix::Repository,
+    resource_cache: &mut gix::diff::blob::Platform,
+    changes: Vec<gix::object::tree::diff::ChangeDetached>,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+    paths: &[String],
+    mut sink: impl FnMut(&[u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut patch = Vec::new();
+    for delta in changes {
+        let source = match &delta {
+            gix::object::tree::diff::ChangeDetached::Rewrite {
+                source_location, ..
+            } => Some(source_location.as_ref()),
+            _ => None,
+        };
+        if !std::iter::once(delta.location())
+            .chain(source)
+            .any(|location| paths::matches(paths, location))
+        {
+            continue;
+        }
+        write_change(
+            &mut patch,
+            resource_cache,
+            repository,
+            algorithm,
+            format,
+            &delta,
+        )?;
+        if !patch.is_empty() {
+            sink(&patch)?;
+            patch.clear();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,394 @@
+//! The function or section a hunk is in, shown after its `@@` header, found
+//! like git: by the `xfuncname` patterns of the file's diff driver (its
+//! `diff` attribute) or otherwise as the closest line above starting with a
+//! letter, `_`, or `$`.
+
+#[cfg(feature = "repository")]
+use gix::bstr::{BStr, BString};
+use regex::bytes::{Regex, RegexBuilder};
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+#[cfg(feature = "repository")]
+use crate::Error;
+
+/// The patterns of git's builtin diff drivers (from its `userdiff.c`), and
+/// whether they ignore case.
+const BUILTIN: &[(&str, bool, &str)] = &[
+    (
+        "ada",
+        true,
+        "!^(.*[ \t])?(is[ \t]+new|renames|is[ \t]+separate)([ \t].*)?$\n\
+         !^[ \t]*with[ \t].*$\n\
+         ^[ \t]*((procedure|function)[ \t]+.*)$\n\
+         ^[ \t]*((package|protected|task)[ \t]+.*)$",
+    ),
+    (
+        "bash",
+        false,
+        "^[ \t]*(([a-zA-Z_][a-zA-Z0-9_]*[ \t]*\\([ \t]*\\))|(function[ \t]+[a-zA-Z_][a-zA-Z0-9_]*(([ \t]*\\([ \t]*\\))|([ \t]+)))[ \t]*(\\{|\\(\\(?|\\[\\[))",
+    ),
+    (
+        "bibtex",
+        false,
+        "(@[a-zA-Z]{1,}[ \t]*\\{{0,1}[ \t]*[^ \t\"@',\\#}{~%]*).*$",
+    ),
+    (
+        "cpp",
+        false,
+        "!^[ \t]*[A-Za-z_][A-Za-z_0-9]*:[[:space:]]*($|/[/*])\n\
+         ^((::[[:space:]]*)?[A-Za-z_].*)$",
+    ),
+    (
+        "csharp",
+        false,
+        "!^[ \t]*(do|while|for|if|else|instanceof|new|return|switch|case|throw|catch|using)\n\
+         ^[ \t]*(((static|public|internal|private|protected|new|virtual|sealed|override|unsafe|async)[ \t]+)*[][<>@.~_[:alnum:]]+[ \t]+[<>@._[:alnum:]]+[ \t]*\\(.*\\))[ \t]*$\n\
+         ^[ \t]*(((static|public|internal|private|protected|new|virtual|sealed|override|unsafe)[ \t]+)*[][<>@.~_[:alnum:]]+[ \t]+[@._[:alnum:]]+)[ \t]*$\n\
+         ^[ \t]*(((static|public|internal|private|protected|new|unsafe|sealed|abstract|partial)[ \t]+)*(class|enum|interface|struct|record)[ \t]+.*)$\n\
+         ^[ \t]*(namespace[ \t]+.*)$",
+    ),
+    (
+        "css",
+        true,
+        "![:;][[:space:]]*$\n\
+         ^[:[@.#]?[_a-z0-9].*$",
+    ),
+    (
+        "dts",
+        false,
+        "!;\n\
+         !=\n\
+         ^[ \t]*((/[ \t]*\\{|&?[a-zA-Z_]).*)",
+    ),
+    (
+        "elixir",
+        false,
+        "^[ \t]*((def(macro|module|impl|protocol|p)?|test)[ \t].*)$",
+    ),
+    (
+        "fortran",
+        true,
+        "!^([C*]|[ \t]*!)\n\
+         !^[ \t]*MODULE[ \t]+PROCEDURE[ \t]\n\
+         ^[ \t]*((END[ \t]+)?(PROGRAM|MODULE|BLOCK[ \t]+DATA|([^!'\" \t]+[ \t]+)*(SUBROUTINE|FUNCTION))[ \t]+[A-Z].*)$",
+    ),
+    (
+        "fountain",
+        true,
+        "^((\\.[^.]|(int|ext|est|int\\.?/ext|i/e)[. ]).*)$",
+    ),
+    (
+        "golang",
+        false,
+        "^[ \t]*(func[ \t]*.*(\\{[ \t]*)?)\n\
+         ^[ \t]*(type[ \t].*(struct|interface)[ \t]*(\\{[ \t]*)?)",
+    ),
+    ("html", false, "^[ \t]*(<[Hh][1-6]([ \t].*)?>.*)$"),
+    (
+        "java",
+        false,
+        "!^[ \t]*(catch|do|for|if|instanceof|new|return|switch|throw|while)\n\
+         ^[ \t]*(([a-z]+[ \t]+)*(class|enum|interface)[ \t]+[A-Za-z][A-Za-z0-9_$]*[ \t]+.*)$\n\
+         ^[ \t]*(([A-Za-z_<>&][][?&<>.,A-Za-z_0-9]*[ \t]+)+[A-Za-z_][A-Za-z_0-9]*[ \t]*\\([^;]*)$",
+    ),
+    (
+        "kotlin",
+        false,
+        "^[ \t]*(([a-z]+[ \t]+)*(fun|class|interface)[ \t]+.*)$",
+    ),
+    ("markdown", false, "^ {0,3}#{1,6}[ \t].*"),
+    (
+        "matlab",
+        false,
+        "^[[:space:]]*((classdef|function)[[:space:]].*)$|^(%%%?|##)[[:space:]].*$",
+    ),
+    (
+        "objc",
+        false,
+        "!^[ \t]*(do|for|if|else|return|switch|while)\n\
+         ^[ \t]*([-+][ \t]*\\([ \t]*[A-Za-z_][A-Za-z_0-9* \t]*\\)[ \t]*[A-Za-z_].*)$\n\
+         ^[ \t]*(([A-Za-z_][A-Za-z_0-9]*[ \t]+)+[A-Za-z_][A-Za-z_0-9]*[ \t]*\\([^;]*)$\n\
+         ^(@(implementation|interface|protocol)[ \t].*)$",
+    ),
+    (
+        "pascal",
+        false,
+        "^(((class[ \t]+)?(procedure|function)|constructor|destructor|interface|implementation|initialization|finalization)[ \t]*.*)$\n\
+         ^(.*=[ \t]*(class|record).*)$",
+    ),
+    (
+        "perl",
+        false,
+        "^package .*\n\
+         ^sub [[:alnum:]_':]+[ \t]*(\\([^)]*\\)[ \t]*)?(:[^;#]*)?(\\{[ \t]*)?(#.*)?$\n\
+         ^(BEGIN|END|INIT|CHECK|UNITCHECK|AUTOLOAD|DESTROY)[ \t]*(\\{[ \t]*)?(#.*)?$\n\
+         ^=head[0-9] .*",
+    ),
+    (
+        "php",
+        false,
+        "^[\t ]*(((public|protected|private|static|abstract|final)[\t ]+)*function.*)$\n\
+         ^[\t ]*((((final|abstract)[\t ]+)?class|enum|interface|trait).*)$",
+    ),
+    (
+        "python",
+        false,
+        "^[ \t]*((class|(async[ \t]+)?def)[ \t].*)$",
+    ),
+    ("ruby", false, "^[ \t]*((class|module|def)[ \t].*)$"),
+    (
+        "rust",
+        false,
+        "^[\t ]*((pub(\\([^\\)]+\\))?[\t ]+)?((async|const|unsafe|extern([\t ]+\"[^\"]+\"))[\t ]+)?(struct|enum|union|mod|trait|fn|impl|macro_rules!)[< \t]+[^;]*)$",
+    ),
+    (
+        "scheme",
+        false,
+        "^[\t ]*(\\(((define|def(struct|syntax|class|method|rules|record|proto|alias)?)[-*/ \t]|(library|module|struct|class)[*+ \t]).*)$",
+    ),
+    (
+        "tex",
+        false,
+        "^(\\\\((sub)*section|chapter|part)\\*{0,1}\\{.*)$",
+    ),
+];
+
+static BUILTIN_PATTERNS: LazyLock<HashMap<&str, Patterns>> = LazyLock::new(|| {
+    BUILTIN
+        .iter()
+        .map(|&(name, ignore_case, patterns)| (name, Patterns::new(patterns, ignore_case).unwrap()))
+        .collect()
+});
+
+/// Rewrite a POSIX extended regular expression for the `regex` crate, which
+/// reads bracket expressions differently: `[` and `\` are literal in them,
+/// a `]` right at the start doesn't end them, and `&&` or `~~` aren't set
+/// operations.
+fn translate(pattern: &str) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => {
+                out.push(character);
+                out.extend(chars.next());
+            }
+            '[' => {
+                out.push('[');
+                if chars.next_if_eq(&'^').is_some() {
+                    out.push('^');
+                }
+                let mut first = true;
+                while let Some(character) = chars.next() {
+                    match character {
+                        ']' if !first => break,
+                        '[' if chars.peek().is_some_and(|next| ":.=".contains(*next)) => {
+                            // A class like `[:alpha:]`, copied up to its `]`.
+                            out.push('[');
+                            for character in chars.by_ref() {
+                                out.push(character);
+                                if character == ']' {
+                                    break;
+                                }
+                            }
+                        }
+                        // Ranges stay as they are, as do characters without a
+                        // meaning in classes of Rust's syntax, which can't be
+                        // escaped there.
+                        _ if "[]\\^&~".contains(character) => {
+                            out.push('\\');
+                            out.push(character);
+                        }
+                        _ => out.push(character),
+                    }
+                    first = false;
+                }
+                out.push(']');
+            }
+            _ => out.push(character),
+        }
+    }
+    out
+}
+
+/// Rewrite a POSIX basic regular expression (of `diff.<driver>.funcname`)
+/// as an extended one, where `(){}|+?` are special without a backslash.
+#[cfg(feature = "repository")]
+fn basic_to_extended(pattern: &str) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    let mut in_brackets = false;
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' if !in_brackets => match chars.next() {
+                Some(special @ ('(' | ')' | '{' | '}' | '|' | '+' | '?')) => out.push(special),
+                Some(other) => {
+                    out.push('\\');
+                    out.push(other);
+                }
+                None => out.push('\\'),
+            },
+            '(' | ')' | '{' | '}' | '|' | '+' | '?' if !in_brackets => {
+                out.push('\\');
+                out.push(character);
+            }
+            '[' => {
+                in_brackets = true;
+                out.push(character);
+            }
+            ']' => {
+                in_brackets = false;
+                out.push(character);
+            }
+            _ => out.push(character),
+        }
+    }
+    out
+}
+
+/// The patterns of a diff driver, one per line. A line of a file starts a
+/// function (named by the pattern's first group, or all it matches) if the
+/// first of them it matches doesn't start with `!`.
+pub struct Patterns(Vec<(Regex, bool)>);
+
+impl Patterns {
+    fn new(patterns: &str, ignore_case: bool) -> Result<Self, regex::Error> {
+        patterns
+            .split('\n')
+            .map(|pattern| {
+                let (pattern, negated) = match pattern.strip_prefix('!') {
+                    Some(pattern) => (pattern, true),
+                    None => (pattern, false),
+                };
+                let regex = RegexBuilder::new(&translate(pattern))
+                    .unicode(false)
+                    .case_insensitive(ignore_case)
+                    .build()?;
+                Ok((regex, negated))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    fn find<'a>(&self, line: &'a [u8]) -> Option<&'a [u8]> {
+        let line = match line.strip_suffix(b"\n") {
+            Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+            None => line,
+        };
+        let (regex, negated) = self.0.iter().find(|(regex, _)| regex.is_match(line))?;
+        if *negated {
+            return None;
+        }
+        let captures = regex.captures(line)?;
+        Some(captures.get(1).or(captures.get(0))?.as_bytes())
+    }
+}
+
+/// The patterns of git's builtin diff driver `name` (e.g. `rust`), `None`
+/// if there is no such driver.
+pub fn builtin(name: &str) -> Option<&'static Patterns> {
+    BUILTIN_PATTERNS.get(name)
+}
+
+/// The name of the function `line` starts by `patterns` or git's default
+/// rule if `None`, cut to 80 bytes and without trailing whitespace like git.
+pub fn function_name<'a>(patterns: Option<&Patterns>, line: &'a [u8]) -> Option<&'a [u8]> {
+    let name = match patterns {
+        Some(patterns) => patterns.find(line)?,
+        None => line
+            .first()
+            .is_some_and(|&byte| byte.is_ascii_alphabetic() || byte == b'_' || byte == b'$')
+            .then_some(line)?,
+    };
+    let mut name = &name[..name.len().min(80)];
+    while let [rest @ .., b' ' | b'\t' | b'\n' | b'\r'] = name {
+        name = rest;
+    }
+    Some(name)
+}
+
+/// The function name patterns of the diff drivers configured with
+/// `diff.<driver>.xfuncname` (or `funcname` as a basic regular expression),
+/// which take precedence over git's builtin ones.
+#[cfg(feature = "repository")]
+#[derive(Default)]
+pub struct Drivers(HashMap<BString, Patterns>);
+
+#[cfg(feature = "repository")]
+impl Drivers {
+    pub fn new(repository: &gix::Repository) -> Result<Self, Error> {
+        let config = repository.config_snapshot();
+        let mut drivers = HashMap::new();
+        for section in config
+            .plumbing()
+            .sections_by_name("diff")
+            .into_iter()
+            .flatten()
+        {
+            let Some(name) = section.header().subsection_name() else {
+                continue;
+            };
+            let pattern = match (section.value("xfuncname"), section.value("funcname")) {
+                (Some(pattern), _) => pattern.to_string(),
+                (None, Some(pattern)) => basic_to_extended(&pattern.to_string()),
+                (None, None) => continue,
+            };
+            let patterns = Patterns::new(&pattern, false).map_err(|error| {
+                Error::Value(format!(
+                    "invalid function name pattern of diff driver {name}: {error}"
+                ))
+            })?;
+            drivers.insert(name.to_owned(), patterns);
+        }
+        Ok(Self(drivers))
+    }
+
+    /// The patterns for a change from `older_location` to `newer_location`,
+    /// those of the older side's driver if it has any like in git. `None`
+    /// for git's default rule.
+    ///
+    /// `resource_cache` is only used for its attributes.
+    pub fn patterns(
+        &self,
+        resource_cache: &mut gix::diff::blob::Platform,
+        objects: &gix::OdbHandle,
+        older_location: &BStr,
+        newer_location: &BStr,
+    ) -> Option<&Patterns> {
+        [older_location, newer_location]
+            .into_iter()
+            .find_map(|location| {
+                let driver = driver(resource_cache, objects, location)?;
+                self.0
+                    .get(driver.as_slice())
+                    .or_else(|| builtin(std::str::from_utf8(&driver).ok()?))
+            })
+    }
+}
+
+/// The diff driver of `location`, `default` if its `diff` attribute is
+/// unspecified and `None` if it's set or unset.
+#[cfg(feature = "repository")]
+pub fn driver(
+    resource_cache: &mut gix::diff::blob::Platform,
+    objects: &gix::OdbHandle,
+    location: &BStr,
+) -> Option<Vec<u8>> {
+    use gix::attrs::StateRef;
+
+    let stack = &mut resource_cache.attr_stack;
+    let mut outcome = stack.selected_attribute_matches(["diff"]);
+    if !stack
+        .at_entry(location, None, objects)
+        .is_ok_and(|entry| entry.matching_attributes(&mut outcome))
+    {
+        return Some(b"default".to_vec());
+    }
+    match outcome.iter_selected().next()?.assignment.state {
+        StateRef::Value(name) => Some(name.as_bstr().to_vec()),
+        StateRef::Unspecified => Some(b"default".to_vec()),
+        StateRef::Set | StateRef::Unset => None,
+    }
+}
@@ -0,0 +1,63 @@
+//! The diff, message, and check logic of gitch, shared by the Python module
+//! and the C ABI of `gitch-ffi`.
+//!
+//! Nothing here depends on Python: failures are returned as [`Error`]s which
+//! the Python module raises as exceptions and the C ABI reports as strings.
+//!
+//! Everything reading from a repository is behind the default `repository`
+//! feature. Without it what's left works on plain text only (parsing
+//! messages, validating trailers, and diffing two texts), for builds like
+//! the `wasm32` one of `gitch-wasm` that have no filesystem.
+
+pub mod binary;
+#[cfg(feature = "repository")]
+pub mod checks;
+#[cfg(feature = "repository")]
+pub mod commit;
+#[cfg(feature = "repository")]
+pub mod diff;
+pub mod funcname;
+#[cfg(feature = "repository")]
+pub mod mailmap;
+pub mod message;
+#[cfg(feature = "repository")]
+pub mod paths;
+#[cfg(feature = "repository")]
+pub mod renames;
+#[cfg(feature = "repository")]
+pub mod submodule;
+#[cfg(feature = "repository")]
+pub mod textconv;
+#[cfg(feature = "repository")]
+pub mod time;
+pub mod unified_diff;
+
+/// Why an operation failed.
+#[derive(Debug)]
+pub enum Error {
+    /// An argument isn't valid, e.g. an unknown option name.
+    Value(String),
+    /// Reading from the repository failed.
+    Repository(Box<dyn std::error::Error + Send + Sync>),
+    /// A function given by the caller (e.g. a `textconv` converter or what a
+    /// patch is streamed to) failed, with the error it returned.
+    Callback(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// A failure to read from the repository.
+    pub fn repository(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::Repository(Box::new(error))
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value(message) => f.write_str(message),
+            Self::Repository(error) | Self::Callback(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+/// The replacement name and/or email of a mailmap entry.
+#[derive(Clone, Default)]
+struct Replacement {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+/// All entries for a single commit email (matched case-insensitively).
+#[derive(Default)]
+struct Entry {
+    /// Applies to any name used with the email.
+    any_name: Option<Replacement>,
+    /// Applies only to a specific (lowercased) name used with the email.
+    by_name: HashMap<String, Replacement>,
+}
+
+/// A parsed `.mailmap` (see `gitmailmap(5)`) mapping the identities used in
+/// commits to canonical ones.
+#[derive(Default)]
+pub struct Mailmap {
+    entries: HashMap<String, Entry>,
+}
+
+/// Split `Name <email>` off the start of `line`, returning the rest.
+fn name_and_email(line: &str) -> Option<(Option<&str>, &str, &str)> {
+    let (name, rest) = line.split_once('<')?;
+    let (email, rest) = rest.split_once('>')?;
+    let name = name.trim();
+    Some(((!name.is_empty()).then_some(name), email.trim(), rest))
+}
+
+impl Mailmap {
+    /// Add the entries of a mailmap file, later entries win like in git and
+    /// malformed lines are ignored.
+    pub fn merge(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let Some((proper_name, proper_email, rest)) = name_and_email(line) else {
+                continue;
+            };
+            let (key, commit_name, replacement) = match name_and_email(rest) {
+                // `Proper Name <proper@email> [Commit Name] <commit@email>`
+                Some((commit_name, commit_email, _)) => (
+                    commit_email,
+                    commit_name,
+                    Replacement {
+                        name: proper_name.map(str::to_string),
+                        email: (!proper_email.is_empty()).then(|| proper_email.to_string()),
+                    },
+                ),
+                // `Proper Name <commit@email>`
+                None => (
+                    proper_email,
+                    None,
+                    Replacement {
+                        name: proper_name.map(str::to_string),
+                        email: None,
+                    },
+                ),
+            };
+
+            let entry = self.entries.entry(key.to_lowercase()).or_default();
+            let existing = match commit_name {
+                Some(name) => entry.by_name.entry(name.to_lowercase()).or_default(),
+                None => entry.any_name.get_or_insert_with(Default::default),
+            };
+            // Entries for the same identity complement each other.
+            if replacement.name.is_some() {
+                existing.name = replacement.name;
+            }
+            if replacement.email.is_some() {
+                existing.email = replacement.email;
+            }
+        }
+    }
+
+    /// Load the mailmap of a repository from the same places git does: the
+    /// `.mailmap` of the working tree (or of `HEAD` in bare repositories), the
+    /// blob named by `mailmap.blob`, and the file named by `mailmap.file`.
+    ///
+    /// Like git, missing or unreadable mailmaps are skipped.
+    pub fn load(repository: &gix::Repository) -> Self {
+        let mut mailmap = Self::default();
+        let config = repository.config_snapshot();
+        let blob = config.string("mailmap.blob").map(|spec| spec.to_string());
+        match repository.workdir() {
+            Some(root) => {
+                if let Ok(text) = std::fs::read(root.join(".mailmap")) {
+                    mailmap.merge(&String::from_utf8_lossy(&text));
+                }
+            }
+            None if blob.is_none() => {
+                if let Some(text) = Self::read_blob(repository, "HEAD:.mailmap") {
+                    mailmap.merge(&text);
+                }
+            }
+            None => {}
+        }
+        if let Some(text) = blob.and_then(|spec| Self::read_blob(repository, &spec)) {
+            mailmap.merge(&text);
+        }
+        if let Some(Ok(path)) = config.trusted_path("mailmap.file")
+            && let Ok(text) = std::fs::read(path)
+        {
+            mailmap.merge(&String::from_utf8_lossy(&text));
+        }
+        mailmap
+    }
+
+    /// The canonical name and email of an identity.
+    pub fn resolve(&self, name: &str, email: &str) -> (String, String) {
+        let replacement = self.entries.get(&email.to_lowercase()).and_then(|entry| {
+            entry
+                .by_name
+                .get(&name.to_lowercase())
+                .or(entry.any_name.as_ref())
+        });
+        match replacement {
+            Some(replacement) => (
+                replacement.name.clone().unwrap_or_else(|| name.to_string()),
+                replacement
+                    .email
+                    .clone()
+                    .unwrap_or_else(|| email.to_string()),
+            ),
+            None => (name.to_string(), email.to_string()),
+        }
+    }
+
+    fn read_blob(repository: &gix::Repository, spec: &str) -> Option<String> {
+        let object = repository.rev_parse_single(spec).ok()?.object().ok()?;
+        Some(String::from_utf8_lossy(&object.data).into_owned())
+    }
+}
@@ -0,0 +1,244 @@
+//! Commit messages decoded from the encoding their commit declares, split
+//! into summary, body, and trailers, and their trailers checked against
+//! gitch's configuration.
+
+use gix_object::bstr::BStr;
+use gix_object::commit::MessageRef;
+use gix_object::commit::message::BodyRef;
+
+use std::collections::BTreeSet;
+
+#[cfg(feature = "repository")]
+use crate::mailmap::Mailmap;
+
+/// A commit message split into its parts.
+pub struct Message {
+    pub summary: String,
+    /// The message without summary and trailers, `None` if nothing is left.
+    pub body: Option<String>,
+    /// The trailers in their original order including repeated ones.
+    pub trailer_list: Vec<(String, String)>,
+}
+
+/// Split a (decoded) commit message into summary, body, and trailers.
+pub fn parse(message: &str) -> Message {
+    let parsed = MessageRef::from_bytes(message.as_bytes());
+    let summary = parsed.summary().to_string();
+    let trailer_list = BodyRef::from_bytes(message.as_bytes())
+        .trailers()
+        .map(|trailer| (trailer.token.to_string(), trailer.value.to_string()))
+        .collect();
+    let body = parsed.body.and_then(|body| {
+        let mut message: Vec<u8> = b"\n\n".to_vec();
+        message.extend_from_slice(body);
+        let body = BodyRef::from_bytes(&message[..]).without_trailer();
+        (!body.is_empty()).then(|| BStr::new(body.trim_ascii()).to_string())
+    });
+    Message {
+        summary,
+        body,
+        trailer_list,
+    }
+}
+
+/// The decoder of the `encoding` a commit declares, like git UTF-8 if it
+/// declares none or one that isn't known.
+pub fn decoder(encoding: Option<&str>) -> &'static encoding_rs::Encoding {
+    encoding
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8)
+}
+
+/// The message of the raw commit object `data`, decoded.
+pub fn decode(data: &[u8]) -> String {
+    let Ok(commit) = gix_object::CommitRef::from_bytes(data) else {
+        return String::new();
+    };
+    let encoding = commit.encoding.map(|encoding| encoding.to_string());
+    decoder(encoding.as_deref())
+        .decode_without_bom_handling(commit.message)
+        .0
+        .into_owned()
+}
+
+/// The values of the `Signed-off-by` trailers (as required by the DCO).
+pub fn signoffs(trailers: &[(String, String)]) -> impl Iterator<Item = &str> {
+    trailers
+        .iter()
+        .filter(|(token, _)| token.eq_ignore_ascii_case("Signed-off-by"))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Whether one of the `Signed-off-by` trailers is by `name <email>`, with
+/// the identities compared after applying `mailmap`.
+#[cfg(feature = "repository")]
+pub fn is_signed_off_by(
+    trailers: &[(String, String)],
+    name: &str,
+    email: &str,
+    mailmap: &Mailmap,
+) -> bool {
+    let (name, email) = mailmap.resolve(name, email);
+    signoffs(trailers).any(|value| {
+        let Some((signoff_name, signoff_email)) = value
+            .trim()
+            .strip_suffix('>')
+            .and_then(|value| value.rsplit_once('<'))
+        else {
+            return false;
+        };
+        let (signoff_name, signoff_email) =
+            mailmap.resolve(signoff_name.trim(), signoff_email.trim());
+        signoff_name == name && signoff_email.eq_ignore_ascii_case(&email)
+    })
+}
+
+/// What the trailers of a token have to be like, one of the `trailers` of
+/// gitch's configuration.
+#[derive(Clone, Debug, Default)]
+pub struct TrailerRule {
+    /// Whether every message needs the trailer.
+    pub mandatory: bool,
+    /// Whether the trailer can only have a single (distinct) value.
+    pub singular: bool,
+    /// The values the trailer can have, any if empty.
+    pub values: BTreeSet<String>,
+}
+
+/// Everything wrong with `trailers` by `rules` of tokens, in the order of the
+/// rules. Tokens are matched exactly, but a missing mandatory one mentions a
+/// token starting similarly (at most three edits away) as a likely typo.
+pub fn validate_trailers(
+    trailers: &[(String, String)],
+    rules: &[(String, TrailerRule)],
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (token, rule) in rules {
+        let values = trailers
+            .iter()
+            .filter(|(name, _)| name == token)
+            .map(|(_, value)| value.as_str())
+            .collect::<BTreeSet<_>>();
+        if values.is_empty() {
+            if rule.mandatory {
+                match similar_token(token, trailers) {
+                    Some(similar) => errors.push(format!(
+                        "trailers['{token}'] not found but '{token}' is mandatory (found similar field: '{similar}')"
+                    )),
+                    None => errors.push(format!(
+                        "trailers['{token}'] not found but '{token}' is mandatory"
+                    )),
+                }
+            }
+            continue;
+        }
+        if rule.singular && values.len() != 1 {
+            errors.push(format!(
+                "expected trailers['{token}'] to be singular instead it has length {}",
+                values.len()
+            ));
+        }
+        let unknown = values
+            .iter()
+            .filter(|value| !rule.values.is_empty() && !rule.values.contains(**value))
+            .map(|value| format!("'{value}'"))
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            errors.push(format!(
+                "trailers['{token}'] has non-configured values {{{}}}",
+                unknown.join(", ")
+            ));
+        }
+    }
+    errors
+}
+
+/// The first token of `trailers` closest to `token` when cut to its length,
+/// if it's at most three edits away.
+fn similar_token<'a>(token: &str, trailers: &'a [(String, String)]) -> Option<&'a str> {
+    let length = token.chars().count();
+    let mut best: Option<(usize, &str)> = None;
+    for (name, _) in trailers {
+        let prefix = name.chars().take(length).collect::<String>();
+        let distance = levenshtein(token, &prefix);
+        if distance <= 3 && best.is_none_or(|(best, _)| distance < best) {
+            best = Some((distance, name));
+        }
+    }
+    best.map(|(_, name)| name)
+}
+
+/// The case-insensitive edit distance between `a` and `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<_>>();
+    let b = b.to_lowercase().chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, &a) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if a == b {
+                diagonal
+            } else {
+                diagonal.min(above).min(row[j]) + 1
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrailerRule, validate_trailers};
+
+    #[test]
+    fn trailers_are_validated_in_rule_order() {
+        let trailers = [
+            ("Reviewed-by".to_string(), "A <a@example.com>".to_string()),
+            ("Reviewed-by".to_string(), "B <b@example.com>".to_string()),
+            ("Sigend-off-by".to_string(), "A <a@example.com>".to_string()),
+            ("Type".to_string(), "feature".to_string()),
+        ];
+        let rules = [
+            (
+                "Type".to_string(),
+                TrailerRule {
+                    values: ["fix".to_string()].into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                "Reviewed-by".to_string(),
+                TrailerRule {
+                    singular: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "Signed-off-by".to_string(),
+                TrailerRule {
+                    mandatory: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "Ticket".to_string(),
+                TrailerRule {
+                    mandatory: true,
+                    ..Default::default()
+                },
+            ),
+        ];
+        assert_eq!(
+            validate_trailers(&trailers, &rules),
+            [
+                "trailers['Type'] has non-configured values {'feature'}",
+                "expected trailers['Reviewed-by'] to be singular instead it has length 2",
+                "trailers['Signed-off-by'] not found but 'Signed-off-by' is mandatory (found similar field: 'Sigend-off-by')",
+                "trailers['Ticket'] not found but 'Ticket' is mandatory",
+            ]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use gix::bstr::BStr;
+
+/// Whether `path` is selected by any of `patterns`, or if there are none.
+///
+/// A pattern selects the path itself, everything below it if it's a
+/// directory, and the paths it matches as a glob, where `*` stays within a
+/// directory and `**` crosses them (e.g. `src/**/*.rs`).
+pub fn matches(patterns: &[String], path: &BStr) -> bool {
+    patterns.is_empty()
+        || patterns.iter().any(|pattern| {
+            let pattern = pattern.trim_end_matches('/');
+            path.strip_prefix(pattern.as_bytes())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(b"/"))
+                || gix::glob::wildmatch(
+                    pattern.into(),
+                    path,
+                    gix::glob::wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+                )
+        })
+}
+
+/// The pathspecs telling git the same as `matches`.
+#[cfg(feature = "git_fallback")]
+pub fn pathspecs(patterns: &[String]) -> Vec<String> {
+    patterns
+        .iter()
+        .map(|pattern| format!(":(glob){}", pattern.trim_end_matches('/')))
+        .collect()
+}
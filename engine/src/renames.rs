@@ -0,0 +1,241 @@
+use gix::object::tree::diff::ChangeDetached;
+use gix::objs::FindExt;
+
+use std::collections::HashMap;
+
+use crate::Error;
+
+/// How renames and copies are found, following the git configuration
+/// (`diff.renames`, `diff.renameLimit`) where nothing is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenameDetection {
+    /// The similarity in percent from which on a changed path is a rename or
+    /// copy (`-M<n>%`).
+    pub threshold: Option<u8>,
+    /// Whether to find copies among the modified files (`-C`).
+    pub copies: bool,
+    /// Whether to find copies among all files (`--find-copies-harder`).
+    pub copies_harder: bool,
+    /// How many files to compare at most (`-l<n>`).
+    pub limit: Option<usize>,
+}
+
+impl RenameDetection {
+    pub fn new(
+        threshold: Option<u8>,
+        copies: bool,
+        copies_harder: bool,
+        limit: Option<usize>,
+    ) -> Result<Self, Error> {
+        if let Some(threshold) = threshold.filter(|&threshold| threshold > 100) {
+            return Err(Error::Value(format!(
+                "rename_threshold is a percentage, {threshold} is more than 100"
+            )));
+        }
+        Ok(Self {
+            threshold,
+            copies,
+            copies_harder,
+            limit,
+        })
+    }
+
+    /// The tree diff options for this, `None` to leave it to the
+    /// configuration.
+    fn options(&self, repository: &gix::Repository) -> Option<gix::diff::Options> {
+        use gix::diff::rewrites::{Copies, CopySource};
+
+        if self.threshold.is_none() && !self.copies && !self.copies_harder && self.limit.is_none() {
+            return None;
+        }
+        let (configured, is_configured) =
+            gix::diff::new_rewrites(&repository.config_snapshot(), true).unwrap_or_default();
+        // Asking for anything turns detection on even if it's configured off.
+        let mut rewrites = configured.filter(|_| is_configured).unwrap_or_default();
+        if let Some(threshold) = self.threshold {
+            rewrites.percentage = Some(f32::from(threshold) / 100.0);
+        }
+        if self.copies || self.copies_harder {
+            rewrites.copies = Some(Copies {
+                source: if self.copies_harder {
+                    CopySource::FromSetOfModifiedFilesAndAllSources
+                } else {
+                    CopySource::FromSetOfModifiedFiles
+                },
+                percentage: rewrites.percentage,
+            });
+        }
+        if let Some(limit) = self.limit {
+            rewrites.limit = limit;
+        }
+        Some(gix::diff::Options::default().with_rewrites(Some(rewrites)))
+    }
+
+    /// The changes from `old` to `new` with renames and copies found like
+    /// this.
+    pub fn changes(
+        &self,
+        repository: &gix::Repository,
+        old: &gix::Tree<'_>,
+        new: &gix::Tree<'_>,
+    ) -> Vec<ChangeDetached> {
+        let mut changes = repository
+            .diff_tree_to_tree(Some(old), Some(new), self.options(repository))
+            .unwrap();
+        if self.copies || self.copies_harder {
+            restore_copy_sources(repository, old, new, &mut changes);
+        }
+        // In git's order: by path, renames and copies at their destination
+        // (gix reports the rewrites it found last).
+        changes.sort_by(|a, b| a.location().cmp(b.location()));
+        changes
+    }
+
+    /// The flags telling git the same.
+    #[cfg(feature = "git_fallback")]
+    pub fn git_flags(&self) -> Vec<String> {
+        let threshold = self
+            .threshold
+            .map(|threshold| format!("{threshold}%"))
+            .unwrap_or_default();
+        let mut flags = Vec::new();
+        if self.threshold.is_some() {
+            flags.push(format!("-M{threshold}"));
+        }
+        if self.copies || self.copies_harder {
+            flags.push(format!("-C{threshold}"));
+        }
+        if self.copies_harder {
+            flags.push("--find-copies-harder".to_string());
+        }
+        if let Some(limit) = self.limit {
+            flags.push(format!("-l{limit}"));
+        }
+        flags
+    }
+}
+
+/// Like git, take copies of modified files from what they were before and
+/// keep their modification.
+///
+/// gix finds such copies in the modified content instead, and as the
+/// modification then counts as a rewrite already it's left out.
+fn restore_copy_sources(
+    repository: &gix::Repository,
+    old: &gix::Tree<'_>,
+    new: &gix::Tree<'_>,
+    changes: &mut Vec<ChangeDetached>,
+) {
+    let Ok(plain) = repository.diff_tree_to_tree(
+        Some(old),
+        Some(new),
+        gix::diff::Options::default().with_rewrites(None),
+    ) else {
+        return;
+    };
+    for modification in plain {
+        let ChangeDetached::Modification {
+            location,
+            previous_entry_mode,
+            previous_id,
+            id,
+            ..
+        } = &modification
+        else {
+            continue;
+        };
+        if changes.iter().any(|change| {
+            matches!(change, ChangeDetached::Modification { location: other, .. } if other == location)
+        }) {
+            continue;
+        }
+
+        let mut first = None;
+        for (index, change) in changes.iter_mut().enumerate() {
+            if let ChangeDetached::Rewrite {
+                source_location,
+                source_entry_mode,
+                source_id,
+                diff,
+                copy: true,
+                ..
+            } = change
+                && source_location == location
+                && source_id == id
+            {
+                (*source_entry_mode, *source_id) = (*previous_entry_mode, *previous_id);
+                // The statistics were for the modified content.
+                *diff = None;
+                first.get_or_insert(index);
+            }
+        }
+        // The modification goes before the first copy like git has it.
+        if let Some(index) = first {
+            changes.insert(index, modification);
+        }
+    }
+}
+
+/// The bytes of `data` by the hash of the spans they are in, as
+/// `hash_chars` in git's `diffcore-delta.c` has them: a span ends after a
+/// line break or 64 bytes, and text ignores the `\r` of `\r\n`.
+fn spans(data: &[u8]) -> HashMap<u32, u64> {
+    const HASHBASE: u32 = 107927;
+
+    let is_text = !crate::binary::looks_binary(data);
+    let mut spans = HashMap::<u32, u64>::new();
+    let (mut accum1, mut accum2, mut len) = (0u32, 0u32, 0);
+    for (index, &byte) in data.iter().enumerate() {
+        if is_text && byte == b'\r' && data.get(index + 1) == Some(&b'\n') {
+            continue;
+        }
+        (accum1, accum2) = (
+            ((accum1 << 7) ^ (accum2 >> 25)).wrapping_add(u32::from(byte)),
+            (accum2 << 7) ^ (accum1 >> 25),
+        );
+        len += 1;
+        if len < 64 && byte != b'\n' {
+            continue;
+        }
+        *spans
+            .entry(accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE)
+            .or_default() += len;
+        (accum1, accum2, len) = (0, 0, 0);
+    }
+    if len > 0 {
+        *spans
+            .entry(accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE)
+            .or_default() += len;
+    }
+    spans
+}
+
+/// The `similarity index` git shows for a rename or copy from `source` to
+/// `destination` in percent, the share of the larger blob made up of the
+/// spans they have in common. `None` if either blob can't be read.
+pub fn similarity(
+    objects: &gix::OdbHandle,
+    source: &gix::oid,
+    destination: &gix::oid,
+) -> Option<u8> {
+    // git scores out of this rather than 100.
+    const MAX_SCORE: u64 = 60000;
+
+    if source == destination {
+        return Some(100);
+    }
+    let (mut source_buffer, mut destination_buffer) = (Vec::new(), Vec::new());
+    let source = objects.find_blob(source, &mut source_buffer).ok()?.data;
+    let destination = objects
+        .find_blob(destination, &mut destination_buffer)
+        .ok()?
+        .data;
+    let (source_spans, destination_spans) = (spans(source), spans(destination));
+    let copied: u64 = source_spans
+        .iter()
+        .map(|(hash, &len)| len.min(destination_spans.get(hash).copied().unwrap_or(0)))
+        .sum();
+    let max_size = source.len().max(destination.len()) as u64;
+    let score = copied * MAX_SCORE / max_size;
+    Some((score * 100 / MAX_SCORE) as u8)
+}
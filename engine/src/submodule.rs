@@ -0,0 +1,222 @@
+//! Submodules in patches, which change the commit they're at rather than any
+//! content: git's `Subproject commit <id>` lines (`--submodule=short`) or the
+//! commits in between in the submodule (`--submodule=log`).
+
+use gix::bstr::BStr;
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::{Error, unified_diff};
+
+/// How changed submodules show in a patch.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// A hunk from `Subproject commit <old>` to `Subproject commit <new>`.
+    #[default]
+    Short,
+    /// A `Submodule <path> <old>..<new>:` line followed by the subjects of
+    /// the commits in between, if the submodule's repository is available.
+    Log,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        match name {
+            "short" => Ok(Self::Short),
+            "log" => Ok(Self::Log),
+            _ => Err(Error::Value(format!(
+                "unknown submodule format {name:?}, expected short or log"
+            ))),
+        }
+    }
+
+    /// The flag telling git the same.
+    #[cfg(feature = "git_fallback")]
+    pub fn git_flag(self) -> &'static str {
+        match self {
+            Self::Short => "--submodule=short",
+            Self::Log => "--submodule=log",
+        }
+    }
+}
+
+/// The path and the commits before and after a change of a submodule (the
+/// null ID for a side without it), `None` for changes of anything else.
+pub fn change(
+    delta: &gix::object::tree::diff::ChangeDetached,
+) -> Option<(&BStr, gix::ObjectId, gix::ObjectId)> {
+    use gix::object::tree::diff::ChangeDetached;
+
+    let null = gix::index::hash::Kind::Sha1.null();
+    match delta {
+        ChangeDetached::Addition {
+            location,
+            entry_mode,
+            id,
+            ..
+        } if entry_mode.is_commit() => Some((location.as_ref(), null, *id)),
+        ChangeDetached::Deletion {
+            location,
+            entry_mode,
+            id,
+            ..
+        } if entry_mode.is_commit() => Some((location.as_ref(), *id, null)),
+        ChangeDetached::Modification {
+            location,
+            previous_entry_mode,
+            entry_mode,
+            previous_id,
+            id,
+        } if previous_entry_mode.is_commit() && entry_mode.is_commit() => {
+            Some((location.as_ref(), *previous_id, *id))
+        }
+        _ => None,
+    }
+}
+
+/// The hunk of a submodule changing from commit `older_id` to `newer_id`,
+/// which git diffs as files of a `Subproject commit <id>` line.
+pub fn hunks(
+    older_id: &gix::oid,
+    newer_id: &gix::oid,
+    format: &unified_diff::PatchFormat,
+) -> Vec<u8> {
+    let text = |id: &gix::oid| {
+        if id.is_null() {
+            String::new()
+        } else {
+            format!("Subproject commit {id}\n")
+        }
+    };
+    unified_diff::unified_diff(
+        text(older_id).as_bytes(),
+        text(newer_id).as_bytes(),
+        gix::diff::blob::Algorithm::Myers,
+        format.context_lines,
+        format.inter_hunk_context,
+        None,
+        None,
+    )
+}
+
+/// The repository of the submodule at `path` if it has been cloned.
+fn open(repository: &gix::Repository, path: &BStr) -> Option<gix::Repository> {
+    repository
+        .submodules()
+        .ok()??
+        .find(|submodule| submodule.path().is_ok_and(|location| location == path))?
+        .open()
+        .ok()?
+}
+
+/// The subjects of the commits on the first-parent line of `tip` that
+/// aren't `hidden`, with their commit times.
+fn subjects(
+    repository: &gix::Repository,
+    tip: gix::ObjectId,
+    hidden: &HashSet<gix::ObjectId>,
+) -> Option<Vec<(i64, String)>> {
+    let mut subjects = Vec::new();
+    for info in repository
+        .rev_walk([tip])
+        .first_parent_only()
+        .sorting(gix::revision::walk::Sorting::ByCommitTime(
+            gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+        ))
+        .selected(|id| !hidden.contains(id))
+        .ok()?
+    {
+        let commit = info.ok()?.object().ok()?;
+        let subject = commit.message().ok()?.summary().to_string();
+        subjects.push((commit.time().ok()?.seconds, subject));
+    }
+    Some(subjects)
+}
+
+/// Append the `--submodule=log` lines of the submodule at `path` changing
+/// from commit `older_id` to `newer_id`.
+///
+/// Like git, the header tells whether the change was a fast-forward (`..`),
+/// went back (`(rewind)`), or neither (`...`). It is followed by the
+/// subjects of the commits only on the new side (`>`) or only on the old
+/// side (`<`), newest first. These need the submodule's commits, without
+/// them the header says `(commits not present)`.
+pub fn write_log(
+    out: &mut Vec<u8>,
+    repository: &gix::Repository,
+    path: &BStr,
+    older_id: gix::ObjectId,
+    newer_id: gix::ObjectId,
+) {
+    let mut message = if older_id.is_null() {
+        Some("(new submodule)")
+    } else if newer_id.is_null() {
+        Some("(submodule deleted)")
+    } else {
+        None
+    };
+    let submodule = open(repository, path);
+    let present = |id: gix::ObjectId| {
+        submodule
+            .as_ref()
+            .is_some_and(|submodule| !id.is_null() && submodule.find_commit(id).is_ok())
+    };
+    if submodule.is_none() {
+        message.get_or_insert("(commits not present)");
+    } else if [older_id, newer_id]
+        .into_iter()
+        .any(|id| !id.is_null() && !present(id))
+    {
+        message = Some("(commits not present)");
+    }
+    let both = present(older_id) && present(newer_id);
+    let base = submodule
+        .as_ref()
+        .filter(|_| both)
+        .and_then(|submodule| submodule.merge_base(older_id, newer_id).ok())
+        .map(|base| base.detach());
+    let (forward, backward) = (base == Some(older_id), base == Some(newer_id));
+
+    write!(
+        out,
+        "Submodule {path} {}{}{}",
+        &older_id.to_string()[..7],
+        if forward || backward { ".." } else { "..." },
+        &newer_id.to_string()[..7],
+    )
+    .unwrap();
+    match message {
+        Some(message) => writeln!(out, " {message}").unwrap(),
+        None if backward => writeln!(out, " (rewind):").unwrap(),
+        None => writeln!(out, ":").unwrap(),
+    }
+    let Some(submodule) = submodule.filter(|_| both) else {
+        return;
+    };
+
+    let mut hidden = HashSet::new();
+    if let Some(base) = base {
+        for info in submodule.rev_walk([base]).all().into_iter().flatten() {
+            let Ok(info) = info else {
+                return;
+            };
+            hidden.insert(info.id);
+        }
+    }
+    let mut commits = Vec::new();
+    for (side, tip) in [('<', older_id), ('>', newer_id)] {
+        let Some(subjects) = subjects(&submodule, tip, &hidden) else {
+            return;
+        };
+        commits.extend(
+            subjects
+                .into_iter()
+                .map(|(time, subject)| (time, side, subject)),
+        );
+    }
+    commits.sort_by_key(|&(time, ..)| std::cmp::Reverse(time));
+    for (_, side, subject) in commits {
+        writeln!(out, "  {side} {subject}").unwrap();
+    }
+}
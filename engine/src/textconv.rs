@@ -0,0 +1,85 @@
+//! Converting files to text before diffing them, like the `textconv` of git's
+//! diff drivers (e.g. `*.pdf diff=pdf` with `diff.pdf.textconv=pdftotext`).
+//!
+//! Configured `textconv` commands are run by gix when it loads the blobs,
+//! the converters here are functions given for a driver instead (e.g. Python
+//! functions passed to `Repository.diff`).
+
+use gix::bstr::BStr;
+
+use std::collections::HashMap;
+
+use crate::{Error, binary, funcname};
+
+/// A function converting the content of a file to text.
+pub type Converter = Box<dyn Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync>;
+
+/// The older and the newer text of a change.
+type Texts = (Vec<u8>, Vec<u8>);
+
+/// The functions converting the content of files to text, by the name of the
+/// diff driver they're for.
+#[derive(Default)]
+pub struct Converters(HashMap<String, Converter>);
+
+impl Converters {
+    pub fn new(converters: HashMap<String, Converter>) -> Self {
+        Self(converters)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The converter of the driver of the file at `location`, if there is
+    /// one.
+    fn converter(
+        &self,
+        resource_cache: &mut gix::diff::blob::Platform,
+        objects: &gix::OdbHandle,
+        location: &BStr,
+    ) -> Option<&Converter> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let driver = funcname::driver(resource_cache, objects, location)?;
+        self.0.get(std::str::from_utf8(&driver).ok()?)
+    }
+
+    /// The texts to diff for a change from `older_id` to `newer_id`, `None`
+    /// if neither side has a converter or a side without one is binary.
+    ///
+    /// Like git, a side with a converter is diffed as text whatever its
+    /// content and a side that doesn't exist is empty. `resource_cache` is
+    /// only used for its attributes.
+    pub fn convert(
+        &self,
+        resource_cache: &mut gix::diff::blob::Platform,
+        objects: &gix::OdbHandle,
+        older_id: &gix::oid,
+        older_location: &BStr,
+        newer_id: &gix::oid,
+        newer_location: &BStr,
+    ) -> Result<Option<Texts>, Error> {
+        let sides = [(older_id, older_location), (newer_id, newer_location)]
+            .map(|(id, location)| (id, self.converter(resource_cache, objects, location)));
+        if sides.iter().all(|(_, converter)| converter.is_none()) {
+            return Ok(None);
+        }
+
+        let mut texts = Vec::new();
+        for (id, converter) in sides {
+            let mut buffer = Vec::new();
+            let data = binary::read(objects, id, &mut buffer);
+            match converter {
+                Some(_) if id.is_null() => texts.push(Vec::new()),
+                Some(converter) => texts.push(converter(data)?),
+                None if binary::looks_binary(data) => return Ok(None),
+                None => texts.push(data.to_vec()),
+            }
+        }
+        let newer = texts.pop().unwrap();
+        let older = texts.pop().unwrap();
+        Ok(Some((older, newer)))
+    }
+}
@@ -0,0 +1,63 @@
+//! The times of commits, tags, and reflog entries as git records them: seconds
+//! since the UNIX epoch and the UTC offset they were made in.
+
+/// A time as a string in the local timezone.
+pub fn local_time_string(time: &jiff::Zoned) -> String {
+    time.with_time_zone(jiff::tz::TimeZone::system())
+        .datetime()
+        .to_string()
+}
+
+/// The time of a signature header line in the signature's own UTC offset.
+pub fn signature_zoned(data: &[u8], header: &[u8]) -> jiff::Zoned {
+    zoned(signature_time(data, header).unwrap_or_default())
+}
+
+/// A time as returned by `signature_time` in its own UTC offset.
+pub fn zoned((seconds, offset): (i64, i32)) -> jiff::Zoned {
+    jiff::Timestamp::from_second(seconds)
+        .unwrap()
+        .to_zoned(jiff::tz::TimeZone::fixed(
+            jiff::tz::Offset::from_seconds(offset).unwrap_or(jiff::tz::Offset::UTC),
+        ))
+}
+
+/// Parse the `<seconds> <offset>` that git stores at the end of a signature
+/// header line (e.g. `committer`) of a raw commit object.
+///
+/// Returns the seconds since the UNIX epoch and the UTC offset in seconds.
+pub fn signature_time(data: &[u8], header: &[u8]) -> Option<(i64, i32)> {
+    let line = data
+        .split(|&byte| byte == b'\n')
+        // The headers end at the first empty line.
+        .take_while(|line| !line.is_empty())
+        .find(|line| {
+            line.strip_prefix(header)
+                .is_some_and(|rest| rest.starts_with(b" "))
+        })?;
+    identity_time(line)
+}
+
+/// Parse the `<seconds> <offset>` following the `Name <email>` of a
+/// signature, which is also how reflog entries record their time.
+pub fn identity_time(line: &[u8]) -> Option<(i64, i32)> {
+    let raw = &line[line.iter().rposition(|&byte| byte == b'>')? + 1..];
+    let raw = std::str::from_utf8(raw).ok()?.trim();
+    let (seconds, offset) = raw.split_once(' ').unwrap_or((raw, "+0000"));
+    let seconds = seconds.parse().ok()?;
+
+    let (sign, offset) = match offset.split_at_checked(1)? {
+        ("-", offset) => (-1, offset),
+        ("+", offset) => (1, offset),
+        _ => return Some((seconds, 0)),
+    };
+    let hours = offset.get(..2).and_then(|hours| hours.parse::<i32>().ok());
+    let minutes = offset
+        .get(2..4)
+        .and_then(|minutes| minutes.parse::<i32>().ok());
+    let offset = match (hours, minutes) {
+        (Some(hours), Some(minutes)) => sign * (hours * 3600 + minutes * 60),
+        _ => 0,
+    };
+    Some((seconds, offset))
+}
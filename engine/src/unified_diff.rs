@@ -0,0 +1,700 @@
+use imara_diff::Sink;
+use imara_diff::intern::{InternedInput, Interner};
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::ops::Range;
+
+use crate::{Error, binary, funcname};
+#[cfg(feature = "repository")]
+use crate::{submodule, textconv};
+
+/// The line diff algorithms, the same as `gix::diff::blob::Algorithm`.
+pub use imara_diff::Algorithm;
+
+/// Which whitespace differences lines are compared without.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IgnoreWhitespace {
+    /// All whitespace (`-w`).
+    All,
+    /// Changes in the amount of whitespace, and whitespace at the end of
+    /// lines (`-b`).
+    Change,
+    /// Whitespace at the end of lines (`--ignore-space-at-eol`).
+    Eol,
+    /// Changes whose lines are all blank (`--ignore-blank-lines`).
+    BlankLines,
+}
+
+impl IgnoreWhitespace {
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        match name {
+            "all" => Ok(Self::All),
+            "change" => Ok(Self::Change),
+            "eol" => Ok(Self::Eol),
+            "blank_lines" => Ok(Self::BlankLines),
+            _ => Err(Error::Value(format!(
+                "unknown ignore_whitespace {name:?}, expected one of all, change, eol, or blank_lines"
+            ))),
+        }
+    }
+
+    /// The flag telling git the same.
+    #[cfg(feature = "git_fallback")]
+    pub fn git_flag(self) -> &'static str {
+        match self {
+            Self::All => "--ignore-all-space",
+            Self::Change => "--ignore-space-change",
+            Self::Eol => "--ignore-space-at-eol",
+            Self::BlankLines => "--ignore-blank-lines",
+        }
+    }
+
+    /// What `line` is compared as.
+    fn key(self, line: &[u8]) -> Cow<'_, [u8]> {
+        // Like git this includes the terminator, so e.g. `a` at the end of a
+        // file without one compares equal to `a\n`.
+        let trimmed = || {
+            let end = line
+                .iter()
+                .rposition(|&byte| !is_space(byte))
+                .map_or(0, |index| index + 1);
+            &line[..end]
+        };
+        match self {
+            Self::All => Cow::Owned(
+                line.iter()
+                    .copied()
+                    .filter(|&byte| !is_space(byte))
+                    .collect(),
+            ),
+            Self::Change => {
+                let mut key = Vec::with_capacity(line.len());
+                for &byte in trimmed() {
+                    if !is_space(byte) {
+                        key.push(byte);
+                    } else if key.last() != Some(&b' ') {
+                        key.push(b' ');
+                    }
+                }
+                Cow::Owned(key)
+            }
+            Self::Eol => Cow::Borrowed(trimmed()),
+            Self::BlankLines => Cow::Borrowed(line),
+        }
+    }
+}
+
+/// Whitespace as far as C's `isspace` (and so git) is concerned.
+pub fn is_space(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || byte == b'\x0b'
+}
+
+/// How a patch is rendered.
+#[cfg(feature = "repository")]
+pub struct PatchFormat {
+    /// Prepended to the old path (`--src-prefix`).
+    pub src_prefix: String,
+    /// Prepended to the new path (`--dst-prefix`).
+    pub dst_prefix: String,
+    /// Whether to emit git's extended header (`diff --git`, modes, `index`,
+    /// renames) before the `---`/`+++` lines.
+    pub git_header: bool,
+    /// The lines of context around changes (`-U<n>`).
+    pub context_lines: u32,
+    /// Up to how many lines between hunks join them (`--inter-hunk-context`)
+    /// on top of those their context lines already join.
+    pub inter_hunk_context: u32,
+    /// Which whitespace differences don't count as changes. Files left
+    /// without hunks by this are left out like git does.
+    pub ignore_whitespace: Option<IgnoreWhitespace>,
+    /// How renames and copies are found.
+    pub renames: crate::renames::RenameDetection,
+    /// Whether binary files get a `GIT binary patch` that `git apply` can
+    /// apply (`--binary`) instead of just `Binary files ... differ`.
+    pub binary: bool,
+    /// Files with a blob of more bytes than this aren't diffed (or even
+    /// loaded), their patch is only a `diff suppressed (<n> bytes)` line.
+    pub max_file_size: Option<u64>,
+    /// The configured patterns finding the function of a hunk for its
+    /// header.
+    pub funcnames: funcname::Drivers,
+    /// The functions converting files to text by their diff driver,
+    /// taking precedence over configured `textconv` commands.
+    pub textconv: textconv::Converters,
+    /// How changed submodules show.
+    pub submodule: submodule::Format,
+}
+
+#[cfg(feature = "repository")]
+impl Default for PatchFormat {
+    fn default() -> Self {
+        Self {
+            src_prefix: "a/".into(),
+            dst_prefix: "b/".into(),
+            git_header: true,
+            context_lines: 3,
+            inter_hunk_context: 0,
+            ignore_whitespace: None,
+            renames: Default::default(),
+            binary: false,
+            max_file_size: None,
+            funcnames: Default::default(),
+            textconv: Default::default(),
+            submodule: Default::default(),
+        }
+    }
+}
+
+#[cfg(feature = "repository")]
+impl PatchFormat {
+    /// The options as part of a `diff_cache` key, `None` with `textconv`
+    /// converters whose output can't be told from their identity.
+    pub fn cache_key(&self) -> Option<String> {
+        if !self.textconv.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{:?} {:?} {} {} {} {:?} {:?} {} {:?} {:?}",
+            self.src_prefix,
+            self.dst_prefix,
+            self.git_header,
+            self.context_lines,
+            self.inter_hunk_context,
+            self.ignore_whitespace,
+            self.renames,
+            self.binary,
+            self.max_file_size,
+            self.submodule,
+        ))
+    }
+}
+
+/// A [`Sink`] that renders the hunks of a line diff in git's unified diff
+/// format.
+///
+/// Unlike the stock `gix` sink the lines are emitted byte-for-byte including
+/// their original terminators (so `\r\n`, trailing whitespace, and BOMs
+/// survive) and a missing newline at the end of either side is marked with
+/// `\ No newline at end of file`. This makes the output safe to feed to
+/// `git apply`.
+///
+/// Like git, context lines are taken from the new side, which only makes a
+/// difference when whitespace is ignored.
+pub struct UnifiedHunks<'a> {
+    before: &'a [&'a [u8]],
+    after: &'a [&'a [u8]],
+    context_lines: u32,
+    inter_hunk_context: u32,
+    ignore_blank_lines: bool,
+    /// How to find the function of a hunk, git's default rule if `None`.
+    funcname: Option<&'a funcname::Patterns>,
+    /// The changes along with whether they are ignorable (only blank lines).
+    changes: Vec<(Range<u32>, Range<u32>, bool)>,
+}
+
+impl<'a> UnifiedHunks<'a> {
+    pub fn new(
+        before: &'a [&'a [u8]],
+        after: &'a [&'a [u8]],
+        context_lines: u32,
+        inter_hunk_context: u32,
+        ignore_blank_lines: bool,
+        funcname: Option<&'a funcname::Patterns>,
+    ) -> Self {
+        Self {
+            before,
+            after,
+            context_lines,
+            inter_hunk_context,
+            ignore_blank_lines,
+            funcname,
+            changes: Vec::new(),
+        }
+    }
+
+    fn write_lines(out: &mut Vec<u8>, lines: &[&[u8]], prefix: u8) {
+        for line in lines {
+            out.push(prefix);
+            out.extend_from_slice(line);
+            if !line.ends_with(b"\n") {
+                // Only the last line of a file can be missing its terminator.
+                out.extend_from_slice(b"\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    /// The index of the last change in the hunk starting at `first`, after
+    /// moving `first` past ignorable changes too far from any other change to
+    /// be shown. `None` if there are no more changes to show.
+    ///
+    /// This follows `xdl_get_hunk` of git's xdiff so ignorable changes are
+    /// shown exactly when git shows them.
+    fn hunk(&self, first: &mut usize) -> Option<usize> {
+        let max_common = 2 * self.context_lines + self.inter_hunk_context;
+        let max_ignorable = self.context_lines;
+        let changes = &self.changes;
+
+        let mut probe = *first;
+        while probe < changes.len() && changes[probe].2 {
+            if changes
+                .get(probe + 1)
+                .is_none_or(|next| next.0.start - changes[probe].0.end >= max_ignorable)
+            {
+                *first = probe + 1;
+            }
+            probe += 1;
+        }
+        if *first >= changes.len() {
+            return None;
+        }
+
+        let (mut last, mut ignored) = (*first, 0);
+        for next in *first + 1..changes.len() {
+            let previous = next - 1;
+            let (before, after, ignore) = &changes[next];
+            let distance = before.start - changes[previous].0.end;
+            if distance > max_common {
+                break;
+            }
+            if distance < max_ignorable && (!ignore || last == previous) {
+                (last, ignored) = (next, 0);
+            } else if distance < max_ignorable {
+                ignored += after.len() as u32;
+            } else if last != previous && before.start + ignored - changes[last].0.end > max_common
+            {
+                break;
+            } else if !ignore {
+                (last, ignored) = (next, 0);
+            } else {
+                ignored += after.len() as u32;
+            }
+        }
+        Some(last)
+    }
+}
+
+/// Format one side of a hunk header the way git does: the length is omitted
+/// when it is 1 and an empty range refers to the line preceding it.
+fn hunk_range(start: u32, len: u32) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Whether `line` counts as blank for `--ignore-blank-lines`. Like git this
+/// is whether it's at most its terminator, which at the end of a file without
+/// one is any single character.
+fn is_blank(line: &[u8]) -> bool {
+    line.len() <= 1
+}
+
+impl Sink for UnifiedHunks<'_> {
+    type Out = Vec<u8>;
+
+    fn process_change(&mut self, before: Range<u32>, after: Range<u32>) {
+        let ignore = self.ignore_blank_lines
+            && self.before[before.start as usize..before.end as usize]
+                .iter()
+                .chain(&self.after[after.start as usize..after.end as usize])
+                .all(|line| is_blank(line));
+        self.changes.push((before, after, ignore));
+    }
+
+    fn finish(self) -> Self::Out {
+        let mut out = Vec::new();
+        let context = self.context_lines;
+        let mut index = 0;
+        // Like git, the function of a hunk is searched for above it in the
+        // old file down to where the search for the previous hunk started,
+        // and is the previous hunk's if there is none in between.
+        let (mut function, mut searched): (&[u8], u32) = (b"", 0);
+
+        // Follow all changes within the context (or the inter-hunk context)
+        // of each other into the same hunk.
+        while let Some(last) = self.hunk(&mut index) {
+            let (first_before, first_after, _) = &self.changes[index];
+            let (last_before, last_after, _) = &self.changes[last];
+
+            let old_start = first_before.start.saturating_sub(context);
+            let new_start = first_after.start.saturating_sub(context);
+            let old_end = (last_before.end + context).min(self.before.len() as u32);
+            let new_end = (last_after.end + context).min(self.after.len() as u32);
+
+            if let Some(name) = self.before[searched as usize..old_start as usize]
+                .iter()
+                .rev()
+                .find_map(|line| funcname::function_name(self.funcname, line))
+            {
+                function = name;
+            }
+            searched = old_start;
+
+            write!(
+                out,
+                "@@ -{} +{} @@",
+                hunk_range(old_start, old_end - old_start),
+                hunk_range(new_start, new_end - new_start),
+            )
+            .unwrap();
+            if !function.is_empty() {
+                out.push(b' ');
+                out.extend_from_slice(function);
+            }
+            out.push(b'\n');
+
+            let (mut old_position, mut new_position) = (old_start, new_start);
+            for (before, after, _) in &self.changes[index..=last] {
+                let common = (before.start - old_position).min(after.start - new_position);
+                Self::write_lines(
+                    &mut out,
+                    &self.after[new_position as usize..(new_position + common) as usize],
+                    b' ',
+                );
+                Self::write_lines(
+                    &mut out,
+                    &self.before[before.start as usize..before.end as usize],
+                    b'-',
+                );
+                Self::write_lines(
+                    &mut out,
+                    &self.after[after.start as usize..after.end as usize],
+                    b'+',
+                );
+                (old_position, new_position) = (before.end, after.end);
+            }
+            Self::write_lines(
+                &mut out,
+                &self.after[new_position as usize..new_end as usize],
+                b' ',
+            );
+
+            index = last + 1;
+        }
+
+        out
+    }
+}
+
+/// Produce the unified diff hunks (without any file headers) turning `old`
+/// into `new`.
+pub fn unified_diff(
+    old: &[u8],
+    new: &[u8],
+    algorithm: Algorithm,
+    context_lines: u32,
+    inter_hunk_context: u32,
+    ignore_whitespace: Option<IgnoreWhitespace>,
+    funcname: Option<&funcname::Patterns>,
+) -> Vec<u8> {
+    let old_lines: Vec<&[u8]> = old.split_inclusive(|&byte| byte == b'\n').collect();
+    let new_lines: Vec<&[u8]> = new.split_inclusive(|&byte| byte == b'\n').collect();
+
+    // The lines are diffed by their keys but rendered as they are.
+    let key = |line| ignore_whitespace.map_or(Cow::Borrowed(line), |ignore| ignore.key(line));
+    let mut input = InternedInput {
+        before: Vec::new(),
+        after: Vec::new(),
+        interner: Interner::new(old_lines.len() + new_lines.len()),
+    };
+    input.update_before(old_lines.iter().copied().map(key));
+    input.update_after(new_lines.iter().copied().map(key));
+
+    imara_diff::diff(
+        algorithm,
+        &input,
+        UnifiedHunks::new(
+            &old_lines,
+            &new_lines,
+            context_lines,
+            inter_hunk_context,
+            ignore_whitespace == Some(IgnoreWhitespace::BlankLines),
+            funcname,
+        ),
+    )
+}
+
+/// The unified diff of two texts as it would appear in a patch of a file
+/// changed from `old` to `new`, empty if they are the same. Like git, if
+/// either looks binary (has a NUL near the start) it's just a `Binary files
+/// ... differ` line.
+///
+/// Hunk headers name their function by `funcname` or git's default rule if
+/// `None`.
+pub fn diff_text(
+    old: &[u8],
+    new: &[u8],
+    algorithm: Algorithm,
+    old_label: &str,
+    new_label: &str,
+    funcname: Option<&funcname::Patterns>,
+) -> Vec<u8> {
+    if old != new && (binary::looks_binary(old) || binary::looks_binary(new)) {
+        return format!("Binary files {old_label} and {new_label} differ\n").into_bytes();
+    }
+    let hunks = unified_diff(old, new, algorithm, 3, 0, None, funcname);
+    if hunks.is_empty() {
+        return hunks;
+    }
+    let mut diff = format!("--- {old_label}\n+++ {new_label}\n").into_bytes();
+    diff.extend_from_slice(&hunks);
+    diff
+}
+
+/// Cut `patch` at the last line break that keeps it within `max_bytes`
+/// bytes and `max_lines` lines and, if anything was cut, note how much in a
+/// `diff truncated (<n> lines, <m> bytes omitted)` line at the end.
+pub fn truncate(mut patch: Vec<u8>, max_bytes: Option<usize>, max_lines: Option<usize>) -> Vec<u8> {
+    let mut end = 0;
+    for (index, line) in patch.split_inclusive(|&byte| byte == b'\n').enumerate() {
+        if max_lines.is_some_and(|max| index >= max)
+            || max_bytes.is_some_and(|max| end + line.len() > max)
+        {
+            break;
+        }
+        end += line.len();
+    }
+    if end == patch.len() {
+        return patch;
+    }
+
+    let lines = patch[end..].split_inclusive(|&byte| byte == b'\n').count();
+    let bytes = patch.len() - end;
+    patch.truncate(end);
+    writeln!(
+        patch,
+        "diff truncated ({lines} lines, {bytes} bytes omitted)"
+    )
+    .unwrap();
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Algorithm, diff_text, funcname, unified_diff};
+
+    use std::path::Path;
+    use std::process::Command;
+
+    /// A tiny xorshift generator so the corpus is reproducible without
+    /// pulling in a dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a [u8]]) -> &'a [u8] {
+            items[self.below(items.len())]
+        }
+    }
+
+    const WORDS: &[&[u8]] = &[
+        b"fn",
+        b"main",
+        b"let",
+        b"x",
+        b"=",
+        b"{",
+        b"}",
+        b"\t",
+        b"caf\xc3\xa9",
+        b"\xe2\x9c\x93",
+        b"",
+        b"\\",
+        b"-",
+        b"+",
+        b"@@",
+    ];
+    const TRAILING: &[&[u8]] = &[b"", b"", b"", b" ", b"\t", b"  \t"];
+    const TERMINATORS: &[&[u8]] = &[b"\n", b"\n", b"\n", b"\r\n", b"\r\n", b"\r\r\n"];
+    const BOM: &[u8] = b"\xef\xbb\xbf";
+
+    fn line(rng: &mut Rng) -> Vec<u8> {
+        let mut line = Vec::new();
+        for index in 0..rng.below(6) {
+            if index > 0 {
+                line.push(b' ');
+            }
+            line.extend_from_slice(rng.pick(WORDS));
+        }
+        line.extend_from_slice(rng.pick(TRAILING));
+        line.extend_from_slice(rng.pick(TERMINATORS));
+        line
+    }
+
+    fn lines(content: &[u8]) -> Vec<Vec<u8>> {
+        content
+            .split_inclusive(|&byte| byte == b'\n')
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+
+    fn generate(rng: &mut Rng) -> Vec<u8> {
+        let mut content = Vec::new();
+        if rng.below(4) == 0 {
+            content.extend_from_slice(BOM);
+        }
+        for _ in 0..rng.below(24) {
+            content.extend(line(rng));
+        }
+        if rng.below(3) == 0 {
+            strip_terminator(&mut content);
+        }
+        content
+    }
+
+    fn strip_terminator(content: &mut Vec<u8>) {
+        while content
+            .last()
+            .is_some_and(|&byte| byte == b'\n' || byte == b'\r')
+        {
+            content.pop();
+        }
+    }
+
+    fn mutate(rng: &mut Rng, old: &[u8]) -> Vec<u8> {
+        let mut lines = lines(old);
+        for _ in 0..=rng.below(5) {
+            let index = rng.below(lines.len() + 1);
+            match rng.below(6) {
+                0 if index < lines.len() => {
+                    lines.remove(index);
+                }
+                1 if index < lines.len() => lines[index] = line(rng),
+                2 if index < lines.len() => {
+                    // Only swap the line terminator.
+                    let mut changed = lines[index].clone();
+                    strip_terminator(&mut changed);
+                    changed.extend_from_slice(rng.pick(TERMINATORS));
+                    lines[index] = changed;
+                }
+                3 if index < lines.len() => {
+                    // Only add trailing whitespace.
+                    let mut changed = lines[index].clone();
+                    strip_terminator(&mut changed);
+                    changed.extend_from_slice(b" \t");
+                    changed.extend_from_slice(rng.pick(TERMINATORS));
+                    lines[index] = changed;
+                }
+                4 => {
+                    // Toggle the BOM.
+                    match lines.first_mut() {
+                        Some(first) if first.starts_with(BOM) => {
+                            first.drain(..BOM.len());
+                        }
+                        Some(first) => {
+                            first.splice(0..0, BOM.iter().copied());
+                        }
+                        None => lines.push(BOM.to_vec()),
+                    }
+                }
+                _ => lines.insert(index, line(rng)),
+            }
+        }
+
+        let mut new = lines.concat();
+        match rng.below(4) {
+            0 => strip_terminator(&mut new),
+            1 if !new.is_empty() && !new.ends_with(b"\n") => new.push(b'\n'),
+            _ => {}
+        }
+        new
+    }
+
+    fn git_apply(directory: &Path, patch: &[u8]) {
+        let patch_path = directory.join("change.patch");
+        std::fs::write(&patch_path, patch).unwrap();
+        let output = Command::new("git")
+            .arg("apply")
+            .arg("--whitespace=nowarn")
+            .arg(&patch_path)
+            .current_dir(directory)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .output()
+            .expect("could not run `git apply`");
+        assert!(
+            output.status.success(),
+            "`git apply` rejected the patch: {}\n{}",
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(patch),
+        );
+    }
+
+    #[test]
+    fn git_apply_round_trips_generated_corpus() {
+        let directory = tempfile::tempdir().unwrap();
+        let file = directory.path().join("file");
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..200 {
+            let old = generate(&mut rng);
+            let new = mutate(&mut rng, &old);
+
+            for algorithm in [
+                Algorithm::Histogram,
+                Algorithm::Myers,
+                Algorithm::MyersMinimal,
+            ] {
+                let hunks = unified_diff(&old, &new, algorithm, 3, 0, None, None);
+                if hunks.is_empty() {
+                    assert_eq!(old, new);
+                    continue;
+                }
+
+                let mut patch = b"diff --git a/file b/file\n--- a/file\n+++ b/file\n".to_vec();
+                patch.extend_from_slice(&hunks);
+
+                std::fs::write(&file, &old).unwrap();
+                git_apply(directory.path(), &patch);
+                assert_eq!(
+                    std::fs::read(&file).unwrap(),
+                    new,
+                    "patch did not reproduce the new blob:\n{}",
+                    String::from_utf8_lossy(&patch),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn missing_newline_is_marked() {
+        let hunks = unified_diff(b"a\nb", b"a\nb\n", Algorithm::Myers, 3, 0, None, None);
+        assert_eq!(
+            hunks,
+            b"@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+    }
+
+    #[test]
+    fn empty_ranges_follow_git() {
+        let hunks = unified_diff(b"", b"a\r\n", Algorithm::Myers, 3, 0, None, None);
+        assert_eq!(hunks, b"@@ -0,0 +1 @@\n+a\r\n");
+    }
+
+    #[test]
+    fn diff_text_names_functions_and_binaries() {
+        let old = b"fn main() {\n    a();\n    b();\n    c();\n    d();\n}\n";
+        let new = b"fn main() {\n    a();\n    b();\n    c();\n    e();\n}\n";
+        assert_eq!(
+            diff_text(old, new, Algorithm::Myers, "old", "new", funcname::builtin("rust")),
+            b"--- old\n+++ new\n@@ -2,5 +2,5 @@ fn main() {\n     a();\n     b();\n     c();\n-    d();\n+    e();\n }\n"
+        );
+        assert!(diff_text(old, old, Algorithm::Myers, "a", "b", None).is_empty());
+        assert_eq!(
+            diff_text(b"\0", b"", Algorithm::Myers, "a", "b", None),
+            b"Binary files a and b differ\n"
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! A small C ABI over the walk, diff, and check logic of `gitch-engine` for
+//! consumers that can't load the Python module (see `include/gitch.h`).
+//!
+//! Neither Python nor the Python module is linked, so everything here works
+//! without an interpreter. Strings returned to the caller are owned by it and
+//! must be released with `gitch_string_free`, bytes with `gitch_bytes_free`.
+//! On failure functions return
+//! null (or `-1`) and the reason is available from `gitch_last_error` on the
+//! same thread. Panics don't unwind into the caller, they fail the call the
+//! same way.
+
+use gitch_engine::{checks, commit, diff, mailmap, message, unified_diff};
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_uchar};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+/// What a panic was raised with, if it's a message.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown reason")
+}
+
+/// The result of `body`, or `failed` with the reason set as the last error
+/// if it fails or panics.
+fn guard<T>(failed: T, body: impl FnOnce() -> Result<T, String>) -> T {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_error(e);
+            failed
+        }
+        Err(panic) => {
+            set_error(format!("panicked: {}", panic_message(panic.as_ref())));
+            failed
+        }
+    }
+}
+
+fn into_c_string(value: String) -> Result<*mut c_char, String> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|e| e.to_string())
+}
+
+/// Read a caller provided string.
+///
+/// # Safety
+///
+/// `value` must be null or a valid NUL-terminated string.
+unsafe fn to_str<'a>(value: *const c_char) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err("unexpected null string".into());
+    }
+    // SAFETY: checked for null above, validity is up to the caller.
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map_err(|e| e.to_string())
+}
+
+/// Read a caller provided repository handle.
+///
+/// # Safety
+///
+/// `repository` must be null or a live handle.
+unsafe fn to_repository<'a>(
+    repository: *const GitchRepository,
+) -> Result<&'a GitchRepository, String> {
+    // SAFETY: validity is up to the caller.
+    unsafe { repository.as_ref() }.ok_or_else(|| "unexpected null repository".into())
+}
+
+/// An open repository handle.
+pub struct GitchRepository {
+    inner: gix::ThreadSafeRepository,
+}
+
+impl GitchRepository {
+    fn commit_id(repository: &gix::Repository, spec: &str) -> Result<gix::ObjectId, String> {
+        Ok(repository
+            .rev_parse_single(spec)
+            .map_err(|e| e.to_string())?
+            .object()
+            .map_err(|e| e.to_string())?
+            .peel_to_commit()
+            .map_err(|e| e.to_string())?
+            .id)
+    }
+
+    /// The commits reachable from the whitespace separated revision `specs`
+    /// (those prefixed with `^` are excluded) newest first.
+    fn walk(&self, specs: &str) -> Result<Vec<gix::ObjectId>, String> {
+        let repository = self.inner.to_thread_local();
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        for spec in specs.split_whitespace() {
+            let (tips, spec) = match spec.strip_prefix('^') {
+                Some(spec) => (&mut exclude, spec),
+                None => (&mut include, spec),
+            };
+            tips.push(Self::commit_id(&repository, spec)?);
+        }
+
+        let mut hidden = std::collections::HashSet::new();
+        if !exclude.is_empty() {
+            for info in repository
+                .rev_walk(exclude)
+                .all()
+                .map_err(|e| e.to_string())?
+            {
+                hidden.insert(info.map_err(|e| e.to_string())?.id);
+            }
+        }
+        let mut commits = Vec::new();
+        for info in repository
+            .rev_walk(include)
+            .sorting(gix::revision::walk::Sorting::ByCommitTime(
+                gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+            ))
+            .selected(move |id| !hidden.contains(id))
+            .map_err(|e| e.to_string())?
+        {
+            commits.push(info.map_err(|e| e.to_string())?.id);
+        }
+        Ok(commits)
+    }
+
+    fn metadata(&self, specs: &str) -> Result<String, String> {
+        let repository = self.inner.to_thread_local();
+        let mut commits = Vec::new();
+        for id in self.walk(specs)? {
+            let object = repository.find_object(id).map_err(|e| e.to_string())?;
+            commits.push(commit::metadata(&id, &object.data));
+        }
+        Ok(serde_json::Value::Array(commits).to_string())
+    }
+
+    fn diff(&self, spec: &str) -> Result<Vec<u8>, String> {
+        let repository = self.inner.to_thread_local();
+        let commit = repository
+            .find_commit(Self::commit_id(&repository, spec)?)
+            .map_err(|e| e.to_string())?;
+        let patch = diff::diff_with_parent(
+            &commit,
+            gix::diff::blob::Algorithm::Myers,
+            &unified_diff::PatchFormat::default(),
+        )
+        .map_err(|error| format!("could not diff {spec}: {error}"))?
+        .unwrap_or_default();
+        Ok(patch)
+    }
+
+    fn has_signoff(&self, spec: &str, match_author: bool) -> Result<bool, String> {
+        let repository = self.inner.to_thread_local();
+        let object = repository
+            .find_object(Self::commit_id(&repository, spec)?)
+            .map_err(|e| e.to_string())?;
+        let trailers = message::parse(&message::decode(&object.data)).trailer_list;
+        if !match_author {
+            return Ok(message::signoffs(&trailers).next().is_some());
+        }
+        let commit = gix::objs::CommitRef::from_bytes(&object.data).map_err(|e| e.to_string())?;
+        let encoding = commit.encoding.map(|encoding| encoding.to_string());
+        let decoder = message::decoder(encoding.as_deref());
+        let decode = |bytes: &[u8]| decoder.decode_without_bom_handling(bytes).0.into_owned();
+        let author = commit.author();
+        Ok(message::is_signed_off_by(
+            &trailers,
+            &decode(author.name),
+            &decode(author.email),
+            &mailmap::Mailmap::load(&repository),
+        ))
+    }
+
+    fn run_checks(&self, specs: &str, names: Option<&str>) -> Result<String, String> {
+        let names = names.map(|names| {
+            names
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        });
+        let results = checks::run_checks(&self.inner, &self.walk(specs)?, names.as_deref())
+            .map_err(|e| e.to_string())?;
+        Ok(serde_json::json!(results).to_string())
+    }
+}
+
+/// Open the repository containing `path`, null on failure.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_repository_open(path: *const c_char) -> *mut GitchRepository {
+    guard(std::ptr::null_mut(), || {
+        let path = unsafe { to_str(path) }?;
+        let repository = gix::discover(path).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(GitchRepository {
+            inner: repository.into_sync(),
+        })))
+    })
+}
+
+/// Close a repository returned by `gitch_repository_open`.
+///
+/// # Safety
+///
+/// `repository` must be null or a handle that wasn't freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_repository_free(repository: *mut GitchRepository) {
+    guard((), || {
+        if !repository.is_null() {
+            // SAFETY: the handle was created by `Box::into_raw` in
+            // `gitch_repository_open`.
+            drop(unsafe { Box::from_raw(repository) });
+        }
+        Ok(())
+    })
+}
+
+/// Walk the commits reachable from the whitespace separated revision `specs`
+/// (those prefixed with `^` are excluded) newest first, as a JSON array of
+/// commit metadata.
+///
+/// # Safety
+///
+/// `repository` must be a live handle and `specs` a valid NUL-terminated
+/// string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_walk(
+    repository: *const GitchRepository,
+    specs: *const c_char,
+) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let repository = unsafe { to_repository(repository) }?;
+        let specs = unsafe { to_str(specs) }?;
+        into_c_string(repository.metadata(specs)?)
+    })
+}
+
+/// The patch of the commit `spec` against its first parent (empty if it
+/// doesn't change anything), as produced by `Repository.diff_bytes`. Its
+/// length is stored in `len`, patches of files that aren't UTF-8 are passed
+/// on as they are and can contain NULs.
+///
+/// # Safety
+///
+/// `repository` must be a live handle, `spec` a valid NUL-terminated
+/// string, and `len` valid to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_diff(
+    repository: *const GitchRepository,
+    spec: *const c_char,
+    len: *mut usize,
+) -> *mut c_uchar {
+    guard(std::ptr::null_mut(), || {
+        let repository = unsafe { to_repository(repository) }?;
+        let spec = unsafe { to_str(spec) }?;
+        // SAFETY: validity is up to the caller.
+        let len = unsafe { len.as_mut() }.ok_or("unexpected null length")?;
+        let patch = repository.diff(spec)?.into_boxed_slice();
+        *len = patch.len();
+        Ok(Box::into_raw(patch).cast())
+    })
+}
+
+/// Whether the commit `spec` is signed off (see `Commit.has_signoff`), `1`
+/// or `0`, and `-1` on failure.
+///
+/// # Safety
+///
+/// `repository` must be a live handle and `spec` a valid NUL-terminated
+/// string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_has_signoff(
+    repository: *const GitchRepository,
+    spec: *const c_char,
+    match_author: bool,
+) -> c_int {
+    guard(-1, || {
+        let repository = unsafe { to_repository(repository) }?;
+        let spec = unsafe { to_str(spec) }?;
+        Ok(c_int::from(repository.has_signoff(spec, match_author)?))
+    })
+}
+
+/// Register the `gitch_check_fn` `function` as the check `name`, replacing
+/// one of the same name. With `needs_patch` it gets each commit's patch as
+/// well. `0` on success and `-1` on failure.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated string and `function` a
+/// `gitch_check_fn` that stays loaded and is safe to call from several
+/// threads at once for the lifetime of the process.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_register_check(
+    name: *const c_char,
+    function: Option<checks::NativeFn>,
+    needs_patch: bool,
+) -> c_int {
+    guard(-1, || {
+        let name = unsafe { to_str(name) }?;
+        let function = function.ok_or("check function is null")?;
+        // SAFETY: the caller vouches for the function.
+        unsafe {
+            checks::register_native_check(name.to_string(), function, needs_patch, Vec::new())
+        };
+        Ok(0)
+    })
+}
+
+/// Run the registered checks named in the whitespace separated `names` (all
+/// if null) on the commits `gitch_walk` walks for `specs`, as a JSON object
+/// of the violations by commit ID for commits that have any (see
+/// `Repository.run_checks`).
+///
+/// # Safety
+///
+/// `repository` must be a live handle, `specs` a valid NUL-terminated
+/// string, and `names` null or one.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_run_checks(
+    repository: *const GitchRepository,
+    specs: *const c_char,
+    names: *const c_char,
+) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let repository = unsafe { to_repository(repository) }?;
+        let specs = unsafe { to_str(specs) }?;
+        let names = if names.is_null() {
+            None
+        } else {
+            Some(unsafe { to_str(names) }?)
+        };
+        into_c_string(repository.run_checks(specs, names)?)
+    })
+}
+
+/// Release a string returned by any of the other functions.
+///
+/// # Safety
+///
+/// `value` must be null or a string returned by this library that wasn't
+/// freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_string_free(value: *mut c_char) {
+    guard((), || {
+        if !value.is_null() {
+            // SAFETY: the string was created by `CString::into_raw`.
+            drop(unsafe { CString::from_raw(value) });
+        }
+        Ok(())
+    })
+}
+
+/// Release bytes of length `len` returned by any of the other functions.
+///
+/// # Safety
+///
+/// `data` must be null or bytes returned by this library with the length it
+/// returned that weren't freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gitch_bytes_free(data: *mut c_uchar, len: usize) {
+    guard((), || {
+        if !data.is_null() {
+            // SAFETY: the bytes were created by `Box::into_raw` of a boxed
+            // slice of this length.
+            drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)) });
+        }
+        Ok(())
+    })
+}
+
+/// The reason the last call on this thread failed, null if none did. The
+/// string is owned by the library and valid until the next failing call.
+#[unsafe(no_mangle)]
+pub extern "C" fn gitch_last_error() -> *const c_char {
+    guard(std::ptr::null(), || {
+        Ok(LAST_ERROR.with(|error| {
+            error
+                .borrow()
+                .as_ref()
+                .map_or(std::ptr::null(), |error| error.as_ptr())
+        }))
+    })
+}
@@ -0,0 +1,212 @@
+//! The C ABI called the way a C program would, without Python.
+
+use gitch_engine::checks::{NativeCommit, Report};
+use gitch_ffi::{
+    gitch_bytes_free, gitch_diff, gitch_has_signoff, gitch_last_error, gitch_register_check,
+    gitch_repository_free, gitch_repository_open, gitch_run_checks, gitch_string_free, gitch_walk,
+};
+
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::path::Path;
+use std::process::Command;
+
+fn git(directory: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(directory)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_AUTHOR_NAME", "Author")
+        .env("GIT_AUTHOR_EMAIL", "author@example.com")
+        .env("GIT_AUTHOR_DATE", "1700000000 +0100")
+        .env("GIT_COMMITTER_NAME", "Committer")
+        .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+        .env("GIT_COMMITTER_DATE", "1700000000 +0100")
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {args:?} failed");
+}
+
+/// Take a string returned by the library, failing with the last error if
+/// there is none.
+fn take(value: *mut c_char) -> String {
+    assert!(!value.is_null(), "{}", last_error());
+    let string = unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .unwrap()
+        .to_string();
+    unsafe { gitch_string_free(value) };
+    string
+}
+
+/// Take bytes of length `len` returned by the library, failing with the last
+/// error if there are none.
+fn take_bytes(data: *mut u8, len: usize) -> Vec<u8> {
+    assert!(!data.is_null(), "{}", last_error());
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) }.to_vec();
+    unsafe { gitch_bytes_free(data, len) };
+    bytes
+}
+
+fn last_error() -> String {
+    let error = gitch_last_error();
+    assert!(!error.is_null());
+    unsafe { CStr::from_ptr(error) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// A `gitch_check_fn` against summaries ending in a period and patches
+/// adding a `TODO`.
+unsafe extern "C" fn check(commit: *const NativeCommit, report: Report, context: *mut c_void) {
+    // SAFETY: called as a `gitch_check_fn` with needs_patch.
+    unsafe {
+        let commit = &*commit;
+        if CStr::from_ptr(commit.summary).to_bytes().ends_with(b".") {
+            report(context, c"summary ends with a period".as_ptr());
+        }
+        let patch = std::slice::from_raw_parts(commit.patch, commit.patch_len);
+        if patch.windows(5).any(|window| window == b"+TODO") {
+            report(context, c"adds a TODO".as_ptr());
+        }
+    }
+}
+
+#[test]
+fn c_abi() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path();
+    git(path, &["init", "-q"]);
+    std::fs::write(path.join("file.txt"), "TODO\n").unwrap();
+    git(path, &["add", "."]);
+    git(path, &["commit", "-q", "-m", "Add the file."]);
+    // Not UTF-8, which the patch has to keep.
+    std::fs::write(path.join("file.txt"), b"d\xf6ne\n").unwrap();
+    git(
+        path,
+        &[
+            "commit",
+            "-q",
+            "-am",
+            "Do it\n\nSigned-off-by: Author <author@example.com>",
+        ],
+    );
+
+    let missing = CString::new(path.join("missing").to_str().unwrap()).unwrap();
+    assert!(unsafe { gitch_repository_open(missing.as_ptr()) }.is_null());
+    assert!(!last_error().is_empty());
+
+    let root = CString::new(path.to_str().unwrap()).unwrap();
+    let repository = unsafe { gitch_repository_open(root.as_ptr()) };
+    assert!(!repository.is_null(), "{}", last_error());
+
+    let commits: serde_json::Value =
+        serde_json::from_str(&take(unsafe { gitch_walk(repository, c"HEAD".as_ptr()) })).unwrap();
+    let summaries = commits
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|commit| commit["summary"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(summaries, ["Do it", "Add the file."]);
+    assert_eq!(
+        commits[0]["trailers"]["Signed-off-by"],
+        serde_json::json!(["Author <author@example.com>"])
+    );
+    let first = commits[1]["id"].as_str().unwrap();
+    let commits: serde_json::Value = serde_json::from_str(&take(unsafe {
+        gitch_walk(repository, c"HEAD ^HEAD~".as_ptr())
+    }))
+    .unwrap();
+    assert_eq!(commits.as_array().unwrap().len(), 1);
+
+    let mut len = 0;
+    let patch = take_bytes(
+        unsafe { gitch_diff(repository, c"HEAD".as_ptr(), &mut len) },
+        len,
+    );
+    assert!(patch.starts_with(b"diff --git a/file.txt b/file.txt\n"));
+    assert!(patch.ends_with(b"-TODO\n+d\xf6ne\n"));
+    assert!(unsafe { gitch_diff(repository, c"no-such-revision".as_ptr(), &mut len) }.is_null());
+    assert!(unsafe { gitch_diff(repository, c"HEAD".as_ptr(), std::ptr::null_mut()) }.is_null());
+    assert_eq!(last_error(), "unexpected null length");
+
+    assert_eq!(
+        unsafe { gitch_has_signoff(repository, c"HEAD".as_ptr(), true) },
+        1
+    );
+    assert_eq!(
+        unsafe { gitch_has_signoff(repository, c"HEAD~".as_ptr(), false) },
+        0
+    );
+
+    assert_eq!(
+        unsafe { gitch_register_check(c"c-abi".as_ptr(), Some(check), true) },
+        0
+    );
+    assert_eq!(
+        unsafe { gitch_register_check(c"c-abi-null".as_ptr(), None, false) },
+        -1
+    );
+    let results: serde_json::Value = serde_json::from_str(&take(unsafe {
+        gitch_run_checks(repository, c"HEAD".as_ptr(), c"c-abi".as_ptr())
+    }))
+    .unwrap();
+    assert_eq!(
+        results,
+        serde_json::json!({
+            first: ["c-abi: summary ends with a period", "c-abi: adds a TODO"],
+        })
+    );
+    assert!(
+        unsafe { gitch_run_checks(repository, c"HEAD".as_ptr(), c"unknown".as_ptr()) }.is_null()
+    );
+    assert!(last_error().contains("no check named \"unknown\""));
+
+    unsafe { gitch_repository_free(repository) };
+}
+
+#[test]
+fn failures_set_the_last_error_of_their_thread() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path();
+    git(path, &["init", "-q"]);
+    git(path, &["commit", "-q", "--allow-empty", "-m", "Empty"]);
+    let root = CString::new(path.to_str().unwrap()).unwrap();
+
+    std::thread::spawn(move || {
+        assert!(gitch_last_error().is_null());
+        assert!(unsafe { gitch_repository_open(std::ptr::null()) }.is_null());
+        assert_eq!(last_error(), "unexpected null string");
+
+        let repository = unsafe { gitch_repository_open(root.as_ptr()) };
+        assert!(!repository.is_null(), "{}", last_error());
+        assert!(unsafe { gitch_walk(repository, c"HEAD no-such-revision".as_ptr()) }.is_null());
+        assert!(last_error().contains("no-such-revision"));
+        assert!(unsafe { gitch_walk(std::ptr::null(), c"HEAD".as_ptr()) }.is_null());
+        assert_eq!(last_error(), "unexpected null repository");
+        assert_eq!(
+            unsafe { gitch_has_signoff(repository, c"no-such-revision".as_ptr(), false) },
+            -1
+        );
+
+        // An empty commit has an empty patch, which is still returned.
+        let mut len = 1;
+        let patch = unsafe { gitch_diff(repository, c"HEAD".as_ptr(), &mut len) };
+        assert!(take_bytes(patch, len).is_empty());
+
+        // Failures of another thread don't show up here.
+        std::thread::spawn(|| assert!(gitch_last_error().is_null()))
+            .join()
+            .unwrap();
+
+        unsafe {
+            gitch_string_free(std::ptr::null_mut());
+            gitch_bytes_free(std::ptr::null_mut(), 0);
+            gitch_repository_free(std::ptr::null_mut());
+            gitch_repository_free(repository);
+        }
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,57 @@
+/* C interface to gitch, the `gitch_ffi` library built from the `gitch-ffi`
+ * crate. It doesn't need Python. */
+#ifndef GITCH_H
+#define GITCH_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#include "gitch_check.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct GitchRepository GitchRepository;
+
+/* Open the repository containing `path`, NULL on failure. */
+GitchRepository *gitch_repository_open(const char *path);
+void gitch_repository_free(GitchRepository *repository);
+
+/* Commits reachable from the whitespace separated revision specs (`^spec`
+ * excludes) newest first, as a JSON array. Free with `gitch_string_free`. */
+char *gitch_walk(const GitchRepository *repository, const char *specs);
+
+/* The patch of a commit against its first parent as `*len` bytes, which
+ * aren't NUL-terminated and can contain NULs for files that aren't text.
+ * Free with `gitch_bytes_free`. */
+unsigned char *gitch_diff(const GitchRepository *repository, const char *spec,
+                          size_t *len);
+
+/* 1 if the commit has a matching Signed-off-by trailer, 0 if not, -1 on
+ * failure. */
+int gitch_has_signoff(const GitchRepository *repository, const char *spec,
+                      bool match_author);
+
+/* Register a check as `name`, replacing one of the same name. It must stay
+ * loaded and be thread safe. 0 on success, -1 on failure. */
+int gitch_register_check(const char *name, gitch_check_fn function,
+                         bool needs_patch);
+
+/* Run the registered checks named in the whitespace separated `names` (all
+ * if NULL) on the commits `gitch_walk` walks for `specs`, as a JSON object of
+ * the violations by commit ID. Free with `gitch_string_free`. */
+char *gitch_run_checks(const GitchRepository *repository, const char *specs,
+                       const char *names);
+
+void gitch_string_free(char *value);
+void gitch_bytes_free(unsigned char *data, size_t len);
+
+/* Why the last failing call on this thread failed, NULL if none did. */
+const char *gitch_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,45 @@
+/* Plugin interface for commit checks implemented in a shared library.
+ *
+ * Export a function of type `gitch_check_fn` and register its address with
+ * `gitch_core.register_native_check(name, address, needs_patch)`, e.g. after
+ * loading the library with `ctypes.CDLL`, or pass it to
+ * `gitch_register_check` of `gitch.h`. */
+#ifndef GITCH_CHECK_H
+#define GITCH_CHECK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Bumped whenever `gitch_check_commit` changes incompatibly. */
+#define GITCH_CHECK_ABI_VERSION 1
+
+/* The commit being checked, only valid for the duration of the call. */
+typedef struct gitch_check_commit {
+    /* `GITCH_CHECK_ABI_VERSION` of the caller. */
+    uint32_t abi_version;
+    const char *id;
+    const char *summary;
+    /* The message as stored in the commit object (see `Commit.raw_message`). */
+    const char *message;
+    /* The patch against the first parent (see `Repository.diff`), NULL unless
+     * the check was registered with `needs_patch`. */
+    const unsigned char *patch;
+    size_t patch_len;
+} gitch_check_commit;
+
+/* Report a violation, may be called any number of times per commit. */
+typedef void (*gitch_check_report)(void *context, const char *error);
+
+/* Check a commit. Called concurrently from several threads. */
+typedef void (*gitch_check_fn)(const gitch_check_commit *commit,
+                               gitch_check_report report, void *context);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
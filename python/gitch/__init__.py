@@ -1,3 +1,4 @@
+import datetime
 from pathlib import Path
 from typing import Any, Iterable
 
@@ -74,6 +75,10 @@ def attributes(object: Any) -> Any:
     def extract(o: Any) -> Any:
         if isinstance(o, (str, int, float, bool, type(None))):
             return o
+        # NOTE these have attributes of their own type (e.g. `datetime.max`)
+        # so walking them never ends.
+        elif isinstance(o, (datetime.datetime, datetime.date, datetime.time)):
+            return o.isoformat()
         elif isinstance(o, dict):
             return {k: extract(v) for k, v in o.items()}
         elif isinstance(o, (list, tuple, set)):
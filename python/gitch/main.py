@@ -27,7 +27,7 @@ import yaml
 import gitch
 import gitch.gitch_core as gitch_core
 import gitch.misc.xdg
-from gitch import attributes, did_you_mean
+from gitch import attributes
 from gitch.configuration import Capitalization, Schema
 
 GITCH_MODEL_DIR_VARIABLE = "GITCH_MODEL_DIR"
@@ -111,6 +111,14 @@ def check(
             help="Connect to the internet to download models",
         ),
     ] = False,
+    oid_maps: Annotated[
+        list[Path] | None,
+        typer.Option(
+            "--oid-map",
+            help="Mapping of commit IDs from a history rewrite ('<old> <new>' per line) "
+            "used to translate commit references in the configuration. Can be repeated.",
+        ),
+    ] = None,
 ):
     """
     Check your Git commits for compliance.
@@ -170,11 +178,27 @@ def check(
     configuration = Schema.model_validate(data)
     PROGRESS.update(task, advance=1)
 
+    # translate commit references that were made before a history rewrite.
+    for oid_map in oid_maps or []:
+        loaded = repository.load_oid_map(oid_map)
+        logging.info(f"loaded {loaded} rewritten commit IDs from {oid_map}")
+    starting_from = configuration.starting_from
+    if starting_from is not None:
+        starting_from = repository.map_old_to_new(starting_from)
+        if starting_from is None:
+            raise Exception(
+                f"'starting-from' commit '{configuration.starting_from}' was dropped by a history rewrite\n"
+                "  | context: the OID map marks the commit as removed\n"
+                "  |    help: update 'starting-from' in the configuration to a commit that still exists"
+            )
+        if starting_from != configuration.starting_from:
+            logging.info(
+                f"translated 'starting-from' {configuration.starting_from[0:7]} to {starting_from[0:7]}"
+            )
+
     commit_results = {}
 
-    commits_with_cutoff = repository.commits(
-        commit_start_cutoff=configuration.starting_from
-    )
+    commits_with_cutoff = repository.commits(commit_start_cutoff=starting_from)
 
     # NOTE: this commit prefix seems to work better:
     # the sentence "When applied this commit will" leads to more
@@ -202,29 +226,11 @@ def check(
                 if not commit.summary[0].isupper():
                     errors.append("summary does not begin with an upper case letter")
 
-        for key, trailer in configuration.trailers.items():
-            if values := commit.trailers.get(key):
-                # the commit has this key in its trailers.
-                if trailer.singular and len(values) != 1:
-                    errors.append(
-                        f"expected trailers['{key}'] to be singular instead it has length {len(values)}"
-                    )
-                if trailer.values and not values.issubset(trailer.values):
-                    errors.append(
-                        f"trailers['{key}'] has non-configured values {values - trailer.values}"
-                    )
-            else:
-                # the commit does not have this key in its trailers.
-                if trailer.mandatory:
-                    possible_match = did_you_mean(key, commit.trailers)
-                    if possible_match:
-                        errors.append(
-                            f"trailers['{key}'] not found but '{key}' is mandatory (found similar field: '{possible_match}')"
-                        )
-                    else:
-                        errors.append(
-                            f"trailers['{key}'] not found but '{key}' is mandatory"
-                        )
+        errors.extend(
+            gitch_core.validate_trailers(
+                commit.trailer_list, list(configuration.trailers.items())
+            )
+        )
         PROGRESS.update(
             task,
             advance=1,
@@ -420,6 +426,30 @@ def extract(
             help="Latest time of commit to include (as an ISO timestamp)",
         ),
     ] = None,
+    timezone: Annotated[
+        str | None,
+        typer.Option(
+            "--timezone",
+            help="Timezone used to interpret timestamps without an offset "
+            "(an IANA name like 'Europe/Paris' or a fixed offset like '+01:00'; "
+            "by default the system timezone)",
+        ),
+    ] = None,
+    stable_order: Annotated[
+        bool,
+        typer.Option(
+            "--stable-order",
+            help="Break ties between commits with the same commit time "
+            "deterministically (children first, then by commit ID)",
+        ),
+    ] = False,
+    simplify_by_decoration: Annotated[
+        bool,
+        typer.Option(
+            "--simplify-by-decoration",
+            help="Only include commits that a branch or tag points at",
+        ),
+    ] = False,
 ):
     """
     Extract information from your Git commits.
@@ -433,6 +463,9 @@ def extract(
         commit_end_cutoff=commit_end_cutoff,
         cutoff_start_timestamp=cutoff_start_timestamp,
         cutoff_end_timestamp=cutoff_end_timestamp,
+        timezone=timezone,
+        stable_order=stable_order,
+        simplify_by_decoration=simplify_by_decoration,
     )
 
     # for each commit extract the commit data
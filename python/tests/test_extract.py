@@ -0,0 +1,82 @@
+"""
+Tests of `gitch extract`, run with `python -m unittest discover python/tests`
+against an installed build (e.g. after `uv sync`).
+"""
+
+import datetime
+import json
+import os
+import subprocess
+import tempfile
+import unittest
+from pathlib import Path
+
+from gitch import attributes
+
+IDENTITY = {
+    "GIT_AUTHOR_NAME": "A U Thor",
+    "GIT_AUTHOR_EMAIL": "author@example.com",
+    "GIT_AUTHOR_DATE": "2024-02-03T04:05:06+01:00",
+    "GIT_COMMITTER_NAME": "C O Mitter",
+    "GIT_COMMITTER_EMAIL": "committer@example.com",
+    "GIT_COMMITTER_DATE": "2024-02-03T04:05:06+01:00",
+}
+
+
+def git(repository: Path, *args: str) -> str:
+    return subprocess.run(
+        ["git", "-C", str(repository), *args],
+        check=True,
+        capture_output=True,
+        text=True,
+        env={**os.environ, **IDENTITY},
+    ).stdout
+
+
+class AttributesTest(unittest.TestCase):
+    def test_dates_are_iso_strings(self):
+        moment = datetime.datetime(
+            2024, 2, 3, 4, 5, 6, tzinfo=datetime.timezone(datetime.timedelta(hours=1))
+        )
+        self.assertEqual(
+            attributes({"at": moment, "on": moment.date(), "time": moment.timetz()}),
+            {
+                "at": "2024-02-03T04:05:06+01:00",
+                "on": "2024-02-03",
+                "time": "04:05:06+01:00",
+            },
+        )
+
+
+class ExtractTest(unittest.TestCase):
+    def setUp(self):
+        directory = tempfile.TemporaryDirectory()
+        self.addCleanup(directory.cleanup)
+        self.repository = Path(directory.name)
+        git(self.repository, "init", "--quiet")
+        (self.repository / "file.txt").write_text("content\n")
+        git(self.repository, "add", "file.txt")
+        git(self.repository, "commit", "--quiet", "--message", "Add a file")
+        self.commit = git(self.repository, "rev-parse", "HEAD").strip()
+
+    def test_extract(self):
+        # Imported here as the CLI needs the optional runtime dependencies.
+        from typer.testing import CliRunner
+
+        from gitch.main import cli
+
+        # Wide enough that the JSON isn't wrapped.
+        result = CliRunner().invoke(
+            cli, ["extract", str(self.repository)], env={"COLUMNS": "100000"}
+        )
+        self.assertEqual(result.exit_code, 0, result.output)
+        commits = json.loads(result.stdout)
+        self.assertEqual(len(commits), 1)
+        self.assertEqual(commits[0]["id"], self.commit)
+        self.assertEqual(commits[0]["summary"], "Add a file")
+        self.assertEqual(commits[0]["author_time"], "2024-02-03T04:05:06+01:00")
+        self.assertEqual(commits[0]["commit_time"], "2024-02-03T04:05:06+01:00")
+
+
+if __name__ == "__main__":
+    unittest.main()
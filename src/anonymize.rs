@@ -0,0 +1,560 @@
+//! Pseudo-anonymized copies of history to share for reproducing bugs: file
+//! contents, file names, messages, and identities are replaced by tokens
+//! while tree shapes, line structure, timestamps, and parents stay as they
+//! were.
+
+use hmac::{Hmac, Mac};
+use pyo3::prelude::*;
+use sha2::Sha256;
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::{IntoPyResult, fast_export, walk};
+
+/// How many hex digits of a hash make up a token, enough to keep accidental
+/// collisions out of any realistic bug report.
+const TOKEN_LEN: usize = 12;
+
+/// What replaces the original content: tokens hashed with a secret key.
+///
+/// Without the key, tokens can't be recovered by hashing guesses like common
+/// lines (`}`) or candidate emails. The same content gets the same token
+/// within an export (and across exports with the same key), which keeps the
+/// shape of diffs.
+struct Tokens(Hmac<Sha256>);
+
+impl Tokens {
+    /// Tokens keyed by `salt`, or by a random key of their own.
+    fn new(salt: Option<&[u8]>) -> PyResult<Self> {
+        let mut random = [0; 32];
+        let salt = match salt {
+            Some(salt) => salt,
+            None => {
+                getrandom::getrandom(&mut random).into_py_result()?;
+                &random
+            }
+        };
+        Ok(Self(
+            Hmac::new_from_slice(salt).expect("HMAC takes keys of any length"),
+        ))
+    }
+
+    /// Hash `data` into a short hex token.
+    fn token(&self, data: &[u8]) -> String {
+        let mut mac = self.0.clone();
+        mac.update(data);
+        mac.finalize().into_bytes()[..TOKEN_LEN / 2]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Replace a word with a token that keeps the case of its first
+    /// character so that capitalization checks behave the same on the
+    /// anonymized history.
+    fn word(&self, word: &str) -> String {
+        let prefix = match word.chars().next() {
+            Some(first) if first.is_uppercase() => 'W',
+            Some(first) if first.is_lowercase() => 'w',
+            _ => '_',
+        };
+        format!("{prefix}{}", self.token(word.as_bytes()))
+    }
+
+    /// Replace every word of a line keeping the whitespace between them.
+    fn words(&self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len());
+        let mut word_start = None;
+        for (index, char) in line.char_indices() {
+            if char.is_whitespace() {
+                if let Some(start) = word_start.take() {
+                    out.push_str(&self.word(&line[start..index]));
+                }
+                out.push(char);
+            } else if word_start.is_none() {
+                word_start = Some(index);
+            }
+        }
+        if let Some(start) = word_start {
+            out.push_str(&self.word(&line[start..]));
+        }
+        out
+    }
+
+    /// Anonymize a commit message keeping its lines, paragraphs, word
+    /// counts, and trailer tokens (but not their values).
+    fn message(&self, message: &[u8]) -> Vec<u8> {
+        let message = String::from_utf8_lossy(message);
+        let trailers_start = message.trim_end().rfind("\n\n").map(|start| start + 2);
+
+        let mut out = String::with_capacity(message.len());
+        let mut offset = 0;
+        for line in message.split_inclusive('\n') {
+            let in_trailers = trailers_start.is_some_and(|start| offset >= start);
+            offset += line.len();
+
+            let (content, terminator) = match line.strip_suffix('\n') {
+                Some(content) => (content, "\n"),
+                None => (line, ""),
+            };
+            match content.split_once(':') {
+                Some((key, value))
+                    if in_trailers
+                        && !key.is_empty()
+                        && key
+                            .chars()
+                            .all(|char| char.is_ascii_alphanumeric() || char == '-') =>
+                {
+                    out.push_str(key);
+                    out.push(':');
+                    out.push_str(&self.words(value));
+                }
+                _ => out.push_str(&self.words(content)),
+            }
+            out.push_str(terminator);
+        }
+        out.into_bytes()
+    }
+
+    /// Anonymize blob contents line by line so that diffs between anonymized
+    /// blobs have the same shape as between the originals.
+    ///
+    /// Line terminators (including `\r\n` and a missing final newline),
+    /// leading indentation, trailing whitespace, and a leading BOM are kept
+    /// verbatim as they commonly matter when reproducing diff issues. Binary
+    /// blobs (those containing a NUL byte in their first 8000 bytes, like git
+    /// checks) are replaced by a single token followed by a NUL byte so they
+    /// stay binary.
+    fn blob(&self, data: &[u8]) -> Vec<u8> {
+        if data[..data.len().min(8000)].contains(&0) {
+            let mut out = self.token(data).into_bytes();
+            out.push(0);
+            return out;
+        }
+
+        let (bom, data) = match data.strip_prefix(b"\xef\xbb\xbf") {
+            Some(rest) => (&b"\xef\xbb\xbf"[..], rest),
+            None => (&b""[..], data),
+        };
+        let mut out = bom.to_vec();
+        for line in data.split_inclusive(|&byte| byte == b'\n') {
+            let content_end = line
+                .iter()
+                .rposition(|&byte| byte != b'\n' && byte != b'\r')
+                .map_or(0, |end| end + 1);
+            let (content, terminator) = line.split_at(content_end);
+            let start = content
+                .iter()
+                .position(|byte| !byte.is_ascii_whitespace())
+                .unwrap_or(content.len());
+            let end = content
+                .iter()
+                .rposition(|byte| !byte.is_ascii_whitespace())
+                .map_or(start, |end| end + 1);
+
+            out.extend_from_slice(&content[..start]);
+            if start < end {
+                out.extend_from_slice(self.token(&content[start..end]).as_bytes());
+            }
+            out.extend_from_slice(&content[end..]);
+            out.extend_from_slice(terminator);
+        }
+        out
+    }
+
+    /// Anonymize a file name keeping its extension. Names of git's own
+    /// control files (e.g. `.gitattributes`) are kept as is.
+    fn filename(&self, name: &[u8]) -> Vec<u8> {
+        if name.starts_with(b".git") {
+            return name.to_vec();
+        }
+        let extension = name
+            .iter()
+            .rposition(|&byte| byte == b'.')
+            .filter(|&dot| dot > 0)
+            .map_or(&b""[..], |dot| &name[dot..]);
+        let mut out = self.token(name).into_bytes();
+        out.extend_from_slice(extension);
+        out
+    }
+
+    /// Replace the identity of a raw `author`/`committer` header value
+    /// keeping its timestamp and offset byte-for-byte.
+    fn signature(&self, value: &[u8]) -> Vec<u8> {
+        let (identity, time) = match value.iter().rposition(|&byte| byte == b'>') {
+            Some(end) => value.split_at(end + 1),
+            None => (value, &b""[..]),
+        };
+        let (name, email) = match identity.iter().position(|&byte| byte == b'<') {
+            Some(start) => identity.split_at(start),
+            None => (identity, &b""[..]),
+        };
+        let name = self.token(name.trim_ascii());
+        let email = self.token(email);
+        format!("Anonymous {name} <{email}@example.invalid>")
+            .into_bytes()
+            .into_iter()
+            .chain(time.iter().copied())
+            .collect()
+    }
+}
+
+/// A commit object as it is, to write its headers byte for byte.
+struct RawCommit(Vec<u8>);
+
+impl gix::objs::WriteTo for RawCommit {
+    fn write_to(&self, out: &mut dyn std::io::Write) -> std::io::Result<()> {
+        out.write_all(&self.0)
+    }
+
+    fn kind(&self) -> gix::object::Kind {
+        gix::object::Kind::Commit
+    }
+
+    fn size(&self) -> u64 {
+        self.0.len() as u64
+    }
+}
+
+struct Exporter<'a> {
+    source: &'a gix::Repository,
+    target: &'a gix::Repository,
+    tokens: Tokens,
+    blobs: HashMap<gix::ObjectId, gix::ObjectId>,
+    trees: HashMap<gix::ObjectId, gix::ObjectId>,
+}
+
+impl Exporter<'_> {
+    fn blob(&mut self, id: gix::ObjectId) -> PyResult<gix::ObjectId> {
+        if let Some(new_id) = self.blobs.get(&id) {
+            return Ok(*new_id);
+        }
+        let blob = self.source.find_object(id).into_py_result()?;
+        let new_id = self
+            .target
+            .write_blob(self.tokens.blob(&blob.data))
+            .into_py_result()?
+            .detach();
+        self.blobs.insert(id, new_id);
+        Ok(new_id)
+    }
+
+    fn tree(&mut self, id: gix::ObjectId) -> PyResult<gix::ObjectId> {
+        if let Some(new_id) = self.trees.get(&id) {
+            return Ok(*new_id);
+        }
+        let object = self.source.find_object(id).into_py_result()?;
+        let mut entries = Vec::new();
+        for entry in gix::objs::TreeRef::from_bytes(&object.data)
+            .into_py_result()?
+            .entries
+        {
+            let oid = if entry.mode.is_tree() {
+                self.tree(entry.oid.to_owned())?
+            } else if entry.mode.is_commit() {
+                // Submodule commits live in another repository, keep them.
+                entry.oid.to_owned()
+            } else {
+                self.blob(entry.oid.to_owned())?
+            };
+            entries.push(gix::objs::tree::Entry {
+                mode: entry.mode,
+                filename: self.tokens.filename(entry.filename).into(),
+                oid,
+            });
+        }
+        // Renaming changes the order git requires the entries to be in.
+        entries.sort();
+
+        let new_id = self
+            .target
+            .write_object(gix::objs::Tree { entries })
+            .into_py_result()?
+            .detach();
+        self.trees.insert(id, new_id);
+        Ok(new_id)
+    }
+
+    /// Write the anonymized version of a commit with the given (already
+    /// anonymized) parents.
+    fn commit(&mut self, id: gix::ObjectId, parents: &[gix::ObjectId]) -> PyResult<gix::ObjectId> {
+        let object = self.source.find_object(id).into_py_result()?;
+        let decoded = gix::objs::CommitRef::from_bytes(&object.data).into_py_result()?;
+        let tree = self.tree(decoded.tree())?;
+
+        // Work on the raw headers so that timestamps survive exactly as they
+        // were written, signatures and other extra headers are dropped.
+        let mut data = format!("tree {tree}\n").into_bytes();
+        for parent in parents {
+            data.extend_from_slice(format!("parent {parent}\n").as_bytes());
+        }
+        let (headers, message) = match object.data.windows(2).position(|pair| pair == b"\n\n") {
+            Some(end) => (&object.data[..end], &object.data[end + 2..]),
+            None => (&object.data[..], &b""[..]),
+        };
+        for line in headers.split(|&byte| byte == b'\n') {
+            for header in [&b"author "[..], b"committer "] {
+                if let Some(value) = line.strip_prefix(header) {
+                    data.extend_from_slice(header);
+                    data.extend_from_slice(&self.tokens.signature(value));
+                    data.push(b'\n');
+                }
+            }
+        }
+        data.push(b'\n');
+        data.extend_from_slice(&self.tokens.message(message));
+
+        Ok(self
+            .target
+            .write_object(RawCommit(data))
+            .into_py_result()?
+            .detach())
+    }
+}
+
+/// Write a new bare repository to `path` containing an anonymized copy of
+/// the commits of `range` (revision specs like `git fast-export` takes them)
+/// and point its HEAD at the anonymized version of the newest one.
+///
+/// Tokens are keyed by `salt` if given, so exports with the same one match,
+/// and otherwise by a random key of the export. Parents outside of the range
+/// are kept as parentless boundary commits so that every exported commit
+/// still diffs against the same (anonymized) parent tree. Returns the ID of
+/// the new HEAD commit.
+pub(crate) fn export(
+    repository: &gix::Repository,
+    range: &str,
+    path: &Path,
+    salt: Option<&[u8]>,
+) -> PyResult<Option<String>> {
+    let specs = fast_export::specs(range)?;
+    let (include, exclude) = walk::resolve_tips(repository, &specs)?;
+    let commits = walk::walk(
+        repository,
+        include,
+        exclude,
+        gix::revision::walk::Sorting::ByCommitTime(
+            gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+        ),
+    )?
+    .map(|info| (info.id, info.parent_ids.to_vec()))
+    .collect::<Vec<_>>();
+
+    let target = gix::init_bare(path).into_py_result()?;
+    let mut exporter = Exporter {
+        source: repository,
+        target: &target,
+        tokens: Tokens::new(salt)?,
+        blobs: HashMap::new(),
+        trees: HashMap::new(),
+    };
+    let parents = commits.iter().cloned().collect::<HashMap<_, _>>();
+
+    // Write parents before children, the walk order is by commit time which
+    // doesn't guarantee that when clocks were skewed.
+    let mut mapping = HashMap::new();
+    let mut visited = HashSet::new();
+    for (id, _) in commits.iter().rev() {
+        let mut stack = vec![(*id, false)];
+        while let Some((id, expanded)) = stack.pop() {
+            let Some(commit_parents) = parents.get(&id) else {
+                // A boundary commit.
+                if let std::collections::hash_map::Entry::Vacant(entry) = mapping.entry(id) {
+                    entry.insert(exporter.commit(id, &[])?);
+                }
+                continue;
+            };
+            if expanded {
+                let new_parents = commit_parents
+                    .iter()
+                    .map(|parent| mapping[parent])
+                    .collect::<Vec<_>>();
+                mapping.insert(id, exporter.commit(id, &new_parents)?);
+                continue;
+            }
+            if !visited.insert(id) {
+                continue;
+            }
+            stack.push((id, true));
+            for parent in commit_parents {
+                if !mapping.contains_key(parent) {
+                    stack.push((*parent, false));
+                }
+            }
+        }
+    }
+
+    let Some(head) = commits.first().map(|(id, _)| mapping[id]) else {
+        return Ok(None);
+    };
+    target
+        .edit_reference(gix::refs::transaction::RefEdit {
+            change: gix::refs::transaction::Change::Update {
+                log: gix::refs::transaction::LogChange {
+                    message: "gitch: anonymized export".into(),
+                    ..Default::default()
+                },
+                expected: gix::refs::transaction::PreviousValue::Any,
+                new: gix::refs::Target::Object(head),
+            },
+            name: "HEAD".try_into().into_py_result()?,
+            deref: true,
+        })
+        .into_py_result()?;
+    Ok(Some(head.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::export;
+    use crate::tests::git;
+
+    use std::collections::HashMap;
+
+    /// The shape of a tree: the modes of its entries with the shapes of
+    /// subtrees, whatever their names.
+    fn shape(repository: &gix::Repository, id: gix::ObjectId) -> Vec<String> {
+        let tree = repository.find_tree(id).unwrap();
+        let mut shape = tree
+            .decode()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|entry| match entry.mode.is_tree() {
+                true => format!("tree {:?}", shape(repository, entry.oid.to_owned())),
+                false => format!("{:?}", entry.mode.kind()),
+            })
+            .collect::<Vec<_>>();
+        shape.sort();
+        shape
+    }
+
+    /// The `author` and `committer` times of a commit as they are written.
+    fn times(data: &[u8]) -> Vec<Vec<u8>> {
+        data.split(|&byte| byte == b'\n')
+            .take_while(|line| !line.is_empty())
+            .filter(|line| line.starts_with(b"author ") || line.starts_with(b"committer "))
+            .map(|line| line[line.iter().rposition(|&byte| byte == b'>').unwrap()..].to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn export_keeps_shapes_times_and_parents() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("source");
+        std::fs::create_dir(&path).unwrap();
+        git(&path, &["init", "-q", "-b", "main"], b"");
+        std::fs::create_dir(path.join("src")).unwrap();
+        std::fs::write(path.join("src/main.rs"), "fn main() {\n}\n").unwrap();
+        std::fs::write(path.join("README"), "secret\n").unwrap();
+        git(&path, &["add", "."], b"");
+        git(
+            &path,
+            &["commit", "-q", "--date", "1600000000 -0730", "-m", "Add"],
+            b"",
+        );
+        git(&path, &["checkout", "-q", "-b", "side"], b"");
+        std::fs::write(path.join("src/lib.rs"), "}\n").unwrap();
+        git(&path, &["add", "."], b"");
+        git(&path, &["commit", "-q", "-m", "Side"], b"");
+        git(&path, &["checkout", "-q", "main"], b"");
+        std::fs::write(path.join("README"), "secret\nmore\n").unwrap();
+        git(&path, &["commit", "-q", "-am", "Main"], b"");
+        git(
+            &path,
+            &["merge", "-q", "--no-ff", "-m", "Merge", "side"],
+            b"",
+        );
+
+        let source = gix::open(&path).unwrap();
+        let out = directory.path().join("export");
+        let head = export(&source, "HEAD", &out, Some(b"salt"))
+            .unwrap()
+            .unwrap();
+        let target = gix::open(&out).unwrap();
+        assert_eq!(target.head_id().unwrap().to_string(), head);
+
+        // Pair up the commits from the heads down, parent by parent.
+        let mut pairs = HashMap::new();
+        let mut stack = vec![(
+            source.head_id().unwrap().detach(),
+            target.head_id().unwrap().detach(),
+        )];
+        while let Some((original, exported)) = stack.pop() {
+            if let Some(previous) = pairs.insert(original, exported) {
+                assert_eq!(previous, exported);
+                continue;
+            }
+            let original = source.find_commit(original).unwrap();
+            let exported = target.find_commit(exported).unwrap();
+            assert_eq!(times(&original.data), times(&exported.data));
+            assert_eq!(
+                shape(&source, original.tree_id().unwrap().detach()),
+                shape(&target, exported.tree_id().unwrap().detach())
+            );
+            let original_parents = original
+                .parent_ids()
+                .map(|id| id.detach())
+                .collect::<Vec<_>>();
+            let exported_parents = exported
+                .parent_ids()
+                .map(|id| id.detach())
+                .collect::<Vec<_>>();
+            assert_eq!(original_parents.len(), exported_parents.len());
+            stack.extend(original_parents.into_iter().zip(exported_parents));
+        }
+        assert_eq!(pairs.len(), 4);
+        assert_eq!(
+            pairs
+                .values()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            4
+        );
+
+        let readme = |repository: &gix::Repository| {
+            let tree = repository.head_commit().unwrap().tree().unwrap();
+            tree.decode()
+                .unwrap()
+                .entries
+                .iter()
+                .filter(|entry| !entry.mode.is_tree())
+                .map(|entry| repository.find_object(entry.oid).unwrap().data.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(
+            !readme(&target)
+                .iter()
+                .any(|data| data.windows(6).any(|window| window == b"secret"))
+        );
+
+        // The same salt gives the same export, another (or a random) one a
+        // different one.
+        let again = directory.path().join("again");
+        assert_eq!(
+            export(&source, "HEAD", &again, Some(b"salt"))
+                .unwrap()
+                .unwrap(),
+            head
+        );
+        let random = directory.path().join("random");
+        assert_ne!(
+            export(&source, "HEAD", &random, None).unwrap().unwrap(),
+            head
+        );
+        let range = directory.path().join("range");
+        let side = export(&source, "main~..side", &range, Some(b"salt"))
+            .unwrap()
+            .unwrap();
+        let exported = gix::open(&range).unwrap();
+        let side = exported
+            .find_commit(gix::ObjectId::from_hex(side.as_bytes()).unwrap())
+            .unwrap();
+        // The parent outside of the range is kept as a boundary commit.
+        let parents = side.parent_ids().collect::<Vec<_>>();
+        assert_eq!(parents.len(), 1);
+        let boundary = exported.find_commit(parents[0]).unwrap();
+        assert_eq!(boundary.parent_ids().count(), 0);
+    }
+}
@@ -0,0 +1,704 @@
+//! Applying patches like `git apply`, to the working tree, the index (like
+//! `git apply --cached`), or a tree.
+
+use gix::bstr::{BStr, BString, ByteSlice};
+use gix::index::entry::{Flags, Mode, Stage, Stat};
+use gix::objs::tree::EntryKind;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::paged::FileDiff;
+use crate::{IntoPyResult, Repository, binary, lock, visit, workdir};
+
+/// A file of a patch that doesn't apply.
+#[pyclass]
+pub struct ApplyConflict {
+    #[pyo3(get)]
+    pub path: String,
+    /// The index of the hunk of the file that doesn't apply, `None` if it's
+    /// the file itself (e.g. it doesn't exist).
+    #[pyo3(get)]
+    pub hunk: Option<usize>,
+    #[pyo3(get)]
+    pub message: String,
+}
+
+/// What applying a patch with `Repository.apply` did.
+#[pyclass]
+pub struct ApplyResult {
+    /// Whether the patch was applied. It's all or nothing: with conflicts
+    /// nothing is changed.
+    #[pyo3(get)]
+    pub applied: bool,
+    /// The paths the patch changes (including those it removes), in the
+    /// order of the patch. With conflicts, those of the files that apply.
+    #[pyo3(get)]
+    pub paths: Vec<String>,
+    /// The ID of the patched tree when applying to a tree, otherwise `None`.
+    #[pyo3(get)]
+    pub tree_id: Option<String>,
+    #[pyo3(get)]
+    pub conflicts: Vec<Py<ApplyConflict>>,
+}
+
+/// A file the way a patch sees it.
+#[derive(Clone)]
+struct File {
+    kind: EntryKind,
+    /// The content, for submodules the line git diffs them as.
+    data: Vec<u8>,
+}
+
+impl File {
+    fn submodule(id: gix::ObjectId) -> Self {
+        Self {
+            kind: EntryKind::Commit,
+            data: format!("Subproject commit {id}\n").into_bytes(),
+        }
+    }
+
+    /// The ID of the file, written as a blob to `repository` unless it's a
+    /// submodule.
+    fn id(&self, repository: &gix::Repository) -> PyResult<gix::ObjectId> {
+        match self.kind {
+            EntryKind::Commit => Ok(submodule_commit(&self.data)
+                .expect("submodules are checked to have a commit when patched")),
+            _ => Ok(repository.write_blob(&self.data).into_py_result()?.detach()),
+        }
+    }
+}
+
+/// The commit of a submodule from the line git diffs it as.
+fn submodule_commit(data: &[u8]) -> Option<gix::ObjectId> {
+    let id = data.strip_prefix(b"Subproject commit ")?.trim_end();
+    gix::ObjectId::from_hex(id).ok()
+}
+
+/// What a patch is applied to.
+enum Target<'repo> {
+    Worktree {
+        root: PathBuf,
+        pipeline: Box<gix::filter::Pipeline<'repo>>,
+        index: gix::worktree::IndexPersistedOrInMemory,
+        capabilities: gix::fs::Capabilities,
+    },
+    Index {
+        index: gix::index::File,
+        lock: gix::lock::File,
+    },
+    Tree(gix::Tree<'repo>),
+}
+
+impl Target<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Worktree { .. } => "working tree",
+            Self::Index { .. } => "index",
+            Self::Tree(_) => "tree",
+        }
+    }
+
+    /// The file at `path`, `None` if there is none.
+    fn file(&mut self, repository: &gix::Repository, path: &BStr) -> PyResult<Option<File>> {
+        let entry = match self {
+            Self::Worktree {
+                root,
+                pipeline,
+                index,
+                capabilities,
+            } => {
+                let entry = index.entry_by_path_and_stage(path, Stage::Unconflicted);
+                // Submodules are at the commit of the index like for git.
+                if let Some(entry) = entry
+                    && entry.mode == Mode::COMMIT
+                {
+                    return Ok(Some(File::submodule(entry.id)));
+                }
+                let location = root.join(gix::path::from_bstr(path));
+                let Ok(metadata) = gix::index::fs::Metadata::from_path_no_follow(&location) else {
+                    return Ok(None);
+                };
+                let Some((kind, data)) =
+                    workdir::read(pipeline, index, *capabilities, &location, path, &metadata)?
+                else {
+                    return Ok(None);
+                };
+                let kind = entry.map_or(kind, |entry| {
+                    workdir::worktree_kind(workdir::kind(entry.mode), kind, *capabilities)
+                });
+                return Ok(Some(File { kind, data }));
+            }
+            Self::Index { index, .. } => index
+                .entry_by_path_and_stage(path, Stage::Unconflicted)
+                .map(|entry| (workdir::kind(entry.mode), entry.id)),
+            Self::Tree(tree) => tree
+                .lookup_entry(workdir::components(path))
+                .into_py_result()?
+                .map(|entry| (entry.mode().kind(), entry.object_id())),
+        };
+        Ok(match entry {
+            None | Some((EntryKind::Tree, _)) => None,
+            Some((EntryKind::Commit, id)) => Some(File::submodule(id)),
+            Some((kind, id)) => Some(File {
+                kind,
+                data: repository.find_blob(id).into_py_result()?.detach().data,
+            }),
+        })
+    }
+
+    /// Write the patched `files`, removing those that are `None`. Returns
+    /// the ID of the patched tree when applying to one.
+    fn write(
+        self,
+        repository: &gix::Repository,
+        files: &[(BString, Option<File>)],
+    ) -> PyResult<Option<gix::ObjectId>> {
+        match self {
+            Self::Worktree {
+                root,
+                mut pipeline,
+                capabilities,
+                ..
+            } => {
+                // Files are removed first in case directories take their place.
+                for (path, _) in files.iter().filter(|(_, file)| file.is_none()) {
+                    remove(&root, &root.join(gix::path::from_bstr(path.as_bstr())))?;
+                }
+                for (path, file) in files {
+                    if let Some(file) = file {
+                        let location = root.join(gix::path::from_bstr(path.as_bstr()));
+                        write(&mut pipeline, capabilities, &location, path.as_ref(), file)?;
+                    }
+                }
+                Ok(None)
+            }
+            Self::Index {
+                mut index,
+                mut lock,
+            } => {
+                for (path, file) in files {
+                    index.remove_entries(|_, entry_path, _| entry_path == path);
+                    if let Some(file) = file {
+                        let mode = match file.kind {
+                            EntryKind::Tree => Mode::DIR,
+                            EntryKind::Blob => Mode::FILE,
+                            EntryKind::BlobExecutable => Mode::FILE_EXECUTABLE,
+                            EntryKind::Link => Mode::SYMLINK,
+                            EntryKind::Commit => Mode::COMMIT,
+                        };
+                        index.dangerously_push_entry(
+                            Stat::default(),
+                            file.id(repository)?,
+                            Flags::empty(),
+                            mode,
+                            path.as_ref(),
+                        );
+                    }
+                }
+                index.sort_entries();
+                // The trees the index keeps track of are out of date now.
+                index.remove_tree();
+                let mut out = std::io::BufWriter::new(&mut lock);
+                index
+                    .write_to(&mut out, Default::default())
+                    .into_py_result()?;
+                out.flush().into_py_result()?;
+                drop(out);
+                lock.commit()
+                    .map_err(|error| error.error)
+                    .into_py_result()?;
+                Ok(None)
+            }
+            Self::Tree(tree) => {
+                let mut editor = gix::objs::tree::Editor::new(
+                    tree.decode().into_py_result()?.into_owned(),
+                    &repository.objects,
+                    repository.object_hash(),
+                );
+                for (path, file) in files {
+                    let components = workdir::components(path.as_ref());
+                    match file {
+                        Some(file) => editor.upsert(components, file.kind, file.id(repository)?),
+                        None => editor.remove(components),
+                    }
+                    .into_py_result()?;
+                }
+                editor
+                    .write(|tree| repository.write_object(tree).map(gix::Id::detach))
+                    .into_py_result()
+                    .map(Some)
+            }
+        }
+    }
+}
+
+/// Remove the file at `location` and the directories it leaves empty up to
+/// `root`. Submodules are only removed if they're empty, like git does.
+fn remove(root: &Path, location: &Path) -> PyResult<()> {
+    match std::fs::symlink_metadata(location) {
+        Ok(metadata) if metadata.is_dir() => {
+            let _ = std::fs::remove_dir(location);
+        }
+        Ok(_) => std::fs::remove_file(location).into_py_result()?,
+        Err(_) => {}
+    }
+    for directory in location.ancestors().skip(1) {
+        if directory == root || std::fs::remove_dir(directory).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Write `file` to `location` in the working tree, with the filters of its
+/// attributes applied.
+fn write(
+    pipeline: &mut gix::filter::Pipeline<'_>,
+    capabilities: gix::fs::Capabilities,
+    location: &Path,
+    path: &BStr,
+    file: &File,
+) -> PyResult<()> {
+    if let Some(parent) = location.parent() {
+        std::fs::create_dir_all(parent).into_py_result()?;
+    }
+    // Submodules are left as they are, there only has to be a directory
+    // for them.
+    if file.kind == EntryKind::Commit {
+        if !location.is_dir() {
+            std::fs::create_dir(location).into_py_result()?;
+        }
+        return Ok(());
+    }
+    if std::fs::symlink_metadata(location).is_ok() {
+        std::fs::remove_file(location).into_py_result()?;
+    }
+    if file.kind == EntryKind::Link && capabilities.symlink {
+        let target = gix::path::from_bstr(file.data.as_bstr());
+        return gix::fs::symlink::create(&target, location).into_py_result();
+    }
+
+    let mut data = Vec::new();
+    pipeline
+        .convert_to_worktree(
+            &file.data,
+            path,
+            gix::filter::plumbing::driver::apply::Delay::Forbid,
+        )
+        .into_py_result()?
+        .read_to_end(&mut data)
+        .into_py_result()?;
+    std::fs::write(location, data).into_py_result()?;
+    #[cfg(unix)]
+    if file.kind == EntryKind::BlobExecutable && capabilities.executable_bit {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Executable by whoever can read it, like git does.
+        let mut permissions = std::fs::metadata(location).into_py_result()?.permissions();
+        permissions.set_mode(permissions.mode() | (permissions.mode() & 0o444) >> 2);
+        std::fs::set_permissions(location, permissions).into_py_result()?;
+    }
+    Ok(())
+}
+
+/// The index of the hunk that doesn't apply (if it's a hunk) and what's
+/// wrong.
+type Conflict = (Option<usize>, String);
+
+/// Apply the `files` of a patch to `to`: the working tree (`worktree`), the
+/// index (`index`), or the tree of any other revision, which is written to
+/// the object database.
+pub(crate) fn apply(
+    py: Python<'_>,
+    repository: &gix::Repository,
+    files: &[Py<FileDiff>],
+    to: &str,
+    wait_for_lock: Option<f64>,
+) -> PyResult<ApplyResult> {
+    let mut target = match to {
+        "worktree" => {
+            let Some(root) = repository.workdir() else {
+                return Err(PyValueError::new_err(
+                    "bare repositories have no working tree",
+                ));
+            };
+            let (pipeline, index) = repository.filter_pipeline(None).into_py_result()?;
+            Target::Worktree {
+                root: root.to_path_buf(),
+                pipeline: Box::new(pipeline),
+                index,
+                capabilities: repository.filesystem_options().into_py_result()?,
+            }
+        }
+        "index" => {
+            // Locked before it's read so that nothing changes in between.
+            let lock = lock::lock_index(repository, wait_for_lock)?;
+            let index = gix::index::File::clone(&*repository.index_or_empty().into_py_result()?);
+            Target::Index { index, lock }
+        }
+        rev => Target::Tree(Repository::rev_tree(repository, rev)?),
+    };
+
+    // The files as patched so far, later files of the patch may change
+    // them again.
+    let mut patched: Vec<(BString, Option<File>)> = Vec::new();
+    let mut conflicts = Vec::new();
+    let name = target.name();
+    for file in files {
+        let file = file.borrow(py);
+        let (previous_path, path) = (
+            BString::from(file.previous_path.as_str()),
+            BString::from(file.path.as_str()),
+        );
+        let mut current = |path: &BString| match patched.iter().find(|(other, _)| other == path) {
+            Some((_, file)) => Ok(file.clone()),
+            None => target.file(repository, path.as_ref()),
+        };
+
+        let conflict = if file.kind != "added" && current(&previous_path)?.is_none() {
+            Err((
+                &previous_path,
+                None,
+                format!("does not exist in the {name}"),
+            ))
+        } else if matches!(file.kind, "added" | "renamed" | "copied") && current(&path)?.is_some() {
+            Err((&path, None, format!("already exists in the {name}")))
+        } else {
+            let old = match file.kind {
+                "added" => None,
+                _ => current(&previous_path)?,
+            };
+            patch_file(py, repository, &file, old.as_ref())
+                .map_err(|(hunk, message)| (&path, hunk, message))
+        };
+        match conflict {
+            Ok(new) => {
+                if file.kind == "renamed" {
+                    set(&mut patched, previous_path, None);
+                }
+                set(&mut patched, path, new);
+            }
+            Err((path, hunk, message)) => conflicts.push(Py::new(
+                py,
+                ApplyConflict {
+                    path: path.to_string(),
+                    hunk,
+                    message,
+                },
+            )?),
+        }
+    }
+
+    let applied = conflicts.is_empty();
+    let tree_id = if applied {
+        target.write(repository, &patched)?
+    } else {
+        None
+    };
+    Ok(ApplyResult {
+        applied,
+        paths: patched.iter().map(|(path, _)| path.to_string()).collect(),
+        tree_id: tree_id.map(|id| id.to_string()),
+        conflicts,
+    })
+}
+
+/// Remember `file` as the patched file at `path`.
+fn set(patched: &mut Vec<(BString, Option<File>)>, path: BString, file: Option<File>) {
+    match patched.iter_mut().find(|(other, _)| *other == path) {
+        Some((_, other)) => *other = file,
+        None => patched.push((path, file)),
+    }
+}
+
+/// The kind of tree entry a file with the octal `mode` of a patch is.
+fn entry_kind(mode: &str) -> Option<EntryKind> {
+    let mode = u32::from_str_radix(mode, 8).ok()?;
+    match mode & 0o170000 {
+        0o100000 if mode & 0o111 != 0 => Some(EntryKind::BlobExecutable),
+        0o100000 => Some(EntryKind::Blob),
+        0o120000 => Some(EntryKind::Link),
+        0o160000 => Some(EntryKind::Commit),
+        _ => None,
+    }
+}
+
+fn describe(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Tree => "directory",
+        EntryKind::Blob | EntryKind::BlobExecutable => "file",
+        EntryKind::Link => "symlink",
+        EntryKind::Commit => "submodule",
+    }
+}
+
+/// The file `old` (`None` if there is none) after the change of `file`,
+/// `None` if it removes it.
+fn patch_file(
+    py: Python<'_>,
+    repository: &gix::Repository,
+    file: &FileDiff,
+    old: Option<&File>,
+) -> Result<Option<File>, Conflict> {
+    let mode = |mode: &Option<String>| {
+        mode.as_deref()
+            .map(|mode| entry_kind(mode).ok_or_else(|| (None, format!("has unknown mode {mode}"))))
+            .transpose()
+    };
+    let (previous_kind, kind) = (mode(&file.previous_mode)?, mode(&file.mode)?);
+    // Only the executable bit may differ from what the patch expects.
+    if let (Some(old), Some(previous_kind)) = (old, previous_kind)
+        && describe(old.kind) != describe(previous_kind)
+    {
+        return Err((
+            None,
+            format!(
+                "is a {} but the patch changes a {}",
+                describe(old.kind),
+                describe(previous_kind)
+            ),
+        ));
+    }
+
+    let old_data = old.map_or(&[][..], |old| old.data.as_slice());
+    let data = if file.binary {
+        binary_data(repository, file, old_data)?
+    } else {
+        let hunks: Vec<_> = file.hunks.iter().map(|hunk| hunk.borrow(py)).collect();
+        let mut lines: Vec<&[u8]> = old_data.split_inclusive(|&byte| byte == b'\n').collect();
+        for (index, hunk) in hunks.iter().enumerate() {
+            if !apply_hunk(&mut lines, hunk) {
+                return Err((
+                    Some(index),
+                    format!("patch does not apply at line {}", hunk.old_start),
+                ));
+            }
+        }
+        lines.concat()
+    };
+
+    if file.kind == "deleted" {
+        if !data.is_empty() {
+            return Err((None, "removal patch leaves file contents".into()));
+        }
+        return Ok(None);
+    }
+    let kind = kind.or(old.map(|old| old.kind)).unwrap_or(EntryKind::Blob);
+    if kind == EntryKind::Commit && submodule_commit(&data).is_none() {
+        return Err((None, "has no commit for the submodule".into()));
+    }
+    Ok(Some(File { kind, data }))
+}
+
+/// The new content of a binary `file` of a patch, which like for git needs
+/// the full IDs of both sides in the patch. The new side is taken from the
+/// object database if it's there, otherwise from the `GIT binary patch` of
+/// `git diff --binary`.
+fn binary_data(
+    repository: &gix::Repository,
+    file: &FileDiff,
+    old: &[u8],
+) -> Result<Vec<u8>, Conflict> {
+    let hash = repository.object_hash();
+    let id = |hex: &str| {
+        (hex.len() == hash.len_in_hex())
+            .then(|| gix::ObjectId::from_hex(hex.as_bytes()).ok())
+            .flatten()
+    };
+    let (Some(previous_id), Some(id)) = (id(&file.previous_id), id(&file.id)) else {
+        return Err((
+            None,
+            "cannot apply a binary patch without the full IDs of the files".into(),
+        ));
+    };
+    let blob_id = |data: &[u8]| gix::objs::compute_hash(hash, gix::objs::Kind::Blob, data).ok();
+    if !previous_id.is_null() && blob_id(old) != Some(previous_id) {
+        return Err((
+            None,
+            format!("does not match {previous_id} that the patch applies to"),
+        ));
+    }
+    if id.is_null() {
+        return Ok(Vec::new());
+    }
+    if let Ok(blob) = repository.find_blob(id) {
+        return Ok(blob.detach().data);
+    }
+    binary::apply_patch(&file.patch, old)
+        .filter(|data| blob_id(data) == Some(id))
+        .ok_or_else(|| (None, "binary patch does not apply".into()))
+}
+
+/// Apply `hunk` to the `lines` of a file like git does, `false` if it
+/// doesn't apply.
+///
+/// The context and removed lines of the hunk have to be in the file exactly
+/// as they are. They're looked for at the line the hunk says (in the file
+/// as patched so far) and then further and further away from it, except
+/// that a hunk at the start of the file, or one that ends at its end (i.e.
+/// without context after its changes), has to stay there.
+fn apply_hunk<'a>(lines: &mut Vec<&'a [u8]>, hunk: &'a visit::Hunk) -> bool {
+    let (mut preimage, mut postimage) = (Vec::<&[u8]>::new(), Vec::<&[u8]>::new());
+    let (mut old, mut new) = (hunk.old_lines, hunk.new_lines);
+    let (mut origin, mut trailing) = (b' ', 0);
+    for line in hunk.content.split_inclusive(|&byte| byte == b'\n').skip(1) {
+        if line.starts_with(b"\\") {
+            // `\ No newline at end of file` belongs to the previous line.
+            let strip = |line: &mut &[u8]| *line = line.strip_suffix(b"\n").unwrap_or(line);
+            if origin != b'+' {
+                preimage.last_mut().map(strip);
+            }
+            if origin != b'-' {
+                postimage.last_mut().map(strip);
+            }
+            continue;
+        }
+        if old == 0 && new == 0 {
+            break;
+        }
+        origin = match line.first() {
+            // Blank context lines may have lost their space.
+            Some(b'\n' | b'\r') => b' ',
+            Some(&origin @ (b' ' | b'-' | b'+')) => origin,
+            _ => break,
+        };
+        let content = if line[0] == origin { &line[1..] } else { line };
+        if origin != b'+' {
+            preimage.push(content);
+            old = old.saturating_sub(1);
+        }
+        if origin != b'-' {
+            postimage.push(content);
+            new = new.saturating_sub(1);
+        }
+        trailing = if origin == b' ' { trailing + 1 } else { 0 };
+    }
+
+    if preimage.len() > lines.len() {
+        return false;
+    }
+    let (beginning, end) = (hunk.old_start <= 1, trailing == 0);
+    let start = if beginning {
+        0
+    } else if end {
+        lines.len() - preimage.len()
+    } else {
+        (hunk.new_start as usize).saturating_sub(1).min(lines.len())
+    };
+    let fits = |at: usize| {
+        (!beginning || at == 0)
+            && (!end || at + preimage.len() == lines.len())
+            && lines.get(at..at + preimage.len()) == Some(&preimage[..])
+    };
+    let Some(at) = (0..=lines.len())
+        .flat_map(|distance| [start.checked_add(distance), start.checked_sub(distance)])
+        .flatten()
+        .find(|&at| fits(at))
+    else {
+        return false;
+    };
+    lines.splice(at..at + preimage.len(), postimage);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply;
+    use crate::patch::Patch;
+    use crate::tests::git;
+
+    use gix::bstr::ByteSlice;
+    use pyo3::prelude::*;
+    use pyo3::types::PyBytes;
+
+    const CHANGE: &[u8] = b"diff --git a/a.txt b/a.txt\n\
+        --- a/a.txt\n\
+        +++ b/a.txt\n\
+        @@ -4,3 +4,3 @@\n \
+        4\n\
+        -5\n\
+        +five\n \
+        6\n\
+        diff --git a/new.txt b/new.txt\n\
+        new file mode 100644\n\
+        --- /dev/null\n\
+        +++ b/new.txt\n\
+        @@ -0,0 +1 @@\n\
+        +new\n";
+
+    /// A new repository with the numbers from 1 to 10 in `a.txt`.
+    fn repository() -> (tempfile::TempDir, gix::Repository) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let numbers = (1..=10).map(|n| format!("{n}\n")).collect::<String>();
+        git(path, &["init", "-q"], b"");
+        std::fs::write(path.join("a.txt"), numbers).unwrap();
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "Add the numbers"], b"");
+        let repository = gix::open(path).unwrap();
+        (directory, repository)
+    }
+
+    fn files(py: Python<'_>, patch: &[u8]) -> Vec<Py<crate::paged::FileDiff>> {
+        Patch::parse(py, PyBytes::new(py, patch).as_any())
+            .unwrap()
+            .files
+    }
+
+    #[test]
+    fn tree_matches_git() {
+        let (directory, repository) = repository();
+        pyo3::prepare_freethreaded_python();
+        let result = Python::with_gil(|py| {
+            apply(py, &repository, &files(py, CHANGE), "HEAD", None).unwrap()
+        });
+        assert!(result.applied);
+        assert_eq!(result.paths, ["a.txt", "new.txt"]);
+
+        let path = directory.path();
+        std::fs::write(path.join("change.patch"), CHANGE).unwrap();
+        git(path, &["apply", "--cached", "change.patch"], b"");
+        let expected = git(path, &["write-tree"], b"");
+        assert_eq!(
+            result.tree_id.unwrap(),
+            String::from_utf8(expected).unwrap().trim()
+        );
+    }
+
+    #[test]
+    fn conflicting_hunk_changes_nothing() {
+        let (_directory, repository) = repository();
+        let mut patch = b"diff --git a/a.txt b/a.txt\n\
+            --- a/a.txt\n\
+            +++ b/a.txt\n\
+            @@ -1,2 +1,2 @@\n\
+            -1\n\
+            +one\n \
+            2\n\
+            @@ -8,3 +8,3 @@\n \
+            8\n\
+            -nine\n\
+            +9\n \
+            10\n"
+            .to_vec();
+        patch.extend_from_slice(&CHANGE[CHANGE.find(b"diff --git a/new.txt").unwrap()..]);
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let result = apply(py, &repository, &files(py, &patch), "HEAD", None).unwrap();
+            assert!(!result.applied);
+            assert_eq!(result.tree_id, None);
+            assert_eq!(result.paths, ["new.txt"]);
+            let [conflict] = &result.conflicts[..] else {
+                panic!("expected a single conflict");
+            };
+            let conflict = conflict.borrow(py);
+            assert_eq!(conflict.path, "a.txt");
+            assert_eq!(conflict.hunk, Some(1));
+            assert_eq!(conflict.message, "patch does not apply at line 8");
+        });
+    }
+}
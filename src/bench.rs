@@ -0,0 +1,134 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::{IntoPyResult, Repository};
+
+/// The timings of a benchmark scenario, one entry per iteration.
+#[pyclass]
+pub struct BenchResult {
+    #[pyo3(get)]
+    pub scenario: String,
+    /// The number of commits processed by each iteration.
+    #[pyo3(get)]
+    pub commits: usize,
+    /// The wall clock time of each iteration in seconds.
+    #[pyo3(get)]
+    pub timings: Vec<f64>,
+    /// The version of gitch that produced the timings.
+    #[pyo3(get)]
+    pub version: &'static str,
+}
+
+#[pymethods]
+impl BenchResult {
+    #[getter]
+    fn min(&self) -> f64 {
+        self.sorted().first().copied().unwrap_or_default()
+    }
+
+    #[getter]
+    fn max(&self) -> f64 {
+        self.sorted().last().copied().unwrap_or_default()
+    }
+
+    #[getter]
+    fn mean(&self) -> f64 {
+        if self.timings.is_empty() {
+            return 0.0;
+        }
+        self.timings.iter().sum::<f64>() / self.timings.len() as f64
+    }
+
+    #[getter]
+    fn median(&self) -> f64 {
+        let sorted = self.sorted();
+        match sorted.len() {
+            0 => 0.0,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+            len => sorted[len / 2],
+        }
+    }
+}
+
+impl BenchResult {
+    fn sorted(&self) -> Vec<f64> {
+        let mut sorted = self.timings.clone();
+        sorted.sort_by(f64::total_cmp);
+        sorted
+    }
+}
+
+/// Run one iteration of a scenario returning the number of commits processed.
+fn run(repository: &Repository, scenario: &str) -> PyResult<usize> {
+    let commits = repository.commits_in_range(None, None)?;
+    if scenario == "walk" {
+        return Ok(commits.len());
+    }
+
+    let thread_local = repository.inner.to_thread_local();
+    for commit in &commits {
+        let commit = commit.inner.clone().attach(&thread_local).into_commit();
+        match scenario {
+            "diff" => {
+                Repository::diff_with_parent(
+                    &commit,
+                    gix::diff::blob::Algorithm::Myers,
+                    &Default::default(),
+                )
+                .unwrap();
+            }
+            _ => {
+                let tree = commit.tree().into_py_result()?;
+                let parent_tree = match commit.parent_ids().next() {
+                    Some(parent_id) => parent_id
+                        .object()
+                        .into_py_result()?
+                        .peel_to_tree()
+                        .into_py_result()?,
+                    None => thread_local.empty_tree(),
+                };
+                thread_local
+                    .diff_tree_to_tree(Some(&parent_tree), Some(&tree), None)
+                    .into_py_result()?;
+            }
+        }
+    }
+    Ok(commits.len())
+}
+
+/// Time a scenario against the history reachable from HEAD of the repository
+/// at `repo_path` so performance reports are comparable.
+///
+/// - `walk`: walk the commits and decode their metadata.
+/// - `diff`: `walk` and render the patch of every commit against its first
+///   parent.
+/// - `stats`: `walk` and diff the tree of every commit against its first
+///   parent without diffing any file contents.
+#[pyfunction]
+#[pyo3(signature=(repo_path, scenario="walk", iterations=10))]
+pub fn bench(repo_path: PathBuf, scenario: &str, iterations: usize) -> PyResult<BenchResult> {
+    if !matches!(scenario, "walk" | "diff" | "stats") {
+        return Err(PyValueError::new_err(format!(
+            "unknown scenario '{scenario}', expected one of 'walk', 'diff', or 'stats'"
+        )));
+    }
+    let repository = Repository::new(repo_path, false)?;
+
+    let mut commits = 0;
+    let mut timings = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        commits = run(&repository, scenario)?;
+        timings.push(start.elapsed().as_secs_f64());
+    }
+
+    Ok(BenchResult {
+        scenario: scenario.to_string(),
+        commits,
+        timings,
+        version: env!("CARGO_PKG_VERSION"),
+    })
+}
@@ -0,0 +1,174 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::{Commit, IntoPyResult, Repository};
+
+const FORMAT: &str = "gitch-review-bundle";
+const VERSION: u64 = 1;
+const ROOT: &str = "gitch-review";
+
+/// A set of commits and their patches read back from a review bundle.
+///
+/// Review bundles are gzipped tarballs laid out as follows:
+///
+/// - `gitch-review/manifest.json`: the format version and the ordered list of
+///   commit IDs.
+/// - `gitch-review/commits/<id>/metadata.json`: human/tool readable commit
+///   metadata.
+/// - `gitch-review/commits/<id>/commit`: the raw commit object.
+/// - `gitch-review/commits/<id>/patch`: the patch against the first parent
+///   (absent for empty commits).
+#[pyclass]
+pub struct ReviewBundle {
+    #[pyo3(get)]
+    pub commits: Vec<Py<Commit>>,
+    patches: HashMap<String, Vec<u8>>,
+}
+
+#[pymethods]
+impl ReviewBundle {
+    #[staticmethod]
+    pub fn load(py: Python<'_>, path: std::path::PathBuf) -> PyResult<Self> {
+        let file = std::fs::File::open(&path).into_py_result()?;
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+
+        let mut files = HashMap::new();
+        for entry in archive.entries().into_py_result()? {
+            let mut entry = entry.into_py_result()?;
+            let name = entry
+                .path()
+                .into_py_result()?
+                .to_string_lossy()
+                .into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).into_py_result()?;
+            files.insert(name, data);
+        }
+
+        let invalid =
+            |message: String| PyValueError::new_err(format!("{}: {message}", path.display()));
+        let manifest = files
+            .get(&format!("{ROOT}/manifest.json"))
+            .ok_or_else(|| invalid("missing manifest".into()))?;
+        let manifest: serde_json::Value = serde_json::from_slice(manifest).into_py_result()?;
+        if manifest["format"] != FORMAT || manifest["version"] != VERSION {
+            return Err(invalid(format!(
+                "unsupported bundle format {} version {}",
+                manifest["format"], manifest["version"]
+            )));
+        }
+
+        let mut commits = Vec::new();
+        let mut patches = HashMap::new();
+        for id in manifest["commits"]
+            .as_array()
+            .ok_or_else(|| invalid("manifest has no commit list".into()))?
+        {
+            let id = id
+                .as_str()
+                .ok_or_else(|| invalid(format!("invalid commit ID {id}")))?;
+            let data = files
+                .remove(&format!("{ROOT}/commits/{id}/commit"))
+                .ok_or_else(|| invalid(format!("missing commit object for {id}")))?;
+            let object = gix::ObjectDetached {
+                id: gix::ObjectId::from_hex(id.as_bytes()).into_py_result()?,
+                kind: gix::object::Kind::Commit,
+                data,
+            };
+            gix::objs::CommitRef::from_bytes(&object.data)
+                .map_err(|error| invalid(format!("invalid commit object for {id}: {error}")))?;
+            let commit = Commit::try_from(object)
+                .map_err(|()| invalid(format!("invalid commit object for {id}")))?;
+            commits.push(Py::new(py, commit)?);
+
+            if let Some(patch) = files.remove(&format!("{ROOT}/commits/{id}/patch")) {
+                patches.insert(id.to_string(), patch);
+            }
+        }
+
+        Ok(Self { commits, patches })
+    }
+
+    /// The patch of the given commit against its first parent.
+    pub fn patch(&self, commit: &Commit) -> Option<Cow<'_, [u8]>> {
+        self.patches
+            .get(&commit.id)
+            .map(|patch| Cow::Borrowed(&patch[..]))
+    }
+}
+
+fn append(
+    builder: &mut tar::Builder<impl std::io::Write>,
+    path: &str,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    // Keep the archive reproducible.
+    header.set_mtime(0);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)
+}
+
+pub(crate) fn write(
+    repository: &gix::Repository,
+    commits: &[Commit],
+    algorithm: gix::diff::blob::Algorithm,
+    path: &Path,
+) -> PyResult<()> {
+    let file = std::fs::File::create(path).into_py_result()?;
+    let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+
+    let manifest = serde_json::json!({
+        "format": FORMAT,
+        "version": VERSION,
+        "commits": commits.iter().map(|commit| &commit.id).collect::<Vec<_>>(),
+    });
+    append(
+        &mut builder,
+        &format!("{ROOT}/manifest.json"),
+        &serde_json::to_vec_pretty(&manifest).into_py_result()?,
+    )
+    .into_py_result()?;
+
+    for commit in commits {
+        let directory = format!("{ROOT}/commits/{}", commit.id);
+        append(
+            &mut builder,
+            &format!("{directory}/metadata.json"),
+            &serde_json::to_vec_pretty(&gitch_engine::commit::metadata(
+                &commit.inner.id,
+                &commit.inner.data,
+            ))
+            .into_py_result()?,
+        )
+        .into_py_result()?;
+        append(
+            &mut builder,
+            &format!("{directory}/commit"),
+            &commit.inner.data,
+        )
+        .into_py_result()?;
+
+        let attached = commit.inner.clone().attach(repository).into_commit();
+        if let Some(patch) =
+            Repository::diff_with_parent(&attached, algorithm, &Default::default())?
+        {
+            append(&mut builder, &format!("{directory}/patch"), &patch).into_py_result()?;
+        }
+    }
+
+    builder
+        .into_inner()
+        .into_py_result()?
+        .finish()
+        .into_py_result()?;
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use jiff::civil::{Date, Weekday};
+
+/// The regions with an embedded table of public holidays.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Region {
+    /// Germany, the holidays observed nationwide.
+    De,
+    /// France.
+    Fr,
+    /// The bank holidays of England and Wales.
+    Gb,
+    /// The United States, federal holidays.
+    Us,
+}
+
+impl Region {
+    pub(crate) fn parse(region: &str) -> PyResult<Self> {
+        match region.to_ascii_uppercase().as_str() {
+            "DE" => Ok(Self::De),
+            "FR" => Ok(Self::Fr),
+            "GB" | "UK" => Ok(Self::Gb),
+            "US" => Ok(Self::Us),
+            _ => Err(PyValueError::new_err(format!(
+                "no holiday table for region {region:?}, expected one of DE, FR, GB, or US"
+            ))),
+        }
+    }
+
+    fn code(self) -> &'static str {
+        match self {
+            Self::De => "DE",
+            Self::Fr => "FR",
+            Self::Gb => "GB",
+            Self::Us => "US",
+        }
+    }
+}
+
+/// Where a commit falls in the calendar of a region.
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub struct CalendarDay {
+    /// The region whose holidays were used, as an ISO 3166 code.
+    #[pyo3(get)]
+    pub region: &'static str,
+    /// The English name of the day of the week, e.g. `Monday`.
+    #[pyo3(get)]
+    pub weekday: &'static str,
+    #[pyo3(get)]
+    pub is_weekend: bool,
+    /// The name of the public holiday on that day, if any. Days a holiday is
+    /// observed on instead of a weekend are marked as well.
+    #[pyo3(get)]
+    pub holiday: Option<&'static str>,
+}
+
+#[pymethods]
+impl CalendarDay {
+    /// Neither a weekend nor a holiday.
+    #[getter]
+    fn is_workday(&self) -> bool {
+        !self.is_weekend && self.holiday.is_none()
+    }
+}
+
+/// `None` for dates outside of jiff's range (past the year 9999).
+fn date(year: i16, month: i8, day: i8) -> Option<Date> {
+    Date::new(year, month, day).ok()
+}
+
+/// The `nth` (1-based, negative from the end) `weekday` of a month.
+fn nth_weekday(year: i16, month: i8, nth: i8, weekday: Weekday) -> Option<Date> {
+    date(year, month, 1)?
+        .nth_weekday_of_month(nth, weekday)
+        .ok()
+}
+
+/// Easter Sunday of the Gregorian calendar (the anonymous algorithm).
+fn easter(year: i16) -> Option<Date> {
+    let y = i32::from(year);
+    let a = y % 19;
+    let b = y / 100;
+    let c = y % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    date(year, month as i8, day as i8)
+}
+
+fn after_easter(year: i16, days: i64) -> Option<Date> {
+    easter(year)?.checked_add(jiff::Span::new().days(days)).ok()
+}
+
+fn is_weekend(date: Date) -> bool {
+    matches!(date.weekday(), Weekday::Saturday | Weekday::Sunday)
+}
+
+/// The holidays of a year, including the days they are observed on.
+fn holidays(region: Region, year: i16) -> Vec<(Date, &'static str)> {
+    let holidays = match region {
+        Region::De => vec![
+            (date(year, 1, 1), "New Year's Day"),
+            (after_easter(year, -2), "Good Friday"),
+            (after_easter(year, 1), "Easter Monday"),
+            (date(year, 5, 1), "Labour Day"),
+            (after_easter(year, 39), "Ascension Day"),
+            (after_easter(year, 50), "Whit Monday"),
+            (date(year, 10, 3), "German Unity Day"),
+            (date(year, 12, 25), "Christmas Day"),
+            (date(year, 12, 26), "Second Day of Christmas"),
+        ],
+        Region::Fr => vec![
+            (date(year, 1, 1), "New Year's Day"),
+            (after_easter(year, 1), "Easter Monday"),
+            (date(year, 5, 1), "Labour Day"),
+            (date(year, 5, 8), "Victory in Europe Day"),
+            (after_easter(year, 39), "Ascension Day"),
+            (after_easter(year, 50), "Whit Monday"),
+            (date(year, 7, 14), "Bastille Day"),
+            (date(year, 8, 15), "Assumption Day"),
+            (date(year, 11, 1), "All Saints' Day"),
+            (date(year, 11, 11), "Armistice Day"),
+            (date(year, 12, 25), "Christmas Day"),
+        ],
+        Region::Gb => vec![
+            (date(year, 1, 1), "New Year's Day"),
+            (after_easter(year, -2), "Good Friday"),
+            (after_easter(year, 1), "Easter Monday"),
+            (
+                nth_weekday(year, 5, 1, Weekday::Monday),
+                "Early May bank holiday",
+            ),
+            (
+                nth_weekday(year, 5, -1, Weekday::Monday),
+                "Spring bank holiday",
+            ),
+            (
+                nth_weekday(year, 8, -1, Weekday::Monday),
+                "Summer bank holiday",
+            ),
+            (date(year, 12, 25), "Christmas Day"),
+            (date(year, 12, 26), "Boxing Day"),
+        ],
+        Region::Us => {
+            let mut holidays = vec![
+                (date(year, 1, 1), "New Year's Day"),
+                (
+                    nth_weekday(year, 1, 3, Weekday::Monday),
+                    "Birthday of Martin Luther King, Jr.",
+                ),
+                (
+                    nth_weekday(year, 2, 3, Weekday::Monday),
+                    "Washington's Birthday",
+                ),
+                (nth_weekday(year, 5, -1, Weekday::Monday), "Memorial Day"),
+                (date(year, 7, 4), "Independence Day"),
+                (nth_weekday(year, 9, 1, Weekday::Monday), "Labor Day"),
+                (nth_weekday(year, 10, 2, Weekday::Monday), "Columbus Day"),
+                (date(year, 11, 11), "Veterans Day"),
+                (
+                    nth_weekday(year, 11, 4, Weekday::Thursday),
+                    "Thanksgiving Day",
+                ),
+                (date(year, 12, 25), "Christmas Day"),
+            ];
+            if year >= 2021 {
+                holidays.push((date(year, 6, 19), "Juneteenth National Independence Day"));
+            }
+            holidays
+        }
+    };
+
+    let mut holidays: Vec<_> = holidays
+        .into_iter()
+        .filter_map(|(date, name)| Some((date?, name)))
+        .collect();
+
+    // Holidays on a weekend are made up for on a workday: federal ones on
+    // the closest one, bank holidays on the next free one.
+    let mut observed = Vec::new();
+    for &(day, name) in &holidays {
+        if !is_weekend(day) {
+            continue;
+        }
+        match region {
+            Region::Us => observed.extend(
+                if day.weekday() == Weekday::Saturday {
+                    day.yesterday()
+                } else {
+                    day.tomorrow()
+                }
+                .ok()
+                .map(|observed| (observed, name)),
+            ),
+            Region::Gb => {
+                let mut substitute = day.tomorrow();
+                while let Ok(taken) = substitute
+                    && (is_weekend(taken)
+                        || holidays
+                            .iter()
+                            .chain(&observed)
+                            .any(|(holiday, _)| *holiday == taken))
+                {
+                    substitute = taken.tomorrow();
+                }
+                observed.extend(substitute.ok().map(|substitute| (substitute, name)));
+            }
+            Region::De | Region::Fr => {}
+        }
+    }
+    holidays.extend(observed);
+    holidays
+}
+
+/// Tag the (local) day `time` falls on.
+pub(crate) fn annotate(region: Region, time: &jiff::Zoned) -> CalendarDay {
+    let day = time.date();
+    // The observed day of New Year's Day can fall into the previous year.
+    let next_year = day.checked_add(jiff::Span::new().years(1)).ok();
+    let holiday = std::iter::once(day.year())
+        .chain(next_year.map(|next| next.year()))
+        .flat_map(|year| holidays(region, year))
+        .find(|(date, _)| *date == day)
+        .map(|(_, name)| name);
+    CalendarDay {
+        region: region.code(),
+        weekday: match day.weekday() {
+            Weekday::Monday => "Monday",
+            Weekday::Tuesday => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday => "Thursday",
+            Weekday::Friday => "Friday",
+            Weekday::Saturday => "Saturday",
+            Weekday::Sunday => "Sunday",
+        },
+        is_weekend: is_weekend(day),
+        holiday,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Region, annotate};
+
+    fn day(time: &str) -> jiff::Zoned {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn holidays_and_observed_days() {
+        let christmas = annotate(Region::Gb, &day("2021-12-25T12:00:00+00:00[UTC]"));
+        assert_eq!(christmas.holiday, Some("Christmas Day"));
+        assert!(christmas.is_weekend);
+        // Christmas Day and Boxing Day 2021 fell on a weekend.
+        let substitute = annotate(Region::Gb, &day("2021-12-28T12:00:00+00:00[UTC]"));
+        assert_eq!(substitute.holiday, Some("Boxing Day"));
+        // New Year's Day 2022 was a Saturday, observed on the Friday before.
+        let observed = annotate(Region::Us, &day("2021-12-31T12:00:00+00:00[UTC]"));
+        assert_eq!(observed.holiday, Some("New Year's Day"));
+    }
+
+    #[test]
+    fn last_representable_year() {
+        for region in [Region::De, Region::Fr, Region::Gb, Region::Us] {
+            for time in [
+                "9999-01-01T12:00:00+00:00[UTC]",
+                "9999-12-25T12:00:00+00:00[UTC]",
+                "9999-12-30T12:00:00+00:00[UTC]",
+                "-009999-01-03T12:00:00+00:00[UTC]",
+            ] {
+                annotate(region, &day(time));
+            }
+        }
+        assert_eq!(
+            annotate(Region::Us, &day("9999-12-30T12:00:00+00:00[UTC]")).holiday,
+            None
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use pyo3::prelude::*;
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::{Commit, walk};
+
+/// Drop the commits that a merge in `commits` already represents, keeping
+/// the merges themselves.
+///
+/// A merge represents the commits it brought in, i.e. those reachable from
+/// any of its other parents but not from its first one. This is what a
+/// changelog of merged pull requests wants: one entry per merge instead of
+/// one for the merge and one for each of its commits.
+pub(crate) fn dedupe_for_changelog(
+    repository: &gix::Repository,
+    commits: Vec<Commit>,
+) -> PyResult<Vec<Commit>> {
+    let in_range = commits
+        .iter()
+        .map(|commit| commit.inner.id)
+        .collect::<HashSet<_>>();
+    let mut represented = HashSet::new();
+    for commit in commits.iter().filter(|commit| commit.is_merge()) {
+        let mut parents = commit
+            .parent_ids
+            .iter()
+            .filter_map(|id| gix::ObjectId::from_str(id).ok());
+        let Some(first_parent) = parents.next() else {
+            continue;
+        };
+        for info in walk::walk(
+            repository,
+            parents,
+            [first_parent],
+            gix::revision::walk::Sorting::BreadthFirst,
+        )? {
+            if in_range.contains(&info.id) {
+                represented.insert(info.id);
+            }
+        }
+    }
+    Ok(commits
+        .into_iter()
+        .filter(|commit| !represented.contains(&commit.inner.id))
+        .collect())
+}
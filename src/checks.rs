@@ -0,0 +1,313 @@
+//! The commit checks of `gitch_engine::checks` for Python, run without the
+//! GIL.
+//!
+//! Checks in shared libraries implement the C interface of
+//! `include/gitch_check.h` and are registered with `register_native_check`.
+
+use gitch_engine::checks::{self, NativeFn};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use std::collections::HashMap;
+
+use crate::{Commit, IntoPyResult, py_err, text};
+
+/// Whether and how a check would run on a commit, see
+/// `Repository.explain_checks`.
+#[pyclass(frozen)]
+pub struct CheckExplanation {
+    #[pyo3(get)]
+    pub name: String,
+    #[pyo3(get)]
+    pub runs: bool,
+    /// Why the check wouldn't run, `None` if it would.
+    #[pyo3(get)]
+    pub reason: Option<String>,
+    #[pyo3(get)]
+    pub settings: Vec<(String, String)>,
+}
+
+/// gitch's configuration read from the attributes of any object (like
+/// `gitch.configuration.Schema`).
+#[derive(FromPyObject)]
+pub struct Configuration<'py> {
+    first_commit_is_empty: bool,
+    starting_from: Option<String>,
+    summary: Summary,
+    trailers: Bound<'py, PyDict>,
+}
+
+/// The `summary` of `Configuration`.
+#[derive(FromPyObject)]
+struct Summary {
+    first_word_is_simple_verb: bool,
+    first_word_capitalization: Option<String>,
+}
+
+impl Configuration<'_> {
+    /// The configuration for `gitch_engine::checks`, with `starting_from`
+    /// resolved in `repository`.
+    pub(crate) fn resolve(self, repository: &gix::Repository) -> PyResult<checks::Configuration> {
+        let starting_from = self
+            .starting_from
+            .map(|spec| -> PyResult<gix::ObjectId> {
+                Ok(repository
+                    .rev_parse_single(spec.as_str())
+                    .into_py_result()?
+                    .detach())
+            })
+            .transpose()?;
+        let trailers = self
+            .trailers
+            .iter()
+            .map(|(token, rule)| {
+                let rule = rule.extract::<text::TrailerRule>()?;
+                Ok((token.extract()?, rule.into()))
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(checks::Configuration {
+            starting_from,
+            first_commit_is_empty: self.first_commit_is_empty,
+            first_word_is_simple_verb: self.summary.first_word_is_simple_verb,
+            first_word_capitalization: self.summary.first_word_capitalization,
+            trailers,
+        })
+    }
+}
+
+/// Register the `gitch_check_fn` at `address` (e.g. from `ctypes`) as the
+/// check `name`, replacing one of the same name. With `needs_patch` it gets
+/// each commit's patch as well. With `paths` it only runs on commits
+/// changing a path they match, as in `Repository.diff`.
+///
+/// The function has to stay loaded and be safe to call from several threads
+/// at once for the lifetime of the process.
+#[pyfunction]
+#[pyo3(signature=(name, address, needs_patch=false, paths=None))]
+pub fn register_native_check(
+    name: String,
+    address: usize,
+    needs_patch: bool,
+    paths: Option<Vec<String>>,
+) -> PyResult<()> {
+    if address == 0 {
+        return Err(PyValueError::new_err("check function address is null"));
+    }
+    // SAFETY: the caller vouches that the address is a `gitch_check_fn` that
+    // stays loaded and is thread safe.
+    unsafe {
+        let function = std::mem::transmute::<usize, NativeFn>(address);
+        checks::register_native_check(name, function, needs_patch, paths.unwrap_or_default());
+    }
+    Ok(())
+}
+
+/// The names of all registered checks in registration order.
+#[pyfunction]
+pub fn registered_checks() -> Vec<String> {
+    checks::registered_checks()
+}
+
+/// See `gitch_engine::checks::explain_checks`.
+pub(crate) fn explain_checks(
+    repository: &gix::Repository,
+    commit: &Commit,
+    names: Option<&[String]>,
+    configuration: Option<&checks::Configuration>,
+) -> PyResult<Vec<CheckExplanation>> {
+    Ok(
+        checks::explain_checks(repository, commit.inner.id, names, configuration)
+            .map_err(py_err)?
+            .into_iter()
+            .map(|explanation| CheckExplanation {
+                name: explanation.name,
+                runs: explanation.runs,
+                reason: explanation.reason,
+                settings: explanation.settings,
+            })
+            .collect(),
+    )
+}
+
+/// See `gitch_engine::checks::run_checks`.
+pub(crate) fn run_checks(
+    repository: &gix::ThreadSafeRepository,
+    commits: &[Commit],
+    names: Option<&[String]>,
+) -> PyResult<HashMap<String, Vec<String>>> {
+    let ids = commits
+        .iter()
+        .map(|commit| commit.inner.id)
+        .collect::<Vec<_>>();
+    checks::run_checks(repository, &ids, names).map_err(py_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain_checks, register_native_check, run_checks};
+    use crate::Commit;
+    use crate::tests::git;
+    use gitch_engine::checks::{self, NativeCommit, NativeFn, Report};
+    use gitch_engine::message;
+
+    use std::collections::HashMap;
+    use std::ffi::{CStr, c_void};
+
+    /// A `gitch_check_fn` against summaries ending in a period and patches
+    /// adding a `TODO`.
+    unsafe extern "C" fn check(commit: *const NativeCommit, report: Report, context: *mut c_void) {
+        // SAFETY: called as a `gitch_check_fn` with needs_patch.
+        unsafe {
+            let commit = &*commit;
+            if commit.abi_version != 1 {
+                report(context, c"unknown ABI version".as_ptr());
+            }
+            if CStr::from_ptr(commit.summary).to_bytes().ends_with(b".") {
+                report(context, c"summary ends with a period".as_ptr());
+            }
+            let patch = std::slice::from_raw_parts(commit.patch, commit.patch_len);
+            if patch.windows(5).any(|window| window == b"+TODO") {
+                report(context, c"adds a TODO".as_ptr());
+            }
+        }
+    }
+
+    #[test]
+    fn native_check() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q"], b"");
+        std::fs::write(path.join("file.txt"), "TODO\n").unwrap();
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "Add the file."], b"");
+        std::fs::write(path.join("file.txt"), "done\n").unwrap();
+        git(path, &["commit", "-q", "-am", "Do it"], b"");
+
+        let repository = gix::open(path).unwrap();
+        let commits = repository
+            .rev_walk([repository.head_id().unwrap()])
+            .all()
+            .unwrap()
+            .map(|info| {
+                let object = repository.find_object(info.unwrap().id).unwrap();
+                Commit::try_from(object.detach()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let function: NativeFn = check;
+        register_native_check("test-native".into(), function as usize, true, None).unwrap();
+        let names = ["test-native".to_string()];
+        let results = run_checks(&repository.clone().into_sync(), &commits, Some(&names)).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[&commits[1].id],
+            [
+                "test-native: summary ends with a period",
+                "test-native: adds a TODO"
+            ]
+        );
+
+        let explanation = explain_checks(&repository, &commits[0], Some(&names), None).unwrap();
+        let native = explanation
+            .iter()
+            .find(|check| check.name == "test-native")
+            .unwrap();
+        assert!(native.runs);
+        assert!(
+            native
+                .settings
+                .contains(&("needs_patch".into(), "true".into()))
+        );
+        let explanation = explain_checks(&repository, &commits[0], Some(&[]), None).unwrap();
+        assert!(explanation.iter().all(|check| !check.runs));
+
+        assert!(register_native_check("test-null".into(), 0, false, None).is_err());
+    }
+
+    #[test]
+    fn explains_configuration_and_skip_reasons() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q"], b"");
+        git(path, &["commit", "-q", "--allow-empty", "-m", "Begin"], b"");
+        std::fs::write(path.join("file.txt"), "one\n").unwrap();
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "Add the file"], b"");
+        std::fs::write(path.join("file.txt"), "two\n").unwrap();
+        git(path, &["commit", "-q", "-am", "Change the file"], b"");
+
+        let repository = gix::open(path).unwrap();
+        let commit = |rev: &str| {
+            let id = repository.rev_parse_single(rev).unwrap();
+            Commit::try_from(repository.find_object(id).unwrap().detach()).unwrap()
+        };
+        let starting_from = repository.rev_parse_single("HEAD~").unwrap().detach();
+        let configuration = checks::Configuration {
+            starting_from: Some(starting_from),
+            first_commit_is_empty: true,
+            first_word_capitalization: Some("upper".into()),
+            trailers: vec![(
+                "Signed-off-by".into(),
+                message::TrailerRule {
+                    mandatory: true,
+                    ..Default::default()
+                },
+            )],
+            ..Default::default()
+        };
+        let function: NativeFn = check;
+        register_native_check(
+            "test-paths".into(),
+            function as usize,
+            false,
+            Some(vec!["docs".into()]),
+        )
+        .unwrap();
+
+        let reasons = |rev: &str| {
+            explain_checks(&repository, &commit(rev), None, Some(&configuration))
+                .unwrap()
+                .into_iter()
+                .map(|check| (check.name, check.reason))
+                .collect::<HashMap<_, _>>()
+        };
+        let suppressed = Some(format!("suppressed: before starting-from {starting_from}"));
+        let off = Some("off in the configuration".to_string());
+
+        let first = reasons("HEAD~2");
+        assert_eq!(first["first-commit-is-empty"], None);
+        assert_eq!(first["summary.first-word-capitalization"], suppressed);
+        assert_eq!(first["summary.first-word-is-simple-verb"], off);
+        assert_eq!(first["trailers.Signed-off-by"], suppressed);
+
+        let last = reasons("HEAD");
+        assert_eq!(
+            last["first-commit-is-empty"].as_deref(),
+            Some("only checks the first commit")
+        );
+        assert_eq!(last["summary.first-word-capitalization"], None);
+        assert_eq!(last["summary.first-word-is-simple-verb"], off);
+        assert_eq!(last["trailers.Signed-off-by"], None);
+        assert_eq!(
+            last["test-paths"].as_deref(),
+            Some("path-filtered: changes nothing in docs")
+        );
+
+        let explanation =
+            explain_checks(&repository, &commit("HEAD"), None, Some(&configuration)).unwrap();
+        let trailer = explanation
+            .iter()
+            .find(|check| check.name == "trailers.Signed-off-by")
+            .unwrap();
+        assert_eq!(
+            trailer.settings,
+            [
+                ("mandatory".into(), "true".into()),
+                ("singular".into(), "false".into()),
+                ("values".into(), String::new()),
+                ("starting-from".into(), starting_from.to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Patches colored with ANSI escapes in git's default palette, the same as
+//! `git diff --color` writes them, whitespace errors of added lines included.
+
+use crate::visit;
+
+const RESET: &[u8] = b"\x1b[m";
+const META: &[u8] = b"\x1b[1m";
+const FRAG: &[u8] = b"\x1b[36m";
+const OLD: &[u8] = b"\x1b[31m";
+const NEW: &[u8] = b"\x1b[32m";
+const WHITESPACE: &[u8] = b"\x1b[41m";
+
+/// The lines git colors as meta information outside of hunks.
+const META_PREFIXES: &[&[u8]] = &[
+    b"diff --git ",
+    b"old mode ",
+    b"new mode ",
+    b"deleted file mode ",
+    b"new file mode ",
+    b"index ",
+    b"similarity index ",
+    b"dissimilarity index ",
+    b"rename from ",
+    b"rename to ",
+    b"copy from ",
+    b"copy to ",
+    b"--- ",
+    b"+++ ",
+    b"Submodule ",
+];
+
+/// git's `isspace`, which unlike `u8::is_ascii_whitespace` leaves out form
+/// feeds.
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+fn is_blank(content: &[u8]) -> bool {
+    content.iter().all(|&byte| is_space(byte))
+}
+
+/// `line` split before its `\r\n` or `\n` terminator.
+fn split_terminator(line: &[u8]) -> (&[u8], &[u8]) {
+    let mut end = line.len();
+    if line[..end].ends_with(b"\n") {
+        end -= 1;
+    }
+    if line[..end].ends_with(b"\r") {
+        end -= 1;
+    }
+    line.split_at(end)
+}
+
+/// `line` in `color`, the terminator after the reset.
+fn emit(out: &mut Vec<u8>, color: &[u8], line: &[u8]) {
+    let (content, terminator) = split_terminator(line);
+    out.extend_from_slice(color);
+    out.extend_from_slice(content);
+    out.extend_from_slice(RESET);
+    out.extend_from_slice(terminator);
+}
+
+fn emit_header(out: &mut Vec<u8>, line: &[u8]) {
+    if line.starts_with(b"  > ") {
+        return emit(out, NEW, line);
+    }
+    if line.starts_with(b"  < ") {
+        return emit(out, OLD, line);
+    }
+    if !META_PREFIXES.iter().any(|prefix| line.starts_with(prefix)) {
+        return out.extend_from_slice(line);
+    }
+    let (content, terminator) = split_terminator(line);
+    // The tab after names with spaces isn't part of the name.
+    let (content, tab) = match content.strip_suffix(b"\t") {
+        Some(content) if line.starts_with(b"--- ") || line.starts_with(b"+++ ") => {
+            (content, &b"\t"[..])
+        }
+        _ => (content, &b""[..]),
+    };
+    out.extend_from_slice(META);
+    out.extend_from_slice(content);
+    out.extend_from_slice(RESET);
+    out.extend_from_slice(tab);
+    out.extend_from_slice(terminator);
+}
+
+/// The `@@ -a,b +c,d @@` part in the fragment color, the function name after
+/// it in none.
+fn emit_hunk_header(out: &mut Vec<u8>, line: &[u8]) {
+    let (content, terminator) = split_terminator(line);
+    let Some(end) = content[2..]
+        .windows(2)
+        .position(|window| window == b"@@")
+        .map(|position| position + 4)
+    else {
+        return out.extend_from_slice(line);
+    };
+    out.extend_from_slice(FRAG);
+    out.extend_from_slice(&content[..end]);
+    out.extend_from_slice(RESET);
+    let rest = &content[end..];
+    let blank = rest
+        .iter()
+        .take_while(|&&byte| byte == b' ' || byte == b'\t')
+        .count();
+    for part in [&rest[..blank], &rest[blank..]] {
+        if !part.is_empty() {
+            out.extend_from_slice(part);
+            out.extend_from_slice(RESET);
+        }
+    }
+    out.extend_from_slice(terminator);
+}
+
+/// The content of an added line with its whitespace errors highlighted:
+/// spaces before a tab in the indent and whitespace at the end of the line.
+fn emit_added(out: &mut Vec<u8>, content: &[u8]) {
+    let (content, newline) = match content.strip_suffix(b"\n") {
+        Some(content) => (content, &b"\n"[..]),
+        None => (content, &b""[..]),
+    };
+    let trailing = content.len()
+        - content
+            .iter()
+            .rev()
+            .take_while(|&&byte| is_space(byte))
+            .count();
+    let mut written = 0;
+    for (i, &byte) in content[..trailing].iter().enumerate() {
+        match byte {
+            b' ' => continue,
+            b'\t' if written < i => {
+                out.extend_from_slice(WHITESPACE);
+                out.extend_from_slice(&content[written..i]);
+                out.extend_from_slice(RESET);
+                out.push(b'\t');
+            }
+            b'\t' => out.extend_from_slice(&content[written..=i]),
+            _ => break,
+        }
+        written = i + 1;
+    }
+    if written < trailing {
+        out.extend_from_slice(NEW);
+        out.extend_from_slice(&content[written..trailing]);
+        out.extend_from_slice(RESET);
+    }
+    if trailing < content.len() {
+        out.extend_from_slice(WHITESPACE);
+        out.extend_from_slice(&content[trailing..]);
+        out.extend_from_slice(RESET);
+    }
+    out.extend_from_slice(newline);
+}
+
+/// The lines from which on added blank lines are at the end of the file, in
+/// git's numbering of the lines before and after, or `None` if the hunk
+/// doesn't add blank lines at the end.
+///
+/// Only hunks that reach the end of the file can tell, as git does that
+/// when there are fewer than `context_lines` lines of context after the
+/// changes or when the last line has no newline.
+fn blank_at_eof(
+    (old_start, old_lines): (u32, u32),
+    (new_start, new_lines): (u32, u32),
+    lines: &[&[u8]],
+    context_lines: u32,
+) -> Option<(u32, u32)> {
+    let trailing_context = lines
+        .iter()
+        .rev()
+        .filter(|line| line[0] != b'\\')
+        .take_while(|line| line[0] == b' ')
+        .count();
+    if trailing_context >= context_lines as usize && !lines.last()?.starts_with(b"\\") {
+        return None;
+    }
+    let trailing_blank = |side: u8| {
+        lines
+            .iter()
+            .rev()
+            .filter(|line| line[0] == b' ' || line[0] == side)
+            .take_while(|line| is_blank(&line[1..]))
+            .count() as u32
+    };
+    let (old_blank, new_blank) = (trailing_blank(b'-'), trailing_blank(b'+'));
+    if new_blank <= old_blank {
+        return None;
+    }
+    Some((
+        old_start + old_lines.saturating_sub(1) - old_blank + 1,
+        new_start + new_lines.saturating_sub(1) - new_blank + 1,
+    ))
+}
+
+fn emit_hunk(
+    out: &mut Vec<u8>,
+    header: &[u8],
+    old: (u32, u32),
+    new: (u32, u32),
+    lines: &[&[u8]],
+    context_lines: u32,
+) {
+    emit_hunk_header(out, header);
+    let blank_at_eof = blank_at_eof(old, new, lines, context_lines);
+    // Numbered like git does, one past the line.
+    let (mut old_lineno, mut new_lineno) = (old.0, new.0);
+    for line in lines {
+        match line[0] {
+            b'-' => {
+                old_lineno += 1;
+                emit(out, OLD, line);
+            }
+            b'+' => {
+                new_lineno += 1;
+                if blank_at_eof.is_some_and(|(old, new)| old_lineno >= old && new_lineno >= new)
+                    && is_blank(&line[1..])
+                {
+                    emit(out, WHITESPACE, line);
+                } else {
+                    out.extend_from_slice(NEW);
+                    out.push(b'+');
+                    out.extend_from_slice(RESET);
+                    emit_added(out, &line[1..]);
+                }
+            }
+            b' ' => {
+                old_lineno += 1;
+                new_lineno += 1;
+                emit(out, b"", line);
+            }
+            _ => emit(out, b"", line),
+        }
+    }
+}
+
+/// The old and new ranges of `line` if it is a hunk header.
+fn hunk_ranges(line: &[u8]) -> Option<((u32, u32), (u32, u32))> {
+    let header = std::str::from_utf8(line.strip_prefix(b"@@ -")?).ok()?;
+    let mut ranges = header.split_whitespace();
+    let old = visit::parse_range(ranges.next()?)?;
+    let new = visit::parse_range(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+/// `patch` with ANSI colors, for a patch of `context_lines` lines of context.
+pub(crate) fn colorize(patch: &[u8], context_lines: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(patch.len() * 2);
+    let mut lines = patch.split_inclusive(|&byte| byte == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        let Some((old, new)) = hunk_ranges(line) else {
+            emit_header(&mut out, line);
+            continue;
+        };
+        let (mut old_left, mut new_left) = (old.1, new.1);
+        let mut hunk = Vec::new();
+        while let Some(&next) = lines.peek() {
+            match next.first() {
+                Some(b' ') if old_left > 0 && new_left > 0 => {
+                    old_left -= 1;
+                    new_left -= 1;
+                }
+                Some(b'-') if old_left > 0 => old_left -= 1,
+                Some(b'+') if new_left > 0 => new_left -= 1,
+                Some(b'\\') if !hunk.is_empty() => {}
+                _ => break,
+            }
+            hunk.push(next);
+            lines.next();
+        }
+        emit_hunk(&mut out, line, old, new, &hunk, context_lines);
+    }
+    out
+}
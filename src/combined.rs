@@ -0,0 +1,730 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::io::Write;
+use std::ops::Range;
+
+use crate::{IntoPyResult, binary};
+
+/// The lines of context around changes, like git's default.
+const CONTEXT: usize = 3;
+
+/// A line of a parent that isn't in the result, along with the parents
+/// (as bits) that lost it.
+struct Lost<'a> {
+    line: &'a [u8],
+    parents: u64,
+}
+
+/// A line of the result, the last one is a sentinel past the end of the file
+/// to hang lines lost at the end on.
+struct Line<'a> {
+    line: &'a [u8],
+    /// The lines lost from parents just before this one.
+    lost: Vec<Lost<'a>>,
+    /// Bit `i` is set if the line was added relative to parent `i`, the two
+    /// bits after those of the parents are `mark` and `no_pre_delete`.
+    flag: u64,
+    /// The line number in each parent the line corresponds to.
+    parent_lines: Vec<usize>,
+}
+
+/// The lines of a file without their terminators.
+fn lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\n").unwrap_or(line))
+        .collect()
+}
+
+/// Merge the lines lost from a parent into the ones already lost from earlier
+/// parents at the same place, so that a line lost from several parents is
+/// only shown once.
+///
+/// Lines are matched by their longest common subsequence, unmatched ones of
+/// the new parent go after the unmatched ones already there.
+fn coalesce<'a>(base: Vec<Lost<'a>>, new: Vec<&'a [u8]>, parent: u64) -> Vec<Lost<'a>> {
+    if base.is_empty() {
+        return new
+            .into_iter()
+            .map(|line| Lost {
+                line,
+                parents: parent,
+            })
+            .collect();
+    }
+    let (m, n) = (base.len(), new.len());
+    let mut lcs = vec![vec![0usize; n + 1]; m + 1];
+    for i in 1..=m {
+        for j in 1..=n {
+            lcs[i][j] = if base[i - 1].line == new[j - 1] {
+                lcs[i - 1][j - 1] + 1
+            } else {
+                lcs[i][j - 1].max(lcs[i - 1][j])
+            };
+        }
+    }
+
+    let mut base: Vec<Option<Lost<'a>>> = base.into_iter().map(Some).collect();
+    let mut merged = Vec::with_capacity(m + n);
+    let (mut i, mut j) = (m, n);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && base[i - 1].as_ref().unwrap().line == new[j - 1] {
+            let mut lost = base[i - 1].take().unwrap();
+            lost.parents |= parent;
+            merged.push(lost);
+            i -= 1;
+            j -= 1;
+        } else if j > 0 && (i == 0 || lcs[i][j - 1] >= lcs[i - 1][j]) {
+            merged.push(Lost {
+                line: new[j - 1],
+                parents: parent,
+            });
+            j -= 1;
+        } else {
+            merged.push(base[i - 1].take().unwrap());
+            i -= 1;
+        }
+    }
+    merged.reverse();
+    merged
+}
+
+/// Record the differences between one parent (number `index`) and the
+/// result in `lines`, returns the line number in the parent past the end.
+fn diff_parent<'a>(
+    lines: &mut [Line<'a>],
+    parent: &'a [u8],
+    result: &'a [u8],
+    index: usize,
+    algorithm: gix::diff::blob::Algorithm,
+) -> usize {
+    let bit = 1 << index;
+    let input = gix::diff::blob::intern::InternedInput::new(
+        gix::diff::blob::sources::byte_lines_with_terminator(parent),
+        gix::diff::blob::sources::byte_lines_with_terminator(result),
+    );
+    let mut changes = Vec::<(Range<u32>, Range<u32>)>::new();
+    gix::diff::blob::diff(algorithm, &input, |before, after| {
+        changes.push((before, after))
+    });
+
+    let parent_lines = self::lines(parent);
+    for (before, after) in changes {
+        // Removed lines are shown before the result line following them.
+        let bucket = &mut lines[after.start as usize];
+        let lost = parent_lines[before.start as usize..before.end as usize].to_vec();
+        if !lost.is_empty() {
+            bucket.lost = coalesce(std::mem::take(&mut bucket.lost), lost, bit);
+        }
+        for line in &mut lines[after.start as usize..after.end as usize] {
+            line.flag |= bit;
+        }
+    }
+
+    let count = lines.len() - 1;
+    let mut parent_line = 1;
+    for (number, line) in lines.iter_mut().enumerate() {
+        line.parent_lines[index] = parent_line;
+        parent_line += line
+            .lost
+            .iter()
+            .filter(|lost| lost.parents & bit != 0)
+            .count();
+        if number < count && line.flag & bit == 0 {
+            parent_line += 1;
+        }
+    }
+    parent_line
+}
+
+/// Lines that are changed relative to some parent or follow lines removed
+/// from some parent.
+fn interesting(line: &Line<'_>, all_mask: u64) -> bool {
+    line.flag & all_mask != 0 || !line.lost.is_empty()
+}
+
+/// Don't count the last line of a hunk as interesting for the trailing
+/// context if it only had lost lines in front of it, it is shown as context
+/// anyway.
+fn adjust_hunk_tail(lines: &[Line<'_>], all_mask: u64, hunk_begin: usize, end: usize) -> usize {
+    if hunk_begin < end && lines[end - 1].flag & all_mask == 0 {
+        end - 1
+    } else {
+        end
+    }
+}
+
+/// The first line from `start` that is (or, if `marked` is false, isn't)
+/// marked.
+fn find_next(lines: &[Line<'_>], mark: u64, start: usize, marked: bool) -> usize {
+    (start..lines.len())
+        .find(|&index| (lines[index].flag & mark != 0) == marked)
+        .unwrap_or(lines.len())
+}
+
+/// Mark the context lines around the marked lines, joining groups that are
+/// close to each other. Returns whether anything is marked at all.
+fn give_context(lines: &mut [Line<'_>], parents: usize) -> bool {
+    let all_mask = (1 << parents) - 1;
+    let mark = 1 << parents;
+    let no_pre_delete = 2 << parents;
+    let end = lines.len();
+
+    let mut i = find_next(lines, mark, 0, true);
+    if i == end {
+        return false;
+    }
+    while i < end {
+        // Lines painted as leading context don't show what was lost before
+        // them.
+        for line in &mut lines[i.saturating_sub(CONTEXT)..i] {
+            if line.flag & mark == 0 {
+                line.flag |= no_pre_delete;
+            }
+            line.flag |= mark;
+        }
+        loop {
+            let j = find_next(lines, mark, i, false);
+            if j == end {
+                return true;
+            }
+            let k = find_next(lines, mark, j, true);
+            let j = adjust_hunk_tail(lines, all_mask, i, j);
+            if k < j + CONTEXT {
+                // Join the next group as the gap is small.
+                for line in &mut lines[j..k] {
+                    line.flag |= mark;
+                }
+                i = k;
+                continue;
+            }
+            for line in &mut lines[j..(j + CONTEXT).min(end)] {
+                line.flag |= mark;
+            }
+            i = k;
+            break;
+        }
+    }
+    true
+}
+
+/// Mark the lines to show, returns whether there are any.
+///
+/// When `dense` (`--cc`), hunks where the result only differs from the
+/// parents in one way are left out, i.e. those that take one side of the
+/// merge as is.
+fn make_hunks(lines: &mut [Line<'_>], parents: usize, dense: bool) -> bool {
+    let all_mask = (1 << parents) - 1;
+    let mark = 1 << parents;
+    let end = lines.len();
+    for line in lines.iter_mut() {
+        if interesting(line, all_mask) {
+            line.flag |= mark;
+        } else {
+            line.flag &= !mark;
+        }
+    }
+    if !dense {
+        return give_context(lines, parents);
+    }
+
+    let mut i = 0;
+    while i < end {
+        while i < end && lines[i].flag & mark == 0 {
+            i += 1;
+        }
+        if i == end {
+            break;
+        }
+        let hunk_begin = i;
+        let mut j = i + 1;
+        while j < end {
+            if lines[j].flag & mark == 0 {
+                // Look beyond the end for an interesting line within the
+                // context span.
+                let lookahead = adjust_hunk_tail(lines, all_mask, hunk_begin, j);
+                let mut lookahead = (lookahead + CONTEXT).min(end);
+                let mut joined = false;
+                while lookahead > j {
+                    lookahead -= 1;
+                    if lines[lookahead].flag & mark != 0 {
+                        joined = true;
+                        break;
+                    }
+                }
+                if !joined {
+                    break;
+                }
+                j = lookahead;
+            }
+            j += 1;
+        }
+        let hunk_end = j.min(end);
+
+        // The hunk is only interesting if the result differs from the
+        // parents in more than one way, or from all of them.
+        let mut same_diff = 0;
+        let mut has_interesting = false;
+        'lines: for line in &lines[i..hunk_end] {
+            let diffs = std::iter::once(line.flag & all_mask)
+                .filter(|&diff| diff != 0)
+                .chain(line.lost.iter().map(|lost| lost.parents));
+            for diff in diffs {
+                if same_diff == 0 {
+                    same_diff = diff;
+                } else if same_diff != diff {
+                    has_interesting = true;
+                    break 'lines;
+                }
+            }
+        }
+        if !has_interesting && same_diff != all_mask {
+            for line in &mut lines[hunk_begin..hunk_end] {
+                line.flag &= !mark;
+            }
+        }
+        i = hunk_end;
+    }
+    give_context(lines, parents)
+}
+
+/// Whether a line can serve as the function name in a hunk header.
+fn is_hunk_comment(line: &[u8]) -> bool {
+    line.first()
+        .is_some_and(|&byte| byte.is_ascii_alphabetic() || byte == b'_' || byte == b'$')
+}
+
+/// Write the marked lines as hunks with one column per parent, `ends` are the
+/// line numbers in the parents past their ends.
+fn write_hunks(out: &mut Vec<u8>, lines: &[Line<'_>], ends: &[usize]) {
+    let parents = ends.len();
+    let mark = 1 << parents;
+    let no_pre_delete = 2 << parents;
+    let count = lines.len() - 1;
+    let markers = "@".repeat(parents + 1);
+
+    let mut index = 0;
+    loop {
+        let mut hunk_comment = None;
+        while index < lines.len() && lines[index].flag & mark == 0 {
+            if is_hunk_comment(lines[index].line) {
+                hunk_comment = Some(lines[index].line);
+            }
+            index += 1;
+        }
+        if index == lines.len() {
+            break;
+        }
+        let hunk_end = find_next(lines, mark, index + 1, false);
+        let result_lines = hunk_end - index - usize::from(hunk_end > count);
+
+        write!(out, "{markers}").unwrap();
+        for (parent, &parent_end) in ends.iter().enumerate() {
+            let start = lines[index].parent_lines[parent];
+            let end = lines
+                .get(hunk_end)
+                .map_or(parent_end, |line| line.parent_lines[parent]);
+            write!(out, " -{start},{}", end - start).unwrap();
+        }
+        write!(out, " +{},{result_lines} {markers}", index + 1).unwrap();
+        if let Some(comment) = hunk_comment {
+            // Like git, up to the last non-space character of the first 40
+            // (exclusive).
+            let comment = &comment[..comment.len().min(40)];
+            let end = comment
+                .iter()
+                .rposition(|byte| !byte.is_ascii_whitespace())
+                .unwrap_or(0);
+            if end > 0 {
+                out.push(b' ');
+                out.extend_from_slice(&comment[..end]);
+            }
+        }
+        out.push(b'\n');
+
+        while index < hunk_end {
+            let line = &lines[index];
+            index += 1;
+            if line.flag & no_pre_delete == 0 {
+                for lost in &line.lost {
+                    for parent in 0..parents {
+                        out.push(if lost.parents & (1 << parent) != 0 {
+                            b'-'
+                        } else {
+                            b' '
+                        });
+                    }
+                    out.extend_from_slice(lost.line);
+                    out.push(b'\n');
+                }
+            }
+            if index > count {
+                break;
+            }
+            for parent in 0..parents {
+                out.push(if line.flag & (1 << parent) != 0 {
+                    b'+'
+                } else {
+                    b' '
+                });
+            }
+            out.extend_from_slice(line.line);
+            out.push(b'\n');
+        }
+    }
+}
+
+/// A blob or submodule on one side of the combined diff, `None` where the
+/// file doesn't exist.
+type Side = Option<(gix::objs::tree::EntryMode, gix::ObjectId)>;
+
+/// Append the combined diff of a single file to `out`.
+fn write_file(
+    out: &mut Vec<u8>,
+    repository: &gix::Repository,
+    path: &str,
+    parents: &[Side],
+    result: Side,
+    algorithm: gix::diff::blob::Algorithm,
+    dense: bool,
+) -> PyResult<()> {
+    // Like git, submodules are diffed as files of a `Subproject commit <id>`
+    // line.
+    let read = |side: &Side| -> PyResult<Vec<u8>> {
+        match side {
+            Some((mode, id)) if mode.is_commit() => {
+                Ok(format!("Subproject commit {id}\n").into_bytes())
+            }
+            Some((_, id)) => Ok(repository.find_object(*id).into_py_result()?.detach().data),
+            None => Ok(Vec::new()),
+        }
+    };
+    let result_data = read(&result)?;
+    let parent_data = parents.iter().map(read).collect::<PyResult<Vec<_>>>()?;
+
+    let mut header = Vec::new();
+    writeln!(
+        header,
+        "diff --{} {path}",
+        if dense { "cc" } else { "combined" }
+    )
+    .unwrap();
+    let null = gix::ObjectId::null(repository.object_hash());
+    let abbreviated = |side: &Side| side.map_or(null, |(_, id)| id).to_string()[..7].to_string();
+    writeln!(
+        header,
+        "index {}..{}",
+        parents
+            .iter()
+            .map(abbreviated)
+            .collect::<Vec<_>>()
+            .join(","),
+        abbreviated(&result)
+    )
+    .unwrap();
+    let mode = |side: &Side| {
+        side.map_or("000000".to_string(), |(mode, _)| {
+            format!("{:0>6}", mode.as_bytes(&mut [0; 6]).to_string())
+        })
+    };
+    let mode_differs = parents
+        .iter()
+        .any(|parent| parent.map(|(mode, _)| mode) != result.map(|(mode, _)| mode));
+    let added = result.is_some() && parents.iter().all(Option::is_none);
+    if mode_differs {
+        if added {
+            writeln!(header, "new file mode {}", mode(&result)).unwrap();
+        } else {
+            if result.is_none() {
+                write!(header, "deleted file ").unwrap();
+            }
+            write!(
+                header,
+                "mode {}",
+                parents.iter().map(mode).collect::<Vec<_>>().join(",")
+            )
+            .unwrap();
+            if result.is_some() {
+                write!(header, "..{}", mode(&result)).unwrap();
+            }
+            writeln!(header).unwrap();
+        }
+    }
+
+    if binary::looks_binary(&result_data)
+        || parent_data.iter().any(|data| binary::looks_binary(data))
+    {
+        out.extend_from_slice(&header);
+        writeln!(out, "Binary files differ").unwrap();
+        return Ok(());
+    }
+
+    let result_lines = if result.is_some() {
+        lines(&result_data)
+    } else {
+        Vec::new()
+    };
+    let mut lines = result_lines
+        .iter()
+        .copied()
+        .chain([&b""[..]])
+        .map(|line| Line {
+            line,
+            lost: Vec::new(),
+            flag: 0,
+            parent_lines: vec![0; parents.len()],
+        })
+        .collect::<Vec<_>>();
+    let ends = parent_data
+        .iter()
+        .enumerate()
+        .map(|(index, data)| diff_parent(&mut lines, data, &result_data, index, algorithm))
+        .collect::<Vec<_>>();
+
+    let show_hunks = make_hunks(&mut lines, parents.len(), dense);
+    if show_hunks || mode_differs {
+        out.extend_from_slice(&header);
+        if added {
+            writeln!(out, "--- /dev/null").unwrap();
+        } else {
+            writeln!(out, "--- a/{path}").unwrap();
+        }
+        if result.is_none() {
+            writeln!(out, "+++ /dev/null").unwrap();
+        } else {
+            writeln!(out, "+++ b/{path}").unwrap();
+        }
+        write_hunks(out, &lines, &ends);
+    }
+    Ok(())
+}
+
+/// The combined diff of a merge against all of its parents at once, like
+/// `git diff --cc` (if `dense`) or `git diff -c`, `None` if it shows nothing.
+///
+/// Only files that differ from every parent are included, with one column
+/// per parent marking the lines added (`+`) or removed (`-`) relative to it.
+pub(crate) fn diff_combined(
+    commit: &gix::Commit<'_>,
+    algorithm: gix::diff::blob::Algorithm,
+    dense: bool,
+) -> PyResult<Option<Vec<u8>>> {
+    let repository = commit.repo;
+    let parent_ids = commit.parent_ids().collect::<Vec<_>>();
+    // Two bits after those of the parents are used to mark lines.
+    if parent_ids.len() > 62 {
+        return Err(PyValueError::new_err(format!(
+            "commit {} has too many parents for a combined diff",
+            commit.id
+        )));
+    }
+    let tree = commit.tree().into_py_result()?;
+    let options = gix::diff::Options::default().with_rewrites(None);
+
+    // The paths changed relative to every parent, sorted by their bytes which
+    // is git's order for full paths.
+    let mut paths = Vec::<(String, Vec<Side>, Side)>::new();
+    for (index, parent_id) in parent_ids.iter().enumerate() {
+        let parent_tree = parent_id
+            .object()
+            .into_py_result()?
+            .peel_to_tree()
+            .into_py_result()?;
+        let changes = repository
+            .diff_tree_to_tree(Some(&parent_tree), Some(&tree), options)
+            .into_py_result()?;
+        let mut changed = std::collections::HashMap::new();
+        for change in changes {
+            use gix::object::tree::diff::ChangeDetached;
+
+            let (location, parent, result) = match change {
+                ChangeDetached::Addition {
+                    location,
+                    entry_mode,
+                    id,
+                    ..
+                } => (location, None, Some((entry_mode, id))),
+                ChangeDetached::Deletion {
+                    location,
+                    entry_mode,
+                    id,
+                    ..
+                } => (location, Some((entry_mode, id)), None),
+                ChangeDetached::Modification {
+                    location,
+                    previous_entry_mode,
+                    previous_id,
+                    entry_mode,
+                    id,
+                } => (
+                    location,
+                    Some((previous_entry_mode, previous_id)),
+                    Some((entry_mode, id)),
+                ),
+                ChangeDetached::Rewrite { .. } => continue,
+            };
+            let files = [parent, result]
+                .iter()
+                .flatten()
+                .all(|(mode, _)| !mode.is_tree());
+            if files {
+                changed.insert(location.to_string(), (parent, result));
+            }
+        }
+        if index == 0 {
+            let mut first = changed.into_iter().collect::<Vec<_>>();
+            first.sort_by(|(a, _), (b, _)| a.cmp(b));
+            paths = first
+                .into_iter()
+                .map(|(path, (parent, result))| (path, vec![parent], result))
+                .collect();
+        } else {
+            paths.retain_mut(|(path, parents, _)| match changed.get(path) {
+                Some((parent, _)) => {
+                    parents.push(*parent);
+                    true
+                }
+                None => false,
+            });
+        }
+    }
+
+    let mut out = Vec::new();
+    for (path, parents, result) in &paths {
+        write_file(
+            &mut out, repository, path, parents, *result, algorithm, dense,
+        )?;
+    }
+    Ok((!out.is_empty()).then_some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_combined;
+    use crate::tests::git;
+
+    use gix::bstr::ByteSlice;
+
+    use std::path::Path;
+
+    fn write(directory: &Path, name: &str, lines: &[&str]) {
+        let content = lines
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        std::fs::write(directory.join(name), content).unwrap();
+    }
+
+    /// A repository whose last commit merges two branches that changed the
+    /// same lines, resolved with some of both and something new.
+    fn repository_with_merge() -> (tempfile::TempDir, gix::Repository) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q", "-b", "main"], b"");
+        write(
+            path,
+            "file.txt",
+            &["1", "2", "3", "4", "5", "6", "7", "8", "9"],
+        );
+        write(path, "same.txt", &["a"]);
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "base"], b"");
+
+        write(
+            path,
+            "file.txt",
+            &["1", "two", "3", "4", "5", "6", "7", "8", "nine"],
+        );
+        write(path, "same.txt", &["b"]);
+        git(path, &["commit", "-q", "-am", "main"], b"");
+        git(path, &["checkout", "-q", "-b", "side", "HEAD~"], b"");
+        write(
+            path,
+            "file.txt",
+            &["1", "II", "3", "4", "5", "6", "7", "8", "9", "10"],
+        );
+        git(path, &["commit", "-q", "-am", "side"], b"");
+
+        git(path, &["checkout", "-q", "main"], b"");
+        git(
+            path,
+            &["merge", "-q", "-s", "ours", "--no-commit", "side"],
+            b"",
+        );
+        write(
+            path,
+            "file.txt",
+            &["1", "two", "II", "3", "4", "5", "6", "7", "8", "nine", "10"],
+        );
+        git(path, &["commit", "-q", "-am", "merge"], b"");
+
+        let repository = gix::open(path).unwrap();
+        (directory, repository)
+    }
+
+    #[test]
+    fn matches_git() {
+        let (directory, repository) = repository_with_merge();
+        let commit = repository.head_commit().unwrap();
+        for (dense, flag) in [(true, "--cc"), (false, "-c")] {
+            let diff = diff_combined(&commit, gix::diff::blob::Algorithm::Myers, dense)
+                .unwrap()
+                .unwrap();
+            let expected = git(
+                directory.path(),
+                &["diff-tree", "-p", flag, "--no-commit-id", "HEAD"],
+                b"",
+            );
+            assert_eq!(diff.as_bstr(), expected.as_bstr(), "{flag}");
+        }
+    }
+
+    #[test]
+    fn shows_submodules_like_git() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let gitlink = |id: &str| {
+            git(
+                path,
+                &[
+                    "update-index",
+                    "--add",
+                    "--cacheinfo",
+                    &format!("160000,{id},sub"),
+                ],
+                b"",
+            );
+        };
+        git(path, &["init", "-q", "-b", "main"], b"");
+        gitlink(&"1".repeat(40));
+        git(path, &["commit", "-q", "-m", "base"], b"");
+        gitlink(&"2".repeat(40));
+        git(path, &["commit", "-q", "-m", "main"], b"");
+        git(path, &["checkout", "-q", "-b", "side", "HEAD~"], b"");
+        gitlink(&"3".repeat(40));
+        git(path, &["commit", "-q", "-m", "side"], b"");
+        git(path, &["checkout", "-q", "main"], b"");
+        git(
+            path,
+            &["merge", "-q", "-s", "ours", "--no-commit", "side"],
+            b"",
+        );
+        gitlink(&"4".repeat(40));
+        git(path, &["commit", "-q", "-m", "merge"], b"");
+
+        let repository = gix::open(path).unwrap();
+        let commit = repository.head_commit().unwrap();
+        for (dense, flag) in [(true, "--cc"), (false, "-c")] {
+            let diff = diff_combined(&commit, gix::diff::blob::Algorithm::Myers, dense)
+                .unwrap()
+                .unwrap();
+            let expected = git(
+                path,
+                &["diff-tree", "-p", flag, "--no-commit-id", "HEAD"],
+                b"",
+            );
+            assert!(diff.contains_str("+Subproject commit 4444"), "{flag}");
+            assert_eq!(diff.as_bstr(), expected.as_bstr(), "{flag}");
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use pyo3::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Commit, IntoPyResult, Repository};
+
+/// The field-level differences between two commits, e.g. the same change
+/// before and after a rebase.
+#[pyclass]
+pub struct CommitComparison {
+    #[pyo3(get)]
+    pub summary_changed: bool,
+    #[pyo3(get)]
+    pub body_changed: bool,
+    /// Trailer values present in the second commit but not the first.
+    #[pyo3(get)]
+    pub trailers_added: HashMap<String, HashSet<String>>,
+    /// Trailer values present in the first commit but not the second.
+    #[pyo3(get)]
+    pub trailers_removed: HashMap<String, HashSet<String>>,
+    #[pyo3(get)]
+    pub author_changed: bool,
+    #[pyo3(get)]
+    pub committer_changed: bool,
+    /// Whether both commits record the exact same tree.
+    #[pyo3(get)]
+    pub tree_same: bool,
+    /// Whether both commits introduce the same change (see `patch_id`), only
+    /// known when a repository to diff in is given.
+    #[pyo3(get)]
+    pub patch_id_equal: Option<bool>,
+}
+
+/// The trailer values of `a` that `b` doesn't have.
+fn trailer_difference(
+    a: &HashMap<String, HashSet<String>>,
+    b: &HashMap<String, HashSet<String>>,
+) -> HashMap<String, HashSet<String>> {
+    a.iter()
+        .filter_map(|(token, values)| {
+            let missing = match b.get(token) {
+                Some(other) => values.difference(other).cloned().collect(),
+                None => values.clone(),
+            };
+            (!missing.is_empty()).then(|| (token.clone(), missing))
+        })
+        .collect()
+}
+
+/// A hash of a patch that ignores whitespace, line numbers, and blob IDs so
+/// that the same change applied to a different base gets the same ID (similar
+/// to `git patch-id`).
+pub(crate) fn patch_id(patch: &[u8]) -> gix::ObjectId {
+    let mut hasher = gix::hash::hasher(gix::hash::Kind::Sha1);
+    for line in patch.split(|&byte| byte == b'\n') {
+        if line.starts_with(b"index ") {
+            continue;
+        }
+        let line = if line.starts_with(b"@@ ") {
+            // Only keep the function context after the line numbers.
+            line.get(2..)
+                .and_then(|rest| rest.windows(2).position(|pair| pair == b"@@"))
+                .map_or(&b""[..], |end| &line[end + 4..])
+        } else {
+            line
+        };
+        let line = line
+            .iter()
+            .copied()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect::<Vec<_>>();
+        hasher.update(&line);
+    }
+    hasher
+        .try_finalize()
+        .unwrap_or_else(|_| gix::ObjectId::null(gix::hash::Kind::Sha1))
+}
+
+/// C's `isspace`, which unlike `u8::is_ascii_whitespace` includes `\v`.
+fn is_space(byte: &u8) -> bool {
+    matches!(byte, b' ' | b'\t'..=b'\r')
+}
+
+/// The line counts of a `@@ -a,b +c,d @@` hunk header, read the way
+/// `git patch-id` does (a missing count is 1).
+fn hunk_counts(line: &[u8], before: &mut i64, after: &mut i64) {
+    /// Skip a line number and read the count after it, returning the count,
+    /// the number of digits read last, and the rest of the line.
+    fn count(rest: &[u8]) -> (i64, usize, &[u8]) {
+        let digits = |rest: &[u8]| rest.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        let start = digits(rest);
+        if rest.get(start) != Some(&b',') {
+            return (1, start, &rest[start..]);
+        }
+        let rest = &rest[start + 1..];
+        let end = digits(rest);
+        let count = std::str::from_utf8(&rest[..end])
+            .ok()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0);
+        (count, end, &rest[end..])
+    }
+
+    let (lines, digits, rest) = count(&line[4..]);
+    *before = lines;
+    if let Some(rest) = rest.strip_prefix(b" +").filter(|_| digits > 0) {
+        *after = count(rest).0;
+    }
+}
+
+/// The patch ID `git patch-id --stable` computes for a patch in the format of
+/// `git show`, `None` if there is no change in it.
+///
+/// Every file is hashed on its own without whitespace, line numbers, and
+/// blob IDs, and the hashes are added up so the order of the files doesn't
+/// matter.
+pub(crate) fn stable_patch_id(patch: &[u8]) -> Option<gix::ObjectId> {
+    let mut result = [0u8; 20];
+    let mut hasher = gix::hash::hasher(gix::hash::Kind::Sha1);
+    let mut flush = |hasher: &mut gix::hash::Hasher| {
+        let hash = std::mem::replace(hasher, gix::hash::hasher(gix::hash::Kind::Sha1))
+            .try_finalize()
+            .unwrap_or_else(|_| gix::ObjectId::null(gix::hash::Kind::Sha1));
+        let mut carry = 0u16;
+        for (sum, byte) in result.iter_mut().zip(hash.as_bytes()) {
+            carry += u16::from(*sum) + u16::from(*byte);
+            *sum = carry as u8;
+            carry >>= 8;
+        }
+    };
+
+    let mut patch_len = 0;
+    let (mut before, mut after) = (-1, -1);
+    let mut is_binary = false;
+    let (mut pre_id, mut post_id) = (&b""[..], &b""[..]);
+    for line in patch.split_inclusive(|&byte| byte == b'\n') {
+        if line.starts_with(b"\\ ") && line.len() > 12 {
+            continue;
+        }
+        if patch_len == 0 && !line.starts_with(b"diff ") {
+            continue;
+        }
+        if before == -1 {
+            if line.starts_with(b"GIT binary patch") || line.starts_with(b"Binary files") {
+                is_binary = true;
+                before = 0;
+                hasher.update(pre_id);
+                hasher.update(post_id);
+                flush(&mut hasher);
+                continue;
+            } else if let Some(ids) = line.strip_prefix(b"index ") {
+                // `index <pre>..<post> <mode>`, the IDs only matter for binary
+                // files.
+                if let Some(dots) = ids.windows(2).position(|pair| pair == b"..") {
+                    let post = &ids[dots + 2..];
+                    let end = post
+                        .iter()
+                        .position(|&byte| byte == b' ')
+                        .unwrap_or(post.len().saturating_sub(1));
+                    (pre_id, post_id) = (&ids[..dots], &post[..end]);
+                }
+                continue;
+            } else if line.starts_with(b"--- ") {
+                (before, after) = (1, 1);
+            } else if !line[0].is_ascii_alphabetic() {
+                break;
+            }
+        }
+
+        if is_binary {
+            if line.starts_with(b"diff ") {
+                is_binary = false;
+                before = -1;
+            }
+            continue;
+        }
+
+        if before == 0 && after == 0 {
+            if line.starts_with(b"@@ -") {
+                hunk_counts(line, &mut before, &mut after);
+                continue;
+            }
+            if !line.starts_with(b"diff ") {
+                break;
+            }
+            // The next file.
+            flush(&mut hasher);
+            (before, after) = (-1, -1);
+        }
+
+        if matches!(line[0], b'-' | b' ') {
+            before -= 1;
+        }
+        if matches!(line[0], b'+' | b' ') {
+            after -= 1;
+        }
+        let line = line
+            .iter()
+            .copied()
+            .filter(|byte| !is_space(byte))
+            .collect::<Vec<_>>();
+        patch_len += line.len();
+        hasher.update(&line);
+    }
+    flush(&mut hasher);
+    (patch_len > 0).then(|| gix::ObjectId::from_bytes_or_panic(&result))
+}
+
+fn tree_id(commit: &Commit) -> PyResult<gix::ObjectId> {
+    Ok(gix::objs::CommitRef::from_bytes(&commit.inner.data)
+        .into_py_result()?
+        .tree())
+}
+
+fn commit_patch_id(repository: &gix::Repository, commit: &Commit) -> PyResult<gix::ObjectId> {
+    let attached = commit.inner.clone().attach(repository).into_commit();
+    let patch = Repository::diff_with_parent(
+        &attached,
+        gix::diff::blob::Algorithm::Myers,
+        &Default::default(),
+    )?
+    .unwrap_or_default();
+    Ok(patch_id(&patch))
+}
+
+/// Compare the metadata of two commits field by field.
+///
+/// The patch IDs are only compared if `repository` is given as the commits
+/// need to be diffed against their parents for it.
+#[pyfunction]
+#[pyo3(signature=(a, b, repository=None))]
+pub fn compare_commits(
+    a: &Commit,
+    b: &Commit,
+    repository: Option<&Repository>,
+) -> PyResult<CommitComparison> {
+    let patch_id_equal = repository
+        .map(|repository| -> PyResult<bool> {
+            let repository = repository.inner.to_thread_local();
+            Ok(commit_patch_id(&repository, a)? == commit_patch_id(&repository, b)?)
+        })
+        .transpose()?;
+
+    Ok(CommitComparison {
+        summary_changed: a.summary() != b.summary(),
+        body_changed: a.body() != b.body(),
+        trailers_added: trailer_difference(b.trailers(), a.trailers()),
+        trailers_removed: trailer_difference(a.trailers(), b.trailers()),
+        author_changed: a.author.name != b.author.name || a.author.email != b.author.email,
+        committer_changed: a.committer.name != b.committer.name
+            || a.committer.email != b.committer.email,
+        tree_same: tree_id(a)? == tree_id(b)?,
+        patch_id_equal,
+    })
+}
@@ -0,0 +1,55 @@
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::IntoPyResult;
+
+/// The branches whose history includes `commit` (see `git branch --contains`),
+/// local ones or, if `remote`, remote-tracking ones like `origin/main`.
+///
+/// Symbolic refs such as `origin/HEAD` are skipped, they only repeat the
+/// branch they point to.
+pub(crate) fn branches_containing(
+    repository: &gix::Repository,
+    commit: gix::ObjectId,
+    remote: bool,
+) -> PyResult<Vec<String>> {
+    let platform = repository.references().into_py_result()?;
+    let references = if remote {
+        platform.remote_branches()
+    } else {
+        platform.local_branches()
+    }
+    .into_py_result()?;
+
+    // Branches often share their tip (e.g. right after a release).
+    let mut contains_by_tip = HashMap::new();
+    let mut containing = Vec::new();
+    for reference in references {
+        let mut reference = reference.map_err(|e| PyOSError::new_err(e.to_string()))?;
+        if matches!(reference.target(), gix::refs::TargetRef::Symbolic(_)) {
+            continue;
+        }
+        let name = reference.name().shorten().to_string();
+        let tip = reference.peel_to_commit().into_py_result()?.id;
+        let contains = match contains_by_tip.get(&tip) {
+            Some(&contains) => contains,
+            None => {
+                // The commit is in the history of the tip exactly if it's their
+                // merge base.
+                let contains = match repository.merge_base(commit, tip) {
+                    Ok(base) => base == commit,
+                    Err(gix::repository::merge_base::Error::NotFound { .. }) => false,
+                    Err(e) => return Err(PyOSError::new_err(e.to_string())),
+                };
+                contains_by_tip.insert(tip, contains);
+                contains
+            }
+        };
+        if contains {
+            containing.push(name);
+        }
+    }
+    Ok(containing)
+}
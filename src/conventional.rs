@@ -0,0 +1,230 @@
+use pyo3::prelude::*;
+
+/// A commit message following the Conventional Commits specification
+/// (<https://www.conventionalcommits.org/en/v1.0.0/>), e.g.
+/// `feat(parser)!: drop support for legacy headers`.
+#[pyclass(frozen)]
+pub struct ConventionalCommit {
+    /// The type of change, e.g. `feat` or `fix`, as written.
+    #[pyo3(get, name = "type")]
+    pub kind: String,
+    #[pyo3(get)]
+    pub scope: Option<String>,
+    /// Whether the header has a `!` or there is a `BREAKING CHANGE` footer.
+    #[pyo3(get)]
+    pub breaking: bool,
+    #[pyo3(get)]
+    pub description: String,
+    #[pyo3(get)]
+    pub body: Option<String>,
+    /// The footers in order, multi-line values are kept as is.
+    #[pyo3(get)]
+    pub footers: Vec<(String, String)>,
+}
+
+#[pymethods]
+impl ConventionalCommit {
+    /// Parse a commit message, `None` if its header doesn't follow the
+    /// specification.
+    #[staticmethod]
+    pub fn parse(message: &str) -> Option<Self> {
+        parse(message)
+    }
+
+    /// The value of the first `BREAKING CHANGE` footer.
+    #[getter]
+    fn breaking_change(&self) -> Option<&str> {
+        self.footers
+            .iter()
+            .find(|(token, _)| is_breaking(token))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn is_breaking(token: &str) -> bool {
+    token == "BREAKING CHANGE" || token == "BREAKING-CHANGE"
+}
+
+/// Split `type(scope)!: description` into its parts.
+fn header(line: &str) -> Option<(String, Option<String>, bool, String)> {
+    let (prefix, description) = line.split_once(": ")?;
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, scope)) => {
+            let scope = scope.strip_suffix(')')?;
+            if scope.is_empty() || scope.contains(['(', ')']) {
+                return None;
+            }
+            (kind, Some(scope.to_string()))
+        }
+        None => (prefix, None),
+    };
+    let description = description.trim();
+    if kind.is_empty()
+        || !kind
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+        || description.is_empty()
+    {
+        return None;
+    }
+    Some((kind.to_string(), scope, breaking, description.to_string()))
+}
+
+/// Split a footer line `token: value` or `token #value` into its parts, the
+/// `#` is kept as part of the value (e.g. `Refs #123`).
+fn footer(line: &str) -> Option<(&str, &str)> {
+    let colon = line.find(": ").map(|index| (index, index + 2));
+    let hash = line.find(" #").map(|index| (index, index + 1));
+    let (end, value_start) = [colon, hash].into_iter().flatten().min()?;
+    let token = &line[..end];
+    let valid = is_breaking(token)
+        || (!token.is_empty()
+            && token
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '-'));
+    valid.then(|| (token, &line[value_start..]))
+}
+
+pub(crate) fn parse(message: &str) -> Option<ConventionalCommit> {
+    let message = message.replace("\r\n", "\n");
+    let mut lines = message.trim_start_matches('\n').splitn(2, '\n');
+    let (kind, scope, mut breaking, description) = header(lines.next()?)?;
+    let rest = lines.next().unwrap_or_default().trim_matches('\n');
+
+    // The footers are the trailing paragraphs that start with one, a value
+    // continues up to the next footer.
+    let paragraphs = rest
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .collect::<Vec<_>>();
+    let footer_start = paragraphs
+        .iter()
+        .rposition(|paragraph| footer(paragraph.lines().next().unwrap_or_default()).is_none())
+        .map_or(0, |index| index + 1);
+    let mut footers = Vec::<(String, String)>::new();
+    for line in paragraphs[footer_start..].join("\n\n").lines() {
+        match (footer(line), footers.last_mut()) {
+            (Some((token, value)), _) => footers.push((token.to_string(), value.to_string())),
+            (None, Some((_, value))) => {
+                value.push('\n');
+                value.push_str(line);
+            }
+            (None, None) => {}
+        }
+    }
+    for (_, value) in &mut footers {
+        *value = value.trim_end().to_string();
+    }
+    breaking |= footers.iter().any(|(token, _)| is_breaking(token));
+
+    let body = paragraphs[..footer_start].join("\n\n");
+    Some(ConventionalCommit {
+        kind,
+        scope,
+        breaking,
+        description,
+        body: (!body.is_empty()).then_some(body),
+        footers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn header() {
+        let commit = parse("feat(parser)!: drop support for legacy headers\n").unwrap();
+        assert_eq!(commit.kind, "feat");
+        assert_eq!(commit.scope.as_deref(), Some("parser"));
+        assert!(commit.breaking);
+        assert_eq!(commit.description, "drop support for legacy headers");
+        assert_eq!(commit.body, None);
+        assert!(commit.footers.is_empty());
+
+        let commit = parse("fix: off by one").unwrap();
+        assert_eq!((commit.kind.as_str(), commit.scope), ("fix", None));
+        assert!(!commit.breaking);
+
+        for message in [
+            "Fix the thing",
+            "feat(): empty scope",
+            "feat(a(b)): nested scope",
+            "feat:",
+            "fe at: space in the type",
+        ] {
+            assert!(parse(message).is_none(), "{message}");
+        }
+    }
+
+    #[test]
+    fn body_and_footers() {
+        let commit = parse(
+            "fix(api): keep the old field\r\n\
+             \r\n\
+             The field is still read by older clients.\r\n\
+             \r\n\
+             Reviewed-by: Z\r\n\
+             Refs #123\r\n\
+             BREAKING CHANGE: the new field is gone,\r\n\
+             \x20 use the old one instead.\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            commit.body.as_deref(),
+            Some("The field is still read by older clients.")
+        );
+        assert_eq!(
+            commit.footers,
+            [
+                ("Reviewed-by".to_string(), "Z".to_string()),
+                ("Refs".to_string(), "#123".to_string()),
+                (
+                    "BREAKING CHANGE".to_string(),
+                    "the new field is gone,\n  use the old one instead.".to_string()
+                ),
+            ]
+        );
+        assert!(commit.breaking);
+        assert_eq!(
+            commit.breaking_change(),
+            Some("the new field is gone,\n  use the old one instead.")
+        );
+    }
+
+    #[test]
+    fn breaking_change_with_a_hyphen() {
+        let commit = parse("chore: bump\n\nBREAKING-CHANGE: needs Python 3.12").unwrap();
+        assert!(commit.breaking);
+        assert_eq!(commit.body, None);
+        assert_eq!(commit.breaking_change(), Some("needs Python 3.12"));
+    }
+
+    #[test]
+    fn paragraphs_before_footers_are_body() {
+        let commit = parse(
+            "docs: explain\n\n\
+             First paragraph.\n\n\
+             Not: a footer because the next paragraph isn't one\n\n\
+             Last paragraph.\n\n\
+             Signed-off-by: A <a@example.com>\n",
+        )
+        .unwrap();
+        assert_eq!(
+            commit.body.as_deref(),
+            Some(
+                "First paragraph.\n\n\
+                 Not: a footer because the next paragraph isn't one\n\n\
+                 Last paragraph."
+            )
+        );
+        assert_eq!(
+            commit.footers,
+            [("Signed-off-by".to_string(), "A <a@example.com>".to_string())]
+        );
+    }
+}
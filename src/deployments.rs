@@ -0,0 +1,140 @@
+use pyo3::prelude::*;
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{Commit, IntoPyResult};
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Deployment records ingested from an external system, each one a deployed
+/// commit and when it was deployed.
+#[derive(Default)]
+pub(crate) struct Deployments {
+    records: Vec<(gix::ObjectId, i64)>,
+}
+
+impl Deployments {
+    pub(crate) fn ingest(&mut self, records: Vec<(String, jiff::Timestamp)>) -> PyResult<usize> {
+        let parsed = records
+            .into_iter()
+            .map(|(id, time)| {
+                Ok((
+                    gix::ObjectId::from_hex(id.as_bytes()).into_py_result()?,
+                    time.as_second(),
+                ))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let ingested = parsed.len();
+        self.records.extend(parsed);
+        // Ties are broken by ID so repeated ingestion gives stable results.
+        self.records
+            .sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+        Ok(ingested)
+    }
+
+    /// The index of the record that first deployed each commit.
+    ///
+    /// Deployments are processed oldest first and a deployment ships all
+    /// ancestors of its commit that no earlier deployment shipped, so every
+    /// commit is only visited once.
+    fn first_deployed(
+        &self,
+        repository: &gix::Repository,
+    ) -> PyResult<HashMap<gix::ObjectId, usize>> {
+        let mut deployed = HashMap::new();
+        for (index, &(id, _)) in self.records.iter().enumerate() {
+            let mut pending = vec![id];
+            while let Some(id) = pending.pop() {
+                if deployed.contains_key(&id) {
+                    continue;
+                }
+                // Deployed commits that aren't in this repository (e.g. from a
+                // different fork) can't be joined against.
+                let Ok(object) = repository.find_object(id) else {
+                    continue;
+                };
+                deployed.insert(id, index);
+                let commit = gix::objs::CommitRef::from_bytes(&object.data).into_py_result()?;
+                pending.extend(commit.parents());
+            }
+        }
+        Ok(deployed)
+    }
+}
+
+/// The time from commit to deployment of a range of commits.
+#[pyclass]
+pub struct LeadTime {
+    /// The lead time of every deployed commit in seconds.
+    #[pyo3(get)]
+    pub samples: Vec<f64>,
+    /// The number of commits that have not been deployed yet.
+    #[pyo3(get)]
+    pub undeployed: usize,
+}
+
+#[pymethods]
+impl LeadTime {
+    #[getter]
+    fn mean(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+
+    #[getter]
+    fn median(&self) -> f64 {
+        let mut sorted = self.samples.clone();
+        sorted.sort_by(f64::total_cmp);
+        match sorted.len() {
+            0 => 0.0,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+            len => sorted[len / 2],
+        }
+    }
+}
+
+pub(crate) fn lead_time(
+    repository: &gix::Repository,
+    deployments: &Deployments,
+    commits: &[Commit],
+) -> PyResult<LeadTime> {
+    let deployed = deployments.first_deployed(repository)?;
+    let mut samples = Vec::new();
+    let mut undeployed = 0;
+    for commit in commits {
+        match deployed.get(&commit.inner.id) {
+            Some(&index) => {
+                let time = deployments.records[index].1;
+                samples.push((time - commit.commit_time.timestamp().as_second()) as f64);
+            }
+            None => undeployed += 1,
+        }
+    }
+    Ok(LeadTime {
+        samples,
+        undeployed,
+    })
+}
+
+/// The number of deployments per day that shipped any of `commits`, measured
+/// over the days between the first and last of those deployments.
+pub(crate) fn deployment_frequency(
+    repository: &gix::Repository,
+    deployments: &Deployments,
+    commits: &[Commit],
+) -> PyResult<f64> {
+    let deployed = deployments.first_deployed(repository)?;
+    let shipping = commits
+        .iter()
+        .filter_map(|commit| deployed.get(&commit.inner.id).copied())
+        .collect::<BTreeSet<_>>();
+    // Records are sorted by time.
+    let (Some(&first), Some(&last)) = (shipping.first(), shipping.last()) else {
+        return Ok(0.0);
+    };
+    let span = deployments.records[last].1 - deployments.records[first].1;
+    let days = (span as f64 / SECONDS_PER_DAY).max(1.0);
+    Ok(shipping.len() as f64 / days)
+}
@@ -0,0 +1,253 @@
+use gix::prelude::ObjectIdExt;
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::{IntoPyResult, signature_time};
+
+/// Like git, stop looking for closer tags after this many.
+const MAX_CANDIDATES: usize = 10;
+
+/// A commit described relative to the closest tag in its history, see
+/// `git describe --tags --long`.
+#[pyclass(frozen)]
+pub struct Description {
+    #[pyo3(get)]
+    pub tag: String,
+    /// The number of commits since the tag, 0 if the commit is tagged.
+    #[pyo3(get)]
+    pub distance: usize,
+    /// The shortest unique abbreviation of the commit ID.
+    #[pyo3(get)]
+    pub abbreviated_id: String,
+}
+
+#[pymethods]
+impl Description {
+    /// `<tag>-<distance>-g<abbreviated_id>` like `git describe --long`.
+    fn __str__(&self) -> String {
+        format!("{}-{}-g{}", self.tag, self.distance, self.abbreviated_id)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Description {}>", self.__str__())
+    }
+}
+
+/// The name of a tag and what git prefers it by among tags of the same
+/// commit: annotated tags over lightweight ones, then newer tagger dates.
+struct Name {
+    name: String,
+    annotated: bool,
+    tagger_time: i64,
+}
+
+/// The best tag name of every tagged commit.
+fn tag_names(repository: &gix::Repository) -> PyResult<HashMap<gix::ObjectId, Name>> {
+    let mut names = HashMap::<gix::ObjectId, Name>::new();
+    let platform = repository.references().into_py_result()?;
+    for reference in platform.tags().into_py_result()? {
+        let mut reference = reference.map_err(|e| PyOSError::new_err(e.to_string()))?;
+        let name = reference.name().shorten().to_string();
+        let tag = reference
+            .target()
+            .try_id()
+            .and_then(|id| repository.find_object(id).ok())
+            .filter(|object| object.kind == gix::object::Kind::Tag);
+        let tagger_time = tag
+            .as_ref()
+            .and_then(|object| signature_time(&object.data, b"tagger"))
+            .map_or(0, |(seconds, _)| seconds);
+        let Ok(target) = reference.peel_to_id_in_place() else {
+            continue;
+        };
+        let Ok(object) = repository.find_object(target) else {
+            continue;
+        };
+        if object.kind != gix::object::Kind::Commit {
+            continue;
+        }
+        let name = Name {
+            name,
+            annotated: tag.is_some(),
+            tagger_time,
+        };
+        match names.get(&object.id) {
+            Some(existing)
+                if (existing.annotated, existing.tagger_time)
+                    >= (name.annotated, name.tagger_time) => {}
+            _ => {
+                names.insert(object.id, name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// The walk state of a commit: its time, the candidate tags it is reachable
+/// from (as bits), and its parents.
+struct Walked {
+    time: i64,
+    within: u32,
+    parents: Vec<gix::ObjectId>,
+}
+
+/// Commits ordered by commit time, newest first, then in the order they were
+/// queued.
+type Queue = BinaryHeap<(i64, std::cmp::Reverse<usize>, gix::ObjectId)>;
+
+struct Walk<'repo> {
+    repository: &'repo gix::Repository,
+    commits: HashMap<gix::ObjectId, Walked>,
+    queue: Queue,
+    queued: usize,
+}
+
+impl Walk<'_> {
+    fn load(&mut self, id: gix::ObjectId) -> PyResult<&mut Walked> {
+        if !self.commits.contains_key(&id) {
+            let object = self.repository.find_object(id).into_py_result()?;
+            let parents = gix::objs::CommitRefIter::from_bytes(&object.data)
+                .parent_ids()
+                .collect();
+            let time = signature_time(&object.data, b"committer").map_or(0, |(seconds, _)| seconds);
+            self.commits.insert(
+                id,
+                Walked {
+                    time,
+                    within: 0,
+                    parents,
+                },
+            );
+        }
+        Ok(self.commits.get_mut(&id).unwrap())
+    }
+
+    fn push(&mut self, id: gix::ObjectId) -> PyResult<()> {
+        let time = self.load(id)?.time;
+        self.queue.push((time, std::cmp::Reverse(self.queued), id));
+        self.queued += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<gix::ObjectId> {
+        self.queue.pop().map(|(.., id)| id)
+    }
+
+    /// Queue the parents that weren't seen yet and mark all of them as
+    /// reachable from whatever `id` is reachable from.
+    fn visit_parents(&mut self, id: gix::ObjectId) -> PyResult<()> {
+        let Walked {
+            within, parents, ..
+        } = &self.commits[&id];
+        let (within, parents) = (*within, parents.clone());
+        for parent in parents {
+            let seen = self.commits.contains_key(&parent);
+            if !seen {
+                self.push(parent)?;
+            }
+            self.load(parent)?.within |= within;
+        }
+        Ok(())
+    }
+}
+
+struct Candidate {
+    name: String,
+    depth: usize,
+    found_order: usize,
+    within: u32,
+}
+
+/// Describe `target` the way `git describe --tags --long` does, `None` if
+/// no tag can be reached from it.
+///
+/// Commits are walked newest first, the first tags met are candidates, and
+/// a candidate's depth is the number of walked commits it can't reach. The
+/// candidate with the smallest depth wins.
+pub(crate) fn describe(
+    repository: &gix::Repository,
+    target: gix::ObjectId,
+) -> PyResult<Option<Description>> {
+    let names = tag_names(repository)?;
+    let abbreviated_id = target.attach(repository).shorten_or_id().to_string();
+    if let Some(name) = names.get(&target) {
+        return Ok(Some(Description {
+            tag: name.name.clone(),
+            distance: 0,
+            abbreviated_id,
+        }));
+    }
+
+    let mut walk = Walk {
+        repository,
+        commits: HashMap::new(),
+        queue: Queue::new(),
+        queued: 0,
+    };
+    walk.push(target)?;
+    let mut candidates = Vec::<Candidate>::new();
+    let mut annotated = 0;
+    let mut seen_commits = 0;
+    let mut gave_up_on = None;
+    while let Some(id) = walk.pop() {
+        seen_commits += 1;
+        if let Some(name) = names.get(&id) {
+            if candidates.len() == MAX_CANDIDATES {
+                gave_up_on = Some(id);
+                break;
+            }
+            let within = 1 << candidates.len();
+            candidates.push(Candidate {
+                name: name.name.clone(),
+                depth: seen_commits - 1,
+                found_order: candidates.len(),
+                within,
+            });
+            walk.load(id)?.within |= within;
+            annotated += usize::from(name.annotated);
+        }
+        let within = walk.commits[&id].within;
+        for candidate in &mut candidates {
+            if within & candidate.within == 0 {
+                candidate.depth += 1;
+            }
+        }
+        if annotated > 0 && walk.queue.is_empty() {
+            break;
+        }
+        walk.visit_parents(id)?;
+    }
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    candidates.sort_by_key(|candidate| (candidate.depth, candidate.found_order));
+    if let Some(id) = gave_up_on {
+        walk.push(id)?;
+    }
+
+    // Finish counting the commits the best candidate can't reach, which
+    // stops as soon as everything left is reachable from it.
+    let best = &mut candidates[0];
+    while let Some(id) = walk.pop() {
+        if walk.commits[&id].within & best.within != 0 {
+            if walk
+                .queue
+                .iter()
+                .all(|(.., id)| walk.commits[id].within & best.within != 0)
+            {
+                break;
+            }
+        } else {
+            best.depth += 1;
+        }
+        walk.visit_parents(id)?;
+    }
+
+    Ok(Some(Description {
+        tag: best.name.clone(),
+        distance: best.depth,
+        abbreviated_id,
+    }))
+}
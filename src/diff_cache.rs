@@ -0,0 +1,107 @@
+//! Patches kept by commit and options so that diffing overlapping ranges
+//! again (e.g. on every CI run) doesn't recompute them.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::unified_diff;
+
+/// The patches computed so far, `None` for commits that changed nothing.
+///
+/// With a directory every patch is also written to a file named by the hash
+/// of its key, the file starts with the key itself so that a patch is only
+/// ever read back for the same commit and options.
+pub(crate) struct DiffCache {
+    patches: Mutex<HashMap<String, Option<Vec<u8>>>>,
+    directory: Option<PathBuf>,
+}
+
+impl DiffCache {
+    pub(crate) fn new(directory: Option<PathBuf>) -> std::io::Result<Self> {
+        if let Some(directory) = &directory {
+            std::fs::create_dir_all(directory)?;
+        }
+        Ok(Self {
+            patches: Default::default(),
+            directory,
+        })
+    }
+
+    /// The key of the patch of `commit` against its parent at index `parent`,
+    /// `None` if the patch can't be cached (see `PatchFormat::cache_key`).
+    /// Patches of other versions of gitch don't share keys in case their
+    /// output differs.
+    pub(crate) fn key(
+        commit: gix::ObjectId,
+        parent: usize,
+        algorithm: gix::diff::blob::Algorithm,
+        paths: &[String],
+        format: &unified_diff::PatchFormat,
+    ) -> Option<String> {
+        Some(format!(
+            "gitch {} {commit} {parent} {algorithm:?} {paths:?} {}",
+            env!("CARGO_PKG_VERSION"),
+            format.cache_key()?
+        ))
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        let mut hasher = gix::hash::hasher(gix::hash::Kind::Sha1);
+        hasher.update(key.as_bytes());
+        let name = hasher.try_finalize().ok()?.to_string();
+        Some(self.directory.as_ref()?.join(name))
+    }
+
+    /// The cached patch for `key`, `None` if there is none.
+    pub(crate) fn get(&self, key: &str) -> std::io::Result<Option<Option<Vec<u8>>>> {
+        if let Some(patch) = self.patches.lock().unwrap().get(key) {
+            return Ok(Some(patch.clone()));
+        }
+        let Some(path) = self.path(key) else {
+            return Ok(None);
+        };
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let patch = match data
+            .strip_prefix(key.as_bytes())
+            .and_then(|rest| rest.split_first())
+        {
+            Some((b'-', [])) => None,
+            Some((b'+', patch)) => Some(patch.to_vec()),
+            // Another key with the same hash, or a file not written by us.
+            _ => return Ok(None),
+        };
+        self.patches
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), patch.clone());
+        Ok(Some(patch))
+    }
+
+    pub(crate) fn insert(&self, key: String, patch: Option<&[u8]>) -> std::io::Result<()> {
+        if let Some(path) = self.path(&key) {
+            // Written whole and then moved in place so that concurrent runs
+            // sharing the directory never read half a patch.
+            let mut file = tempfile::NamedTempFile::new_in(path.parent().unwrap())?;
+            file.write_all(key.as_bytes())?;
+            match patch {
+                Some(patch) => {
+                    file.write_all(b"+")?;
+                    file.write_all(patch)?;
+                }
+                None => file.write_all(b"-")?,
+            }
+            file.persist(path)?;
+        }
+        self.patches
+            .lock()
+            .unwrap()
+            .insert(key, patch.map(<[u8]>::to_vec));
+        Ok(())
+    }
+}
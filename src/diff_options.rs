@@ -0,0 +1,110 @@
+use gitch_engine::{funcname, renames, submodule, unified_diff};
+use pyo3::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::{py_err, textconv};
+
+/// The options of `Repository.diff`, see there for what each does.
+pub(crate) struct DiffOptions<'a> {
+    pub(crate) src_prefix: &'a str,
+    pub(crate) dst_prefix: &'a str,
+    pub(crate) no_prefix: bool,
+    pub(crate) git_header: bool,
+    pub(crate) parent: usize,
+    pub(crate) paths: Option<Vec<String>>,
+    pub(crate) context_lines: u32,
+    pub(crate) inter_hunk_context: u32,
+    pub(crate) ignore_whitespace: Option<&'a str>,
+    pub(crate) rename_threshold: Option<u8>,
+    pub(crate) find_copies: bool,
+    pub(crate) find_copies_harder: bool,
+    pub(crate) rename_limit: Option<usize>,
+    pub(crate) binary: bool,
+    pub(crate) max_file_size: Option<u64>,
+    pub(crate) max_bytes: Option<usize>,
+    pub(crate) max_lines: Option<usize>,
+    pub(crate) textconv: Option<HashMap<String, Py<PyAny>>>,
+    pub(crate) submodule: &'a str,
+    pub(crate) color: bool,
+}
+
+impl Default for DiffOptions<'_> {
+    /// The defaults of the keyword arguments of `Repository.diff`.
+    fn default() -> Self {
+        Self {
+            src_prefix: "a/",
+            dst_prefix: "b/",
+            no_prefix: false,
+            git_header: true,
+            parent: 0,
+            paths: None,
+            context_lines: 3,
+            inter_hunk_context: 0,
+            ignore_whitespace: None,
+            rename_threshold: None,
+            find_copies: false,
+            find_copies_harder: false,
+            rename_limit: None,
+            binary: false,
+            max_file_size: None,
+            max_bytes: None,
+            max_lines: None,
+            textconv: None,
+            submodule: "short",
+            color: false,
+        }
+    }
+}
+
+impl DiffOptions<'_> {
+    /// The format of the patches of `Repository.diff_bytes` and
+    /// `Repository.diff_to`. The `textconv` converters move into it.
+    pub(crate) fn patch_format(
+        &mut self,
+        repository: &gix::Repository,
+    ) -> PyResult<unified_diff::PatchFormat> {
+        let (src_prefix, dst_prefix) = if self.no_prefix {
+            ("", "")
+        } else {
+            (self.src_prefix, self.dst_prefix)
+        };
+        Ok(unified_diff::PatchFormat {
+            src_prefix: src_prefix.to_string(),
+            dst_prefix: dst_prefix.to_string(),
+            git_header: self.git_header,
+            binary: self.binary,
+            submodule: submodule::Format::parse(self.submodule).map_err(py_err)?,
+            ..self.structured_format(repository)?
+        })
+    }
+
+    /// The format of the files of `Repository.diff_structured`, which only
+    /// takes the options that aren't about the patch's text. The `textconv`
+    /// converters move into it.
+    pub(crate) fn structured_format(
+        &mut self,
+        repository: &gix::Repository,
+    ) -> PyResult<unified_diff::PatchFormat> {
+        Ok(unified_diff::PatchFormat {
+            context_lines: self.context_lines,
+            inter_hunk_context: self.inter_hunk_context,
+            ignore_whitespace: self
+                .ignore_whitespace
+                .map(unified_diff::IgnoreWhitespace::parse)
+                .transpose()
+                .map_err(py_err)?,
+            renames: renames::RenameDetection::new(
+                self.rename_threshold,
+                self.find_copies,
+                self.find_copies_harder,
+                self.rename_limit,
+            )
+            .map_err(py_err)?,
+            max_file_size: self.max_file_size,
+            funcnames: funcname::Drivers::new(repository).map_err(py_err)?,
+            textconv: textconv::converters(self.textconv.take()),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,236 @@
+//! Commits as emails the way `git format-patch` writes them, to be sent for
+//! review and applied with `git am`.
+
+use pyo3::prelude::*;
+
+use std::fmt::Write;
+
+use crate::{Commit, IntoPyResult, Repository, funcname, py_err, stats, unified_diff};
+
+/// How long header lines get before they are folded.
+const MAX_LENGTH: usize = 78;
+
+/// How long header lines with encoded words get, per RFC 2047.
+const MAX_ENCODED_LENGTH: usize = 76;
+
+/// How wide the `--stat` of a patch is.
+const STAT_WIDTH: usize = 72;
+
+/// The patch of `commit` against its first parent and its files, `None`
+/// for merges and commits that change nothing as git doesn't format those.
+pub(crate) fn changes(
+    repository: &gix::Repository,
+    commit: &Commit,
+    algorithm: gix::diff::blob::Algorithm,
+) -> PyResult<Option<(Vec<u8>, Vec<stats::FileStat>)>> {
+    if commit.parent_ids.len() > 1 {
+        return Ok(None);
+    }
+    let commit = commit.inner.clone().attach(repository).into_commit();
+    let format = unified_diff::PatchFormat {
+        binary: true,
+        funcnames: funcname::Drivers::new(repository).map_err(py_err)?,
+        ..Default::default()
+    };
+    let Some(patch) = Repository::diff_with_nth_parent(&commit, 0, algorithm, &format, &[])? else {
+        return Ok(None);
+    };
+    let files = Repository::count_files(&commit, 0, &patch)?;
+    Ok(Some((patch, files)))
+}
+
+/// `commit` as an email of its message and its `changes`. `number` is the
+/// position of the patch in a series and the length of the series
+/// (`[PATCH 2/5]`).
+pub(crate) fn format_patch(
+    commit: &Commit,
+    (patch, files): &(Vec<u8>, Vec<stats::FileStat>),
+    number: Option<(usize, usize)>,
+    subject_prefix: &str,
+) -> PyResult<String> {
+    let message = commit.message();
+    let (title, body) = split_message(&message);
+
+    let mut out = format!("From {} Mon Sep 17 00:00:00 2001\n", commit.id);
+    out.push_str("From: ");
+    let name = &commit.author.name;
+    let max_length = if needs_rfc2047(name) {
+        add_rfc2047(&mut out, name, true);
+        MAX_ENCODED_LENGTH
+    } else {
+        if name.contains([
+            '(', ')', '<', '>', '[', ']', ':', ';', '@', '\\', ',', '.', '"',
+        ]) {
+            let quoted = name.replace('\\', "\\\\").replace('"', "\\\"");
+            add_wrapped(&mut out, &format!("\"{quoted}\""));
+        } else {
+            add_wrapped(&mut out, name);
+        }
+        MAX_LENGTH
+    };
+    if last_line_length(&out) + commit.author.email.len() + 3 > max_length {
+        out.push('\n');
+    }
+    writeln!(out, " <{}>", commit.author.email).unwrap();
+    writeln!(
+        out,
+        "Date: {}",
+        jiff::fmt::rfc2822::to_string(&commit.author_time).into_py_result()?
+    )
+    .unwrap();
+
+    out.push_str("Subject: ");
+    match number {
+        Some((number, total)) => {
+            let separator = if subject_prefix.is_empty() { "" } else { " " };
+            let digits = total.to_string().len();
+            write!(
+                out,
+                "[{subject_prefix}{separator}{number:0digits$}/{total}] "
+            )
+            .unwrap();
+        }
+        None if !subject_prefix.is_empty() => write!(out, "[{subject_prefix}] ").unwrap(),
+        None => {}
+    }
+    if needs_rfc2047(&title) {
+        add_rfc2047(&mut out, &title, false);
+    } else {
+        add_wrapped(&mut out, &title);
+    }
+    out.push('\n');
+    if !title.is_ascii() || !body.is_ascii() {
+        out.push_str("MIME-Version: 1.0\n");
+        out.push_str("Content-Type: text/plain; charset=UTF-8\n");
+        out.push_str("Content-Transfer-Encoding: 8bit\n");
+    }
+    out.push('\n');
+    if !body.is_empty() {
+        out.push_str(&body);
+        out.push('\n');
+    }
+
+    out.push_str("---\n");
+    if let Some(stat) = stats::stat(files, STAT_WIDTH) {
+        out.push_str(&stat);
+    }
+    out.push_str(&stats::summary(files));
+    out.push('\n');
+    out.push_str(&String::from_utf8_lossy(patch));
+    writeln!(out, "-- \ngitch {}\n", env!("CARGO_PKG_VERSION")).unwrap();
+    Ok(out)
+}
+
+/// The subject (the first paragraph on one line) and the body of a commit
+/// message, without trailing whitespace on any line and the blank lines
+/// around them.
+fn split_message(message: &str) -> (String, String) {
+    let mut lines = message
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.is_empty());
+    let title = lines
+        .by_ref()
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let body = lines
+        .skip_while(|line| line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (title, body.trim_end().to_string())
+}
+
+/// The columns taken by the last line of `out`.
+fn last_line_length(out: &str) -> usize {
+    out.len() - out.rfind('\n').map_or(0, |newline| newline + 1)
+}
+
+/// Whether a header value can't be written as is (`needs_rfc2047_encoding`
+/// of git).
+fn needs_rfc2047(text: &str) -> bool {
+    !text.is_ascii() || text.contains('\n') || text.contains("=?")
+}
+
+/// Append `text` as RFC 2047 encoded words, folded so that lines stay
+/// within 76 columns without splitting a character. Phrases in addresses
+/// (`address`) encode all but a few more characters.
+fn add_rfc2047(out: &mut String, text: &str, address: bool) {
+    let is_special = |byte: u8| {
+        !(byte.is_ascii_graphic() || byte == b' ')
+            || matches!(byte, b' ' | b'=' | b'?' | b'_')
+            || address
+                && !(byte.is_ascii_alphanumeric()
+                    || matches!(byte, b'!' | b'*' | b'+' | b'-' | b'/'))
+    };
+    let mut line_len = last_line_length(out) + "=?UTF-8?q?".len();
+    out.push_str("=?UTF-8?q?");
+    for character in text.chars() {
+        let mut bytes = [0; 4];
+        let bytes = character.encode_utf8(&mut bytes).as_bytes();
+        let special = bytes.len() > 1 || is_special(bytes[0]);
+        let encoded_len = if special { 3 * bytes.len() } else { 1 };
+        // Leave room for the closing `?=`.
+        if line_len + encoded_len + 2 > MAX_ENCODED_LENGTH {
+            out.push_str("?=\n =?UTF-8?q?");
+            line_len = " =?UTF-8?q?".len();
+        }
+        if special {
+            for byte in bytes {
+                write!(out, "={byte:02X}").unwrap();
+            }
+        } else {
+            out.push(character);
+        }
+        line_len += encoded_len;
+    }
+    out.push_str("?=");
+}
+
+/// Append the ASCII `text` folded between words so that lines stay within
+/// 78 columns where possible, continuation lines indented by a space
+/// (`strbuf_add_wrapped_text` of git).
+fn add_wrapped(out: &mut String, text: &str) {
+    let bytes = text.as_bytes();
+    let mut width = last_line_length(out);
+    // Where the current line of `text` starts, and the whitespace before the
+    // word at hand if the line has one.
+    let (mut start, mut space) = (0, Some(0));
+    let mut at = 0;
+    loop {
+        let byte = bytes.get(at).copied();
+        if byte.is_some_and(|byte| !byte.is_ascii_whitespace()) {
+            width += 1;
+            at += 1;
+            continue;
+        }
+        if width <= MAX_LENGTH || space.is_none() {
+            if byte.is_none() && at == start {
+                return;
+            }
+            match space {
+                Some(space) => out.push_str(&text[space..at]),
+                None => {
+                    out.push(' ');
+                    out.push_str(&text[start..at]);
+                }
+            }
+            if byte.is_none() {
+                return;
+            }
+            space = Some(at);
+            if byte == Some(b'\t') {
+                width |= 7;
+            }
+            width += 1;
+            at += 1;
+        } else {
+            // Move the word to the next line.
+            out.push('\n');
+            let space = space.take().unwrap();
+            start = space + usize::from(bytes.get(space).is_some_and(u8::is_ascii_whitespace));
+            at = start;
+            width = 1;
+        }
+    }
+}
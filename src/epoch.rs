@@ -0,0 +1,100 @@
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::{Commit, IntoPyResult, signature_time};
+
+/// Candidates for `SOURCE_DATE_EPOCH` (see reproducible-builds.org) derived
+/// from a range of commits, as seconds since the UNIX epoch.
+#[pyclass]
+pub struct SourceDateEpoch {
+    /// The latest committer time in the range.
+    #[pyo3(get)]
+    pub latest_commit: Option<i64>,
+    /// The time of the latest tag pointing into the range, the tagger time for
+    /// annotated tags and the tagged commit's time otherwise.
+    #[pyo3(get)]
+    pub latest_tag: Option<i64>,
+    /// The name of the tag `latest_tag` is the time of.
+    #[pyo3(get)]
+    pub latest_tag_name: Option<String>,
+}
+
+fn utc(seconds: Option<i64>) -> Option<String> {
+    jiff::Timestamp::from_second(seconds?)
+        .ok()
+        .map(|timestamp| timestamp.to_string())
+}
+
+#[pymethods]
+impl SourceDateEpoch {
+    /// `latest_commit` as an RFC 3339 timestamp in UTC.
+    #[getter]
+    fn latest_commit_utc(&self) -> Option<String> {
+        utc(self.latest_commit)
+    }
+
+    /// `latest_tag` as an RFC 3339 timestamp in UTC.
+    #[getter]
+    fn latest_tag_utc(&self) -> Option<String> {
+        utc(self.latest_tag)
+    }
+
+    /// The value to export as `SOURCE_DATE_EPOCH`: the latest commit time,
+    /// which is what most build tooling expects.
+    fn env_value(&self) -> Option<String> {
+        self.latest_commit.map(|seconds| seconds.to_string())
+    }
+}
+
+pub(crate) fn source_date_epoch(
+    repository: &gix::Repository,
+    commits: &[Commit],
+) -> PyResult<SourceDateEpoch> {
+    let latest_commit = commits
+        .iter()
+        .map(|commit| commit.commit_time.timestamp().as_second())
+        .max();
+
+    let in_range = commits
+        .iter()
+        .map(|commit| (commit.inner.id, commit))
+        .collect::<HashMap<_, _>>();
+    let mut latest_tag = None::<(i64, String)>;
+    let platform = repository.references().into_py_result()?;
+    for reference in platform.tags().into_py_result()? {
+        let mut reference = reference.map_err(|e| PyOSError::new_err(e.to_string()))?;
+        let name = reference.name().shorten().to_string();
+        // Annotated tags have their own time, lightweight ones only the one
+        // of the commit.
+        let tagger_time = reference
+            .target()
+            .try_id()
+            .and_then(|id| repository.find_object(id).ok())
+            .filter(|object| object.kind == gix::object::Kind::Tag)
+            .and_then(|object| signature_time(&object.data, b"tagger"))
+            .map(|(seconds, _)| seconds);
+        let Ok(target) = reference.peel_to_id_in_place() else {
+            continue;
+        };
+        let Some(commit) = in_range.get(&target.detach()) else {
+            continue;
+        };
+        let time = tagger_time.unwrap_or_else(|| commit.commit_time.timestamp().as_second());
+
+        if latest_tag
+            .as_ref()
+            .is_none_or(|(latest, latest_name)| (time, &name) > (*latest, latest_name))
+        {
+            latest_tag = Some((time, name));
+        }
+    }
+
+    let (latest_tag, latest_tag_name) = latest_tag.unzip();
+    Ok(SourceDateEpoch {
+        latest_commit,
+        latest_tag,
+        latest_tag_name,
+    })
+}
@@ -0,0 +1,435 @@
+//! History as a stream of `git fast-import` commands, the way `git
+//! fast-export` writes it.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::{IntoPyResult, paths, simplify, walk};
+
+/// A revision the range includes, under the name its commits are exported.
+struct Tip {
+    /// The full name of the ref (e.g. `refs/heads/main`), or the revision as
+    /// given if it isn't one.
+    name: String,
+    /// Whether the revision is a ref.
+    reference: bool,
+    commit: gix::ObjectId,
+    /// The annotated tag the ref points at.
+    tag: Option<gix::ObjectId>,
+}
+
+/// Split a range like `git fast-export` takes it into revision specs: `A..B`
+/// is `^A B` (with `HEAD` for a missing side) and specs can be separated by
+/// whitespace.
+pub(crate) fn specs(range: &str) -> PyResult<Vec<String>> {
+    let mut specs = Vec::new();
+    for spec in range.split_whitespace() {
+        if spec.contains("...") {
+            return Err(PyValueError::new_err(format!(
+                "symmetric differences like {spec:?} are not supported"
+            )));
+        }
+        match spec.split_once("..") {
+            Some((from, to)) => {
+                let or_head = |rev: &str| if rev.is_empty() { "HEAD" } else { rev }.to_string();
+                specs.push(format!("^{}", or_head(from)));
+                specs.push(or_head(to));
+            }
+            None => specs.push(spec.to_string()),
+        }
+    }
+    Ok(specs)
+}
+
+/// Resolve an included revision spec, following symbolic refs like `HEAD` to
+/// the branch they are on.
+fn tip(repository: &gix::Repository, spec: &str, commit: gix::ObjectId) -> PyResult<Tip> {
+    // Specs like `main~2` aren't ref names at all.
+    let Ok(Some(mut reference)) = repository.try_find_reference(spec) else {
+        return Ok(Tip {
+            name: spec.to_string(),
+            reference: false,
+            commit,
+            tag: None,
+        });
+    };
+    while let Some(target) = reference.follow() {
+        reference = target.into_py_result()?;
+    }
+    let tag = reference
+        .target()
+        .try_id()
+        .map(|id| id.to_owned())
+        .filter(|id| {
+            repository
+                .try_find_header(*id)
+                .ok()
+                .flatten()
+                .is_some_and(|header| header.kind() == gix::object::Kind::Tag)
+        });
+    Ok(Tip {
+        name: reference.name().as_bstr().to_string(),
+        reference: true,
+        commit,
+        tag,
+    })
+}
+
+/// The commits of `parents` (newest first in `newest_first`) ordered so
+/// that parents come before their children, the reverse of `git log
+/// --topo-order`.
+fn topological(
+    parents: &HashMap<gix::ObjectId, Vec<gix::ObjectId>>,
+    newest_first: &[gix::ObjectId],
+) -> Vec<gix::ObjectId> {
+    // One more than the number of children yet to be ordered.
+    let mut indegree = newest_first
+        .iter()
+        .map(|id| (*id, 1))
+        .collect::<HashMap<_, usize>>();
+    for id in newest_first {
+        for parent in &parents[id] {
+            if let Some(indegree) = indegree.get_mut(parent) {
+                *indegree += 1;
+            }
+        }
+    }
+    let mut stack = newest_first
+        .iter()
+        .rev()
+        .filter(|id| indegree[*id] == 1)
+        .copied()
+        .collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(newest_first.len());
+    while let Some(id) = stack.pop() {
+        for parent in &parents[&id] {
+            if let Some(indegree) = indegree.get_mut(parent) {
+                *indegree -= 1;
+                if *indegree == 1 {
+                    stack.push(*parent);
+                }
+            }
+        }
+        order.push(id);
+    }
+    order.reverse();
+    order
+}
+
+/// Quote `path` like git does where it has to (`quote_c_style`), paths with
+/// spaces are only put in quotes.
+fn quote(path: &[u8]) -> Vec<u8> {
+    let printable = |byte: u8| byte.is_ascii_graphic() || byte == b' ';
+    let needs_quoting = path
+        .iter()
+        .any(|&byte| !printable(byte) || byte == b'"' || byte == b'\\');
+    if !needs_quoting {
+        return if path.contains(&b' ') {
+            [b"\"", path, b"\""].concat()
+        } else {
+            path.to_vec()
+        };
+    }
+    let mut out = vec![b'"'];
+    for &byte in path {
+        match byte {
+            b'\x07' => out.extend_from_slice(b"\\a"),
+            b'\x08' => out.extend_from_slice(b"\\b"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\x0b' => out.extend_from_slice(b"\\v"),
+            b'\x0c' => out.extend_from_slice(b"\\f"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'"' | b'\\' => out.extend_from_slice(&[b'\\', byte]),
+            _ if !printable(byte) => out.extend_from_slice(format!("\\{byte:03o}").as_bytes()),
+            _ => out.push(byte),
+        }
+    }
+    out.push(b'"');
+    out
+}
+
+/// The headers (without the `tree` and `parent` lines) and the message of
+/// a raw commit or tag.
+fn split_object(data: &[u8]) -> (Vec<&[u8]>, &[u8]) {
+    let (headers, message) = match data.windows(2).position(|window| window == b"\n\n") {
+        Some(end) => (&data[..end], &data[end + 2..]),
+        None => (data, &[][..]),
+    };
+    (headers.split(|&byte| byte == b'\n').collect(), message)
+}
+
+/// The raw header line starting with `name` and a space.
+fn header<'a>(headers: &[&'a [u8]], name: &str) -> Option<&'a [u8]> {
+    headers.iter().copied().find(|line| {
+        line.strip_prefix(name.as_bytes())
+            .is_some_and(|rest| rest.starts_with(b" "))
+    })
+}
+
+/// The commits of `range` (see `specs`) as a `git fast-import` stream: every
+/// blob once before the first commit using it, then the commit with its
+/// changes against its first parent. With `paths` only those paths are
+/// exported and history is simplified for them like `git fast-export <range>
+/// -- <paths>` does.
+pub(crate) fn fast_export(
+    repository: &gix::Repository,
+    range: &str,
+    paths: &[PathBuf],
+) -> PyResult<Vec<u8>> {
+    let specs = specs(range)?;
+    let (include, exclude) = walk::resolve_tips(repository, &specs)?;
+    let tips = specs
+        .iter()
+        .filter(|spec| !spec.starts_with('^'))
+        .zip(&include)
+        .map(|(spec, commit)| tip(repository, spec, *commit))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let infos = walk::walk(
+        repository,
+        include.iter().copied(),
+        exclude,
+        gix::revision::walk::Sorting::ByCommitTime(
+            gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+        ),
+    )?
+    .collect::<Vec<_>>();
+    let (parents, dropped) = if paths.is_empty() {
+        let parents = infos
+            .iter()
+            .map(|info| (info.id, info.parent_ids.to_vec()))
+            .collect::<HashMap<_, _>>();
+        (parents, HashMap::new())
+    } else {
+        simplify::by_paths_with_dropped(repository, &infos, paths)?
+    };
+    let newest_first = infos
+        .iter()
+        .map(|info| info.id)
+        .filter(|id| parents.contains_key(id))
+        .collect::<Vec<_>>();
+    let patterns = paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    // Like git, commits are exported under the name of the revision they are
+    // reached from first, refs before other revisions and newer children
+    // before older ones.
+    let mut names = HashMap::new();
+    let (references, others) = tips.iter().partition::<Vec<_>, _>(|tip| tip.reference);
+    for tip in references.into_iter().chain(others) {
+        names.entry(tip.commit).or_insert(tip.name.as_str());
+    }
+    for info in &infos {
+        let Some(&name) = names.get(&info.id) else {
+            continue;
+        };
+        for parent in &info.parent_ids {
+            names.entry(*parent).or_insert(name);
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut marks = HashMap::<gix::ObjectId, usize>::new();
+    let options = gix::diff::Options::default().with_rewrites(None);
+    for id in topological(&parents, &newest_first) {
+        let commit = repository.find_commit(id).into_py_result()?;
+        let commit_parents = &parents[&id];
+        // Without its first parent the commit adds all of its files.
+        let parent_tree = match commit_parents.first().filter(|id| marks.contains_key(*id)) {
+            Some(parent) => repository
+                .find_commit(*parent)
+                .into_py_result()?
+                .tree()
+                .into_py_result()?,
+            None => repository.empty_tree(),
+        };
+        let tree = commit.tree().into_py_result()?;
+        let mut changes = repository
+            .diff_tree_to_tree(Some(&parent_tree), Some(&tree), Some(options))
+            .into_py_result()?
+            .into_iter()
+            .filter(|change| {
+                !change.entry_mode().is_tree() && paths::matches(&patterns, change.location())
+            })
+            .collect::<Vec<_>>();
+        changes.sort_by(|a, b| a.location().cmp(b.location()));
+
+        for change in &changes {
+            let (mode, blob) = change.entry_mode_and_id();
+            if mode.is_commit()
+                || matches!(
+                    change,
+                    gix::object::tree::diff::ChangeDetached::Deletion { .. }
+                )
+                || marks.contains_key(blob)
+            {
+                continue;
+            }
+            let data = repository
+                .find_blob(blob.to_owned())
+                .into_py_result()?
+                .take_data();
+            let mark = marks.len() + 1;
+            marks.insert(blob.to_owned(), mark);
+            write!(out, "blob\nmark :{mark}\ndata {}\n", data.len()).unwrap();
+            out.extend_from_slice(&data);
+            out.push(b'\n');
+        }
+
+        let name = names[&id];
+        if commit_parents.is_empty() {
+            writeln!(out, "reset {name}").unwrap();
+        }
+        let mark = marks.len() + 1;
+        marks.insert(id, mark);
+        write!(out, "commit {name}\nmark :{mark}\n").unwrap();
+        let (headers, message) = split_object(&commit.data);
+        for name in ["author", "committer", "encoding"] {
+            if let Some(line) = header(&headers, name) {
+                out.extend_from_slice(line);
+                out.push(b'\n');
+            }
+        }
+        writeln!(out, "data {}", message.len()).unwrap();
+        out.extend_from_slice(message);
+        let exported = commit_parents.iter().filter_map(|parent| marks.get(parent));
+        for (index, mark) in exported.enumerate() {
+            let command = if index == 0 { "from" } else { "merge" };
+            writeln!(out, "{command} :{mark}").unwrap();
+        }
+
+        // Files are deleted before a directory of the same name is added
+        // (`depth_first` of git).
+        changes.sort_by(|a, b| {
+            let (a, b) = (a.location(), b.location());
+            let len = a.len().min(b.len());
+            a[..len].cmp(&b[..len]).then(b.len().cmp(&a.len()))
+        });
+        for change in &changes {
+            let path = quote(change.location());
+            if let gix::object::tree::diff::ChangeDetached::Deletion { .. } = change {
+                out.extend_from_slice(b"D ");
+            } else {
+                let (mode, id) = change.entry_mode_and_id();
+                let mut backing = [0; 6];
+                // Submodules refer to commits of another repository.
+                if mode.is_commit() {
+                    write!(out, "M {} {id} ", mode.as_bytes(&mut backing)).unwrap();
+                } else {
+                    write!(out, "M {} :{} ", mode.as_bytes(&mut backing), marks[id]).unwrap();
+                }
+            }
+            out.extend_from_slice(&path);
+            out.push(b'\n');
+        }
+        out.push(b'\n');
+    }
+
+    // Refs no commit was exported under are set where their commit (or the
+    // nearest exported commit it was simplified to) is, annotated tags are
+    // recreated. Refs to commits that weren't exported are deleted.
+    let exported = |id| {
+        let id = dropped.get(&id).copied().flatten().unwrap_or(id);
+        marks.get(&id).copied()
+    };
+    let used = names
+        .iter()
+        .filter(|(id, _)| marks.contains_key(*id))
+        .map(|(_, name)| *name)
+        .collect::<HashSet<_>>();
+    let null = gix::ObjectId::null(repository.object_hash());
+    let resets = tips
+        .iter()
+        .rev()
+        .filter(|tip| tip.reference && tip.tag.is_none() && !used.contains(tip.name.as_str()));
+    let tags = tips.iter().rev().filter(|tip| tip.tag.is_some());
+    for tip in resets.chain(tags) {
+        let Some(mark) = exported(tip.commit) else {
+            write!(out, "reset {}\nfrom {null}\n\n", tip.name).unwrap();
+            continue;
+        };
+        let Some(tag) = tip.tag else {
+            write!(out, "reset {}\nfrom :{mark}\n\n", tip.name).unwrap();
+            continue;
+        };
+        let tag = repository.find_object(tag).into_py_result()?;
+        let (headers, message) = split_object(&tag.data);
+        let name = tip.name.strip_prefix("refs/tags/").unwrap_or(&tip.name);
+        write!(out, "tag {name}\nfrom :{mark}\n").unwrap();
+        if let Some(line) = header(&headers, "tagger") {
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+        writeln!(out, "data {}", message.len()).unwrap();
+        out.extend_from_slice(message);
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fast_export;
+    use crate::tests::git;
+
+    /// A repository with a merge, a rename, paths with a space and non-ASCII
+    /// characters, and an annotated tag.
+    fn repository() -> (tempfile::TempDir, gix::Repository) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q", "-b", "main"], b"");
+        std::fs::create_dir(path.join("dir")).unwrap();
+        std::fs::write(path.join("dir/with space.txt"), "space\n").unwrap();
+        std::fs::write(path.join("old.txt"), "renamed\n").unwrap();
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "base"], b"");
+        git(path, &["switch", "-q", "-c", "side"], b"");
+        std::fs::write(path.join("naïve.txt"), "naïve\n").unwrap();
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "side"], b"");
+        git(path, &["switch", "-q", "main"], b"");
+        git(path, &["mv", "old.txt", "new.txt"], b"");
+        git(path, &["commit", "-q", "-m", "rename"], b"");
+        git(
+            path,
+            &["merge", "-q", "--no-ff", "-m", "merge", "side"],
+            b"",
+        );
+        git(path, &["tag", "-a", "-m", "release\n\nnotes", "v1.0"], b"");
+        let repository = gix::open(path).unwrap();
+        (directory, repository)
+    }
+
+    #[test]
+    fn exports_like_git() {
+        let (directory, repository) = repository();
+        let exported = fast_export(&repository, "main v1.0", &[]).unwrap();
+        let expected = git(
+            directory.path(),
+            &["fast-export", "--reencode=no", "main", "v1.0"],
+            b"",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&exported),
+            String::from_utf8_lossy(&expected)
+        );
+    }
+
+    #[test]
+    fn imports_to_the_same_history() {
+        let (directory, repository) = repository();
+        let exported = fast_export(&repository, "main v1.0", &[]).unwrap();
+        let imported = tempfile::tempdir().unwrap();
+        git(imported.path(), &["init", "-q", "-b", "main"], b"");
+        git(imported.path(), &["fast-import", "--quiet"], &exported);
+        let refs = |path| git(path, &["show-ref", "main", "v1.0"], b"");
+        assert_eq!(refs(imported.path()), refs(directory.path()));
+    }
+}
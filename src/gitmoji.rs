@@ -0,0 +1,250 @@
+use pyo3::prelude::*;
+
+/// The gitmojis of <https://gitmoji.dev> as `(emoji, shortcode, meaning)`.
+///
+/// The emojis are stored without variation selectors (U+FE0F) since those are
+/// optional when matching.
+const GITMOJIS: &[(&str, &str, &str)] = &[
+    (
+        "\u{1F3A8}",
+        "art",
+        "Improve structure / format of the code.",
+    ),
+    ("\u{26A1}", "zap", "Improve performance."),
+    ("\u{1F525}", "fire", "Remove code or files."),
+    ("\u{1F41B}", "bug", "Fix a bug."),
+    ("\u{1F691}", "ambulance", "Critical hotfix."),
+    ("\u{2728}", "sparkles", "Introduce new features."),
+    ("\u{1F4DD}", "memo", "Add or update documentation."),
+    ("\u{1F680}", "rocket", "Deploy stuff."),
+    (
+        "\u{1F484}",
+        "lipstick",
+        "Add or update the UI and style files.",
+    ),
+    ("\u{1F389}", "tada", "Begin a project."),
+    (
+        "\u{2705}",
+        "white_check_mark",
+        "Add, update, or pass tests.",
+    ),
+    ("\u{1F512}", "lock", "Fix security or privacy issues."),
+    (
+        "\u{1F510}",
+        "closed_lock_with_key",
+        "Add or update secrets.",
+    ),
+    ("\u{1F516}", "bookmark", "Release / Version tags."),
+    (
+        "\u{1F6A8}",
+        "rotating_light",
+        "Fix compiler / linter warnings.",
+    ),
+    ("\u{1F6A7}", "construction", "Work in progress."),
+    ("\u{1F49A}", "green_heart", "Fix CI Build."),
+    ("\u{2B07}", "arrow_down", "Downgrade dependencies."),
+    ("\u{2B06}", "arrow_up", "Upgrade dependencies."),
+    (
+        "\u{1F4CC}",
+        "pushpin",
+        "Pin dependencies to specific versions.",
+    ),
+    (
+        "\u{1F477}",
+        "construction_worker",
+        "Add or update CI build system.",
+    ),
+    (
+        "\u{1F4C8}",
+        "chart_with_upwards_trend",
+        "Add or update analytics or track code.",
+    ),
+    ("\u{267B}", "recycle", "Refactor code."),
+    ("\u{2795}", "heavy_plus_sign", "Add a dependency."),
+    ("\u{2796}", "heavy_minus_sign", "Remove a dependency."),
+    ("\u{1F527}", "wrench", "Add or update configuration files."),
+    ("\u{1F528}", "hammer", "Add or update development scripts."),
+    (
+        "\u{1F310}",
+        "globe_with_meridians",
+        "Internationalization and localization.",
+    ),
+    ("\u{270F}", "pencil2", "Fix typos."),
+    (
+        "\u{1F4A9}",
+        "poop",
+        "Write bad code that needs to be improved.",
+    ),
+    ("\u{23EA}", "rewind", "Revert changes."),
+    ("\u{1F500}", "twisted_rightwards_arrows", "Merge branches."),
+    (
+        "\u{1F4E6}",
+        "package",
+        "Add or update compiled files or packages.",
+    ),
+    (
+        "\u{1F47D}",
+        "alien",
+        "Update code due to external API changes.",
+    ),
+    (
+        "\u{1F69A}",
+        "truck",
+        "Move or rename resources (e.g.: files, paths, routes).",
+    ),
+    ("\u{1F4C4}", "page_facing_up", "Add or update license."),
+    ("\u{1F4A5}", "boom", "Introduce breaking changes."),
+    ("\u{1F371}", "bento", "Add or update assets."),
+    ("\u{267F}", "wheelchair", "Improve accessibility."),
+    (
+        "\u{1F4A1}",
+        "bulb",
+        "Add or update comments in source code.",
+    ),
+    ("\u{1F37B}", "beers", "Write code drunkenly."),
+    (
+        "\u{1F4AC}",
+        "speech_balloon",
+        "Add or update text and literals.",
+    ),
+    (
+        "\u{1F5C3}",
+        "card_file_box",
+        "Perform database related changes.",
+    ),
+    ("\u{1F50A}", "loud_sound", "Add or update logs."),
+    ("\u{1F507}", "mute", "Remove logs."),
+    (
+        "\u{1F465}",
+        "busts_in_silhouette",
+        "Add or update contributor(s).",
+    ),
+    (
+        "\u{1F6B8}",
+        "children_crossing",
+        "Improve user experience / usability.",
+    ),
+    (
+        "\u{1F3D7}",
+        "building_construction",
+        "Make architectural changes.",
+    ),
+    ("\u{1F4F1}", "iphone", "Work on responsive design."),
+    ("\u{1F921}", "clown_face", "Mock things."),
+    ("\u{1F95A}", "egg", "Add or update an easter egg."),
+    (
+        "\u{1F648}",
+        "see_no_evil",
+        "Add or update a .gitignore file.",
+    ),
+    ("\u{1F4F8}", "camera_flash", "Add or update snapshots."),
+    ("\u{2697}", "alembic", "Perform experiments."),
+    ("\u{1F50D}", "mag", "Improve SEO."),
+    ("\u{1F3F7}", "label", "Add or update types."),
+    ("\u{1F331}", "seedling", "Add or update seed files."),
+    (
+        "\u{1F6A9}",
+        "triangular_flag_on_post",
+        "Add, update, or remove feature flags.",
+    ),
+    ("\u{1F945}", "goal_net", "Catch errors."),
+    (
+        "\u{1F4AB}",
+        "dizzy",
+        "Add or update animations and transitions.",
+    ),
+    (
+        "\u{1F5D1}",
+        "wastebasket",
+        "Deprecate code that needs to be cleaned up.",
+    ),
+    (
+        "\u{1F6C2}",
+        "passport_control",
+        "Work on code related to authorization, roles and permissions.",
+    ),
+    (
+        "\u{1FA79}",
+        "adhesive_bandage",
+        "Simple fix for a non-critical issue.",
+    ),
+    ("\u{1F9D0}", "monocle_face", "Data exploration/inspection."),
+    ("\u{26B0}", "coffin", "Remove dead code."),
+    ("\u{1F9EA}", "test_tube", "Add a failing test."),
+    ("\u{1F454}", "necktie", "Add or update business logic."),
+    ("\u{1FA7A}", "stethoscope", "Add or update healthcheck."),
+    ("\u{1F9F1}", "bricks", "Infrastructure related changes."),
+    (
+        "\u{1F9D1}\u{200D}\u{1F4BB}",
+        "technologist",
+        "Improve developer experience.",
+    ),
+    (
+        "\u{1F4B8}",
+        "money_with_wings",
+        "Add sponsorships or money related infrastructure.",
+    ),
+    (
+        "\u{1F9F5}",
+        "thread",
+        "Add or update code related to multithreading or concurrency.",
+    ),
+    (
+        "\u{1F9BA}",
+        "safety_vest",
+        "Add or update code related to validation.",
+    ),
+    ("\u{2708}", "airplane", "Improve offline support."),
+];
+
+/// A gitmoji leading a commit summary, e.g. `:bug: Fix the parser` or
+/// `🐛 Fix the parser`.
+#[pyclass(frozen)]
+pub struct Gitmoji {
+    /// The emoji, also when the summary uses the shortcode.
+    #[pyo3(get)]
+    pub emoji: &'static str,
+    /// The shortcode including the colons, e.g. `:bug:`.
+    #[pyo3(get)]
+    pub code: String,
+    /// What the gitmoji stands for.
+    #[pyo3(get)]
+    pub description: &'static str,
+    /// The summary without the gitmoji.
+    #[pyo3(get)]
+    pub summary: String,
+}
+
+#[pymethods]
+impl Gitmoji {
+    /// Recognize the gitmoji a summary starts with, `None` if there is none.
+    #[staticmethod]
+    pub fn parse(summary: &str) -> Option<Self> {
+        parse(summary)
+    }
+}
+
+pub(crate) fn parse(summary: &str) -> Option<Gitmoji> {
+    let summary = summary.trim_start();
+    let (&(emoji, code, description), rest) = if let Some(rest) = summary.strip_prefix(':') {
+        let (code, rest) = rest.split_once(':')?;
+        (GITMOJIS.iter().find(|(_, known, _)| *known == code)?, rest)
+    } else {
+        // Longest first so e.g. a ZWJ sequence isn't mistaken for its first
+        // emoji.
+        GITMOJIS
+            .iter()
+            .filter(|(emoji, ..)| summary.starts_with(emoji))
+            .max_by_key(|(emoji, ..)| emoji.len())
+            .map(|gitmoji| {
+                let rest = &summary[gitmoji.0.len()..];
+                (gitmoji, rest.strip_prefix('\u{FE0F}').unwrap_or(rest))
+            })?
+    };
+    Some(Gitmoji {
+        emoji,
+        code: format!(":{code}:"),
+        description,
+        summary: rest.trim_start().to_string(),
+    })
+}
@@ -0,0 +1,558 @@
+use gitch_engine::time::signature_time;
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use ssh_key::{HashAlg, PublicKey, SshSig};
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::IntoPyResult;
+
+/// The cryptographic signature of a commit and what it signs, to be checked
+/// by external verification tooling.
+#[pyclass(frozen)]
+pub struct CommitSignature {
+    /// One of `gpg`, `ssh`, `x509`, or `unknown`, detected from the armor.
+    #[pyo3(get)]
+    pub kind: &'static str,
+    signature: Vec<u8>,
+    payload: Vec<u8>,
+}
+
+#[pymethods]
+impl CommitSignature {
+    /// The armored signature from the `gpgsig` header.
+    #[getter]
+    fn signature(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.signature)
+    }
+
+    /// The commit object without the signature, which is what was signed.
+    #[getter]
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.payload)
+    }
+}
+
+fn kind(signature: &[u8]) -> &'static str {
+    if signature.starts_with(b"-----BEGIN PGP SIGNATURE-----") {
+        "gpg"
+    } else if signature.starts_with(b"-----BEGIN SSH SIGNATURE-----") {
+        "ssh"
+    } else if signature.starts_with(b"-----BEGIN SIGNED MESSAGE-----") {
+        "x509"
+    } else {
+        "unknown"
+    }
+}
+
+/// Split the `gpgsig` header off a raw commit object.
+///
+/// Like git, the continuation lines of the header (which start with a space)
+/// belong to the signature and everything else but other signature headers,
+/// including the message, is the signed payload.
+pub(crate) fn split(data: &[u8]) -> Option<CommitSignature> {
+    let mut signature = Vec::new();
+    let mut payload = Vec::with_capacity(data.len());
+    let mut in_headers = true;
+    // Whether we are in a signature header, and if it is the `gpgsig` one.
+    let mut in_signature = None;
+    for line in data.split_inclusive(|&byte| byte == b'\n') {
+        if line == b"\n" {
+            in_headers = false;
+        }
+        if in_headers && let Some(value) = line.strip_prefix(b"gpgsig ") {
+            in_signature = Some(true);
+            signature.extend_from_slice(value);
+            continue;
+        }
+        // The signature over the SHA-256 form of the object isn't part of the
+        // payload either.
+        if in_headers && line.starts_with(b"gpgsig-sha256 ") {
+            in_signature = Some(false);
+            continue;
+        }
+        if let Some(ours) = in_signature
+            && let Some(continuation) = line.strip_prefix(b" ")
+        {
+            if ours {
+                signature.extend_from_slice(continuation);
+            }
+            continue;
+        }
+        in_signature = None;
+        payload.extend_from_slice(line);
+    }
+    if signature.is_empty() {
+        return None;
+    }
+    Some(CommitSignature {
+        kind: kind(&signature),
+        signature,
+        payload,
+    })
+}
+
+/// The outcome of verifying a commit signature.
+#[pyclass(frozen)]
+pub struct SignatureVerification {
+    /// Whether the signature is good and made by a trusted key.
+    #[pyo3(get)]
+    pub valid: bool,
+    /// The signer, the principal from the allowed signers file for SSH and the
+    /// user ID of the key for OpenPGP.
+    #[pyo3(get)]
+    pub signer: Option<String>,
+    /// The fingerprint of the signing key.
+    #[pyo3(get)]
+    pub key: Option<String>,
+    /// What the verification program printed (or for SSH what `ssh-keygen`
+    /// would print) for diagnostics.
+    #[pyo3(get)]
+    pub output: String,
+}
+
+/// A line of an SSH allowed signers file (see `ssh-keygen(1)`): the
+/// principals that a key is trusted for, along with its options.
+struct AllowedSigner {
+    /// The comma-separated principals (or patterns of them).
+    principals: String,
+    /// The namespaces the key may sign for, any if `None`.
+    namespaces: Option<Vec<String>>,
+    valid_after: Option<jiff::Timestamp>,
+    valid_before: Option<jiff::Timestamp>,
+    key: PublicKey,
+}
+
+impl AllowedSigner {
+    /// Parse a line, `None` for blank lines, comments, and lines that aren't
+    /// valid, which `ssh-keygen` skips as well. `cert-authority` lines are
+    /// left out since certificates aren't supported.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (principals, rest) = split_field(line);
+        // The options are optional, so the key is either next or after them.
+        let (options, key) = match PublicKey::from_openssh(rest) {
+            Ok(key) => ("", key),
+            Err(_) => {
+                let (options, rest) = split_field(rest);
+                (options, PublicKey::from_openssh(rest).ok()?)
+            }
+        };
+        let mut signer = Self {
+            principals: principals.replace('"', ""),
+            namespaces: None,
+            valid_after: None,
+            valid_before: None,
+            key,
+        };
+        for option in split_options(options) {
+            let (name, value) = option.split_once('=').unwrap_or((option, ""));
+            let value = value.trim_matches('"');
+            match name.to_ascii_lowercase().as_str() {
+                "cert-authority" => return None,
+                "namespaces" => {
+                    signer.namespaces = Some(value.split(',').map(str::to_string).collect());
+                }
+                "valid-after" => signer.valid_after = Some(parse_time(value)?),
+                "valid-before" => signer.valid_before = Some(parse_time(value)?),
+                _ => return None,
+            }
+        }
+        Some(signer)
+    }
+
+    /// Whether `key` may sign for `namespace` at `time` (seconds since the
+    /// epoch) by this line.
+    fn allows(&self, key: &ssh_key::public::KeyData, namespace: &str, time: i64) -> bool {
+        self.key.key_data() == key
+            && self
+                .namespaces
+                .as_ref()
+                .is_none_or(|namespaces| namespaces.iter().any(|name| name == namespace))
+            && self
+                .valid_after
+                .is_none_or(|after| after.as_second() <= time)
+            && self
+                .valid_before
+                .is_none_or(|before| time <= before.as_second())
+    }
+}
+
+/// Split the first field off a line, up to whitespace outside of quotes.
+fn split_field(line: &str) -> (&str, &str) {
+    let mut quoted = false;
+    let end = line
+        .char_indices()
+        .find(|&(_, character)| {
+            if character == '"' {
+                quoted = !quoted;
+            }
+            !quoted && character.is_ascii_whitespace()
+        })
+        .map_or(line.len(), |(index, _)| index);
+    (&line[..end], line[end..].trim_start())
+}
+
+/// Split the options of an allowed signers line at commas outside of quotes.
+fn split_options(options: &str) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    options
+        .split(move |character| {
+            if character == '"' {
+                quoted = !quoted;
+            }
+            !quoted && character == ','
+        })
+        .filter(|option| !option.is_empty())
+}
+
+/// Parse a `valid-after` or `valid-before` time, `YYYYMMDD[HHMM[SS]]` in
+/// local time or in UTC with a `Z` at the end.
+fn parse_time(value: &str) -> Option<jiff::Timestamp> {
+    let (digits, zone) = match value.strip_suffix(['Z', 'z']) {
+        Some(digits) => (digits, jiff::tz::TimeZone::UTC),
+        None => (value, jiff::tz::TimeZone::system()),
+    };
+    if !matches!(digits.len(), 8 | 12 | 14) || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    // Missing hours, minutes, or seconds are zero.
+    let number = |start: usize, end: usize| -> Option<i16> {
+        digits
+            .get(start..end)
+            .map_or(Some(0), |number| number.parse().ok())
+    };
+    let date = jiff::civil::Date::new(number(0, 4)?, number(4, 6)? as i8, number(6, 8)? as i8);
+    let time = jiff::civil::Time::new(
+        number(8, 10)? as i8,
+        number(10, 12)? as i8,
+        number(12, 14)? as i8,
+        0,
+    );
+    let time = date.ok()?.to_datetime(time.ok()?);
+    time.to_zoned(zone).ok().map(|time| time.timestamp())
+}
+
+/// The programs and trust anchors to verify signatures with, see
+/// `gpg.program`, `gpg.x509.program`, and `gpg.ssh.allowedSignersFile`.
+pub(crate) struct Verifier {
+    pub(crate) gpg_program: String,
+    pub(crate) x509_program: String,
+    pub(crate) allowed_signers: Option<PathBuf>,
+    pub(crate) keyring: Option<PathBuf>,
+}
+
+impl Verifier {
+    /// Fill in what isn't given from the configuration of `repository`.
+    pub(crate) fn new(
+        repository: Option<&gix::Repository>,
+        allowed_signers: Option<PathBuf>,
+        keyring: Option<PathBuf>,
+    ) -> Self {
+        let config = repository.map(|repository| repository.config_snapshot());
+        let string = |key: &str| {
+            config
+                .as_ref()
+                .and_then(|config| config.string(key))
+                .map(|value| value.to_string())
+        };
+        let allowed_signers = allowed_signers.or_else(|| {
+            config
+                .as_ref()
+                .and_then(|config| config.trusted_path("gpg.ssh.allowedSignersFile"))
+                .and_then(Result::ok)
+                .map(|path| path.into_owned())
+        });
+        Self {
+            gpg_program: string("gpg.program").unwrap_or_else(|| "gpg".into()),
+            x509_program: string("gpg.x509.program").unwrap_or_else(|| "gpgsm".into()),
+            allowed_signers,
+            keyring,
+        }
+    }
+
+    pub(crate) fn verify(&self, signature: &CommitSignature) -> PyResult<SignatureVerification> {
+        if signature.kind == "ssh" {
+            return self.verify_ssh(signature);
+        }
+        let mut file = tempfile::NamedTempFile::new().into_py_result()?;
+        file.write_all(&signature.signature).into_py_result()?;
+        match signature.kind {
+            "gpg" => self.verify_gpg(&self.gpg_program, true, file.path(), &signature.payload),
+            "x509" => self.verify_gpg(&self.x509_program, false, file.path(), &signature.payload),
+            _ => Ok(SignatureVerification {
+                valid: false,
+                signer: None,
+                key: None,
+                output: "unknown signature format".into(),
+            }),
+        }
+    }
+
+    /// Verify like git does with `ssh-keygen -Y`, but without running it:
+    /// find the first line of the allowed signers file that allows the
+    /// signing key for the `git` namespace at the commit time and verify the
+    /// signature for its first principal.
+    fn verify_ssh(&self, signature: &CommitSignature) -> PyResult<SignatureVerification> {
+        let Some(allowed_signers) = &self.allowed_signers else {
+            return Err(PyValueError::new_err(
+                "verifying SSH signatures requires an allowed signers file",
+            ));
+        };
+        let allowed_signers = std::fs::read_to_string(allowed_signers).map_err(|e| {
+            PyOSError::new_err(format!(
+                "could not read allowed signers file {}: {e}",
+                allowed_signers.display()
+            ))
+        })?;
+        let invalid = |signer, key, output| SignatureVerification {
+            valid: false,
+            signer,
+            key,
+            output,
+        };
+        let ssh_signature = match SshSig::from_pem(&signature.signature) {
+            Ok(ssh_signature) => ssh_signature,
+            Err(e) => return Ok(invalid(None, None, format!("invalid SSH signature: {e}"))),
+        };
+        let key = ssh_signature.public_key();
+        let time =
+            signature_time(&signature.payload, b"committer").map_or(0, |(seconds, _)| seconds);
+        let Some(principal) = allowed_signers
+            .lines()
+            .filter_map(AllowedSigner::parse)
+            .find(|signer| signer.allows(key, "git", time))
+            .and_then(|signer| signer.principals.split(',').next().map(str::to_string))
+        else {
+            return Ok(invalid(
+                None,
+                None,
+                "no principal matched the signing key".into(),
+            ));
+        };
+
+        let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+        match PublicKey::from(key.clone()).verify("git", &signature.payload, &ssh_signature) {
+            Ok(()) => Ok(SignatureVerification {
+                valid: true,
+                output: format!(
+                    "Good \"git\" signature for {principal} with {} key {fingerprint}",
+                    key.algorithm()
+                ),
+                signer: Some(principal),
+                key: Some(fingerprint),
+            }),
+            Err(e) => Ok(invalid(
+                Some(principal),
+                Some(fingerprint),
+                format!("Signature verification failed: {e}"),
+            )),
+        }
+    }
+
+    /// Verify with `gpg` (`openpgp`) or `gpgsm` and read the result from
+    /// their machine-readable status lines. Only `gpg` knows the key ID
+    /// format and uses `keyring`.
+    fn verify_gpg(
+        &self,
+        program: &str,
+        openpgp: bool,
+        signature: &Path,
+        payload: &[u8],
+    ) -> PyResult<SignatureVerification> {
+        let mut command = Command::new(program);
+        command.arg("--status-fd=1");
+        if openpgp {
+            command.arg("--keyid-format=long");
+            if let Some(keyring) = &self.keyring {
+                command
+                    .args(["--no-default-keyring", "--keyring"])
+                    .arg(keyring);
+            }
+        }
+        command.arg("--verify").arg(signature).arg("-");
+        let (_, output) = run(&mut command, payload)?;
+
+        let mut signer = None;
+        let mut key = None;
+        let mut good = false;
+        let mut trusted = false;
+        for line in output.lines() {
+            let Some(status) = line.strip_prefix("[GNUPG:] ") else {
+                continue;
+            };
+            let (keyword, rest) = status.split_once(' ').unwrap_or((status, ""));
+            match keyword {
+                "GOODSIG" => {
+                    good = true;
+                    signer = rest.split_once(' ').map(|(_, uid)| uid.to_string());
+                }
+                "VALIDSIG" => key = rest.split(' ').next().map(str::to_string),
+                // Like git, marginal trust is enough.
+                "TRUST_MARGINAL" | "TRUST_FULLY" | "TRUST_ULTIMATE" => trusted = true,
+                _ => {}
+            }
+        }
+        Ok(SignatureVerification {
+            valid: good && trusted,
+            signer,
+            key,
+            output,
+        })
+    }
+}
+
+/// Run a verification program with `input` on stdin, returning whether it
+/// succeeded and what it printed.
+fn run(command: &mut Command, input: &[u8]) -> PyResult<(bool, String)> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| PyOSError::new_err(format!("could not run {command:?}: {e}")))?;
+    // Writing could fail if the program exits early, its status says why.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    let output = child.wait_with_output().into_py_result()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Verifier, split};
+    use crate::tests::git;
+
+    use std::process::Command;
+
+    #[test]
+    fn ssh_signatures_are_verified_natively() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q"], b"");
+        let allowed_signers = path.join("allowed_signers");
+        let verifier = Verifier {
+            gpg_program: "gpg".into(),
+            x509_program: "gpgsm".into(),
+            allowed_signers: Some(allowed_signers.clone()),
+            keyring: None,
+        };
+
+        for kind in ["ed25519", "ecdsa", "rsa"] {
+            let key = path.join(format!("id_{kind}"));
+            let status = Command::new("ssh-keygen")
+                .args(["-q", "-t", kind, "-N", "", "-C", "", "-f"])
+                .arg(&key)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let public_key = std::fs::read_to_string(key.with_extension("pub")).unwrap();
+            git(
+                path,
+                &[
+                    "-c",
+                    "gpg.format=ssh",
+                    "-c",
+                    &format!("user.signingkey={}", key.display()),
+                    "commit",
+                    "-q",
+                    "--allow-empty",
+                    "-S",
+                    "-m",
+                    kind,
+                ],
+                b"",
+            );
+            let repository = gix::open(path).unwrap();
+            let commit = repository.head_commit().unwrap();
+            let signature = split(&commit.data).unwrap();
+            assert_eq!(signature.kind, "ssh");
+
+            let verify = |allowed: &str| {
+                std::fs::write(&allowed_signers, allowed).unwrap();
+                verifier.verify(&signature).unwrap()
+            };
+            let verification = verify(&format!(
+                "# comment\nother@example.com namespaces=\"file\" {public_key}author@example.com,*@example.org namespaces=\"file,git\",valid-after=20230101 {public_key}"
+            ));
+            assert!(verification.valid, "{}", verification.output);
+            assert_eq!(verification.signer.as_deref(), Some("author@example.com"));
+            assert!(verification.key.unwrap().starts_with("SHA256:"));
+
+            for allowed in [
+                format!("author@example.com namespaces=\"file\" {public_key}"),
+                format!("author@example.com valid-before=20230101Z {public_key}"),
+                format!("author@example.com cert-authority {public_key}"),
+            ] {
+                let verification = verify(&allowed);
+                assert!(!verification.valid);
+                assert_eq!(verification.output, "no principal matched the signing key");
+            }
+
+            let mut tampered = split(&commit.data).unwrap();
+            tampered.payload.extend_from_slice(b"tampered\n");
+            std::fs::write(&allowed_signers, format!("author@example.com {public_key}")).unwrap();
+            let verification = verifier.verify(&tampered).unwrap();
+            assert!(!verification.valid);
+            assert_eq!(verification.signer.as_deref(), Some("author@example.com"));
+        }
+    }
+
+    #[test]
+    fn x509_signatures_are_verified_with_the_configured_program() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let home = path.join("gnupg");
+        std::fs::create_dir(&home).unwrap();
+        // `gpgsm` in a home of its own, which also shows the program is
+        // taken from the verifier.
+        let program = path.join("gpgsm");
+        std::fs::write(
+            &program,
+            format!(
+                "#!/bin/sh\nexec gpgsm --homedir '{}' \"$@\"\n",
+                home.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(
+            &program,
+            std::os::unix::fs::PermissionsExt::from_mode(0o755),
+        )
+        .unwrap();
+        let verifier = Verifier {
+            gpg_program: "gpg".into(),
+            x509_program: program.to_str().unwrap().into(),
+            allowed_signers: None,
+            keyring: Some(path.join("keyring")),
+        };
+
+        let signature = split(
+            b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+              author Author <author@example.com> 1700000000 +0100\n\
+              committer Committer <committer@example.com> 1700000000 +0100\n\
+              gpgsig -----BEGIN SIGNED MESSAGE-----\n \
+              MIAGCSqGSIb3DQEHAqCAMIACAQE=\n \
+              -----END SIGNED MESSAGE-----\n\
+              \n\
+              Signed\n",
+        )
+        .unwrap();
+        assert_eq!(signature.kind, "x509");
+        let verification = verifier.verify(&signature).unwrap();
+        assert!(!verification.valid);
+        assert!(
+            verification.output.contains("[GNUPG:] "),
+            "{}",
+            verification.output
+        );
+        assert!(!verification.output.contains("invalid option"));
+    }
+}
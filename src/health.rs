@@ -0,0 +1,270 @@
+//! Detection of repository features that gix doesn't handle (well) and, with
+//! the `git_fallback` feature, running the git CLI instead for the
+//! operations they affect.
+
+use pyo3::prelude::*;
+
+/// The `extensions.*` gix reads repositories with.
+const KNOWN_EXTENSIONS: &[&str] = &[
+    "noop",
+    "noop-v1",
+    "objectformat",
+    "partialclone",
+    "preciousobjects",
+    "refstorage",
+    "worktreeconfig",
+];
+
+/// What `Repository.preflight` found.
+#[pyclass(frozen)]
+pub struct Preflight {
+    /// The problematic features as `(feature, consequence)`, e.g.
+    /// `("partial-clone", ...)`.
+    #[pyo3(get)]
+    pub issues: Vec<(String, String)>,
+    /// Whether affected operations run the git CLI instead, see
+    /// `Repository.fallbacks`.
+    #[pyo3(get)]
+    pub git_fallback: bool,
+}
+
+#[pymethods]
+impl Preflight {
+    /// Nothing was found.
+    #[getter]
+    fn ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Whether objects may be missing and only git can fetch them on demand.
+fn is_partial_clone(repository: &gix::Repository) -> bool {
+    let config = repository.config_snapshot();
+    config.string("extensions.partialClone").is_some()
+        || config
+            .plumbing()
+            .sections_by_name("remote")
+            .into_iter()
+            .flatten()
+            .filter_map(|section| section.header().subsection_name())
+            .any(|remote| {
+                config.boolean(format!("remote.{remote}.promisor").as_str()) == Some(true)
+            })
+}
+
+/// Look for the features of a repository that gix doesn't support or that
+/// make some operations fail.
+pub(crate) fn preflight(repository: &gix::Repository, git_fallback: bool) -> Preflight {
+    let config = repository.config_snapshot();
+    let mut issues = Vec::new();
+    if let Some(version) = config.integer("core.repositoryFormatVersion")
+        && version > 1
+    {
+        issues.push((
+            "repository-format".to_string(),
+            format!("repository format version {version} is newer than what gix knows"),
+        ));
+    }
+    for section in config
+        .plumbing()
+        .sections_by_name("extensions")
+        .into_iter()
+        .flatten()
+    {
+        for name in section.value_names() {
+            let name = name.to_string().to_lowercase();
+            if !KNOWN_EXTENSIONS.contains(&name.as_str()) {
+                issues.push((
+                    format!("extensions.{name}"),
+                    "the extension is unknown to gix".to_string(),
+                ));
+            }
+        }
+    }
+    if let Some(storage) = config.string("extensions.refStorage")
+        && storage.to_string() != "files"
+    {
+        issues.push((
+            "ref-storage".to_string(),
+            format!("refs stored as {storage} can't be read"),
+        ));
+    }
+    if let Some(format) = config.string("extensions.objectFormat")
+        && !format.to_string().eq_ignore_ascii_case("sha1")
+    {
+        issues.push((
+            "object-format".to_string(),
+            format!("{format} object IDs aren't supported"),
+        ));
+    }
+    if is_partial_clone(repository) {
+        issues.push((
+            "partial-clone".to_string(),
+            "diffs fail on objects that weren't fetched yet".to_string(),
+        ));
+    }
+    for section in config
+        .plumbing()
+        .sections_by_name("filter")
+        .into_iter()
+        .flatten()
+    {
+        if section.value("process").is_some() {
+            let name = section
+                .header()
+                .subsection_name()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            issues.push((
+                format!("filter.{name}.process"),
+                "long-running filter processes aren't run, working tree content may differ"
+                    .to_string(),
+            ));
+        }
+    }
+    Preflight {
+        issues,
+        git_fallback,
+    }
+}
+
+/// Whether objects needed to diff `commit` against its parent at index
+/// `parent` are missing, as they can be in partial clones.
+#[cfg(feature = "git_fallback")]
+pub(crate) fn diff_objects_missing(commit: &gix::Commit<'_>, parent: usize) -> bool {
+    use gix::object::tree::diff::ChangeDetached;
+
+    let repository = commit.repo;
+    let tree = commit.tree().ok();
+    let parent_tree = match commit.parent_ids().nth(parent) {
+        Some(parent) => parent
+            .object()
+            .ok()
+            .and_then(|parent| parent.peel_to_tree().ok()),
+        None => Some(repository.empty_tree()),
+    };
+    let (Some(tree), Some(parent_tree)) = (tree, parent_tree) else {
+        return true;
+    };
+    // Subtrees can be missing as well (e.g. in treeless clones).
+    let Ok(changes) = repository.diff_tree_to_tree(Some(&parent_tree), Some(&tree), None) else {
+        return true;
+    };
+    changes.iter().any(|change| {
+        let blobs = match change {
+            ChangeDetached::Addition { id, entry_mode, .. }
+            | ChangeDetached::Deletion { id, entry_mode, .. } => {
+                [entry_mode.is_blob().then_some(*id), None]
+            }
+            ChangeDetached::Modification {
+                previous_id,
+                id,
+                entry_mode,
+                ..
+            } => [
+                entry_mode.is_blob().then_some(*previous_id),
+                entry_mode.is_blob().then_some(*id),
+            ],
+            ChangeDetached::Rewrite {
+                source_id,
+                id,
+                entry_mode,
+                ..
+            } => [
+                entry_mode.is_blob().then_some(*source_id),
+                entry_mode.is_blob().then_some(*id),
+            ],
+        };
+        blobs
+            .into_iter()
+            .flatten()
+            .any(|id| !repository.has_object(id))
+    })
+}
+
+/// The patch of `commit` against its parent at index `parent` (restricted to
+/// `paths`) from `git diff`, which fetches missing objects of partial clones
+/// on demand.
+#[cfg(feature = "git_fallback")]
+pub(crate) fn git_diff(
+    commit: &gix::Commit<'_>,
+    parent: usize,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &crate::unified_diff::PatchFormat,
+    paths: &[String],
+) -> PyResult<Option<Vec<u8>>> {
+    use crate::IntoPyResult;
+
+    let parent = commit
+        .parent_ids()
+        .nth(parent)
+        .map(|parent| parent.detach())
+        .unwrap_or_else(|| gix::ObjectId::empty_tree(commit.repo.object_hash()));
+    let algorithm = match algorithm {
+        gix::diff::blob::Algorithm::Histogram => "histogram",
+        gix::diff::blob::Algorithm::Myers => "myers",
+        gix::diff::blob::Algorithm::MyersMinimal => "minimal",
+    };
+    let output = std::process::Command::new("git")
+        .arg("--git-dir")
+        .arg(commit.repo.git_dir())
+        // git's closest match, it treats bigger files as binary instead.
+        .args(
+            format
+                .max_file_size
+                .map(|max| ["-c".to_string(), format!("core.bigFileThreshold={max}")])
+                .into_iter()
+                .flatten(),
+        )
+        // Configured `textconv` commands apply as they do without git, the
+        // converters of `format` are Python functions git can't run.
+        .args(["diff", "--no-color", "--no-ext-diff"])
+        .arg(format!("--diff-algorithm={algorithm}"))
+        .arg(format!("--src-prefix={}", format.src_prefix))
+        .arg(format!("--dst-prefix={}", format.dst_prefix))
+        .arg(format!("--unified={}", format.context_lines))
+        .arg(format!(
+            "--inter-hunk-context={}",
+            format.inter_hunk_context
+        ))
+        .args(format.ignore_whitespace.map(|ignore| ignore.git_flag()))
+        .args(format.renames.git_flags())
+        .args(format.binary.then_some("--binary"))
+        .arg(format.submodule.git_flag())
+        .arg(parent.to_string())
+        .arg(commit.id.to_string())
+        .arg("--")
+        .args(crate::paths::pathspecs(paths))
+        .output()
+        .into_py_result()?;
+    if !output.status.success() {
+        return Err(pyo3::exceptions::PyOSError::new_err(format!(
+            "`git diff` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let mut diff = output.stdout;
+    if !format.git_header {
+        diff = without_git_header(&diff);
+    }
+    Ok((!diff.is_empty()).then_some(diff))
+}
+
+/// Strip git's extended headers from a patch, dropping files without hunks
+/// like the patches gix writes do.
+#[cfg(feature = "git_fallback")]
+fn without_git_header(patch: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::new();
+    let mut in_header = false;
+    for line in patch.split_inclusive(|&byte| byte == b'\n') {
+        if line.starts_with(b"diff --git ") {
+            in_header = true;
+        } else if in_header && line.starts_with(b"--- ") {
+            in_header = false;
+        }
+        if !in_header {
+            stripped.extend_from_slice(line);
+        }
+    }
+    stripped
+}
@@ -0,0 +1,191 @@
+//! Diffs as HTML fragments to embed in pages, in a unified or a side-by-side
+//! layout.
+//!
+//! There are no styles, only classes to style: the fragment is a
+//! `gitch-diff` block of `gitch-file` blocks (also classed by their kind,
+//! e.g. `gitch-renamed`), each with a `gitch-file-header` naming the file
+//! and a `gitch-hunks` table, or a `gitch-note` for files without hunks.
+//! Tables have `gitch-hunk` rows for the `@@` headers, `gitch-lineno` and
+//! `gitch-code` cells, and lines are classed `gitch-context`,
+//! `gitch-deletion`, or `gitch-addition`, and also `gitch-moved` if they
+//! were moved verbatim elsewhere in the diff. Changed words of removed and
+//! added lines are in `<del>` and `<ins>`. Code cells need `white-space: pre`
+//! to keep their indentation.
+
+use pyo3::prelude::*;
+
+use std::fmt::Write;
+
+use crate::paged::FileDiff;
+use crate::visit::Line;
+
+fn escape(out: &mut String, text: &[u8]) {
+    for character in String::from_utf8_lossy(text).chars() {
+        match character {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(character),
+        }
+    }
+}
+
+fn class(line: &Line) -> &'static str {
+    match (line.origin, line.moved.is_some()) {
+        ('-', false) => "gitch-deletion",
+        ('-', true) => "gitch-deletion gitch-moved",
+        ('+', false) => "gitch-addition",
+        ('+', true) => "gitch-addition gitch-moved",
+        _ => "gitch-context",
+    }
+}
+
+/// The line number and content cells of one side of a line, empty cells for
+/// `None`.
+fn cells(out: &mut String, line: Option<&Line>, lineno: Option<u32>) {
+    let Some(line) = line else {
+        out.push_str("<td class=\"gitch-lineno\"></td><td class=\"gitch-code gitch-empty\"></td>");
+        return;
+    };
+    write!(
+        out,
+        "<td class=\"gitch-lineno\">{}</td><td class=\"gitch-code {}\">",
+        lineno.map(|lineno| lineno.to_string()).unwrap_or_default(),
+        class(line)
+    )
+    .unwrap();
+    code(out, line);
+    out.push_str("</td>");
+}
+
+/// The content of `line` without its terminator, with its changed ranges
+/// marked.
+fn code(out: &mut String, line: &Line) {
+    let tag = if line.origin == '-' { "del" } else { "ins" };
+    let content = line.content.strip_suffix(b"\n").unwrap_or(&line.content);
+    let mut at = 0;
+    for &(start, end) in &line.changed_ranges {
+        escape(out, &content[at..start]);
+        write!(out, "<{tag}>").unwrap();
+        escape(out, &content[start..end]);
+        write!(out, "</{tag}>").unwrap();
+        at = end;
+    }
+    escape(out, &content[at..]);
+}
+
+fn unified(out: &mut String, lines: &[Line]) {
+    for line in lines {
+        write!(out, "<tr class=\"{}\">", class(line)).unwrap();
+        for lineno in [line.old_lineno, line.new_lineno] {
+            write!(
+                out,
+                "<td class=\"gitch-lineno\">{}</td>",
+                lineno.map(|lineno| lineno.to_string()).unwrap_or_default()
+            )
+            .unwrap();
+        }
+        out.push_str("<td class=\"gitch-code\">");
+        code(out, line);
+        out.push_str("</td></tr>");
+    }
+}
+
+/// Removed lines on the left next to the lines added in their place on the
+/// right, context lines on both sides.
+fn side_by_side(out: &mut String, lines: &[Line]) {
+    let mut index = 0;
+    while index < lines.len() {
+        if lines[index].origin == ' ' {
+            out.push_str("<tr>");
+            cells(out, Some(&lines[index]), lines[index].old_lineno);
+            cells(out, Some(&lines[index]), lines[index].new_lineno);
+            out.push_str("</tr>");
+            index += 1;
+            continue;
+        }
+        let removed = lines[index..]
+            .iter()
+            .take_while(|line| line.origin == '-')
+            .count();
+        let added = lines[index + removed..]
+            .iter()
+            .take_while(|line| line.origin == '+')
+            .count();
+        for offset in 0..removed.max(added) {
+            let old = (offset < removed).then(|| &lines[index + offset]);
+            let new = (offset < added).then(|| &lines[index + removed + offset]);
+            out.push_str("<tr>");
+            cells(out, old, old.and_then(|line| line.old_lineno));
+            cells(out, new, new.and_then(|line| line.new_lineno));
+            out.push_str("</tr>");
+        }
+        index += removed + added;
+    }
+}
+
+fn file(py: Python<'_>, out: &mut String, file: &FileDiff, side_by_side: bool) {
+    write!(
+        out,
+        "<div class=\"gitch-file gitch-{}\"><div class=\"gitch-file-header\">",
+        file.kind
+    )
+    .unwrap();
+    if file.previous_path != file.path {
+        escape(out, file.previous_path.as_bytes());
+        out.push_str(" \u{2192} ");
+    }
+    escape(out, file.path.as_bytes());
+    out.push_str("</div>");
+
+    let note = if file.binary {
+        Some("Binary files differ".to_string())
+    } else if let Some(size) = file.suppressed_size {
+        Some(format!("Diff suppressed ({size} bytes)"))
+    } else if let (Some(previous_mode), Some(mode), true) =
+        (&file.previous_mode, &file.mode, file.hunks.is_empty())
+    {
+        (previous_mode != mode).then(|| format!("Mode changed from {previous_mode} to {mode}"))
+    } else {
+        None
+    };
+    if let Some(note) = note {
+        write!(out, "<div class=\"gitch-note\">{note}</div>").unwrap();
+    }
+
+    if !file.hunks.is_empty() {
+        let (layout, columns) = if side_by_side {
+            ("gitch-side-by-side", 4)
+        } else {
+            ("gitch-unified", 3)
+        };
+        write!(out, "<table class=\"gitch-hunks {layout}\">").unwrap();
+        for hunk in &file.hunks {
+            let hunk = hunk.borrow(py);
+            let header = hunk.content.split(|&byte| byte == b'\n').next();
+            write!(out, "<tr class=\"gitch-hunk\"><td colspan=\"{columns}\">").unwrap();
+            escape(out, header.unwrap_or_default());
+            out.push_str("</td></tr>");
+            let lines = hunk.lines();
+            if side_by_side {
+                self::side_by_side(out, &lines);
+            } else {
+                unified(out, &lines);
+            }
+        }
+        out.push_str("</table>");
+    }
+    out.push_str("</div>");
+}
+
+/// Render `files` as an HTML fragment, side by side if `side_by_side`.
+pub(crate) fn render(py: Python<'_>, files: &[FileDiff], side_by_side: bool) -> String {
+    let mut out = String::from("<div class=\"gitch-diff\">");
+    for diff in files {
+        file(py, &mut out, diff, side_by_side);
+    }
+    out.push_str("</div>\n");
+    out
+}
@@ -1,43 +1,601 @@
-use pyo3::exceptions::PyOSError;
+mod anonymize;
+mod apply;
+mod bench;
+mod bundle;
+mod calendar;
+mod changelog;
+mod checks;
+mod color;
+mod combined;
+mod compare;
+mod contains;
+mod conventional;
+mod deployments;
+mod describe;
+mod diff_cache;
+mod diff_options;
+mod email;
+mod epoch;
+mod fast_export;
+mod gitmoji;
+mod gpgsig;
+mod health;
+mod html;
+mod lock;
+mod mailmap;
+mod moved;
+mod notes;
+mod oid_map;
+mod orphans;
+mod paged;
+mod patch;
+mod prefetch;
+mod provenance;
+mod references;
+mod reflog;
+mod rewrite;
+mod serialize;
+mod similar;
+mod simplify;
+mod snapshot;
+mod stats;
+mod template;
+mod text;
+mod textconv;
+mod tree;
+mod unreachable;
+mod visit;
+mod walk;
+mod word_diff;
+mod workdir;
+
+use gitch_engine::time::{
+    identity_time, local_time_string, signature_time, signature_zoned, zoned,
+};
+use gitch_engine::{binary, diff, funcname, message, paths, renames, unified_diff};
+use gix::objs::FindHeader;
+use pyo3::exceptions::{PyOSError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rayon::prelude::*;
 
-use std::fmt::Write;
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use std::str::FromStr;
 
-#[pyclass]
+#[pyclass(frozen)]
 #[derive(Clone)]
 pub struct Signature {
     #[pyo3(get)]
     pub name: String,
     #[pyo3(get)]
     pub email: String,
+    /// The UTC offset in seconds (east positive) the signature was made in.
+    #[pyo3(get)]
+    pub utc_offset: i32,
 }
 
-#[pyclass]
+/// A commit read from a repository.
+///
+/// Commits are immutable (`frozen`) and `Send + Sync` so they can be shared
+/// between Python threads, including on free-threaded builds, without any
+/// locking.
+#[pyclass(frozen, module = "gitch.gitch_core")]
 pub struct Commit {
     inner: gix::ObjectDetached,
+    /// The repository the commit was read from, commits loaded from elsewhere
+    /// (e.g. a review bundle) have none.
+    repository: std::sync::OnceLock<std::sync::Arc<gix::ThreadSafeRepository>>,
+    /// The git directory of the repository an unpickled commit was read from,
+    /// it is reopened on first use.
+    repository_path: Option<PathBuf>,
 
     #[pyo3(get)]
     pub id: String,
-    #[pyo3(get)]
-    pub summary: String,
-    #[pyo3(get)]
-    pub body: Option<String>,
-    pub time: jiff::civil::DateTime,
+    /// The summary, body, and trailers, parsed from the message on first
+    /// access (or right away in eager walks).
+    parsed: std::sync::OnceLock<ParsedMessage>,
+    /// When the change was originally authored, in the author's UTC offset.
+    pub author_time: jiff::Zoned,
+    /// When the commit was last created (e.g. by a rebase), in the committer's
+    /// UTC offset.
+    pub commit_time: jiff::Zoned,
     #[pyo3(get)]
     pub author: Signature,
     #[pyo3(get)]
     pub committer: Signature,
     #[pyo3(get)]
-    pub trailers: std::collections::HashMap<String, std::collections::HashSet<String>>,
+    pub parent_ids: Vec<String>,
+    /// The encoding declared by the commit's `encoding` header, the message
+    /// is decoded from it (or UTF-8 if there is none).
+    #[pyo3(get)]
+    pub encoding: Option<String>,
+    /// Headers besides the standard ones (e.g. `gpgsig`, `mergetag`, or
+    /// jj's `change-id`) in their original order, multi-line values are
+    /// unfolded.
+    #[pyo3(get)]
+    pub extra_headers: Vec<(String, String)>,
+    /// The parents after history simplification, only set when the walk was
+    /// asked to rewrite parents.
+    #[pyo3(get)]
+    pub rewritten_parent_ids: Option<Vec<String>>,
+    /// Whether this commit is not part of the walked range but a parent of
+    /// one that is (like `git log --boundary`).
+    #[pyo3(get)]
+    pub is_boundary: bool,
+    /// Whether the parents of this commit are missing because the repository
+    /// is a shallow clone.
+    #[pyo3(get)]
+    pub is_shallow: bool,
+    /// Where the commit time falls in a region's calendar, only set when the
+    /// walk was asked to annotate it.
+    #[pyo3(get)]
+    pub calendar: Option<calendar::CalendarDay>,
+}
+
+/// The parts of a commit message that are only parsed when needed.
+struct ParsedMessage {
+    message: message::Message,
+    trailers: std::collections::HashMap<String, std::collections::HashSet<String>>,
+}
+
+#[pymethods]
+impl Signature {
+    fn __repr__(&self) -> String {
+        format!(
+            "Signature(name={:?}, email={:?}, utc_offset={})",
+            self.name, self.email, self.utc_offset
+        )
+    }
+
+    /// Signatures are the same identity if name and email match, the UTC
+    /// offset is only where it was used.
+    fn __eq__(&self, other: &Self) -> bool {
+        self.name == other.name && self.email == other.email
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = std::hash::DefaultHasher::new();
+        (&self.name, &self.email).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The signature as a dict of plain values.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        serialize::to_py(py, &serialize::signature(self))
+    }
+}
+
+/// What a pickled commit is made of: the raw object, where its repository
+/// is, and what a walk adds on top (including the author and committer
+/// identities, as a mailmap may have changed them).
+type CommitState<Data> = (
+    String,
+    Data,
+    Option<PathBuf>,
+    Option<Vec<String>>,
+    bool,
+    bool,
+    Option<String>,
+    [(String, String); 2],
+);
+
+impl Commit {
+    /// The full message decoded like `summary` and `body` are.
+    fn message(&self) -> String {
+        let decoder = message::decoder(self.encoding.as_deref());
+        let message = gix::objs::CommitRef::from_bytes(&self.inner.data)
+            .map(|commit| commit.message)
+            .unwrap_or_default();
+        decoder.decode_without_bom_handling(message).0.into_owned()
+    }
+
+    /// Parse the message, once.
+    fn parsed(&self) -> &ParsedMessage {
+        self.parsed.get_or_init(|| {
+            let message = message::parse(&self.message());
+            let trailers = message.trailer_list.iter().cloned().fold(
+                std::collections::HashMap::new(),
+                |mut acc, (token, value)| {
+                    let trailer: &mut std::collections::HashSet<_> = acc.entry(token).or_default();
+                    trailer.insert(value);
+                    acc
+                },
+            );
+            ParsedMessage { message, trailers }
+        })
+    }
+
+    /// The repository the commit was read from, reopening it for unpickled
+    /// commits.
+    fn repository(&self) -> Option<&std::sync::Arc<gix::ThreadSafeRepository>> {
+        if let Some(repository) = self.repository.get() {
+            return Some(repository);
+        }
+        let repository = gix::open(self.repository_path.as_ref()?).ok()?;
+        Some(
+            self.repository
+                .get_or_init(|| repository.into_sync().into()),
+        )
+    }
+
+    /// A handle on the repository the commit was read from, for the methods
+    /// that need more than the commit object.
+    fn attached(&self) -> PyResult<gix::Repository> {
+        let Some(repository) = self.repository() else {
+            return Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "commit {} is not attached to a repository",
+                self.id
+            )));
+        };
+        Ok(repository.to_thread_local())
+    }
+
+    /// Make sure `parent` is an index into the parents, root commits can only
+    /// be diffed against the empty tree at index 0.
+    fn check_parent(&self, parent: usize) -> PyResult<()> {
+        if parent > 0 && parent >= self.parent_ids.len() {
+            return Err(PyValueError::new_err(format!(
+                "commit {} has {} parent(s), there is no parent {parent}",
+                self.id,
+                self.parent_ids.len()
+            )));
+        }
+        Ok(())
+    }
 }
 
 #[pymethods]
 impl Commit {
+    fn __repr__(&self) -> String {
+        format!("<Commit {} {:?}>", &self.id[..7], self.summary())
+    }
+
+    /// Commits are equal if they have the same ID.
+    fn __eq__(&self, other: &Self) -> bool {
+        self.inner.id == other.inner.id
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = std::hash::DefaultHasher::new();
+        self.inner.id.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The commit as a dict of plain values (times as RFC 3339 strings,
+    /// trailers as `[token, value]` pairs) that can be dumped as JSON as is.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        serialize::to_py(py, &serialize::commit(self))
+    }
+
+    /// `to_dict` rendered as JSON, indented if `pretty`.
+    #[pyo3(signature=(pretty=false))]
+    fn to_json(&self, pretty: bool) -> String {
+        serialize::to_json(&serialize::commit(self), pretty)
+    }
+
+    /// Everything else is parsed from the object again when unpickling, and
+    /// the repository is only reopened once it's needed.
+    fn __getstate__(&self) -> CommitState<Cow<'_, [u8]>> {
+        (
+            self.id.clone(),
+            Cow::Borrowed(&self.inner.data),
+            self.repository
+                .get()
+                .map(|repository| repository.to_thread_local().git_dir().to_path_buf())
+                .or_else(|| self.repository_path.clone()),
+            self.rewritten_parent_ids.clone(),
+            self.is_boundary,
+            self.is_shallow,
+            self.calendar.as_ref().map(|day| day.region.to_string()),
+            [&self.author, &self.committer]
+                .map(|signature| (signature.name.clone(), signature.email.clone())),
+        )
+    }
+
+    fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyAny>,))> {
+        Ok((
+            slf.get_type().getattr("_from_state")?,
+            (slf.call_method0("__getstate__")?,),
+        ))
+    }
+
+    #[staticmethod]
+    fn _from_state(state: CommitState<Vec<u8>>) -> PyResult<Self> {
+        let (
+            id,
+            data,
+            repository_path,
+            rewritten_parent_ids,
+            is_boundary,
+            is_shallow,
+            region,
+            [author, committer],
+        ) = state;
+        let inner = gix::ObjectDetached {
+            id: gix::ObjectId::from_hex(id.as_bytes()).into_py_result()?,
+            kind: gix::object::Kind::Commit,
+            data,
+        };
+        gix::objs::CommitRef::from_bytes(&inner.data).into_py_result()?;
+        let mut commit = Commit::try_from(inner).unwrap();
+        commit.repository_path = repository_path;
+        commit.rewritten_parent_ids = rewritten_parent_ids;
+        commit.is_boundary = is_boundary;
+        commit.is_shallow = is_shallow;
+        if let Some(region) = region {
+            let region = calendar::Region::parse(&region)?;
+            commit.calendar = Some(calendar::annotate(region, &commit.commit_time));
+        }
+        (commit.author.name, commit.author.email) = author;
+        (commit.committer.name, commit.committer.email) = committer;
+        Ok(commit)
+    }
+
     #[getter]
-    fn time(&self) -> String {
-        self.time.to_string()
+    fn summary(&self) -> &str {
+        &self.parsed().message.summary
+    }
+
+    #[getter]
+    fn body(&self) -> Option<&str> {
+        self.parsed().message.body.as_deref()
+    }
+
+    #[getter]
+    fn trailers(&self) -> &std::collections::HashMap<String, std::collections::HashSet<String>> {
+        &self.parsed().trailers
+    }
+
+    /// The trailers in their original order including repeated ones.
+    #[getter]
+    fn trailer_list(&self) -> &[(String, String)] {
+        &self.parsed().message.trailer_list
+    }
+
+    /// The author time as a timezone-aware `datetime`.
+    #[getter]
+    fn author_time(&self) -> jiff::Zoned {
+        self.author_time.clone()
+    }
+
+    /// The committer time as a timezone-aware `datetime`.
+    #[getter]
+    fn commit_time(&self) -> jiff::Zoned {
+        self.commit_time.clone()
+    }
+
+    /// The same as `commit_time`.
+    #[getter]
+    fn time(&self) -> jiff::Zoned {
+        self.commit_time.clone()
+    }
+
+    /// The committer time as a string in the local timezone.
+    #[getter]
+    fn time_str(&self) -> String {
+        local_time_string(&self.commit_time)
+    }
+
+    /// The author time as a string in the local timezone.
+    #[getter]
+    fn author_time_str(&self) -> String {
+        local_time_string(&self.author_time)
+    }
+
+    /// The identities from `Co-authored-by: Name <email>` trailers in order.
+    ///
+    /// Trailers carry no time so co-authors share the author's UTC offset,
+    /// malformed ones are skipped.
+    #[getter]
+    fn co_authors(&self) -> Vec<Signature> {
+        self.trailer_list()
+            .iter()
+            .filter(|(token, _)| token.eq_ignore_ascii_case("Co-authored-by"))
+            .filter_map(|(_, value)| {
+                let (name, email) = value.trim().strip_suffix('>')?.rsplit_once('<')?;
+                Some(Signature {
+                    name: name.trim().to_string(),
+                    email: email.trim().to_string(),
+                    utc_offset: self.author.utc_offset,
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the commit has a `Signed-off-by` trailer (as required by the
+    /// DCO), when `match_author` one whose identity is the author's.
+    ///
+    /// Identities are compared after applying the repository's mailmap, so
+    /// commits that aren't attached to a repository compare them as is.
+    #[pyo3(signature=(match_author=true))]
+    fn has_signoff(&self, match_author: bool) -> bool {
+        if !match_author {
+            return message::signoffs(self.trailer_list()).next().is_some();
+        }
+        let mailmap = self
+            .repository()
+            .map(|repository| gitch_engine::mailmap::Mailmap::load(&repository.to_thread_local()))
+            .unwrap_or_default();
+        message::is_signed_off_by(
+            self.trailer_list(),
+            &self.author.name,
+            &self.author.email,
+            &mailmap,
+        )
+    }
+
+    #[getter]
+    fn is_merge(&self) -> bool {
+        self.parent_ids.len() > 1
+    }
+
+    /// The message parsed as a Conventional Commit, `None` if it isn't one.
+    fn conventional(&self) -> Option<conventional::ConventionalCommit> {
+        conventional::parse(&self.message())
+    }
+
+    /// The gitmoji the summary starts with, `None` if it doesn't.
+    fn gitmoji(&self) -> Option<gitmoji::Gitmoji> {
+        gitmoji::parse(self.summary())
+    }
+
+    /// The issue and ticket references in the message, see
+    /// `references::references`. `patterns` are `(kind, prefix)` pairs and
+    /// default to `#123`, `GH-123`, and `JIRA-123`.
+    #[pyo3(signature=(patterns=None))]
+    fn references(
+        &self,
+        patterns: Option<Vec<(String, String)>>,
+    ) -> PyResult<Vec<references::Reference>> {
+        references::references(&self.message(), patterns)
+    }
+
+    /// The message exactly as stored in the commit object.
+    #[getter]
+    fn raw_message(&self) -> PyResult<Cow<'_, [u8]>> {
+        Ok(Cow::Borrowed(
+            gix::objs::CommitRef::from_bytes(&self.inner.data)
+                .into_py_result()?
+                .message,
+        ))
+    }
+
+    /// The GPG, SSH, or X.509 signature of the commit, `None` if unsigned.
+    #[getter]
+    fn signature(&self) -> Option<gpgsig::CommitSignature> {
+        gpgsig::split(&self.inner.data)
+    }
+
+    /// Verify the signature of the commit like `git verify-commit`, `None` if
+    /// it isn't signed.
+    ///
+    /// SSH signatures are verified in-process against `allowed_signers` (keys
+    /// only, not certificates), OpenPGP ones with `gpg` against the default
+    /// keyring or `keyring`, and X.509 ones with `gpgsm`. Unless given, the
+    /// programs and the allowed signers file come from the repository's
+    /// `gpg.*` config.
+    #[pyo3(signature=(allowed_signers=None, keyring=None))]
+    fn verify_signature(
+        &self,
+        allowed_signers: Option<PathBuf>,
+        keyring: Option<PathBuf>,
+    ) -> PyResult<Option<gpgsig::SignatureVerification>> {
+        let Some(signature) = gpgsig::split(&self.inner.data) else {
+            return Ok(None);
+        };
+        let repository = self
+            .repository()
+            .map(|repository| repository.to_thread_local());
+        gpgsig::Verifier::new(repository.as_ref(), allowed_signers, keyring)
+            .verify(&signature)
+            .map(Some)
+    }
+
+    /// The shortest abbreviation of the ID that is unique in the object
+    /// database, at least `core.abbrev` hex digits long.
+    #[getter]
+    fn short_id(&self) -> PyResult<String> {
+        let repository = self.attached()?;
+        Ok(
+            gix::prelude::ObjectIdExt::attach(self.inner.id, &repository)
+                .shorten()
+                .into_py_result()?
+                .to_string(),
+        )
+    }
+
+    /// The ID of the commit's tree, read from the commit object alone.
+    #[getter]
+    fn tree_id(&self) -> PyResult<String> {
+        Ok(gix::objs::CommitRefIter::from_bytes(&self.inner.data)
+            .tree_id()
+            .into_py_result()?
+            .to_string())
+    }
+
+    /// The tree IDs of the parents in order, e.g. to tell that a commit
+    /// didn't change anything without diffing it.
+    ///
+    /// They are looked up in the commit-graph if there is one, otherwise only
+    /// the parent commit objects are read.
+    #[getter]
+    fn parent_tree_ids(&self) -> PyResult<Vec<String>> {
+        let repository = self.attached()?;
+        let graph = repository.commit_graph_if_enabled().into_py_result()?;
+        gix::objs::CommitRefIter::from_bytes(&self.inner.data)
+            .parent_ids()
+            .map(|parent| {
+                if let Some(commit) = graph.as_ref().and_then(|graph| graph.commit_by_id(parent)) {
+                    return Ok(commit.root_tree_id().to_string());
+                }
+                let parent = repository.find_object(parent).into_py_result()?;
+                Ok(gix::objs::CommitRefIter::from_bytes(&parent.data)
+                    .tree_id()
+                    .into_py_result()?
+                    .to_string())
+            })
+            .collect()
+    }
+
+    /// List the tree of this commit (see `git ls-tree -l`).
+    #[pyo3(signature=(recursive=true, path=None))]
+    fn ls_tree(&self, recursive: bool, path: Option<&str>) -> PyResult<Vec<tree::TreeEntry>> {
+        let repository = self.attached()?;
+        tree::ls_tree(
+            &repository,
+            gix::ObjectId::from_hex(self.tree_id()?.as_bytes()).into_py_result()?,
+            recursive,
+            path,
+        )
+    }
+
+    /// The stable patch ID of the change against the first parent, the same
+    /// as `git show <id> | git patch-id --stable` for non-merge commits
+    /// whenever both produce the same hunks (git's indent heuristic can shift
+    /// them). `None` if the commit changes nothing.
+    fn patch_id(&self) -> PyResult<Option<String>> {
+        let repository = self.attached()?;
+        let commit = self.inner.clone().attach(&repository).into_commit();
+        let patch = Repository::diff_with_parent(
+            &commit,
+            gix::diff::blob::Algorithm::Myers,
+            &Default::default(),
+        )?
+        .unwrap_or_default();
+        Ok(compare::stable_patch_id(&patch).map(|id| id.to_string()))
+    }
+
+    /// The commit as an email like `git format-patch` writes it: the `From`,
+    /// `Date`, and `Subject` headers from the author and message, the body,
+    /// and the `--stat` and patch (with binary files as `git apply` can apply
+    /// them) against the first parent. The subject starts with
+    /// `[<subject_prefix>]`. `None` for merges and commits that change
+    /// nothing, which git doesn't format either.
+    #[pyo3(signature=(subject_prefix="PATCH", algorithm=Algorithm::Myers))]
+    fn format_patch(&self, subject_prefix: &str, algorithm: Algorithm) -> PyResult<Option<String>> {
+        let repository = self.attached()?;
+        email::changes(&repository, self, algorithm.into())?
+            .map(|changes| email::format_patch(self, &changes, None, subject_prefix))
+            .transpose()
+    }
+
+    /// The closest tag in the history of this commit, see
+    /// `Repository.describe`.
+    fn describe(&self) -> PyResult<Option<describe::Description>> {
+        let repository = self.attached()?;
+        describe::describe(&repository, self.inner.id)
+    }
+
+    /// The note attached to this commit in the notes ref `ref` (see `git
+    /// notes`), `None` if there is none.
+    #[pyo3(signature=(r#ref="refs/notes/commits"))]
+    fn notes(&self, r#ref: &str) -> PyResult<Option<String>> {
+        let repository = self.attached()?;
+        notes::note(&repository, r#ref, self.inner.id)
     }
 }
 
@@ -45,72 +603,74 @@ impl<'r> TryFrom<gix::revision::walk::Info<'r>> for Commit {
     type Error = ();
 
     fn try_from(info: gix::revision::walk::Info<'r>) -> Result<Self, Self::Error> {
-        let commit_object = info.object().unwrap();
-        let commit = commit_object.decode().unwrap();
+        info.object().unwrap().detached().try_into()
+    }
+}
+
+impl TryFrom<gix::ObjectDetached> for Commit {
+    type Error = ();
+
+    fn try_from(commit_object: gix::ObjectDetached) -> Result<Self, Self::Error> {
+        let commit = gix::objs::CommitRef::from_bytes(&commit_object.data).unwrap();
         // Get the commit ID.
-        let id = info.id().to_string();
+        let id = commit_object.id.to_string();
 
-        // Get the commit time.
-        let seconds = info.commit_time();
-        let time = jiff::Timestamp::from_second(seconds)
-            .unwrap()
-            .to_zoned(jiff::tz::TimeZone::system())
-            .datetime();
+        // Get the author and commit times.
+        let author_time = signature_zoned(&commit_object.data, b"author");
+        let commit_time = signature_zoned(&commit_object.data, b"committer");
+
+        // Like git, decode the message and identities according to the
+        // declared encoding and assume UTF-8 without one.
+        let encoding = commit.encoding.map(|encoding| encoding.to_string());
+        let decoder = message::decoder(encoding.as_deref());
+        let decode = |bytes: &[u8]| decoder.decode_without_bom_handling(bytes).0.into_owned();
 
         // Get the commit author.
         let author = commit.author();
         let author = Signature {
-            name: author.name.to_string(),
-            email: author.email.to_string(),
+            name: decode(author.name),
+            email: decode(author.email),
+            utc_offset: author_time.offset().seconds(),
         };
 
         // Get the commit committer.
         let committer = commit.committer();
         let committer = Signature {
-            name: committer.name.to_string(),
-            email: committer.email.to_string(),
+            name: decode(committer.name),
+            email: decode(committer.email),
+            utc_offset: commit_time.offset().seconds(),
         };
 
-        // Get commit summary.
-        let summary = commit.message_summary().to_string();
+        let extra_headers = commit
+            .extra_headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
 
-        // Get commit trailers.
-        let trailers =
-            commit
-                .message_trailers()
-                .fold(std::collections::HashMap::new(), |mut acc, trailer| {
-                    let token = trailer.token.to_string();
-                    let value = trailer.value.to_string();
-                    let trailer: &mut std::collections::HashSet<_> = acc.entry(token).or_default();
-                    trailer.insert(value);
-                    acc
-                });
-
-        // Get commit body.
-        let body = match commit.message().body {
-            Some(body) => {
-                let mut message: Vec<u8> = b"\n\n".to_vec();
-                message.extend_from_slice(body);
-                let body = gix::diff::object::commit::message::BodyRef::from_bytes(&message[..])
-                    .without_trailer();
-                if body.is_empty() {
-                    None
-                } else {
-                    Some(gix::diff::object::bstr::BStr::new(body.trim_ascii()).to_string())
-                }
-            }
-            None => None,
-        };
+        // Get the commit parents.
+        let parent_ids = commit.parents().map(|id| id.to_string()).collect();
+
+        // The decoded commit borrows the object data, release it before moving
+        // the object into the result.
+        drop(commit);
 
         Ok(Commit {
-            inner: commit_object.detached(),
+            inner: commit_object,
+            repository: Default::default(),
+            repository_path: None,
             id,
-            summary,
-            body,
-            time,
+            parsed: Default::default(),
+            author_time,
+            commit_time,
             author,
             committer,
-            trailers,
+            parent_ids,
+            encoding,
+            extra_headers,
+            rewritten_parent_ids: None,
+            is_boundary: false,
+            is_shallow: false,
+            calendar: None,
         })
     }
 }
@@ -133,247 +693,598 @@ impl From<Algorithm> for gix::diff::blob::Algorithm {
     }
 }
 
+/// The operations that ran the git CLI and why, in order.
+type Fallbacks = std::sync::Arc<std::sync::Mutex<Vec<(String, String)>>>;
+
 #[pyclass]
 pub struct Repository {
-    inner: gix::ThreadSafeRepository,
+    inner: std::sync::Arc<gix::ThreadSafeRepository>,
+    oid_map: oid_map::OidMap,
+    deployments: deployments::Deployments,
+    /// Whether operations gix can't do run the git CLI instead.
+    git_fallback: bool,
+    fallbacks: Fallbacks,
+    /// The patches already computed, once `enable_diff_cache` was called.
+    diff_cache: Option<std::sync::Arc<diff_cache::DiffCache>>,
 }
 
-impl Repository {
-    fn unified_diff_files(
+/// What diffing the commits of a `Repository` goes through besides its
+/// objects, cheap to clone for threads diffing in the background.
+#[derive(Clone)]
+struct DiffContext {
+    cache: Option<std::sync::Arc<diff_cache::DiffCache>>,
+    /// Where falling back to the git CLI is logged, `None` unless the
+    /// repository was opened with `git_fallback`.
+    #[cfg(feature = "git_fallback")]
+    fallbacks: Option<Fallbacks>,
+}
+
+impl DiffContext {
+    /// The patch of `commit` against its parent at index `parent` from the
+    /// diff cache if it is enabled and has it, otherwise computed and
+    /// cached.
+    fn diff(
+        &self,
         resource_cache: &mut gix::diff::blob::Platform,
-        objects: &gix::OdbHandle,
+        commit: &gix::Commit<'_>,
+        parent: usize,
         algorithm: gix::diff::blob::Algorithm,
-        older_id: &gix::oid,
-        older_location: &gix::diff::object::bstr::BStr,
-        newer_id: &gix::oid,
-        newer_location: &gix::diff::object::bstr::BStr,
-    ) -> Result<String, ()> {
-        resource_cache
-            .set_resource(
-                older_id.into(),
-                gix::object::tree::EntryKind::Blob,
-                older_location.as_ref(),
-                gix::diff::blob::ResourceKind::OldOrSource,
-                objects,
-            )
-            .unwrap();
-        resource_cache
-            .set_resource(
-                newer_id.into(),
-                gix::object::tree::EntryKind::Blob,
-                newer_location.as_ref(),
-                gix::diff::blob::ResourceKind::NewOrDestination,
-                objects,
-            )
-            .unwrap();
-        let outcome = resource_cache.prepare_diff().unwrap();
-
-        let interner = gix::diff::blob::intern::InternedInput::new(
-            gix::diff::blob::sources::byte_lines(outcome.old.data.as_slice().unwrap_or_default()),
-            gix::diff::blob::sources::byte_lines(outcome.new.data.as_slice().unwrap_or_default()),
-        );
-
-        let unified_diff = gix::diff::blob::UnifiedDiff::new(
-            &interner,
-            String::new(),
-            gix::diff::blob::unified_diff::NewlineSeparator::AfterHeaderAndLine("\n"),
-            gix::diff::blob::unified_diff::ContextSize::symmetrical(3),
-        );
+        format: &unified_diff::PatchFormat,
+        paths: &[String],
+    ) -> PyResult<Option<Vec<u8>>> {
+        let cached = self.cache.as_ref().zip(diff_cache::DiffCache::key(
+            commit.id, parent, algorithm, paths, format,
+        ));
+        if let Some((cache, key)) = &cached
+            && let Some(diff) = cache.get(key).into_py_result()?
+        {
+            return Ok(diff);
+        }
 
-        Ok(gix::diff::blob::diff(algorithm, &interner, unified_diff).unwrap())
+        let diff = self.uncached_diff(resource_cache, commit, parent, algorithm, format, paths)?;
+        if let Some((cache, key)) = cached {
+            cache.insert(key, diff.as_deref()).into_py_result()?;
+        }
+        Ok(diff)
     }
 
-    fn diff_with_parent(
+    /// The patch of `commit` against its parent at index `parent`, by the git
+    /// CLI if gix can't read the objects (see `Repository.git_fallback`).
+    fn uncached_diff(
+        &self,
+        resource_cache: &mut gix::diff::blob::Platform,
         commit: &gix::Commit<'_>,
+        parent: usize,
         algorithm: gix::diff::blob::Algorithm,
-    ) -> Result<Option<String>, ()> {
-        let tree = commit.tree().unwrap();
-        let parent_tree = if let Some(parent_id) = commit.parent_ids().next() {
-            parent_id.object().unwrap().peel_to_tree().unwrap()
-        } else {
-            tree.repo.empty_tree()
-        };
+        format: &unified_diff::PatchFormat,
+        paths: &[String],
+    ) -> PyResult<Option<Vec<u8>>> {
+        #[cfg(feature = "git_fallback")]
+        if let Some(fallbacks) = &self.fallbacks
+            && health::diff_objects_missing(commit, parent)
+        {
+            fallbacks.lock().unwrap().push((
+                "diff".to_string(),
+                format!("objects of commit {} are missing", commit.id),
+            ));
+            return health::git_diff(commit, parent, algorithm, format, paths);
+        }
+        let mut diff = Vec::new();
+        Repository::stream_changes(
+            commit.repo,
+            resource_cache,
+            diff::changes_with_nth_parent(commit, parent, &format.renames),
+            algorithm,
+            format,
+            paths,
+            |patch| {
+                diff.extend_from_slice(patch);
+                Ok(())
+            },
+        )?;
+        Ok((!diff.is_empty()).then_some(diff))
+    }
+}
 
-        let deltas = commit
-            .repo
-            .diff_tree_to_tree(Some(&parent_tree), Some(&tree), None)
-            .unwrap();
+impl Repository {
+    /// Read a commit from a walk keeping a handle to this repository.
+    fn commit(&self, info: gix::revision::walk::Info<'_>) -> Commit {
+        let mut commit = Commit::try_from(info).unwrap();
+        commit.repository = self.inner.clone().into();
+        commit
+    }
 
-        let mut diff = String::new();
-        let mut resource_cache = commit
-            .repo
-            .diff_resource_cache(
-                gix::diff::blob::pipeline::Mode::ToGitUnlessBinaryToTextIsPresent,
-                Default::default(),
+    /// The commits between the two cutoffs with all other walk options left at
+    /// their defaults.
+    fn commits_in_range(
+        &self,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+    ) -> PyResult<Vec<Commit>> {
+        Python::with_gil(|py| {
+            self.walk_commits(
+                py,
+                walk::WalkOptions {
+                    commit_start_cutoff,
+                    commit_end_cutoff,
+                    ..Default::default()
+                },
             )
-            .unwrap();
-        let objects = &commit.repo.objects;
-
-        for delta in deltas {
-            let (older_location, newer_location, older_id, newer_id) = match &delta {
-                gix::object::tree::diff::ChangeDetached::Addition {
-                    location,
-                    entry_mode,
-                    id,
-                    ..
-                } => {
-                    // Skip anything that's not blob-diffable.
-                    // This includes the addition of new directories that git
-                    // will not normally show.
-                    if !entry_mode.is_blob() {
-                        continue;
-                    }
-                    // older is nothing
-                    // newer is everything
-                    let previous_id = gix::index::hash::Kind::Sha1.null();
-                    let backing = &mut [0; 6];
-                    writeln!(diff, "diff --git a/{location} b/{location}").unwrap();
-                    writeln!(diff, "new file mode {}", entry_mode.as_bytes(backing)).unwrap();
-                    writeln!(
-                        diff,
-                        "index {}..{}",
-                        &previous_id.to_string()[0..7],
-                        &id.to_string()[0..7],
-                    )
-                    .unwrap();
-                    writeln!(diff, "--- /dev/null").unwrap();
-                    writeln!(diff, "+++ b/{location}").unwrap();
-                    (
-                        location.as_ref(),
-                        location.as_ref(),
-                        &gix::index::hash::Kind::Sha1.null(),
-                        id,
-                    )
-                }
-                gix::object::tree::diff::ChangeDetached::Deletion {
-                    location,
-                    entry_mode,
-                    id,
-                    ..
-                } => {
-                    // Skip anything that's not blob-diffable.
-                    // This includes the addition of new directories that git
-                    // will not normally show.
-                    if !entry_mode.is_blob() {
-                        continue;
-                    }
-                    // newer is nothing
-                    // older is everything
-                    let newer_id = gix::index::hash::Kind::Sha1.null();
-                    let backing = &mut [0; 6];
-                    writeln!(diff, "diff --git a/{location} b/{location}").unwrap();
-                    writeln!(diff, "deleted file mode {}", entry_mode.as_bytes(backing)).unwrap();
-
-                    writeln!(
-                        diff,
-                        "index {}..{}",
-                        &id.to_string()[0..7],
-                        &newer_id.to_string()[0..7],
-                    )
-                    .unwrap();
-                    writeln!(diff, "--- a/{location}").unwrap();
-                    writeln!(diff, "+++ /dev/null").unwrap();
-                    (
-                        location.as_ref(),
-                        location.as_ref(),
-                        id,
-                        &gix::index::hash::Kind::Sha1.null(),
-                    )
-                }
-                gix::object::tree::diff::ChangeDetached::Modification {
-                    location,
-                    entry_mode,
-                    previous_id,
-                    id,
-                    ..
-                } => {
-                    // Skip anything that's not blob-diffable.
-                    // This includes the addition of new directories that git
-                    // will not normally show.
-                    if !entry_mode.is_blob() {
-                        continue;
-                    }
+        })
+    }
 
-                    writeln!(diff, "diff --git a/{location} b/{location}").unwrap();
-                    let backing = &mut [0; 6];
-                    writeln!(
-                        diff,
-                        "index {}..{} {}",
-                        &previous_id.to_string()[0..7],
-                        &id.to_string()[0..7],
-                        entry_mode.as_bytes(backing)
-                    )
-                    .unwrap();
-                    writeln!(diff, "--- a/{location}").unwrap();
-                    writeln!(diff, "+++ b/{location}").unwrap();
+    /// The commits of `Repository.commits` for `options`.
+    fn walk_commits(
+        &self,
+        py: Python<'_>,
+        options: walk::WalkOptions<'_>,
+    ) -> PyResult<Vec<Commit>> {
+        let walk::WalkOptions {
+            commit_start_cutoff,
+            commit_end_cutoff,
+            cutoff_start_timestamp,
+            cutoff_end_timestamp,
+            timezone,
+            stable_order,
+            simplify_by_decoration,
+            paths,
+            rewrite_parents,
+            boundary,
+            annotate_calendar,
+            use_mailmap,
+            eager,
+        } = options;
+        // Tag commits with weekday and holiday flags of this region.
+        let calendar = annotate_calendar.map(calendar::Region::parse).transpose()?;
+        let commit_start_cutoff =
+            commit_start_cutoff.map(|cutoff| gix::ObjectId::from_str(cutoff).unwrap());
+        let commit_end_cutoff =
+            commit_end_cutoff.map(|cutoff| gix::ObjectId::from_str(cutoff).unwrap());
+        // Dates without an explicit offset are interpreted in this timezone.
+        let timezone = timezone
+            .map(try_parse_timezone)
+            .transpose()
+            .into_py_result()?
+            .unwrap_or_else(jiff::tz::TimeZone::system);
+        let cutoff_start_timestamp = cutoff_start_timestamp
+            .map(|cutoff| try_parse_start_timestamp(cutoff, &timezone))
+            .transpose()
+            .into_py_result()?
+            .map(|timestamp| {
+                timestamp
+                    .duration_since(jiff::Timestamp::UNIX_EPOCH)
+                    .as_secs()
+            });
+        let cutoff_end_timestamp = cutoff_end_timestamp
+            .map(|cutoff| try_parse_end_timestamp(cutoff, &timezone))
+            .transpose()
+            .into_py_result()?
+            .map(|timestamp| {
+                timestamp
+                    .duration_since(jiff::Timestamp::UNIX_EPOCH)
+                    .as_secs()
+            });
 
-                    (location.as_ref(), location.as_ref(), previous_id, id)
+        py.allow_threads(|| {
+            let repository = self.inner.to_thread_local();
+            // Like `git log --simplify-by-decoration` only keep commits that a ref
+            // points at.
+            let decorated = if simplify_by_decoration {
+                Some(decorated_ids(&repository)?)
+            } else {
+                None
+            };
+            let target = repository
+                .head()
+                .unwrap()
+                .peel_to_commit_in_place()
+                .unwrap();
+            let sorting = if let Some(cutoff) = cutoff_start_timestamp {
+                gix::revision::walk::Sorting::ByCommitTimeCutoff {
+                    order: gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+                    seconds: cutoff,
                 }
-                gix::object::tree::diff::ChangeDetached::Rewrite {
-                    source_location,
-                    location,
-                    source_entry_mode,
-                    entry_mode,
-                    source_id,
-                    id,
-                    ..
-                } => {
-                    if !(source_entry_mode.is_blob() && entry_mode.is_blob()) {
-                        continue;
+            } else {
+                gix::revision::walk::Sorting::ByCommitTime(
+                    gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+                )
+            };
+            let mut commits = walk::walk(&repository, [target.id], [], sorting)?
+                .skip_while(move |info| {
+                    if let Some(id_cutoff) = commit_end_cutoff {
+                        id_cutoff != info.id
+                    } else {
+                        false
                     }
-
-                    writeln!(diff, "diff --git a/{source_location} b/{location}").unwrap();
-                    if id == source_id {
-                        // This is a perfect copy.
-                        let backing = &mut [0; 6];
-                        writeln!(diff, "old mode {}", source_entry_mode.as_bytes(backing)).unwrap();
-                        writeln!(diff, "new mode {}", entry_mode.as_bytes(backing)).unwrap();
-                        writeln!(diff, "similarity index 100%").unwrap();
-                        writeln!(diff, "rename from {source_location}").unwrap();
-                        writeln!(diff, "rename to {location}").unwrap();
-                        continue;
+                })
+                .skip_while(move |info| {
+                    if let (Some(commit_time), Some(cutoff)) =
+                        (info.commit_time, cutoff_end_timestamp)
+                    {
+                        commit_time > cutoff
                     } else {
-                        // TODO(noxpardalis): what to do if the entry modes are different?
-                        debug_assert_eq!(source_entry_mode, entry_mode);
-
-                        let backing = &mut [0; 6];
-                        writeln!(
-                            diff,
-                            "index {}..{} {}",
-                            &source_id.to_string()[0..7],
-                            &id.to_string()[0..7],
-                            entry_mode.as_bytes(backing)
-                        )
-                        .unwrap();
-                        writeln!(diff, "--- a/{source_location}").unwrap();
-                        writeln!(diff, "+++ b/{location}").unwrap();
+                        false
+                    }
+                })
+                .scan(false, move |cutoff_seen, info| {
+                    if *cutoff_seen {
+                        None
+                    } else if Some(info.id) == commit_start_cutoff {
+                        *cutoff_seen = true;
+                        Some(info)
+                    } else {
+                        Some(info)
+                    }
+                })
+                .collect::<Vec<_>>();
+            // The parents of the walked range that the cutoffs excluded.
+            let boundary = if boundary {
+                let walked = commits
+                    .iter()
+                    .map(|info| info.id)
+                    .collect::<std::collections::HashSet<_>>();
+                let mut boundary = Vec::new();
+                for parent_id in commits.iter().flat_map(|info| &info.parent_ids) {
+                    if !walked.contains(parent_id) && !boundary.contains(parent_id) {
+                        boundary.push(*parent_id);
                     }
-                    (source_location.as_ref(), location.as_ref(), source_id, id)
                 }
+                boundary
+            } else {
+                Vec::new()
             };
-
-            writeln!(
-                diff,
-                "{}",
-                Self::unified_diff_files(
-                    &mut resource_cache,
-                    objects,
-                    algorithm,
-                    older_id,
-                    older_location,
-                    newer_id,
-                    newer_location,
-                )
-                .unwrap()
-                .trim()
-            )
-            .unwrap();
-        }
-        if diff.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(diff))
+            let shallow = repository
+                .shallow_commits()
+                .into_py_result()?
+                .map(|shallow| shallow.iter().copied().collect())
+                .unwrap_or_else(std::collections::HashSet::new);
+            // Simplify the history for the given paths the way `git log -- <paths>`
+            // does, optionally rewriting parents so the result stays connected.
+            let simplified = if paths.is_some() || rewrite_parents {
+                let mut simplified =
+                    simplify::by_paths(&repository, &commits, &paths.unwrap_or_default())?;
+                commits.retain(|info| simplified.contains_key(&info.id));
+                if !rewrite_parents {
+                    simplified.clear();
+                }
+                simplified
+            } else {
+                std::collections::HashMap::new()
+            };
+            if let Some(decorated) = decorated {
+                commits.retain(|info| decorated.contains(&info.id));
+            }
+            let commits = if stable_order {
+                break_commit_time_ties(commits)
+            } else {
+                commits
+            };
+            let mut commits = commits
+                .into_iter()
+                .map(|info| {
+                    let parents = simplified.get(&info.id).map(|parents| {
+                        parents
+                            .iter()
+                            .map(|parent| parent.to_string())
+                            .collect::<Vec<_>>()
+                    });
+                    let mut commit = self.commit(info);
+                    commit.rewritten_parent_ids = parents;
+                    commit.is_shallow = shallow.contains(&commit.inner.id);
+                    commit
+                })
+                .collect::<Vec<_>>();
+            for id in boundary {
+                // Parents missing from a shallow clone can't be shown.
+                let Some(object) = repository.try_find_object(id).into_py_result()? else {
+                    continue;
+                };
+                let mut commit = Commit::try_from(object.detach()).unwrap();
+                commit.repository = self.inner.clone().into();
+                commit.is_boundary = true;
+                commit.is_shallow = shallow.contains(&id);
+                commits.push(commit);
+            }
+            if let Some(region) = calendar {
+                for commit in &mut commits {
+                    commit.calendar = Some(calendar::annotate(region, &commit.commit_time));
+                }
+            }
+            // Canonicalize the author and committer identities like `git log
+            // --use-mailmap`.
+            if use_mailmap {
+                let mailmap = mailmap::Mailmap::load(&repository);
+                for commit in &mut commits {
+                    for signature in [&mut commit.author, &mut commit.committer] {
+                        (signature.name, signature.email) =
+                            mailmap.resolve(&signature.name, &signature.email);
+                    }
+                }
+            }
+            // Messages are otherwise parsed on first access, parse them all now
+            // when they will be read anyway (e.g. for an export).
+            if eager {
+                for commit in &commits {
+                    commit.parsed();
+                }
+            }
+
+            Ok(commits)
+        })
+    }
+
+    /// See `diff::unified_diff_files`.
+    #[allow(clippy::too_many_arguments)]
+    fn unified_diff_files(
+        resource_cache: &mut gix::diff::blob::Platform,
+        objects: &gix::OdbHandle,
+        algorithm: gix::diff::blob::Algorithm,
+        format: &unified_diff::PatchFormat,
+        older_id: &gix::oid,
+        older_location: &gix::diff::object::bstr::BStr,
+        newer_id: &gix::oid,
+        newer_location: &gix::diff::object::bstr::BStr,
+    ) -> PyResult<Option<Vec<u8>>> {
+        diff::unified_diff_files(
+            resource_cache,
+            objects,
+            algorithm,
+            format,
+            older_id,
+            older_location,
+            newer_id,
+            newer_location,
+        )
+        .map_err(py_err)
+    }
+
+    /// The tree of revision `rev`, the empty tree if it is an unborn `HEAD`.
+    fn rev_tree<'repo>(
+        repository: &'repo gix::Repository,
+        rev: &str,
+    ) -> PyResult<gix::Tree<'repo>> {
+        if rev == "HEAD" && repository.head().into_py_result()?.is_unborn() {
+            return Ok(repository.empty_tree());
         }
+        repository
+            .rev_parse_single(rev)
+            .into_py_result()?
+            .object()
+            .into_py_result()?
+            .peel_to_tree()
+            .into_py_result()
+    }
+
+    /// The patch from tree `old` to tree `new`.
+    fn diff_trees(
+        repository: &gix::Repository,
+        old: &gix::Tree<'_>,
+        new: &gix::Tree<'_>,
+    ) -> PyResult<Option<Vec<u8>>> {
+        let format = unified_diff::PatchFormat {
+            funcnames: funcname::Drivers::new(repository).map_err(py_err)?,
+            ..Default::default()
+        };
+        let changes = format.renames.changes(repository, old, new);
+        Self::write_changes(repository, changes, Algorithm::Myers.into(), &format, &[])
+    }
+
+    /// See `diff::write_change`.
+    fn write_change(
+        diff: &mut Vec<u8>,
+        resource_cache: &mut gix::diff::blob::Platform,
+        repository: &gix::Repository,
+        algorithm: gix::diff::blob::Algorithm,
+        format: &unified_diff::PatchFormat,
+        delta: &gix::object::tree::diff::ChangeDetached,
+    ) -> PyResult<()> {
+        diff::write_change(diff, resource_cache, repository, algorithm, format, delta)
+            .map_err(py_err)
+    }
+
+    fn diff_context(&self) -> DiffContext {
+        DiffContext {
+            cache: self.diff_cache.clone(),
+            #[cfg(feature = "git_fallback")]
+            fallbacks: self.git_fallback.then(|| self.fallbacks.clone()),
+        }
+    }
+
+    fn diff_with_parent(
+        commit: &gix::Commit<'_>,
+        algorithm: gix::diff::blob::Algorithm,
+        format: &unified_diff::PatchFormat,
+    ) -> PyResult<Option<Vec<u8>>> {
+        diff::diff_with_parent(commit, algorithm, format).map_err(py_err)
+    }
+
+    /// See `diff::diff_with_nth_parent`.
+    fn diff_with_nth_parent(
+        commit: &gix::Commit<'_>,
+        parent: usize,
+        algorithm: gix::diff::blob::Algorithm,
+        format: &unified_diff::PatchFormat,
+        paths: &[String],
+    ) -> PyResult<Option<Vec<u8>>> {
+        diff::diff_with_nth_parent(commit, parent, algorithm, format, paths).map_err(py_err)
+    }
+
+    /// See `diff::write_changes`.
+    fn write_changes(
+        repository: &gix::Repository,
+        changes: Vec<gix::object::tree::diff::ChangeDetached>,
+        algorithm: gix::diff::blob::Algorithm,
+        format: &unified_diff::PatchFormat,
+        paths: &[String],
+    ) -> PyResult<Option<Vec<u8>>> {
+        diff::write_changes(repository, changes, algorithm, format, paths).map_err(py_err)
+    }
+
+    /// Pass the patch of `changes` to `sink` file by file as it is
+    /// produced, see `diff::stream_changes`.
+    fn stream_changes(
+        repository: &gix::Repository,
+        resource_cache: &mut gix::diff::blob::Platform,
+        changes: Vec<gix::object::tree::diff::ChangeDetached>,
+        algorithm: gix::diff::blob::Algorithm,
+        format: &unified_diff::PatchFormat,
+        paths: &[String],
+        mut sink: impl FnMut(&[u8]) -> PyResult<()>,
+    ) -> PyResult<()> {
+        diff::stream_changes(
+            repository,
+            resource_cache,
+            changes,
+            algorithm,
+            format,
+            paths,
+            |patch| sink(patch).map_err(|error| gitch_engine::Error::Callback(Box::new(error))),
+        )
+        .map_err(py_err)
+    }
+
+    /// The patch of `commit` for `options`, see `diff_bytes`.
+    fn patch(
+        &self,
+        py: Python<'_>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        mut options: diff_options::DiffOptions<'_>,
+    ) -> PyResult<Option<Cow<'static, [u8]>>> {
+        py.allow_threads(|| {
+            commit.check_parent(options.parent)?;
+            let repository = self.inner.to_thread_local();
+            let format = options.patch_format(&repository)?;
+            let commit = commit.inner.clone().attach(&repository);
+            let commit = commit.into_commit();
+            Ok(self
+                .diff_context()
+                .diff(
+                    &mut diff::resource_cache(&repository),
+                    &commit,
+                    options.parent,
+                    algorithm.into(),
+                    &format,
+                    options.paths.as_deref().unwrap_or_default(),
+                )?
+                .map(|diff| {
+                    let diff = if options.color {
+                        color::colorize(&diff, options.context_lines)
+                    } else {
+                        diff
+                    };
+                    Cow::Owned(unified_diff::truncate(
+                        diff,
+                        options.max_bytes,
+                        options.max_lines,
+                    ))
+                }))
+        })
+    }
+
+    /// The files of `commit` for `options`, see `diff_structured`.
+    fn structured_files(
+        &self,
+        py: Python<'_>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        mut options: diff_options::DiffOptions<'_>,
+    ) -> PyResult<Vec<paged::FileDiff>> {
+        commit.check_parent(options.parent)?;
+        let format = options.structured_format(&self.inner.to_thread_local())?;
+        paged::diff_structured(
+            py,
+            &self.inner,
+            commit,
+            options.parent,
+            algorithm.into(),
+            &format,
+            &options.paths.unwrap_or_default(),
+        )
+    }
+
+    /// The files changed by `commit` against its parent at index
+    /// `options.parent` as `--stat`, `--numstat`, and `--dirstat` count
+    /// them, see `diff` for the options.
+    fn file_stats(
+        &self,
+        py: Python<'_>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        options: diff_options::DiffOptions<'_>,
+    ) -> PyResult<Vec<stats::FileStat>> {
+        let parent = options.parent;
+        // Counted from a patch that tells the files apart.
+        let patch = self.patch(
+            py,
+            commit,
+            algorithm,
+            diff_options::DiffOptions {
+                parent,
+                paths: options.paths,
+                ignore_whitespace: options.ignore_whitespace,
+                rename_threshold: options.rename_threshold,
+                find_copies: options.find_copies,
+                find_copies_harder: options.find_copies_harder,
+                rename_limit: options.rename_limit,
+                max_file_size: options.max_file_size,
+                textconv: options.textconv,
+                ..Default::default()
+            },
+        )?;
+        py.allow_threads(|| {
+            let repository = self.inner.to_thread_local();
+            let commit = commit.inner.clone().attach(&repository).into_commit();
+            Self::count_files(&commit, parent, patch.as_deref().unwrap_or_default())
+        })
+    }
+
+    /// The files of `patch`, the changes of `commit` against its parent at
+    /// index `parent`, with the sizes of binary files looked up in the trees
+    /// on either side (see `stats::file_stats`).
+    fn count_files(
+        commit: &gix::Commit<'_>,
+        parent: usize,
+        patch: &[u8],
+    ) -> PyResult<Vec<stats::FileStat>> {
+        let repository = commit.repo;
+        let tree = commit.tree().into_py_result()?;
+        let parent_tree = match commit.parent_ids().nth(parent) {
+            Some(parent_id) => parent_id
+                .object()
+                .into_py_result()?
+                .peel_to_tree()
+                .into_py_result()?,
+            None => repository.empty_tree(),
+        };
+        let mut resource_cache = diff::resource_cache(repository);
+        let is_binary = |path: &[u8]| {
+            let Ok(Some(entry)) = tree.lookup_entry(path.split(|&byte| byte == b'/')) else {
+                return false;
+            };
+            let path = path.into();
+            entry.mode().is_blob()
+                && binary::is_binary(
+                    &mut resource_cache,
+                    &repository.objects,
+                    entry.oid(),
+                    path,
+                    entry.oid(),
+                    path,
+                )
+        };
+        let size = |kind, path: &[u8]| {
+            let tree = match kind {
+                gix::diff::blob::ResourceKind::OldOrSource => &parent_tree,
+                gix::diff::blob::ResourceKind::NewOrDestination => &tree,
+            };
+            tree.lookup_entry(path.split(|&byte| byte == b'/'))
+                .ok()
+                .flatten()
+                .and_then(|entry| repository.objects.try_header(entry.oid()).ok().flatten())
+                .map_or(0, |header| header.size)
+        };
+        Ok(stats::file_stats(patch, is_binary, size))
     }
 }
 
@@ -391,48 +1302,216 @@ impl<T, E: std::error::Error> IntoPyResult for Result<T, E> {
     }
 }
 
-fn try_parse_start_timestamp(str: &str) -> Result<jiff::Timestamp, jiff::Error> {
+/// An error of the engine as the Python exception of its kind, the
+/// exceptions raised by Python callbacks as they were.
+fn py_err(error: gitch_engine::Error) -> PyErr {
+    match error {
+        gitch_engine::Error::Value(message) => PyValueError::new_err(message),
+        gitch_engine::Error::Repository(error) => PyOSError::new_err(error.to_string()),
+        gitch_engine::Error::Callback(error) => match error.downcast::<PyErr>() {
+            Ok(error) => *error,
+            Err(error) => pyo3::exceptions::PyRuntimeError::new_err(error.to_string()),
+        },
+    }
+}
+
+fn try_parse_timezone(str: &str) -> Result<jiff::tz::TimeZone, jiff::Error> {
+    static PARSER: jiff::fmt::temporal::DateTimeParser = jiff::fmt::temporal::DateTimeParser::new();
+    // Accepts IANA names (e.g. `Europe/Paris`), fixed offsets (e.g. `+01:00`),
+    // and POSIX TZ strings.
+    PARSER.parse_time_zone(str)
+}
+
+fn try_parse_start_timestamp(
+    str: &str,
+    timezone: &jiff::tz::TimeZone,
+) -> Result<jiff::Timestamp, jiff::Error> {
     // Try timestamp
     if let Ok(timestamp) = jiff::Timestamp::from_str(str) {
         Ok(timestamp)
     } else {
         // Try date time
         if let Ok(dt) = jiff::civil::DateTime::from_str(str) {
-            Ok(dt.to_zoned(jiff::tz::TimeZone::system())?.timestamp())
+            Ok(dt.to_zoned(timezone.clone())?.timestamp())
         } else {
             // Try date set to start of day.
             jiff::civil::Date::from_str(str)
-                .map(|d| d.to_zoned(jiff::tz::TimeZone::system()))?
+                .map(|d| d.to_zoned(timezone.clone()))?
                 .map(|d| d.start_of_day())?
                 .map(|d| d.timestamp())
         }
     }
 }
 
-fn try_parse_end_timestamp(str: &str) -> Result<jiff::Timestamp, jiff::Error> {
+fn try_parse_end_timestamp(
+    str: &str,
+    timezone: &jiff::tz::TimeZone,
+) -> Result<jiff::Timestamp, jiff::Error> {
     // Try timestamp
     if let Ok(timestamp) = jiff::Timestamp::from_str(str) {
         Ok(timestamp)
     } else {
         // Try date time
         if let Ok(dt) = jiff::civil::DateTime::from_str(str) {
-            Ok(dt.to_zoned(jiff::tz::TimeZone::system())?.timestamp())
+            Ok(dt.to_zoned(timezone.clone())?.timestamp())
         } else {
             // Try date set to end of day.
             jiff::civil::Date::from_str(str)
-                .map(|d| d.to_zoned(jiff::tz::TimeZone::system()))?
+                .map(|d| d.to_zoned(timezone.clone()))?
                 .map(|d| d.end_of_day())?
                 .map(|d| d.timestamp())
         }
     }
 }
 
+/// The IDs of all commits that are pointed at by a ref (peeling tags) or HEAD.
+fn decorated_ids(
+    repository: &gix::Repository,
+) -> PyResult<std::collections::HashSet<gix::ObjectId>> {
+    let mut decorated = std::collections::HashSet::new();
+    for reference in repository
+        .references()
+        .into_py_result()?
+        .all()
+        .into_py_result()?
+    {
+        let mut reference = reference.map_err(|e| PyOSError::new_err(e.to_string()))?;
+        // Refs that can't be peeled (e.g. dangling) simply don't decorate
+        // anything.
+        if let Ok(id) = reference.peel_to_id_in_place() {
+            decorated.insert(id.detach());
+        }
+    }
+    if let Ok(head) = repository.head_id() {
+        decorated.insert(head.detach());
+    }
+    Ok(decorated)
+}
+
+/// Deterministically order runs of commits that share the same commit time.
+///
+/// Within such a run children always come before their parents (like
+/// `--topo-order`) and any remaining ties are broken by ascending object id so
+/// that repeated walks over the same history produce the same sequence.
+fn break_commit_time_ties(
+    infos: Vec<gix::revision::walk::Info<'_>>,
+) -> Vec<gix::revision::walk::Info<'_>> {
+    let mut ordered = Vec::with_capacity(infos.len());
+
+    for run in infos.chunk_by(|a, b| a.commit_time == b.commit_time) {
+        if run.len() == 1 {
+            ordered.extend_from_slice(run);
+            continue;
+        }
+
+        // Count for each commit how many of its children are in this run,
+        // a commit is only ready to be emitted once all of them have been.
+        let mut pending_children = run
+            .iter()
+            .map(|info| (info.id, 0usize))
+            .collect::<std::collections::HashMap<_, _>>();
+        for info in run {
+            for parent_id in &info.parent_ids {
+                if let Some(count) = pending_children.get_mut(parent_id) {
+                    *count += 1;
+                }
+            }
+        }
+        let by_id = run
+            .iter()
+            .map(|info| (info.id, info))
+            .collect::<std::collections::HashMap<_, _>>();
+        let mut ready = pending_children
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(id, _)| *id)
+            .collect::<std::collections::BTreeSet<_>>();
+
+        while let Some(id) = ready.pop_first() {
+            let info = by_id[&id];
+            ordered.push(info.clone());
+            for parent_id in &info.parent_ids {
+                if let Some(count) = pending_children.get_mut(parent_id) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert(*parent_id);
+                    }
+                }
+            }
+        }
+    }
+
+    ordered
+}
+
 #[pymethods]
 impl Repository {
+    /// Open the repository containing `repository`.
+    ///
+    /// With `git_fallback` operations that gix can't do on this repository
+    /// (see `preflight`) run the git CLI instead, this needs gitch to be built
+    /// with the `git_fallback` feature.
     #[new]
-    pub fn new(repository: PathBuf) -> PyResult<Self> {
-        let inner = gix::discover(&repository).into_py_result()?.into_sync();
-        Ok(Self { inner })
+    #[pyo3(signature=(repository, git_fallback=false))]
+    pub fn new(repository: PathBuf, git_fallback: bool) -> PyResult<Self> {
+        if git_fallback && !cfg!(feature = "git_fallback") {
+            return Err(PyValueError::new_err(
+                "gitch was built without the `git_fallback` feature",
+            ));
+        }
+        let inner = gix::discover(&repository)
+            .into_py_result()?
+            .into_sync()
+            .into();
+        Ok(Self {
+            inner,
+            oid_map: Default::default(),
+            deployments: Default::default(),
+            git_fallback,
+            fallbacks: Default::default(),
+            diff_cache: None,
+        })
+    }
+
+    /// Keep the patches of `diff`, `diff_bytes`, `diffs`, and the stats
+    /// counted from them by commit and options, so asking for the same
+    /// patch again returns it without diffing. With `directory` they are
+    /// also written there (it is created if missing) and read back by later
+    /// runs, e.g. of CI over overlapping ranges, sharing it.
+    ///
+    /// Patches are only looked up by the options given, the cache has to be
+    /// cleared when the diff configuration (e.g. `diff` attributes and their
+    /// `xfuncname` patterns) changes. Patches with `textconv` converters are
+    /// never cached.
+    #[pyo3(signature=(directory=None))]
+    pub fn enable_diff_cache(&mut self, directory: Option<PathBuf>) -> PyResult<()> {
+        self.diff_cache = Some(
+            diff_cache::DiffCache::new(directory)
+                .into_py_result()?
+                .into(),
+        );
+        Ok(())
+    }
+
+    /// Check the repository for features that gix doesn't support or that
+    /// make some operations fail, e.g. unknown extensions or objects missing
+    /// from a partial clone.
+    fn preflight(&self) -> health::Preflight {
+        health::preflight(&self.inner.to_thread_local(), self.git_fallback)
+    }
+
+    /// The operations that ran the git CLI instead of gix as `(operation,
+    /// reason)`, in order. Everything else used gix.
+    #[getter]
+    fn fallbacks(&self) -> Vec<(String, String)> {
+        self.fallbacks.lock().unwrap().clone()
+    }
+
+    /// Whether the repository is a shallow clone, walks stop at its shallow
+    /// commits (see `Commit.is_shallow`).
+    #[getter]
+    fn is_shallow(&self) -> bool {
+        self.inner.to_thread_local().is_shallow()
     }
 
     #[getter]
@@ -445,11 +1524,693 @@ impl Repository {
             .into_py_result()
     }
 
-    pub fn diff(&self, commit: &Commit, algorithm: Algorithm) -> Option<String> {
+    /// The patch of a commit against its first parent, or against the parent
+    /// at index `parent` of `commit.parent_ids` (e.g. `parent=1` for what a
+    /// merge brought in from its second parent).
+    ///
+    /// Paths are prefixed with `src_prefix`/`dst_prefix` (none at all with
+    /// `no_prefix`) and `git_header=False` leaves out git's extended headers
+    /// (`diff --git`, modes, `index`, renames) for a plain unified diff.
+    ///
+    /// `paths` restricts the patch to files that are, are below, or match as
+    /// a glob one of the given paths (e.g. `["docs", "src/**/*.rs"]`).
+    ///
+    /// Hunks have `context_lines` lines of context (like `-U<n>`) and hunks
+    /// at most `inter_hunk_context` lines apart are joined (like
+    /// `--inter-hunk-context`). Like git, the `@@` header of a hunk names the
+    /// function or section it is in, as found by the `xfuncname` pattern of
+    /// the file's diff driver (its `diff` attribute, e.g. `*.py diff=python`
+    /// for git's builtin ones or `diff.<driver>.xfuncname`) or else by the
+    /// last line before it starting with a letter, `_`, or `$`.
+    ///
+    /// `ignore_whitespace` compares lines without some whitespace like git:
+    /// `"all"` (`-w`), `"change"` in its amount (`-b`), at the `"eol"`
+    /// (`--ignore-space-at-eol`), or ignores changes of only
+    /// `"blank_lines"` (`--ignore-blank-lines`). Files only changed that way
+    /// are left out, so the patch of a commit that just reformats is `None`.
+    ///
+    /// Renames and copies are found as configured (`diff.renames`) unless
+    /// set: files at least `rename_threshold` percent similar are renames
+    /// (like `-M<n>%`), `find_copies` also finds copies of modified files
+    /// (`-C`) and `find_copies_harder` of any file (`--find-copies-harder`),
+    /// and `rename_limit` caps the files compared (`-l<n>`). Renames and
+    /// copies have their similarity in the `similarity index` header.
+    ///
+    /// Binary files (by their `diff` attribute or a NUL byte near the start,
+    /// as git tells them apart) only get a `Binary files ... differ` line,
+    /// unless `binary` asks for a `GIT binary patch` that `git apply` can
+    /// apply (like `--binary`).
+    ///
+    /// Files whose diff driver has a `textconv` command (e.g. `*.pdf
+    /// diff=pdf` and `diff.pdf.textconv=pdftotext`) are diffed as the text
+    /// it converts them to, like git does. `textconv` maps names of drivers
+    /// to Python functions doing the converting instead, which take the
+    /// content as `bytes` and return `str` or `bytes` (e.g. `{"ipynb":
+    /// notebook_to_text}`). Such patches show the converted text and can't be
+    /// applied.
+    ///
+    /// Submodules show as a hunk from `Subproject commit <old>` to
+    /// `Subproject commit <new>` like git does. With `submodule="log"` (like
+    /// `--submodule=log`) they show as a `Submodule <path> <old>..<new>:`
+    /// line followed by the subjects of the commits in between, which are
+    /// only known if the submodule has been cloned. Symlinks show as changes
+    /// of their target, and like git a file that became a symlink or a
+    /// submodule (or the other way around) as deleted and added.
+    ///
+    /// Files with a blob larger than `max_file_size` bytes are neither
+    /// loaded nor diffed (much like `core.bigFileThreshold`), their patch
+    /// just says `diff suppressed (<n> bytes)` with the larger size.
+    ///
+    /// A patch longer than `max_bytes` bytes or `max_lines` lines is cut at
+    /// the last line that fits, followed by a `diff truncated (<n> lines, <m>
+    /// bytes omitted)` line.
+    ///
+    /// With `color` the patch is colored with ANSI escapes in git's default
+    /// palette, like `git diff --color=always`: bold headers, cyan
+    /// `@@` lines, red removed and green added lines, and added whitespace
+    /// errors (trailing whitespace, spaces before tabs in the indent, blank
+    /// lines added at the end of a file) on red. The caps count the escapes.
+    ///
+    /// With `format="numstat"` the result is a list of `(insertions,
+    /// deletions, path)` like the lines of `git diff --numstat` instead, with
+    /// `"-"` for the counts of binary files and `old => new` paths for
+    /// renames and copies. With `format="stat"` it is the text of `git diff
+    /// --stat` laid out in `stat_width` columns (like `--stat=<width>`), a
+    /// histogram of the changed lines of every file and a `<n> files
+    /// changed` summary, or `None` if nothing changed. The options only
+    /// about the patch's text are ignored.
+    ///
+    /// With `format="json"` it is a JSON document of the files with their
+    /// hunks and lines and the totals, the schema is described by
+    /// `FileDiff`, `Hunk`, and `Line` and versioned in its `format` and
+    /// `version` keys. `format="html"` renders the same as an HTML fragment
+    /// to embed in a page, in a unified layout or `side_by_side`, without
+    /// styles but `gitch-` classes to style it by (e.g. `gitch-addition` and
+    /// `gitch-deletion` lines with the changed words in `<ins>`/`<del>`).
+    /// Prefixes, `git_header`, `binary`, and the caps of the patch's size
+    /// don't apply to either.
+    #[pyo3(
+        signature=(
+            commit,
+            algorithm,
+            src_prefix="a/",
+            dst_prefix="b/",
+            no_prefix=false,
+            git_header=true,
+            parent=0,
+            paths=None,
+            context_lines=3,
+            inter_hunk_context=0,
+            ignore_whitespace=None,
+            rename_threshold=None,
+            find_copies=false,
+            find_copies_harder=false,
+            rename_limit=None,
+            binary=false,
+            max_file_size=None,
+            max_bytes=None,
+            max_lines=None,
+            format="patch",
+            stat_width=80,
+            side_by_side=false,
+            textconv=None,
+            submodule="short",
+            color=false
+        ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn diff<'py>(
+        &self,
+        py: Python<'py>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        src_prefix: &str,
+        dst_prefix: &str,
+        no_prefix: bool,
+        git_header: bool,
+        parent: usize,
+        paths: Option<Vec<String>>,
+        context_lines: u32,
+        inter_hunk_context: u32,
+        ignore_whitespace: Option<&str>,
+        rename_threshold: Option<u8>,
+        find_copies: bool,
+        find_copies_harder: bool,
+        rename_limit: Option<usize>,
+        binary: bool,
+        max_file_size: Option<u64>,
+        max_bytes: Option<usize>,
+        max_lines: Option<usize>,
+        format: &str,
+        stat_width: usize,
+        side_by_side: bool,
+        textconv: Option<std::collections::HashMap<String, Py<PyAny>>>,
+        submodule: &str,
+        color: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options = diff_options::DiffOptions {
+            src_prefix,
+            dst_prefix,
+            no_prefix,
+            git_header,
+            parent,
+            paths,
+            context_lines,
+            inter_hunk_context,
+            ignore_whitespace,
+            rename_threshold,
+            find_copies,
+            find_copies_harder,
+            rename_limit,
+            binary,
+            max_file_size,
+            max_bytes,
+            max_lines,
+            textconv,
+            submodule,
+            color,
+        };
+        let format = stats::OutputFormat::parse(format)?;
+        if matches!(
+            format,
+            stats::OutputFormat::Json | stats::OutputFormat::Html
+        ) {
+            let files = self.structured_files(py, commit, algorithm, options)?;
+            let out = match format {
+                stats::OutputFormat::Json => serialize::diff(py, &files).to_string(),
+                _ => html::render(py, &files, side_by_side),
+            };
+            return Ok(out.into_pyobject(py)?.into_any());
+        }
+        if format != stats::OutputFormat::Patch {
+            let files = self.file_stats(py, commit, algorithm, options)?;
+            return Ok(match format {
+                stats::OutputFormat::Numstat => stats::numstat(files).into_pyobject(py)?.into_any(),
+                _ => stats::stat(&files, stat_width)
+                    .into_pyobject(py)?
+                    .into_any(),
+            });
+        }
+        Ok(self
+            .patch(py, commit, algorithm, options)?
+            .map(|diff| String::from_utf8_lossy(&diff).into_owned())
+            .into_pyobject(py)?
+            .into_any())
+    }
+
+    /// The same as `diff` but returns the patch as raw bytes so that files
+    /// which aren't valid UTF-8 still round-trip through `git apply`.
+    #[pyo3(
+        signature=(
+            commit,
+            algorithm,
+            src_prefix="a/",
+            dst_prefix="b/",
+            no_prefix=false,
+            git_header=true,
+            parent=0,
+            paths=None,
+            context_lines=3,
+            inter_hunk_context=0,
+            ignore_whitespace=None,
+            rename_threshold=None,
+            find_copies=false,
+            find_copies_harder=false,
+            rename_limit=None,
+            binary=false,
+            max_file_size=None,
+            max_bytes=None,
+            max_lines=None,
+            textconv=None,
+            submodule="short",
+            color=false
+        ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn diff_bytes(
+        &self,
+        py: Python<'_>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        src_prefix: &str,
+        dst_prefix: &str,
+        no_prefix: bool,
+        git_header: bool,
+        parent: usize,
+        paths: Option<Vec<String>>,
+        context_lines: u32,
+        inter_hunk_context: u32,
+        ignore_whitespace: Option<&str>,
+        rename_threshold: Option<u8>,
+        find_copies: bool,
+        find_copies_harder: bool,
+        rename_limit: Option<usize>,
+        binary: bool,
+        max_file_size: Option<u64>,
+        max_bytes: Option<usize>,
+        max_lines: Option<usize>,
+        textconv: Option<std::collections::HashMap<String, Py<PyAny>>>,
+        submodule: &str,
+        color: bool,
+    ) -> PyResult<Option<Cow<'static, [u8]>>> {
+        self.patch(
+            py,
+            commit,
+            algorithm,
+            diff_options::DiffOptions {
+                src_prefix,
+                dst_prefix,
+                no_prefix,
+                git_header,
+                parent,
+                paths,
+                context_lines,
+                inter_hunk_context,
+                ignore_whitespace,
+                rename_threshold,
+                find_copies,
+                find_copies_harder,
+                rename_limit,
+                binary,
+                max_file_size,
+                max_bytes,
+                max_lines,
+                textconv,
+                submodule,
+                color,
+            },
+        )
+    }
+
+    /// The same patch as `diff_bytes`, written to `out` file by file as it is
+    /// produced instead of built up in memory, for commits too large to hold
+    /// their whole diff. `out` is either a binary file-like object, whose
+    /// `write` gets each chunk, or a callable called with each chunk. See
+    /// `visit_deltas` to go through the hunks of the files instead.
+    ///
+    /// Returns the number of bytes written, 0 if nothing changed.
+    #[pyo3(
+        signature=(
+            commit,
+            out,
+            algorithm=Algorithm::Myers,
+            src_prefix="a/",
+            dst_prefix="b/",
+            no_prefix=false,
+            git_header=true,
+            parent=0,
+            paths=None,
+            context_lines=3,
+            inter_hunk_context=0,
+            ignore_whitespace=None,
+            rename_threshold=None,
+            find_copies=false,
+            find_copies_harder=false,
+            rename_limit=None,
+            binary=false,
+            max_file_size=None,
+            textconv=None,
+            submodule="short",
+            color=false
+        ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn diff_to(
+        &self,
+        commit: &Commit,
+        out: &Bound<'_, PyAny>,
+        algorithm: Algorithm,
+        src_prefix: &str,
+        dst_prefix: &str,
+        no_prefix: bool,
+        git_header: bool,
+        parent: usize,
+        paths: Option<Vec<String>>,
+        context_lines: u32,
+        inter_hunk_context: u32,
+        ignore_whitespace: Option<&str>,
+        rename_threshold: Option<u8>,
+        find_copies: bool,
+        find_copies_harder: bool,
+        rename_limit: Option<usize>,
+        binary: bool,
+        max_file_size: Option<u64>,
+        textconv: Option<std::collections::HashMap<String, Py<PyAny>>>,
+        submodule: &str,
+        color: bool,
+    ) -> PyResult<usize> {
+        let mut options = diff_options::DiffOptions {
+            src_prefix,
+            dst_prefix,
+            no_prefix,
+            git_header,
+            parent,
+            paths,
+            context_lines,
+            inter_hunk_context,
+            ignore_whitespace,
+            rename_threshold,
+            find_copies,
+            find_copies_harder,
+            rename_limit,
+            binary,
+            max_file_size,
+            textconv,
+            submodule,
+            color,
+            ..Default::default()
+        };
+        let write = if out.hasattr("write")? {
+            out.getattr("write")?
+        } else if out.is_callable() {
+            out.clone()
+        } else {
+            return Err(PyTypeError::new_err(
+                "out must be a file-like object with a write method or a callable",
+            ));
+        };
+        commit.check_parent(parent)?;
         let repository = self.inner.to_thread_local();
+        let format = options.patch_format(&repository)?;
+        let paths = options.paths.unwrap_or_default();
         let commit = commit.inner.clone().attach(&repository);
         let commit = commit.into_commit();
-        Repository::diff_with_parent(&commit, algorithm.into()).unwrap()
+        #[cfg(feature = "git_fallback")]
+        if self.git_fallback && health::diff_objects_missing(&commit, parent) {
+            self.fallbacks.lock().unwrap().push((
+                "diff".to_string(),
+                format!("objects of commit {} are missing", commit.id),
+            ));
+            let mut patch = health::git_diff(&commit, parent, algorithm.into(), &format, &paths)?
+                .unwrap_or_default();
+            if color {
+                patch = color::colorize(&patch, context_lines);
+            }
+            if !patch.is_empty() {
+                write.call1((PyBytes::new(out.py(), &patch),))?;
+            }
+            return Ok(patch.len());
+        }
+        let mut written = 0;
+        Self::stream_changes(
+            &repository,
+            &mut diff::resource_cache(&repository),
+            diff::changes_with_nth_parent(&commit, parent, &format.renames),
+            algorithm.into(),
+            &format,
+            &paths,
+            |patch| {
+                let colored;
+                let patch = if color {
+                    colored = color::colorize(patch, context_lines);
+                    &colored
+                } else {
+                    patch
+                };
+                write.call1((PyBytes::new(out.py(), patch),))?;
+                written += patch.len();
+                Ok(())
+            },
+        )?;
+        Ok(written)
+    }
+
+    /// The patches of many commits against their first parents, the same as
+    /// `diff` with the default options for each, computed in parallel on
+    /// `threads` threads (all cores by default). Each thread keeps its own
+    /// caches for all of the commits it diffs. The patches are in the order
+    /// of `commits`, `None` for commits that change nothing.
+    #[pyo3(signature=(commits, algorithm=Algorithm::Myers, threads=None))]
+    pub fn diffs(
+        &self,
+        py: Python<'_>,
+        commits: Vec<PyRef<'_, Commit>>,
+        algorithm: Algorithm,
+        threads: Option<usize>,
+    ) -> PyResult<Vec<Option<String>>> {
+        let commits = commits
+            .iter()
+            .map(|commit| commit.inner.clone())
+            .collect::<Vec<_>>();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.unwrap_or(0))
+            .build()
+            .into_py_result()?;
+        let format =
+            diff_options::DiffOptions::default().patch_format(&self.inner.to_thread_local())?;
+        let context = self.diff_context();
+        py.allow_threads(|| {
+            pool.install(|| {
+                commits
+                    .par_iter()
+                    .map_init(
+                        || {
+                            let repository = self.inner.to_thread_local();
+                            let resource_cache = diff::resource_cache(&repository);
+                            (repository, resource_cache)
+                        },
+                        |(repository, resource_cache), commit| {
+                            let commit = commit.clone().attach(&*repository).into_commit();
+                            let diff = context.diff(
+                                resource_cache,
+                                &commit,
+                                0,
+                                algorithm.into(),
+                                &format,
+                                &[],
+                            )?;
+                            Ok(diff.map(|diff| String::from_utf8_lossy(&diff).into_owned()))
+                        },
+                    )
+                    .collect()
+            })
+        })
+    }
+
+    /// The patches of `commits` like `diffs`, but as an iterator of
+    /// `(commit, patch)` pairs whose patches a background thread computes
+    /// while the caller is busy with the previous ones (e.g. running a model
+    /// on them), up to `lookahead` commits ahead.
+    #[pyo3(signature=(commits, algorithm=Algorithm::Myers, lookahead=8))]
+    pub fn prefetch_diffs(
+        &self,
+        py: Python<'_>,
+        commits: Vec<Py<Commit>>,
+        algorithm: Algorithm,
+        lookahead: usize,
+    ) -> PyResult<prefetch::DiffPrefetch> {
+        let format = unified_diff::PatchFormat {
+            funcnames: funcname::Drivers::new(&self.inner.to_thread_local()).map_err(py_err)?,
+            ..Default::default()
+        };
+        Ok(prefetch::prefetch_diffs(
+            py,
+            &self.inner,
+            self.diff_context(),
+            commits,
+            algorithm.into(),
+            format,
+            lookahead,
+        ))
+    }
+
+    /// The patch of the uncommitted changes in the working tree against
+    /// revision `rev`, like `git diff HEAD`, or `None` if there are none.
+    /// Before the first commit, everything is new. Files that aren't tracked
+    /// only show (as added) with `include_untracked`, unless they're ignored.
+    ///
+    /// Files are compared as they would be committed (e.g. with their line
+    /// endings converted by the `text` and `eol` attributes), and renames
+    /// are found as configured (`diff.renames`).
+    #[pyo3(signature=(rev="HEAD", include_untracked=false))]
+    pub fn diff_workdir(&self, rev: &str, include_untracked: bool) -> PyResult<Option<String>> {
+        Ok(self
+            .diff_workdir_bytes(rev, include_untracked)?
+            .map(|diff| String::from_utf8_lossy(&diff).into_owned()))
+    }
+
+    /// The same as `diff_workdir` but returns the patch as raw bytes so that
+    /// files which aren't valid UTF-8 still round-trip through `git apply`.
+    #[pyo3(signature=(rev="HEAD", include_untracked=false))]
+    pub fn diff_workdir_bytes(
+        &self,
+        rev: &str,
+        include_untracked: bool,
+    ) -> PyResult<Option<Cow<'static, [u8]>>> {
+        // The files of the working tree are written as blobs to diff them,
+        // which only needs them in memory.
+        let repository = self.inner.to_thread_local().with_object_memory();
+        let workdir = workdir::tree(&repository, include_untracked)?;
+        Ok(Self::diff_trees(
+            &repository,
+            &Self::rev_tree(&repository, rev)?,
+            &repository.find_tree(workdir).into_py_result()?,
+        )?
+        .map(Cow::Owned))
+    }
+
+    /// The patch of the changes staged in the index against revision `rev`,
+    /// like `git diff --cached`, or `None` if there are none. Before the
+    /// first commit, everything in the index is new.
+    ///
+    /// Renames are found as configured (`diff.renames`). Conflicted files
+    /// show as on our side (with `HEAD` that's no change) and files only
+    /// added with `git add --intent-to-add` aren't staged yet.
+    #[pyo3(signature=(rev="HEAD"))]
+    pub fn diff_staged(&self, rev: &str) -> PyResult<Option<String>> {
+        Ok(self
+            .diff_staged_bytes(rev)?
+            .map(|diff| String::from_utf8_lossy(&diff).into_owned()))
+    }
+
+    /// The same as `diff_staged` but returns the patch as raw bytes so that
+    /// files which aren't valid UTF-8 still round-trip through `git apply`.
+    #[pyo3(signature=(rev="HEAD"))]
+    pub fn diff_staged_bytes(&self, rev: &str) -> PyResult<Option<Cow<'static, [u8]>>> {
+        // The index has no tree of its own, it's only needed in memory.
+        let repository = self.inner.to_thread_local().with_object_memory();
+        let index = workdir::index_tree(&repository)?;
+        Ok(Self::diff_trees(
+            &repository,
+            &Self::rev_tree(&repository, rev)?,
+            &repository.find_tree(index).into_py_result()?,
+        )?
+        .map(Cow::Owned))
+    }
+
+    /// The patch between the trees of two revisions, like `git diff <old_ref>
+    /// <new_ref>`, or `None` if they're the same. Either can be anything
+    /// that has a tree (e.g. `v1.0`, `main~2`, or `HEAD:src`).
+    ///
+    /// Renames are found as configured (`diff.renames`).
+    pub fn diff_refs(&self, old_ref: &str, new_ref: &str) -> PyResult<Option<String>> {
+        Ok(self
+            .diff_refs_bytes(old_ref, new_ref)?
+            .map(|diff| String::from_utf8_lossy(&diff).into_owned()))
+    }
+
+    /// The same as `diff_refs` but returns the patch as raw bytes so that
+    /// files which aren't valid UTF-8 still round-trip through `git apply`.
+    pub fn diff_refs_bytes(
+        &self,
+        old_ref: &str,
+        new_ref: &str,
+    ) -> PyResult<Option<Cow<'static, [u8]>>> {
+        let repository = self.inner.to_thread_local();
+        Ok(Self::diff_trees(
+            &repository,
+            &Self::rev_tree(&repository, old_ref)?,
+            &Self::rev_tree(&repository, new_ref)?,
+        )?
+        .map(Cow::Owned))
+    }
+
+    /// Apply a patch like `git apply`: to the working tree (`to="worktree"`),
+    /// to the index (`to="index"`, like `git apply --cached`), or to the tree
+    /// of any other revision `to`. The patched tree is then written to the
+    /// object database (see `tree_id` of the result) and nothing else
+    /// changes.
+    ///
+    /// The patch is its text (`str` or `bytes`) or a `Patch`. Every hunk has
+    /// to match with all of its context, though not necessarily at the line
+    /// it says, and files have to exist (or not) as the patch expects. It's
+    /// all or nothing: if any of it doesn't apply nothing changes, and the
+    /// result has the conflicts. Binary files need the full IDs in the patch
+    /// (e.g. of `git diff --binary`).
+    ///
+    /// Raises `RepositoryLocked` if another git process holds the lock on the
+    /// index, waiting up to `wait_for_lock` seconds for it to be released.
+    #[pyo3(signature=(patch, to="worktree", wait_for_lock=None))]
+    pub fn apply(
+        &self,
+        py: Python<'_>,
+        patch: &Bound<'_, PyAny>,
+        to: &str,
+        wait_for_lock: Option<f64>,
+    ) -> PyResult<apply::ApplyResult> {
+        let files = match patch.downcast::<patch::Patch>() {
+            Ok(patch) => patch
+                .borrow()
+                .files
+                .iter()
+                .map(|file| file.clone_ref(py))
+                .collect(),
+            Err(_) => patch::Patch::parse(py, patch)?.files,
+        };
+        let repository = self.inner.to_thread_local();
+        apply::apply(py, &repository, &files, to, wait_for_lock)
+    }
+
+    /// The commits of `range` as a stream for `git fast-import`, like `git
+    /// fast-export <range>` writes it without any options: blobs, then each
+    /// commit with its author, committer, message, and changed files,
+    /// parents before their children. Refs given in the range name the
+    /// commits (e.g. `refs/heads/main` for `main`), annotated tags are
+    /// recreated.
+    ///
+    /// The range is revision specs separated by whitespace, `A..B` and
+    /// `^A` exclude what is reachable from `A`. Commits whose parents are
+    /// left out start from the branch as it is in the importing repository.
+    /// With `paths` only those files and directories are exported and
+    /// commits that don't change them are left out like with `git log --
+    /// <paths>`, refs and tags to those move to the commit they were
+    /// simplified to like with `--tag-of-filtered-object=rewrite`.
+    /// Messages are exported in the encoding they were written in, with an
+    /// `encoding` line if it isn't UTF-8, like with `--reencode=no`.
+    #[pyo3(signature=(range, paths=None))]
+    pub fn fast_export(
+        &self,
+        range: &str,
+        paths: Option<Vec<PathBuf>>,
+    ) -> PyResult<Cow<'static, [u8]>> {
+        let repository = self.inner.to_thread_local();
+        Ok(Cow::Owned(fast_export::fast_export(
+            &repository,
+            range,
+            &paths.unwrap_or_default(),
+        )?))
+    }
+
+    /// The commits in the range as an mbox of `git format-patch` emails (see
+    /// `Commit.format_patch`), oldest first and numbered like `[PATCH 2/5]`
+    /// if there is more than one. Merges and commits that change nothing are
+    /// left out, `None` if that leaves none.
+    #[pyo3(
+        signature=(
+            commit_start_cutoff=None,
+            commit_end_cutoff=None,
+            subject_prefix="PATCH",
+            algorithm=Algorithm::Myers,
+        )
+    )]
+    pub fn format_patch(
+        &self,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+        subject_prefix: &str,
+        algorithm: Algorithm,
+    ) -> PyResult<Option<String>> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        let repository = self.inner.to_thread_local();
+        // Like git, commits that change nothing still count towards the
+        // numbers of the others.
+        let commits = commits
+            .iter()
+            .rev()
+            .filter(|commit| commit.parent_ids.len() <= 1)
+            .collect::<Vec<_>>();
+        let total = commits.len();
+        let mut emails = Vec::new();
+        for (index, commit) in commits.into_iter().enumerate() {
+            let Some(changes) = email::changes(&repository, commit, algorithm.into())? else {
+                continue;
+            };
+            let number = (total > 1).then_some((index + 1, total));
+            emails.push(email::format_patch(
+                commit,
+                &changes,
+                number,
+                subject_prefix,
+            )?);
+        }
+        Ok((!emails.is_empty()).then(|| emails.join("\n")))
     }
 
     #[pyo3(
@@ -457,112 +2218,815 @@ impl Repository {
             commit_start_cutoff=None,
             commit_end_cutoff=None,
             cutoff_start_timestamp=None,
-            cutoff_end_timestamp=None
+            cutoff_end_timestamp=None,
+            timezone=None,
+            stable_order=false,
+            simplify_by_decoration=false,
+            paths=None,
+            rewrite_parents=false,
+            boundary=false,
+            annotate_calendar=None,
+            use_mailmap=false,
+            eager=false
         ))]
+    #[allow(clippy::too_many_arguments)]
     pub fn commits(
         &self,
+        py: Python<'_>,
         commit_start_cutoff: Option<&str>,
         commit_end_cutoff: Option<&str>,
         cutoff_start_timestamp: Option<&str>,
         cutoff_end_timestamp: Option<&str>,
+        timezone: Option<&str>,
+        stable_order: bool,
+        simplify_by_decoration: bool,
+        paths: Option<Vec<PathBuf>>,
+        rewrite_parents: bool,
+        boundary: bool,
+        annotate_calendar: Option<&str>,
+        use_mailmap: bool,
+        eager: bool,
     ) -> PyResult<Vec<Commit>> {
-        let commit_start_cutoff =
-            commit_start_cutoff.map(|cutoff| gix::ObjectId::from_str(cutoff).unwrap());
-        let commit_end_cutoff =
-            commit_end_cutoff.map(|cutoff| gix::ObjectId::from_str(cutoff).unwrap());
-        let cutoff_start_timestamp = cutoff_start_timestamp
-            .map(try_parse_start_timestamp)
-            .transpose()
+        self.walk_commits(
+            py,
+            walk::WalkOptions {
+                commit_start_cutoff,
+                commit_end_cutoff,
+                cutoff_start_timestamp,
+                cutoff_end_timestamp,
+                timezone,
+                stable_order,
+                simplify_by_decoration,
+                paths,
+                rewrite_parents,
+                boundary,
+                annotate_calendar,
+                use_mailmap,
+                eager,
+            },
+        )
+    }
+
+    /// The content of the file at `path` as of revision `rev` (see `git show
+    /// <rev>:<path>`).
+    pub fn show(&self, rev: &str, path: &str) -> PyResult<Cow<'static, [u8]>> {
+        let repository = self.inner.to_thread_local();
+        let tree_id = repository
+            .rev_parse_single(rev)
             .into_py_result()?
-            .map(|timestamp| {
-                timestamp
-                    .duration_since(jiff::Timestamp::UNIX_EPOCH)
-                    .as_secs()
-            });
-        let cutoff_end_timestamp = cutoff_end_timestamp
-            .map(try_parse_end_timestamp)
-            .transpose()
+            .object()
             .into_py_result()?
-            .map(|timestamp| {
-                timestamp
-                    .duration_since(jiff::Timestamp::UNIX_EPOCH)
-                    .as_secs()
-            });
+            .peel_to_tree()
+            .into_py_result()?
+            .id;
+        Ok(Cow::Owned(tree::show(&repository, tree_id, path)?))
+    }
 
+    /// Describe a commit (`HEAD` by default) relative to the closest tag in
+    /// its history like `git describe --tags --long`, `None` if there is no
+    /// tag to describe it with.
+    #[pyo3(signature=(revision="HEAD"))]
+    pub fn describe(&self, revision: &str) -> PyResult<Option<describe::Description>> {
+        let repository = self.inner.to_thread_local();
+        let id = repository
+            .rev_parse_single(revision)
+            .into_py_result()?
+            .object()
+            .into_py_result()?
+            .peel_to_commit()
+            .into_py_result()?
+            .id;
+        describe::describe(&repository, id)
+    }
+
+    /// The repository's mailmap (`.mailmap`, `mailmap.blob`, and
+    /// `mailmap.file`) for looking up canonical identities.
+    pub fn mailmap(&self) -> mailmap::Mailmap {
+        mailmap::Mailmap::load(&self.inner.to_thread_local())
+    }
+
+    /// The commits of several ranges, each a `(commit_start_cutoff,
+    /// commit_end_cutoff)` pair like for `commits`, from a single walk.
+    ///
+    /// Overlapping ranges (e.g. consecutive releases) don't walk the shared
+    /// history again, and commits in several ranges are the same object in
+    /// each result.
+    pub fn commits_multi(
+        &self,
+        py: Python<'_>,
+        ranges: Vec<(Option<String>, Option<String>)>,
+    ) -> PyResult<Vec<Vec<Py<Commit>>>> {
+        let cutoff = |cutoff: &Option<String>| {
+            cutoff
+                .as_deref()
+                .map(gix::ObjectId::from_str)
+                .transpose()
+                .into_py_result()
+        };
+        let ranges = ranges
+            .iter()
+            .map(|(start, end)| Ok((cutoff(start)?, cutoff(end)?)))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        // Ranges start at their end cutoff (or right away) and are done after
+        // their start cutoff.
+        let mut started = ranges
+            .iter()
+            .map(|(_, end)| end.is_none())
+            .collect::<Vec<_>>();
+        let mut done = vec![false; ranges.len()];
+        let mut results = ranges.iter().map(|_| Vec::new()).collect::<Vec<_>>();
         let repository = self.inner.to_thread_local();
         let target = repository
             .head()
             .unwrap()
             .peel_to_commit_in_place()
             .unwrap();
-        let commits = target
-            .ancestors()
-            .sorting(if let Some(cutoff) = cutoff_start_timestamp {
-                gix::revision::walk::Sorting::ByCommitTimeCutoff {
-                    order: gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
-                    seconds: cutoff,
-                }
-            } else {
-                gix::revision::walk::Sorting::ByCommitTime(
-                    gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
-                )
-            })
-            .all()
-            .unwrap()
-            .flatten()
-            .skip_while(move |info| {
-                if let Some(id_cutoff) = commit_end_cutoff {
-                    id_cutoff != info.id
-                } else {
-                    false
-                }
-            })
-            .skip_while(move |info| {
-                if let (Some(commit_time), Some(cutoff)) = (info.commit_time, cutoff_end_timestamp)
-                {
-                    commit_time > cutoff
-                } else {
-                    false
-                }
-            })
-            .scan(false, move |cutoff_seen, info| {
-                if *cutoff_seen {
-                    None
-                } else if Some(info.id) == commit_start_cutoff {
-                    *cutoff_seen = true;
-                    Some(info)
-                } else {
-                    Some(info)
+        let sorting = gix::revision::walk::Sorting::ByCommitTime(
+            gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+        );
+        for info in walk::walk(&repository, [target.id], [], sorting)? {
+            if done.iter().all(|done| *done) {
+                break;
+            }
+            let mut members = Vec::new();
+            for (index, (start, end)) in ranges.iter().enumerate() {
+                started[index] |= Some(info.id) == *end;
+                if started[index] && !done[index] {
+                    members.push(index);
+                    done[index] = Some(info.id) == *start;
                 }
+            }
+            if members.is_empty() {
+                continue;
+            }
+            let commit = Py::new(py, self.commit(info))?;
+            for index in members {
+                results[index].push(commit.clone_ref(py));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Gather ref counts, HEAD information, the latest tag, commit and recent
+    /// contributor counts, and the on-disk size in one call.
+    pub fn snapshot(&self) -> PyResult<snapshot::RepositorySnapshot> {
+        let repository = self.inner.to_thread_local();
+        snapshot::snapshot(&repository)
+    }
+
+    /// The files a commit changes against its first parent as `(path,
+    /// change_type, old_path)`, from the tree diff alone.
+    ///
+    /// `change_type` is one of `added`, `deleted`, `modified`, `renamed`, or
+    /// `copied` and `old_path` is only set for the latter two.
+    pub fn changed_files(&self, commit: &Commit) -> Vec<(String, &'static str, Option<String>)> {
+        let repository = self.inner.to_thread_local();
+        let commit = commit.inner.clone().attach(&repository).into_commit();
+        let mut changed = diff::changes_with_parent(&commit)
+            .into_iter()
+            .filter_map(|change| {
+                use gix::object::tree::diff::ChangeDetached;
+                let (path, change_type, old_path) = match change {
+                    ChangeDetached::Addition {
+                        location,
+                        entry_mode,
+                        ..
+                    } if !entry_mode.is_tree() => (location, "added", None),
+                    ChangeDetached::Deletion {
+                        location,
+                        entry_mode,
+                        ..
+                    } if !entry_mode.is_tree() => (location, "deleted", None),
+                    ChangeDetached::Modification {
+                        location,
+                        entry_mode,
+                        ..
+                    } if !entry_mode.is_tree() => (location, "modified", None),
+                    ChangeDetached::Rewrite {
+                        source_location,
+                        location,
+                        entry_mode,
+                        copy,
+                        ..
+                    } if !entry_mode.is_tree() => (
+                        location,
+                        if copy { "copied" } else { "renamed" },
+                        Some(source_location.to_string()),
+                    ),
+                    _ => return None,
+                };
+                Some((path.to_string(), change_type, old_path))
             })
-            .map(move |info| Commit::try_from(info).unwrap())
             .collect::<Vec<_>>();
+        // Like git, in path order.
+        changed.sort();
+        changed
+    }
+
+    /// The number of changed files, inserted lines, and deleted lines of a
+    /// commit against its first parent, without formatting its patch.
+    #[pyo3(signature=(commit, algorithm=Algorithm::Myers))]
+    pub fn stats(&self, commit: &Commit, algorithm: Algorithm) -> PyResult<stats::DiffStat> {
+        let repository = self.inner.to_thread_local();
+        let commit = commit.inner.clone().attach(&repository).into_commit();
+        stats::stats(&commit, algorithm.into())
+    }
 
-        Ok(commits)
+    /// The directories a commit changed most like `git diff --dirstat=lines`:
+    /// `(percentage, directory)` for every directory with at least `limit`
+    /// percent of the changed lines, directories with a trailing slash.
+    ///
+    /// Like git, a directory doesn't count the changes already reported for
+    /// its subdirectories unless `cumulative`, and binary files count a line
+    /// for every 64 bytes. See `diff` for the other options.
+    #[pyo3(
+        signature=(
+            commit,
+            algorithm=Algorithm::Myers,
+            parent=0,
+            paths=None,
+            ignore_whitespace=None,
+            rename_threshold=None,
+            find_copies=false,
+            find_copies_harder=false,
+            rename_limit=None,
+            limit=3.0,
+            cumulative=false,
+            textconv=None
+        ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn dirstat(
+        &self,
+        py: Python<'_>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        parent: usize,
+        paths: Option<Vec<String>>,
+        ignore_whitespace: Option<&str>,
+        rename_threshold: Option<u8>,
+        find_copies: bool,
+        find_copies_harder: bool,
+        rename_limit: Option<usize>,
+        limit: f64,
+        cumulative: bool,
+        textconv: Option<std::collections::HashMap<String, Py<PyAny>>>,
+    ) -> PyResult<Vec<(f64, String)>> {
+        let files = self.file_stats(
+            py,
+            commit,
+            algorithm,
+            diff_options::DiffOptions {
+                parent,
+                paths,
+                ignore_whitespace,
+                rename_threshold,
+                find_copies,
+                find_copies_harder,
+                rename_limit,
+                textconv,
+                ..Default::default()
+            },
+        )?;
+        Ok(stats::dirstat(&files, limit, cumulative))
+    }
+
+    /// The same patch as `diff_bytes` split per file and delivered in pages of
+    /// `page_size_files` files, each page is only diffed once it is iterated
+    /// to so the first files of a huge commit are available right away.
+    /// `Line.moved` only knows of lines moved within the same page.
+    #[pyo3(
+        signature=(
+            commit,
+            algorithm=Algorithm::Myers,
+            page_size_files=100,
+            context_lines=3,
+            inter_hunk_context=0,
+            ignore_whitespace=None,
+            rename_threshold=None,
+            find_copies=false,
+            find_copies_harder=false,
+            rename_limit=None,
+            max_file_size=None,
+            textconv=None
+        ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn diff_paged(
+        &self,
+        commit: &Commit,
+        algorithm: Algorithm,
+        page_size_files: usize,
+        context_lines: u32,
+        inter_hunk_context: u32,
+        ignore_whitespace: Option<&str>,
+        rename_threshold: Option<u8>,
+        find_copies: bool,
+        find_copies_harder: bool,
+        rename_limit: Option<usize>,
+        max_file_size: Option<u64>,
+        textconv: Option<std::collections::HashMap<String, Py<PyAny>>>,
+    ) -> PyResult<paged::DiffPages> {
+        let format = diff_options::DiffOptions {
+            context_lines,
+            inter_hunk_context,
+            ignore_whitespace,
+            rename_threshold,
+            find_copies,
+            find_copies_harder,
+            rename_limit,
+            max_file_size,
+            textconv,
+            ..Default::default()
+        }
+        .structured_format(&self.inner.to_thread_local())?;
+        Ok(paged::diff_paged(
+            &self.inner,
+            commit,
+            algorithm.into(),
+            format,
+            page_size_files,
+        ))
     }
 
-    pub fn first_commit(&self) -> PyResult<Commit> {
+    /// The combined diff of a merge against all of its parents, like `git
+    /// diff --cc` or, if not `dense`, `git diff -c`. Only files that differ
+    /// from every parent show up, and with `dense` only the hunks where the
+    /// merge didn't just take one side, e.g. conflict resolutions.
+    ///
+    /// For commits with a single parent (or none) this is the same as `diff`.
+    #[pyo3(signature=(commit, algorithm=Algorithm::Myers, dense=true))]
+    pub fn diff_combined(
+        &self,
+        commit: &Commit,
+        algorithm: Algorithm,
+        dense: bool,
+    ) -> PyResult<Option<String>> {
         let repository = self.inner.to_thread_local();
-        let target = repository
-            .head()
-            .unwrap()
-            .peel_to_commit_in_place()
-            .unwrap();
+        let commit = commit.inner.clone().attach(&repository).into_commit();
+        let diff = if commit.parent_ids().count() > 1 {
+            combined::diff_combined(&commit, algorithm.into(), dense)?
+        } else {
+            Repository::diff_with_parent(&commit, algorithm.into(), &Default::default())?
+        };
+        Ok(diff.map(|diff| String::from_utf8_lossy(&diff).into_owned()))
+    }
+
+    /// The patch of `diff` with the changes shown by words inline rather than
+    /// by lines, like `git diff --word-diff=plain`: removed words as
+    /// `[-...-]` and added ones as `{+...+}`. `Hunk.words` has the same
+    /// changes as a list.
+    #[pyo3(
+        signature=(
+            commit,
+            algorithm=Algorithm::Myers,
+            parent=0,
+            paths=None,
+            context_lines=3
+        ))]
+    pub fn diff_words(
+        &self,
+        py: Python<'_>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        parent: usize,
+        paths: Option<Vec<String>>,
+        context_lines: u32,
+    ) -> PyResult<Option<String>> {
+        Ok(self
+            .patch(
+                py,
+                commit,
+                algorithm,
+                diff_options::DiffOptions {
+                    parent,
+                    paths,
+                    context_lines,
+                    ..Default::default()
+                },
+            )?
+            .map(|diff| String::from_utf8_lossy(&word_diff::word_diff(&diff)).into_owned()))
+    }
+
+    /// The changes of the commit against its first parent (or the one at
+    /// index `parent`, see `diff`) as `FileDiff`s with their hunks and lines,
+    /// instead of one patch to parse. `paths` restricts them to files and
+    /// `textconv` converts them to text as in `diff`.
+    #[pyo3(
+        signature=(
+            commit,
+            algorithm=Algorithm::Myers,
+            parent=0,
+            context_lines=3,
+            inter_hunk_context=0,
+            ignore_whitespace=None,
+            rename_threshold=None,
+            find_copies=false,
+            find_copies_harder=false,
+            rename_limit=None,
+            max_file_size=None,
+            paths=None,
+            textconv=None
+        ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn diff_structured(
+        &self,
+        py: Python<'_>,
+        commit: &Commit,
+        algorithm: Algorithm,
+        parent: usize,
+        context_lines: u32,
+        inter_hunk_context: u32,
+        ignore_whitespace: Option<&str>,
+        rename_threshold: Option<u8>,
+        find_copies: bool,
+        find_copies_harder: bool,
+        rename_limit: Option<usize>,
+        max_file_size: Option<u64>,
+        paths: Option<Vec<String>>,
+        textconv: Option<std::collections::HashMap<String, Py<PyAny>>>,
+    ) -> PyResult<Vec<paged::FileDiff>> {
+        self.structured_files(
+            py,
+            commit,
+            algorithm,
+            diff_options::DiffOptions {
+                parent,
+                paths,
+                context_lines,
+                inter_hunk_context,
+                ignore_whitespace,
+                rename_threshold,
+                find_copies,
+                find_copies_harder,
+                rename_limit,
+                max_file_size,
+                textconv,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Candidates for `SOURCE_DATE_EPOCH` from the commits in the range, see
+    /// `SourceDateEpoch`.
+    #[pyo3(signature=(commit_start_cutoff=None, commit_end_cutoff=None))]
+    pub fn source_date_epoch(
+        &self,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+    ) -> PyResult<epoch::SourceDateEpoch> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        let repository = self.inner.to_thread_local();
+        epoch::source_date_epoch(&repository, &commits)
+    }
+
+    /// Run the registered native checks (all or those named in `checks`) on
+    /// the commits in the range in parallel, returning the violations by
+    /// commit ID for the commits that have any.
+    #[pyo3(signature=(commit_start_cutoff=None, commit_end_cutoff=None, checks=None))]
+    pub fn run_checks(
+        &self,
+        py: Python<'_>,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+        checks: Option<Vec<String>>,
+    ) -> PyResult<std::collections::HashMap<String, Vec<String>>> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        py.allow_threads(|| checks::run_checks(&self.inner, &commits, checks.as_deref()))
+    }
+
+    /// Which of the registered native checks `run_checks` would run on
+    /// `commit` with the selection `checks`, why the others wouldn't, and
+    /// the settings of each. Nothing is actually checked.
+    ///
+    /// With `configuration` (e.g. a `gitch.configuration.Schema`) the checks
+    /// `gitch check` runs by it follow, named by their settings (e.g.
+    /// `summary.first-word-capitalization` or `trailers.Signed-off-by`).
+    /// `checks` only selects among the registered ones.
+    ///
+    /// Reasons start with their kind: `not selected`, `suppressed` (e.g.
+    /// before `starting-from`), `path-filtered` (for checks registered with
+    /// `paths`), `off in the configuration`, or `only checks the first
+    /// commit`.
+    #[pyo3(signature=(commit, checks=None, configuration=None))]
+    pub fn explain_checks(
+        &self,
+        commit: &Commit,
+        checks: Option<Vec<String>>,
+        configuration: Option<checks::Configuration<'_>>,
+    ) -> PyResult<Vec<checks::CheckExplanation>> {
+        let repository = self.inner.to_thread_local();
+        let configuration = configuration
+            .map(|configuration| configuration.resolve(&repository))
+            .transpose()?;
+        checks::explain_checks(
+            &repository,
+            commit,
+            checks.as_deref(),
+            configuration.as_ref(),
+        )
+    }
+
+    /// The commits in the range without those that a merge in the range
+    /// already represents, see `changelog::dedupe_for_changelog`.
+    #[pyo3(signature=(commit_start_cutoff=None, commit_end_cutoff=None))]
+    pub fn dedupe_for_changelog(
+        &self,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+    ) -> PyResult<Vec<Commit>> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        let repository = self.inner.to_thread_local();
+        changelog::dedupe_for_changelog(&repository, commits)
+    }
+
+    /// How closely the messages of the commits in the range follow the
+    /// sections of the commit template at `template_path`, per month (see
+    /// `template::template_drift`).
+    #[pyo3(signature=(commit_start_cutoff=None, commit_end_cutoff=None, template_path=PathBuf::from(".gitmessage")))]
+    pub fn template_drift(
+        &self,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+        template_path: PathBuf,
+    ) -> PyResult<Vec<template::TemplateCompliance>> {
+        let template = template::read(&self.inner.to_thread_local(), &template_path)?;
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        Ok(template::template_drift(&template, &commits))
+    }
+
+    /// Pairs of blobs of at least `min_size` bytes anywhere in the object
+    /// database that share at least `threshold` of their content, most
+    /// similar first (see `similar::similar_blobs`).
+    #[pyo3(signature=(threshold=0.8, min_size=1024 * 1024))]
+    pub fn similar_blobs(
+        &self,
+        py: Python<'_>,
+        threshold: f64,
+        min_size: u64,
+    ) -> PyResult<Vec<similar::SimilarBlobs>> {
+        py.allow_threads(|| similar::similar_blobs(&self.inner, threshold, min_size))
+    }
+
+    /// Summarize the objects that aren't reachable from any ref, HEAD, or the
+    /// index, nor from the reflogs unless `reflogs=False`.
+    #[pyo3(signature=(reflogs=true))]
+    pub fn unreachable_report(&self, reflogs: bool) -> PyResult<unreachable::UnreachableReport> {
+        let repository = self.inner.to_thread_local();
+        unreachable::unreachable_report(&repository, reflogs)
+    }
+
+    /// When a ref (e.g. a release tag) was created, falling back from its
+    /// reflog to the tagger time and finally to the time of the commit it
+    /// points to.
+    pub fn ref_created_at(&self, name: &str) -> PyResult<Option<jiff::Zoned>> {
+        let repository = self.inner.to_thread_local();
+        reflog::ref_created_at(&repository, name)
+    }
+
+    /// Where the lines of `path` at HEAD (or those of `line_range`, 1-based
+    /// and inclusive) came from, following moves and copies between files
+    /// like `git blame -C -C`.
+    #[pyo3(signature=(path, line_range=None))]
+    pub fn code_provenance(
+        &self,
+        path: &str,
+        line_range: Option<(usize, usize)>,
+    ) -> PyResult<Vec<provenance::LineProvenance>> {
+        let repository = self.inner.to_thread_local();
+        provenance::code_provenance(&repository, path, line_range)
+    }
+
+    /// The local branches that share no merge base with any of the
+    /// `protected` branches (see `orphans::orphaned_branches`).
+    #[pyo3(signature=(protected=vec!["main".to_string()]))]
+    pub fn orphaned_branches(&self, protected: Vec<String>) -> PyResult<Vec<String>> {
+        let repository = self.inner.to_thread_local();
+        orphans::orphaned_branches(&repository, &protected)
+    }
+
+    /// The local (or, if `remote`, remote-tracking) branches that contain
+    /// `commit`, any revision resolving to one (see
+    /// `contains::branches_containing`).
+    #[pyo3(signature=(commit, remote=false))]
+    pub fn branches_containing(&self, commit: &str, remote: bool) -> PyResult<Vec<String>> {
+        let repository = self.inner.to_thread_local();
+        let id = repository
+            .rev_parse_single(commit)
+            .into_py_result()?
+            .object()
+            .into_py_result()?
+            .peel_to_commit()
+            .into_py_result()?
+            .id;
+        contains::branches_containing(&repository, id, remote)
+    }
+
+    /// Visit the changes of a commit against its first parent, calling
+    /// `visitor.on_file(change_info)` for every changed file and, if the
+    /// visitor has it, `visitor.on_hunk(hunk)` for every hunk of it.
+    ///
+    /// Either callback can return `False` to stop the visit early, which skips
+    /// diffing the remaining files. Returns whether all changes were visited.
+    #[pyo3(signature=(commit, visitor, algorithm=Algorithm::Myers))]
+    pub fn visit_deltas(
+        &self,
+        commit: &Commit,
+        visitor: &Bound<'_, PyAny>,
+        algorithm: Algorithm,
+    ) -> PyResult<bool> {
+        let repository = self.inner.to_thread_local();
+        visit::visit_deltas(&repository, commit, visitor, algorithm.into())
+    }
 
-        let commit = target
-            .ancestors()
-            .sorting(gix::revision::walk::Sorting::ByCommitTime(
+    /// Walk the commits reachable from any of the given tips, newest first.
+    ///
+    /// Tips are revision specs (commit IDs, branches, tags, ...), those
+    /// prefixed with `^` exclude the commits reachable from them like in
+    /// `git rev-list`.
+    #[pyo3(signature=(ids, stable_order=false))]
+    pub fn walk_from(&self, ids: Vec<String>, stable_order: bool) -> PyResult<Vec<Commit>> {
+        let repository = self.inner.to_thread_local();
+        let (include, exclude) = walk::resolve_tips(&repository, &ids)?;
+        let commits = walk::walk(
+            &repository,
+            include,
+            exclude,
+            gix::revision::walk::Sorting::ByCommitTime(
                 gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
-            ))
-            .all()
-            .unwrap()
-            .last()
-            .unwrap()
-            .unwrap()
-            .try_into()
-            .unwrap();
+            ),
+        )?
+        .collect::<Vec<_>>();
+        let commits = if stable_order {
+            break_commit_time_ties(commits)
+        } else {
+            commits
+        };
 
-        Ok(commit)
+        Ok(commits.into_iter().map(|info| self.commit(info)).collect())
+    }
+
+    /// Write the commits in the given range together with their patches to a
+    /// compressed bundle for offline review (see `ReviewBundle`).
+    #[pyo3(
+        signature=(
+            out_path,
+            commit_start_cutoff=None,
+            commit_end_cutoff=None,
+            algorithm=Algorithm::Myers
+        ))]
+    pub fn review_bundle(
+        &self,
+        out_path: PathBuf,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+        algorithm: Algorithm,
+    ) -> PyResult<()> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        let repository = self.inner.to_thread_local();
+        bundle::write(&repository, &commits, algorithm.into(), &out_path)
+    }
+
+    /// Export the commits of `range` to a new bare repository at `out_path`
+    /// with file contents, file names, messages, and identities replaced by
+    /// hashes.
+    ///
+    /// Tree shapes, line structure, message structure (including trailer
+    /// tokens), timestamps, and parents are preserved so the export can be
+    /// shared to reproduce bugs without leaking source. The range is revision
+    /// specs like for `fast_export`. The hashes are keyed by a random salt of
+    /// the export, or by `salt` to get the same export every time. Returns
+    /// the ID of the exported HEAD commit, if any.
+    #[pyo3(signature=(range, out_path, salt=None))]
+    pub fn anonymize_export(
+        &self,
+        range: &str,
+        out_path: PathBuf,
+        salt: Option<&[u8]>,
+    ) -> PyResult<Option<String>> {
+        let repository = self.inner.to_thread_local();
+        anonymize::export(&repository, range, &out_path, salt)
+    }
+
+    /// Plan a message-only rewrite of the commits in the given range.
+    #[pyo3(signature=(rules, commit_start_cutoff=None, commit_end_cutoff=None))]
+    pub fn rewrite_plan(
+        &self,
+        py: Python<'_>,
+        rules: &rewrite::RewriteRules,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+    ) -> PyResult<rewrite::RewritePlan> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        let repository = self.inner.to_thread_local();
+        rewrite::plan(py, &repository, &commits, rules)
+    }
+
+    /// Execute a rewrite plan returning the mapping of old to new commit IDs.
+    ///
+    /// The mapping is also remembered for `map_old_to_new` and, if a path is
+    /// given, exported so it can be loaded again with `load_oid_map`.
+    ///
+    /// Raises `RepositoryLocked` if another git process holds a lock on the
+    /// refs, waiting up to `wait_for_lock` seconds for it to be released.
+    #[pyo3(signature=(plan, oid_map_path=None, wait_for_lock=None))]
+    pub fn execute_rewrite(
+        &mut self,
+        py: Python<'_>,
+        plan: &rewrite::RewritePlan,
+        oid_map_path: Option<PathBuf>,
+        wait_for_lock: Option<f64>,
+    ) -> PyResult<std::collections::HashMap<String, String>> {
+        let repository = lock::wait_for_lock(self.inner.to_thread_local(), wait_for_lock)?;
+        let mapping = rewrite::execute(py, &repository, plan)?;
+        if let Some(path) = oid_map_path {
+            oid_map::OidMap::write(&path, &mapping).into_py_result()?;
+        }
+        self.oid_map.extend(&mapping);
+        Ok(mapping
+            .into_iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect())
+    }
+
+    /// Add deployment records, pairs of a deployed commit ID and the
+    /// (timezone-aware) `datetime` it was deployed at, for `lead_time` and
+    /// `deployment_frequency`. Returns the number of records added.
+    pub fn ingest_deployments(
+        &mut self,
+        records: Vec<(String, jiff::Timestamp)>,
+    ) -> PyResult<usize> {
+        self.deployments.ingest(records)
+    }
+
+    /// The time from commit to first deployment of the commits in the range.
+    ///
+    /// A commit counts as deployed by the earliest deployment of any of its
+    /// descendants.
+    #[pyo3(signature=(commit_start_cutoff=None, commit_end_cutoff=None))]
+    pub fn lead_time(
+        &self,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+    ) -> PyResult<deployments::LeadTime> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        let repository = self.inner.to_thread_local();
+        deployments::lead_time(&repository, &self.deployments, &commits)
+    }
+
+    /// The number of deployments per day that shipped commits of the range.
+    #[pyo3(signature=(commit_start_cutoff=None, commit_end_cutoff=None))]
+    pub fn deployment_frequency(
+        &self,
+        commit_start_cutoff: Option<&str>,
+        commit_end_cutoff: Option<&str>,
+    ) -> PyResult<f64> {
+        let commits = self.commits_in_range(commit_start_cutoff, commit_end_cutoff)?;
+        let repository = self.inner.to_thread_local();
+        deployments::deployment_frequency(&repository, &self.deployments, &commits)
+    }
+
+    /// Load a mapping of rewritten commit IDs (`<old> <new>` per line) as
+    /// written by `execute_rewrite`, `git filter-repo`, or a `post-rewrite`
+    /// hook, returning the number of entries loaded.
+    pub fn load_oid_map(&mut self, path: PathBuf) -> PyResult<usize> {
+        self.oid_map.load(&path).into_py_result()
+    }
+
+    /// Translate a commit ID from before a rewrite to its current ID.
+    ///
+    /// IDs that were never rewritten are returned unchanged, commits that
+    /// were dropped by the rewrite map to `None`.
+    pub fn map_old_to_new(&self, oid: &str) -> PyResult<Option<String>> {
+        let id = gix::ObjectId::from_hex(oid.as_bytes()).into_py_result()?;
+        Ok(match self.oid_map.map(id) {
+            Some(new) if new.is_null() => None,
+            Some(new) => Some(new.to_string()),
+            None => Some(id.to_string()),
+        })
+    }
+
+    pub fn first_commit(&self, py: Python<'_>) -> PyResult<Commit> {
+        py.allow_threads(|| {
+            let repository = self.inner.to_thread_local();
+            let target = repository
+                .head()
+                .unwrap()
+                .peel_to_commit_in_place()
+                .unwrap();
+
+            let info = target
+                .ancestors()
+                .sorting(gix::revision::walk::Sorting::ByCommitTime(
+                    gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+                ))
+                .all()
+                .unwrap()
+                .last()
+                .unwrap()
+                .unwrap();
+
+            Ok(self.commit(info))
+        })
     }
 }
 
@@ -573,5 +3037,272 @@ fn gitch_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Commit>()?;
     m.add_class::<Signature>()?;
     m.add_class::<Algorithm>()?;
+    m.add_class::<bench::BenchResult>()?;
+    m.add_class::<bundle::ReviewBundle>()?;
+    m.add_class::<compare::CommitComparison>()?;
+    m.add_class::<visit::ChangeInfo>()?;
+    m.add_class::<visit::Hunk>()?;
+    m.add_class::<visit::Line>()?;
+    m.add_class::<word_diff::Word>()?;
+    m.add_class::<snapshot::RepositorySnapshot>()?;
+    m.add_class::<tree::TreeEntry>()?;
+    m.add_class::<text::Message>()?;
+    m.add_class::<gpgsig::CommitSignature>()?;
+    m.add_class::<gpgsig::SignatureVerification>()?;
+    m.add_class::<epoch::SourceDateEpoch>()?;
+    m.add_class::<unreachable::UnreachableReport>()?;
+    m.add_class::<deployments::LeadTime>()?;
+    m.add_class::<paged::DiffPages>()?;
+    m.add_class::<paged::FileDiff>()?;
+    m.add_class::<prefetch::DiffPrefetch>()?;
+    m.add_class::<patch::Patch>()?;
+    m.add_class::<apply::ApplyResult>()?;
+    m.add_class::<apply::ApplyConflict>()?;
+    m.add_class::<provenance::LineProvenance>()?;
+    m.add_class::<stats::DiffStat>()?;
+    m.add_class::<calendar::CalendarDay>()?;
+    m.add_class::<conventional::ConventionalCommit>()?;
+    m.add_class::<checks::CheckExplanation>()?;
+    m.add_class::<similar::SimilarBlobs>()?;
+    m.add_class::<gitmoji::Gitmoji>()?;
+    m.add_class::<template::TemplateCompliance>()?;
+    m.add_class::<references::Reference>()?;
+    m.add_class::<mailmap::Mailmap>()?;
+    m.add_class::<describe::Description>()?;
+    m.add_class::<health::Preflight>()?;
+    m.add_class::<rewrite::Capitalization>()?;
+    m.add_class::<rewrite::RewriteRules>()?;
+    m.add_class::<rewrite::RewriteEntry>()?;
+    m.add_class::<rewrite::RewritePlan>()?;
+    m.add_function(wrap_pyfunction!(bench::bench, m)?)?;
+    m.add_function(wrap_pyfunction!(compare::compare_commits, m)?)?;
+    m.add_function(wrap_pyfunction!(text::parse_message, m)?)?;
+    m.add_function(wrap_pyfunction!(checks::register_native_check, m)?)?;
+    m.add_function(wrap_pyfunction!(checks::registered_checks, m)?)?;
+    m.add_function(wrap_pyfunction!(text::diff_text, m)?)?;
+    m.add_function(wrap_pyfunction!(text::validate_trailers, m)?)?;
+    m.add(
+        "RepositoryLocked",
+        m.py().get_type::<lock::RepositoryLocked>(),
+    )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Commit, Repository, Signature, compare, email, stats};
+
+    use std::io::Write;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::Arc;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn commit_is_send_and_sync() {
+        assert_send_sync::<Commit>();
+        assert_send_sync::<Signature>();
+    }
+
+    #[test]
+    fn commit_can_be_shared_across_threads() {
+        let data = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+            author Author <author@example.com> 1700000000 +0100\n\
+            committer Committer <committer@example.com> 1700000000 -0200\n\
+            \n\
+            Add the thing\n\
+            \n\
+            Some details.\n\
+            \n\
+            Commit-type: feature\n";
+        let commit = Arc::new(
+            Commit::try_from(gix::ObjectDetached {
+                id: gix::ObjectId::from_hex(b"0123456789abcdef0123456789abcdef01234567").unwrap(),
+                kind: gix::object::Kind::Commit,
+                data: data.to_vec(),
+            })
+            .unwrap(),
+        );
+
+        let threads = (0..8)
+            .map(|_| {
+                let commit = Arc::clone(&commit);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        assert_eq!(commit.summary(), "Add the thing");
+                        assert_eq!(commit.body(), Some("Some details."));
+                        assert!(commit.trailers()["Commit-type"].contains("feature"));
+                        assert_eq!(commit.author.utc_offset, 3600);
+                        assert_eq!(commit.committer.utc_offset, -7200);
+                        assert!(commit.raw_message().unwrap().starts_with(b"Add the thing"));
+                        assert!(!commit.is_merge());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    /// The output of git run in `directory` with `input` on its standard
+    /// input, without any configuration of the machine.
+    pub(crate) fn git(directory: &Path, args: &[&str], input: &[u8]) -> Vec<u8> {
+        let mut child = Command::new("git")
+            .args(args)
+            .current_dir(directory)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_AUTHOR_NAME", "Author")
+            .env("GIT_AUTHOR_EMAIL", "author@example.com")
+            .env("GIT_AUTHOR_DATE", "1700000000 +0100")
+            .env("GIT_COMMITTER_NAME", "Committer")
+            .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+            .env("GIT_COMMITTER_DATE", "1700000000 +0100")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("could not run git");
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr),
+        );
+        output.stdout
+    }
+
+    /// A repository whose last commit renames a file to a path before the
+    /// other files it changes, one of them binary.
+    fn repository_with_rename() -> (tempfile::TempDir, gix::Repository) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let lines = (1..=20).map(|n| format!("line {n}\n")).collect::<String>();
+        git(path, &["init", "-q"], b"");
+        std::fs::write(path.join("b.txt"), &lines).unwrap();
+        std::fs::write(path.join("m.bin"), b"\0binary\0").unwrap();
+        std::fs::write(path.join("z.txt"), "first\n").unwrap();
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "Add the files"], b"");
+
+        std::fs::remove_file(path.join("b.txt")).unwrap();
+        std::fs::write(path.join("a.txt"), lines.replace("line 10\n", "ten\n")).unwrap();
+        std::fs::write(path.join("m.bin"), b"\0changed\0").unwrap();
+        std::fs::write(path.join("z.txt"), "first\nsecond\n").unwrap();
+        git(path, &["add", "-A"], b"");
+        git(path, &["commit", "-q", "-m", "Rename a file"], b"");
+
+        let repository = gix::open(path).unwrap();
+        (directory, repository)
+    }
+
+    #[test]
+    fn patch_id_of_rename_matches_git() {
+        let (directory, repository) = repository_with_rename();
+        let commit = repository.head_commit().unwrap();
+        let patch = Repository::diff_with_parent(
+            &commit,
+            gix::diff::blob::Algorithm::Myers,
+            &Default::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        let shown = git(directory.path(), &["show", "-M", "HEAD"], b"");
+        let expected = git(directory.path(), &["patch-id", "--stable"], &shown);
+        let expected = String::from_utf8(expected).unwrap();
+        assert_eq!(
+            compare::stable_patch_id(&patch).unwrap().to_string(),
+            expected.split_whitespace().next().unwrap(),
+            "patch:\n{}",
+            String::from_utf8_lossy(&patch),
+        );
+        // The stable ID doesn't depend on the order of the files, the plain
+        // one of `compare_commits` does.
+        let headers = |patch: &[u8]| {
+            patch
+                .split(|&byte| byte == b'\n')
+                .filter(|line| line.starts_with(b"diff --git "))
+                .map(<[u8]>::to_vec)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(headers(&patch), headers(&shown));
+    }
+
+    /// The patch of the last commit of `repository` and its files.
+    fn head_files(repository: &gix::Repository) -> Vec<stats::FileStat> {
+        let commit = repository.head_commit().unwrap();
+        let patch = Repository::diff_with_parent(
+            &commit,
+            gix::diff::blob::Algorithm::Myers,
+            &Default::default(),
+        )
+        .unwrap()
+        .unwrap();
+        Repository::count_files(&commit, 0, &patch).unwrap()
+    }
+
+    #[test]
+    fn stat_of_rename_matches_git() {
+        let (directory, repository) = repository_with_rename();
+        let expected = git(
+            directory.path(),
+            &["diff", "-M", "--stat=80", "HEAD~", "HEAD"],
+            b"",
+        );
+        assert_eq!(
+            stats::stat(&head_files(&repository), 80).unwrap(),
+            String::from_utf8(expected).unwrap(),
+        );
+    }
+
+    #[test]
+    fn numstat_of_rename_matches_git() {
+        let (directory, repository) = repository_with_rename();
+        let expected = git(
+            directory.path(),
+            &["diff", "-M", "--numstat", "HEAD~", "HEAD"],
+            b"",
+        );
+        let count = |count: stats::Count| match count {
+            stats::Count::Lines(lines) => lines.to_string(),
+            stats::Count::Binary(binary) => binary.to_string(),
+        };
+        let numstat = stats::numstat(head_files(&repository))
+            .into_iter()
+            .map(|(insertions, deletions, name)| {
+                format!("{}\t{}\t{name}\n", count(insertions), count(deletions))
+            })
+            .collect::<String>();
+        assert_eq!(numstat, String::from_utf8(expected).unwrap());
+    }
+
+    #[test]
+    fn format_patch_diffstat_of_rename_matches_git() {
+        let (directory, repository) = repository_with_rename();
+        let id = repository.head_id().unwrap();
+        let commit = Commit::try_from(repository.find_object(id).unwrap().detach()).unwrap();
+        let changes = email::changes(&repository, &commit, gix::diff::blob::Algorithm::Myers)
+            .unwrap()
+            .unwrap();
+        let email = email::format_patch(&commit, &changes, None, "PATCH").unwrap();
+        let expected = git(
+            directory.path(),
+            &["format-patch", "-M", "-1", "--stdout", "HEAD"],
+            b"",
+        );
+        // From the `---` after the message to the patch.
+        let diffstat = |email: &str| {
+            let (_, rest) = email.split_once("\n---\n").unwrap();
+            rest[..rest.find("diff --git ").unwrap()].to_owned()
+        };
+        assert_eq!(
+            diffstat(&email),
+            diffstat(&String::from_utf8(expected).unwrap())
+        );
+    }
+}
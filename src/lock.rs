@@ -0,0 +1,82 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+use std::time::Duration;
+
+create_exception!(
+    gitch_core,
+    RepositoryLocked,
+    PyOSError,
+    "Raised when a lock on refs or the index is held by another git process."
+);
+
+/// Configure `repository` to wait up to `wait_for_lock` seconds for locks on
+/// refs (and packed refs) held by other processes, retrying with backoff.
+///
+/// Without waiting git's defaults apply: 100ms for loose refs and 1s for
+/// packed refs.
+pub(crate) fn wait_for_lock(
+    mut repository: gix::Repository,
+    wait_for_lock: Option<f64>,
+) -> PyResult<gix::Repository> {
+    let Some(seconds) = wait_for_lock else {
+        return Ok(repository);
+    };
+    let milliseconds = seconds_to_wait(seconds)?.as_millis().to_string();
+    let mut config = repository.config_snapshot_mut();
+    for key in [
+        &gix::config::tree::Core::FILES_REF_LOCK_TIMEOUT,
+        &gix::config::tree::Core::PACKED_REFS_TIMEOUT,
+    ] {
+        config
+            .set_value(key, milliseconds.as_str())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    }
+    config
+        .commit()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(repository)
+}
+
+/// Lock the index of `repository` to write it, waiting up to `wait_for_lock`
+/// seconds for a lock held by another process. Without waiting it fails
+/// right away like git does.
+pub(crate) fn lock_index(
+    repository: &gix::Repository,
+    wait_for_lock: Option<f64>,
+) -> PyResult<gix::lock::File> {
+    let fail = match wait_for_lock {
+        Some(seconds) => {
+            gix::lock::acquire::Fail::AfterDurationWithBackoff(seconds_to_wait(seconds)?)
+        }
+        None => gix::lock::acquire::Fail::Immediately,
+    };
+    gix::lock::File::acquire_to_update_resource(repository.index_path(), fail, None)
+        .map_err(into_py_err)
+}
+
+fn seconds_to_wait(seconds: f64) -> PyResult<Duration> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "wait_for_lock must be a non-negative number of seconds, got {seconds}"
+        )));
+    }
+    Ok(Duration::from_millis((seconds * 1000.0).round() as u64))
+}
+
+/// Convert an error into `RepositoryLocked` if it was caused by failing to
+/// acquire a lock, otherwise into an `OSError` like `IntoPyResult` does.
+pub(crate) fn into_py_err(error: impl std::error::Error + 'static) -> PyErr {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&error);
+    while let Some(cause) = source {
+        if cause.is::<gix::lock::acquire::Error>() {
+            return RepositoryLocked::new_err(format!(
+                "{error}, another git process seems to be running: retry later or pass \
+                 `wait_for_lock` to wait for it"
+            ));
+        }
+        source = cause.source();
+    }
+    PyOSError::new_err(format!("{error}"))
+}
@@ -0,0 +1,22 @@
+use pyo3::prelude::*;
+
+/// A parsed `.mailmap` (see `gitmailmap(5)`) mapping the identities used in
+/// commits to canonical ones.
+#[pyclass(frozen)]
+#[derive(Default)]
+pub struct Mailmap(gitch_engine::mailmap::Mailmap);
+
+impl Mailmap {
+    /// See `gitch_engine::mailmap::Mailmap::load`.
+    pub(crate) fn load(repository: &gix::Repository) -> Self {
+        Self(gitch_engine::mailmap::Mailmap::load(repository))
+    }
+}
+
+#[pymethods]
+impl Mailmap {
+    /// The canonical name and email of an identity.
+    pub fn resolve(&self, name: &str, email: &str) -> (String, String) {
+        self.0.resolve(name, email)
+    }
+}
@@ -0,0 +1,163 @@
+//! Lines moved verbatim within a diff, removed in one place and added in
+//! another, detected like git's `--color-moved` does.
+
+use pyo3::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::paged::FileDiff;
+
+/// Blocks with fewer alphanumeric characters than this are too common (e.g.
+/// lone braces) to count as moved, the same as git's
+/// `COLOR_MOVED_MIN_ALNUM_COUNT`.
+const MIN_ALNUM_COUNT: usize = 20;
+
+/// A line of the diff, `hunk` is the index of the hunk for lines of hunks
+/// and `None` for the `@@` headers and `\ No newline at end of file`.
+struct Symbol<'a> {
+    origin: u8,
+    content: &'a [u8],
+    hunk: Option<usize>,
+}
+
+/// Whether the block of `length` lines before `end` has enough alphanumeric
+/// characters to be moved, if not its lines aren't moved after all.
+fn keep_block(
+    symbols: &[Symbol<'_>],
+    moved: &mut [Option<u32>],
+    end: usize,
+    length: usize,
+) -> bool {
+    let alnum = symbols[end - length..end]
+        .iter()
+        .flat_map(|symbol| symbol.content)
+        .filter(|byte| byte.is_ascii_alphanumeric())
+        .count();
+    if alnum >= MIN_ALNUM_COUNT {
+        return true;
+    }
+    moved[end - length..end].fill(None);
+    false
+}
+
+/// The block number of every line of `hunks` (the content of each hunk
+/// including its header), `None` for lines that weren't moved.
+///
+/// A removed or added line is moved if the lines of the opposite kind have
+/// the same content. Consecutive moved lines form a block as long as their
+/// counterparts are consecutive too, each block is matched as long as
+/// possible, and blocks without enough alphanumeric characters are left out
+/// (git's default `zebra` mode).
+fn detect<'a>(hunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<Vec<Option<u32>>> {
+    let mut symbols: Vec<Symbol<'_>> = Vec::new();
+    let mut count = 0;
+    for (index, content) in hunks.into_iter().enumerate() {
+        count += 1;
+        for (number, line) in content.split_inclusive(|&byte| byte == b'\n').enumerate() {
+            let origin = line[0];
+            let hunk = (number > 0 && matches!(origin, b' ' | b'-' | b'+')).then_some(index);
+            if origin == b'\\'
+                && let Some(previous) = symbols.last_mut()
+            {
+                previous.content = previous
+                    .content
+                    .strip_suffix(b"\n")
+                    .unwrap_or(previous.content);
+            }
+            symbols.push(Symbol {
+                origin,
+                content: &line[1..],
+                hunk,
+            });
+        }
+    }
+
+    let mut removed: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    let mut added: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (index, symbol) in symbols.iter().enumerate() {
+        match (symbol.origin, symbol.hunk) {
+            (b'-', Some(_)) => removed.entry(symbol.content).or_default().push(index),
+            (b'+', Some(_)) => added.entry(symbol.content).or_default().push(index),
+            _ => {}
+        }
+    }
+
+    let mut moved = vec![None; symbols.len()];
+    // The last lines of the blocks the current block could have moved from
+    // or to.
+    let mut potential: Vec<usize> = Vec::new();
+    let (mut origin, mut length, mut blocks) = (0, 0, 0);
+    let mut index = 0;
+    while index < symbols.len() {
+        let symbol = &symbols[index];
+        let matches = match (symbol.origin, symbol.hunk) {
+            (b'+', Some(_)) => removed.get(symbol.content),
+            (b'-', Some(_)) => added.get(symbol.content),
+            _ => None,
+        };
+
+        if !potential.is_empty() && (matches.is_none() || symbol.origin != origin) {
+            potential.clear();
+            if !keep_block(&symbols, &mut moved, index, length) && length > 1 {
+                // Another block might start at the second line of this one.
+                index -= length - 1;
+                (origin, length) = (0, 0);
+                continue;
+            }
+            length = 0;
+        }
+        let Some(matches) = matches else {
+            origin = 0;
+            index += 1;
+            continue;
+        };
+
+        potential.retain_mut(|last| {
+            let next = *last + 1;
+            let continues = symbols.get(next).is_some_and(|next| {
+                next.origin == symbols[*last].origin && next.content == symbol.content
+            });
+            *last = next;
+            continues
+        });
+        if potential.is_empty() {
+            if !keep_block(&symbols, &mut moved, index, length) && length > 1 {
+                index -= length - 1;
+                (origin, length) = (0, 0);
+                continue;
+            }
+            potential.clone_from(matches);
+            origin = symbol.origin;
+            length = 0;
+            blocks += 1;
+        }
+        length += 1;
+        moved[index] = Some(blocks - 1);
+        index += 1;
+    }
+    keep_block(&symbols, &mut moved, index, length);
+
+    // Numbered again without the blocks that were left out.
+    let mut numbers = HashMap::new();
+    let mut lines = vec![Vec::new(); count];
+    for (symbol, block) in symbols.iter().zip(moved) {
+        if let Some(hunk) = symbol.hunk {
+            let next = numbers.len() as u32;
+            lines[hunk].push(block.map(|block| *numbers.entry(block).or_insert(next)));
+        }
+    }
+    lines
+}
+
+/// Find the lines moved within all of `files`, see `Line.moved`.
+pub(crate) fn mark(py: Python<'_>, files: &[FileDiff]) {
+    let mut hunks = files
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .map(|hunk| hunk.borrow_mut(py))
+        .collect::<Vec<_>>();
+    let moved = detect(hunks.iter().map(|hunk| hunk.content.as_slice()));
+    for (hunk, moved) in hunks.iter_mut().zip(moved) {
+        hunk.moved = moved;
+    }
+}
@@ -0,0 +1,58 @@
+use pyo3::prelude::*;
+
+use crate::IntoPyResult;
+
+/// The note `notes_ref` attaches to `id`, `None` if there is none (or no such
+/// notes ref).
+///
+/// Like `git notes --ref`, a name that doesn't start with `refs/` is taken to
+/// be under `refs/notes/`. Notes trees can fan out into directories named by
+/// the leading hex digits of the annotated object, e.g. `ab/cdef...`.
+pub(crate) fn note(
+    repository: &gix::Repository,
+    notes_ref: &str,
+    id: gix::ObjectId,
+) -> PyResult<Option<String>> {
+    let name = if notes_ref.starts_with("refs/") {
+        notes_ref.to_string()
+    } else {
+        format!("refs/notes/{notes_ref}")
+    };
+    let Some(mut reference) = repository
+        .try_find_reference(name.as_str())
+        .into_py_result()?
+    else {
+        return Ok(None);
+    };
+    let mut tree_id = reference
+        .peel_to_commit()
+        .into_py_result()?
+        .tree_id()
+        .into_py_result()?
+        .detach();
+
+    let hex = id.to_string();
+    let mut remaining = hex.as_str();
+    loop {
+        let tree = repository.find_object(tree_id).into_py_result()?;
+        let entries = gix::objs::TreeRef::from_bytes(&tree.data)
+            .into_py_result()?
+            .entries;
+        if let Some(entry) = entries
+            .iter()
+            .find(|entry| entry.mode.is_blob() && entry.filename == remaining.as_bytes())
+        {
+            let blob = repository.find_object(entry.oid).into_py_result()?;
+            return Ok(Some(String::from_utf8_lossy(&blob.data).into_owned()));
+        }
+        let Some(entry) = entries.iter().find(|entry| {
+            entry.mode.is_tree()
+                && entry.filename.len() == 2
+                && remaining.as_bytes().starts_with(entry.filename)
+        }) else {
+            return Ok(None);
+        };
+        tree_id = entry.oid.to_owned();
+        remaining = &remaining[2..];
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// A mapping of commit IDs from before a history rewrite to after it.
+///
+/// The on-disk format is one `<old> <new>` pair per line which is what git
+/// passes to the `post-rewrite` hook and what `git filter-repo` writes to
+/// `.git/filter-repo/commit-map` (its `old new` header line is skipped).
+#[derive(Default)]
+pub(crate) struct OidMap {
+    inner: HashMap<gix::ObjectId, gix::ObjectId>,
+}
+
+impl OidMap {
+    pub(crate) fn load(&mut self, path: &Path) -> std::io::Result<usize> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut loaded = 0;
+        for (index, line) in file.lines().enumerate() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let (Some(old), Some(new)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            if index == 0 && old == "old" && new == "new" {
+                continue;
+            }
+            let parse = |hex: &str| {
+                gix::ObjectId::from_hex(hex.as_bytes()).map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{}:{}: {e}", path.display(), index + 1),
+                    )
+                })
+            };
+            self.inner.insert(parse(old)?, parse(new)?);
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
+    pub(crate) fn extend(&mut self, mapping: &HashMap<gix::ObjectId, gix::ObjectId>) {
+        self.inner.extend(mapping);
+    }
+
+    /// Follow the mapping through any number of successive rewrites.
+    ///
+    /// Returns `None` if the commit was never rewritten and the null ID if it
+    /// was dropped by the rewrite.
+    pub(crate) fn map(&self, id: gix::ObjectId) -> Option<gix::ObjectId> {
+        let mut current = *self.inner.get(&id)?;
+        // Bound the chain so a malformed map with a cycle can't hang us.
+        for _ in 0..self.inner.len() {
+            match self.inner.get(&current) {
+                Some(next) if !current.is_null() && *next != current => current = *next,
+                _ => break,
+            }
+        }
+        Some(current)
+    }
+
+    pub(crate) fn write(
+        path: &Path,
+        mapping: &HashMap<gix::ObjectId, gix::ObjectId>,
+    ) -> std::io::Result<()> {
+        let mut pairs = mapping.iter().collect::<Vec<_>>();
+        pairs.sort();
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for (old, new) in pairs {
+            writeln!(file, "{old} {new}")?;
+        }
+        file.flush()
+    }
+}
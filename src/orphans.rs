@@ -0,0 +1,61 @@
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::IntoPyResult;
+
+/// The local branches whose tips share no history with any of the `protected`
+/// branches, e.g. accidentally unrelated histories left behind by subtree
+/// imports.
+///
+/// Protected branches that don't exist are ignored, but at least one of them
+/// has to.
+pub(crate) fn orphaned_branches(
+    repository: &gix::Repository,
+    protected: &[String],
+) -> PyResult<Vec<String>> {
+    let mut protected_tips = Vec::new();
+    for name in protected {
+        if let Some(mut reference) = repository
+            .try_find_reference(format!("refs/heads/{name}").as_str())
+            .into_py_result()?
+        {
+            protected_tips.push(reference.peel_to_commit().into_py_result()?.id);
+        }
+    }
+    if protected_tips.is_empty() {
+        return Err(PyValueError::new_err(format!(
+            "none of the protected branches {protected:?} exist"
+        )));
+    }
+
+    let mut orphaned = Vec::new();
+    for reference in repository
+        .references()
+        .into_py_result()?
+        .local_branches()
+        .into_py_result()?
+    {
+        let mut reference = reference.map_err(|e| PyOSError::new_err(e.to_string()))?;
+        let name = reference.name().shorten().to_string();
+        if protected.contains(&name) {
+            continue;
+        }
+        let tip = reference.peel_to_commit().into_py_result()?.id;
+
+        let mut related = false;
+        for &protected_tip in &protected_tips {
+            match repository.merge_base(tip, protected_tip) {
+                Ok(_) => {
+                    related = true;
+                    break;
+                }
+                Err(gix::repository::merge_base::Error::NotFound { .. }) => {}
+                Err(e) => return Err(PyOSError::new_err(e.to_string())),
+            }
+        }
+        if !related {
+            orphaned.push(name);
+        }
+    }
+    Ok(orphaned)
+}
@@ -0,0 +1,239 @@
+use pyo3::prelude::*;
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use gix::object::tree::diff::ChangeDetached;
+
+use crate::{Commit, Repository, diff, moved, paths, unified_diff, visit};
+
+/// The patch of a single changed file, both as text and broken down into
+/// hunks and lines.
+#[pyclass]
+pub struct FileDiff {
+    /// One of `added`, `deleted`, `modified`, `renamed`, or `copied`.
+    #[pyo3(get)]
+    pub kind: &'static str,
+    #[pyo3(get)]
+    pub path: String,
+    /// The path before a rename or copy, otherwise the same as `path`.
+    #[pyo3(get)]
+    pub previous_path: String,
+    /// The octal file mode (e.g. `100644`), `None` for deletions.
+    #[pyo3(get)]
+    pub mode: Option<String>,
+    /// The octal file mode before the change, `None` for additions.
+    #[pyo3(get)]
+    pub previous_mode: Option<String>,
+    #[pyo3(get)]
+    pub id: String,
+    #[pyo3(get)]
+    pub previous_id: String,
+    /// How similar in percent a renamed or copied file is to the original,
+    /// the `similarity index` of `git diff -M`. `None` for other changes.
+    #[pyo3(get)]
+    pub similarity: Option<u8>,
+    /// Whether either side is binary, binary files have no hunks.
+    #[pyo3(get)]
+    pub binary: bool,
+    /// The size in bytes of the larger blob if the diff was left out for
+    /// exceeding `max_file_size`, such files have no hunks either.
+    #[pyo3(get)]
+    pub suppressed_size: Option<u64>,
+    #[pyo3(get)]
+    pub hunks: Vec<Py<visit::Hunk>>,
+    pub(crate) patch: Vec<u8>,
+}
+
+#[pymethods]
+impl FileDiff {
+    /// The patch in the same format as `Repository.diff_bytes`.
+    #[getter]
+    fn patch(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.patch)
+    }
+}
+
+/// The diff of a single change, `None` if it has no patch (e.g. directories
+/// and submodules).
+fn file_diff(
+    py: Python<'_>,
+    repository: &gix::Repository,
+    resource_cache: &mut gix::diff::blob::Platform,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+    change: &ChangeDetached,
+) -> PyResult<Option<FileDiff>> {
+    let mut patch = Vec::new();
+    Repository::write_change(
+        &mut patch,
+        resource_cache,
+        repository,
+        algorithm,
+        format,
+        change,
+    )?;
+    file_diff_of_patch(py, repository, change, patch)
+}
+
+/// The diff of a single change from its `patch`, see `file_diff`.
+fn file_diff_of_patch(
+    py: Python<'_>,
+    repository: &gix::Repository,
+    change: &ChangeDetached,
+    patch: Vec<u8>,
+) -> PyResult<Option<FileDiff>> {
+    let null = gix::ObjectId::null(repository.object_hash());
+    let Some((info, ..)) =
+        visit::change_info(&repository.objects, change, null).filter(|_| !patch.is_empty())
+    else {
+        return Ok(None);
+    };
+    let hunks = visit::split_hunks(&info.path, &patch);
+    let binary = hunks.is_empty()
+        && patch
+            .split(|&byte| byte == b'\n')
+            .any(|line| line.starts_with(b"Binary files "));
+    let suppressed_size = patch.split(|&byte| byte == b'\n').find_map(|line| {
+        std::str::from_utf8(line.strip_prefix(b"diff suppressed (")?)
+            .ok()?
+            .strip_suffix(" bytes)")?
+            .parse()
+            .ok()
+    });
+    Ok(Some(FileDiff {
+        kind: info.kind,
+        path: info.path,
+        previous_path: info.previous_path,
+        mode: info.mode,
+        previous_mode: info.previous_mode,
+        id: info.id,
+        previous_id: info.previous_id,
+        similarity: info.similarity,
+        binary,
+        suppressed_size,
+        hunks: hunks
+            .into_iter()
+            .map(|hunk| Py::new(py, hunk))
+            .collect::<PyResult<_>>()?,
+        patch,
+    }))
+}
+
+/// An iterator over the patch of a commit in pages of `page_size_files` files.
+///
+/// Only the tree diff is computed up front, the files of each page are diffed
+/// when the page is requested.
+#[pyclass]
+pub struct DiffPages {
+    repository: Arc<gix::ThreadSafeRepository>,
+    changes: VecDeque<ChangeDetached>,
+    algorithm: gix::diff::blob::Algorithm,
+    format: unified_diff::PatchFormat,
+    page_size_files: usize,
+}
+
+#[pymethods]
+impl DiffPages {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<Vec<FileDiff>>> {
+        let repository = self.repository.to_thread_local();
+        let mut resource_cache = diff::resource_cache(&repository);
+        let mut page = Vec::new();
+        while page.len() < self.page_size_files
+            && let Some(change) = self.changes.pop_front()
+        {
+            // Changes without a patch (e.g. directories) don't take up a slot.
+            if let Some(file_diff) = file_diff(
+                py,
+                &repository,
+                &mut resource_cache,
+                self.algorithm,
+                &self.format,
+                &change,
+            )? {
+                page.push(file_diff);
+            }
+        }
+        moved::mark(py, &page);
+        Ok((!page.is_empty()).then_some(page))
+    }
+}
+
+pub(crate) fn diff_paged(
+    repository: &Arc<gix::ThreadSafeRepository>,
+    commit: &Commit,
+    algorithm: gix::diff::blob::Algorithm,
+    format: unified_diff::PatchFormat,
+    page_size_files: usize,
+) -> DiffPages {
+    let changes = {
+        let local = repository.to_thread_local();
+        let commit = commit.inner.clone().attach(&local).into_commit();
+        diff::changes_with_nth_parent(&commit, 0, &format.renames)
+    };
+    DiffPages {
+        repository: repository.clone(),
+        changes: changes.into(),
+        algorithm,
+        format,
+        page_size_files: page_size_files.max(1),
+    }
+}
+
+/// The patch of `commit` against its parent at index `parent` as one
+/// `FileDiff` per changed file, only of the files matching `paths` (see
+/// `Repository.diff`) unless empty. The files are diffed without holding the
+/// GIL.
+pub(crate) fn diff_structured(
+    py: Python<'_>,
+    repository: &gix::ThreadSafeRepository,
+    commit: &Commit,
+    parent: usize,
+    algorithm: gix::diff::blob::Algorithm,
+    format: &unified_diff::PatchFormat,
+    paths: &[String],
+) -> PyResult<Vec<FileDiff>> {
+    let patches = py.allow_threads(|| {
+        let repository = repository.to_thread_local();
+        let commit = commit.inner.clone().attach(&repository).into_commit();
+        let mut resource_cache = diff::resource_cache(&repository);
+        let mut patches = Vec::new();
+        for change in diff::changes_with_nth_parent(&commit, parent, &format.renames) {
+            let source = match &change {
+                ChangeDetached::Rewrite {
+                    source_location, ..
+                } => Some(source_location.as_ref()),
+                _ => None,
+            };
+            if !std::iter::once(change.location())
+                .chain(source)
+                .any(|location| paths::matches(paths, location))
+            {
+                continue;
+            }
+            let mut patch = Vec::new();
+            Repository::write_change(
+                &mut patch,
+                &mut resource_cache,
+                &repository,
+                algorithm,
+                format,
+                &change,
+            )?;
+            patches.push((change, patch));
+        }
+        PyResult::Ok(patches)
+    })?;
+    let repository = repository.to_thread_local();
+    let mut files = Vec::new();
+    for (change, patch) in patches {
+        files.extend(file_diff_of_patch(py, &repository, &change, patch)?);
+    }
+    moved::mark(py, &files);
+    Ok(files)
+}
@@ -0,0 +1,452 @@
+//! Reading patches back into `FileDiff`s, from unified diffs with or without
+//! git's extended headers (e.g. of `git diff`, `git format-patch`, or `diff
+//! -u`).
+
+use gix::bstr::ByteSlice;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+use std::borrow::Cow;
+
+use crate::paged::FileDiff;
+use crate::{moved, visit};
+
+/// The files of a patch read from its text, see `Patch.parse`.
+#[pyclass]
+pub struct Patch {
+    /// The changed files in the order of the patch.
+    #[pyo3(get)]
+    pub files: Vec<Py<FileDiff>>,
+}
+
+#[pymethods]
+impl Patch {
+    /// Parse the text of a patch (`str` or `bytes`) into its files with
+    /// their hunks and lines, the same as `Repository.diff_structured` has
+    /// them. Anything between the files (e.g. the message of a `git
+    /// format-patch` email) is skipped.
+    ///
+    /// Paths lose their prefixes (like git's `a/` and `b/`) and git's
+    /// quoting. The IDs are only as long as in the `index` line (empty
+    /// without one), and the modes are `None` unless the header has them. The `patch` of each file is
+    /// its text as is, so the patches of all of them are the patch again
+    /// (see `bytes(patch)`).
+    #[staticmethod]
+    pub fn parse(py: Python<'_>, text: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let text = if let Ok(text) = text.downcast::<PyString>() {
+            text.to_str()?.as_bytes()
+        } else if let Ok(text) = text.downcast::<PyBytes>() {
+            text.as_bytes()
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "expected the patch as str or bytes, not {}",
+                text.get_type().name()?
+            )));
+        };
+        let files = parse(py, text)?;
+        moved::mark(py, &files);
+        Ok(Self {
+            files: files
+                .into_iter()
+                .map(|file| Py::new(py, file))
+                .collect::<PyResult<_>>()?,
+        })
+    }
+
+    /// The patches of the files one after another.
+    fn __bytes__(&self, py: Python<'_>) -> Cow<'static, [u8]> {
+        Cow::Owned(
+            self.files
+                .iter()
+                .flat_map(|file| file.borrow(py).patch.clone())
+                .collect(),
+        )
+    }
+}
+
+/// What the headers of a file in a patch tell about it.
+#[derive(Default)]
+struct Header {
+    kind: Option<&'static str>,
+    /// The paths of the `diff --git` line.
+    git_paths: Option<(Vec<u8>, Vec<u8>)>,
+    /// The paths of the `---` and `+++` lines.
+    paths: Option<(Vec<u8>, Vec<u8>)>,
+    /// The paths of a rename or copy, which never have a prefix.
+    renamed_paths: (Option<Vec<u8>>, Option<Vec<u8>>),
+    modes: (Option<String>, Option<String>),
+    ids: (String, String),
+    similarity: Option<u8>,
+    binary: bool,
+    suppressed_size: Option<u64>,
+}
+
+impl Header {
+    /// Take in a line of git's extended header, `false` if it's none.
+    fn extend(&mut self, line: &[u8]) -> bool {
+        let line = line.trim_end_with(|c| matches!(c, '\n' | '\r'));
+        let value = |name: &str| {
+            line.strip_prefix(name.as_bytes())
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        if let Some(mode) = value("old mode ") {
+            self.modes.0 = Some(mode);
+        } else if let Some(mode) = value("new mode ") {
+            self.modes.1 = Some(mode);
+        } else if let Some(mode) = value("deleted file mode ") {
+            self.kind = Some("deleted");
+            self.modes.0 = Some(mode);
+        } else if let Some(mode) = value("new file mode ") {
+            self.kind = Some("added");
+            self.modes.1 = Some(mode);
+        } else if let Some(from) = line.strip_prefix(b"rename from ") {
+            self.kind = Some("renamed");
+            self.renamed_paths.0 = Some(path(from));
+        } else if let Some(to) = line.strip_prefix(b"rename to ") {
+            self.renamed_paths.1 = Some(path(to));
+        } else if let Some(from) = line.strip_prefix(b"copy from ") {
+            self.kind = Some("copied");
+            self.renamed_paths.0 = Some(path(from));
+        } else if let Some(to) = line.strip_prefix(b"copy to ") {
+            self.renamed_paths.1 = Some(path(to));
+        } else if let Some(similarity) = value("similarity index ") {
+            self.similarity = similarity
+                .strip_suffix('%')
+                .and_then(|similarity| similarity.parse().ok());
+        } else if let Some(index) = value("index ") {
+            // The mode is only there if it didn't change.
+            let (ids, mode) = index
+                .split_once(' ')
+                .map_or((index.as_str(), None), |(ids, mode)| (ids, Some(mode)));
+            if let Some((old, new)) = ids.split_once("..") {
+                self.ids = (old.to_string(), new.to_string());
+            }
+            if let Some(mode) = mode {
+                self.modes = (Some(mode.to_string()), Some(mode.to_string()));
+            }
+        } else if let Some(size) = value("diff suppressed (") {
+            self.suppressed_size = size
+                .strip_suffix(" bytes)")
+                .and_then(|size| size.parse().ok());
+        } else if line.starts_with(b"Binary files ") || line == b"GIT binary patch" {
+            self.binary = true;
+        } else if !line.starts_with(b"dissimilarity index ") {
+            return false;
+        }
+        true
+    }
+
+    fn into_file_diff(self, py: Python<'_>, patch: Vec<u8>) -> PyResult<FileDiff> {
+        let (old, new) = self
+            .paths
+            .clone()
+            .or_else(|| self.git_paths.clone())
+            .unwrap_or_default();
+        let mut kind = self.kind;
+        if old == NULL {
+            kind = Some("added");
+        } else if new == NULL {
+            kind = Some("deleted");
+        }
+        // The `diff --git` line has the paths even if the other lines don't.
+        let prefixed = self
+            .git_paths
+            .as_ref()
+            .or(self.paths.as_ref())
+            .is_some_and(|(old, new)| prefixed(old, new));
+        let strip = |path: Vec<u8>| {
+            if prefixed {
+                without_prefix(&path).to_vec()
+            } else {
+                path
+            }
+        };
+        let (mut old, mut new) = (
+            self.renamed_paths.0.unwrap_or_else(|| strip(old)),
+            self.renamed_paths.1.unwrap_or_else(|| strip(new)),
+        );
+        let kind = kind.unwrap_or("modified");
+        let (previous_mode, mode) = match kind {
+            "added" => {
+                old.clone_from(&new);
+                (None, self.modes.1)
+            }
+            "deleted" => {
+                new.clone_from(&old);
+                (self.modes.0, None)
+            }
+            _ => self.modes,
+        };
+        let path = String::from_utf8_lossy(&new).into_owned();
+        Ok(FileDiff {
+            kind,
+            previous_path: String::from_utf8_lossy(&old).into_owned(),
+            mode,
+            previous_mode,
+            id: self.ids.1,
+            previous_id: self.ids.0,
+            similarity: self.similarity,
+            binary: self.binary,
+            suppressed_size: self.suppressed_size,
+            hunks: visit::split_hunks(&path, &patch)
+                .into_iter()
+                .map(|hunk| Py::new(py, hunk))
+                .collect::<PyResult<_>>()?,
+            path,
+            patch,
+        })
+    }
+}
+
+/// The path of the side of a patch that doesn't exist.
+const NULL: &[u8] = b"/dev/null";
+
+/// The path without its first component.
+fn without_prefix(path: &[u8]) -> &[u8] {
+    path.split_once_str("/").map_or(path, |(_, path)| path)
+}
+
+/// Whether `old` and `new`, the paths of the same file on either side of a
+/// patch, have prefixes (e.g. git's `a/` and `b/`). They do if they only
+/// differ by their first component, or for a side that doesn't exist if
+/// the other starts with git's prefix.
+fn prefixed(old: &[u8], new: &[u8]) -> bool {
+    if old == NULL {
+        new.starts_with(b"b/")
+    } else if new == NULL {
+        old.starts_with(b"a/")
+    } else {
+        old != new && without_prefix(old) == without_prefix(new)
+    }
+}
+
+/// Undo git's quoting of unusual paths (see `core.quotePath`): in double
+/// quotes with C-style escapes. Returns the path and what follows it, `None`
+/// if `text` isn't quoted.
+fn unquote(text: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let quoted = text.strip_prefix(b"\"")?;
+    let mut path = Vec::new();
+    let mut index = 0;
+    while let Some(&byte) = quoted.get(index) {
+        index += 1;
+        match byte {
+            b'"' => return Some((path, &quoted[index..])),
+            b'\\' => {
+                let escaped = *quoted.get(index)?;
+                index += 1;
+                path.push(match escaped {
+                    b'a' => 0x07,
+                    b'b' => 0x08,
+                    b't' => b'\t',
+                    b'n' => b'\n',
+                    b'v' => 0x0b,
+                    b'f' => 0x0c,
+                    b'r' => b'\r',
+                    b'0'..=b'7' => {
+                        let digits = quoted.get(index - 1..index + 2)?;
+                        index += 2;
+                        u8::from_str_radix(digits.to_str().ok()?, 8).ok()?
+                    }
+                    _ => escaped,
+                });
+            }
+            _ => path.push(byte),
+        }
+    }
+    None
+}
+
+/// The path written in a header line, without anything after a tab (like
+/// the timestamps of `diff -u`).
+fn path(text: &[u8]) -> Vec<u8> {
+    let text = text.trim_end_with(|c| matches!(c, '\n' | '\r'));
+    match unquote(text) {
+        Some((path, _)) => path,
+        None => text.split_str("\t").next().unwrap_or_default().to_vec(),
+    }
+}
+
+/// The two paths of a `diff --git` line. Unless they're renamed (when the
+/// header says which they are) both are the same path with a different
+/// prefix, which tells where they're split if they have spaces.
+fn git_paths(text: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let text = text.trim_end_with(|c| matches!(c, '\n' | '\r'));
+    if let Some((old, rest)) = unquote(text) {
+        return Some((old, path(rest.strip_prefix(b" ")?)));
+    }
+    for split in text.find_iter(" \"") {
+        if let Some((new, b"")) = unquote(&text[split + 1..]) {
+            return Some((text[..split].to_vec(), new));
+        }
+    }
+    let spaces: Vec<usize> = text.find_iter(" ").collect();
+    let split = spaces
+        .iter()
+        .find(|&&split| without_prefix(&text[..split]) == without_prefix(&text[split + 1..]))
+        .or(spaces.last())?;
+    Some((text[..*split].to_vec(), text[*split + 1..].to_vec()))
+}
+
+/// The line counts of the old and new side of a hunk header.
+fn hunk_lines(header: &[u8]) -> Option<(u32, u32)> {
+    let header = header.to_str().ok()?;
+    let mut ranges = header.split_whitespace().skip(1);
+    let (_, old) = visit::parse_range(ranges.next()?.strip_prefix('-')?)?;
+    let (_, new) = visit::parse_range(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+/// The files of the patch `text`.
+fn parse(py: Python<'_>, text: &[u8]) -> PyResult<Vec<FileDiff>> {
+    let lines: Vec<&[u8]> = text.split_inclusive(|&byte| byte == b'\n').collect();
+    let line = |index: usize| lines.get(index).copied().unwrap_or_default();
+    let is_file_start =
+        |index: usize| line(index).starts_with(b"--- ") && line(index + 1).starts_with(b"+++ ");
+
+    let mut files = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let start = index;
+        let mut header = Header::default();
+        if let Some(paths) = line(index).strip_prefix(b"diff --git ") {
+            header.git_paths = git_paths(paths);
+            index += 1;
+            while index < lines.len() && header.extend(line(index)) {
+                index += 1;
+            }
+        } else if !is_file_start(index) {
+            index += 1;
+            continue;
+        }
+        if is_file_start(index) {
+            header.paths = Some((path(&line(index)[4..]), path(&line(index + 1)[4..])));
+            index += 2;
+        }
+
+        while line(index).starts_with(b"@@ -") {
+            let (mut old, mut new) = hunk_lines(line(index)).unwrap_or_default();
+            index += 1;
+            while old > 0 || new > 0 {
+                match line(index).first() {
+                    // Blank context lines may have lost their space.
+                    Some(b' ' | b'\n' | b'\r') => {
+                        (old, new) = (old.saturating_sub(1), new.saturating_sub(1))
+                    }
+                    Some(b'-') => old = old.saturating_sub(1),
+                    Some(b'+') => new = new.saturating_sub(1),
+                    Some(b'\\') => {}
+                    _ => break,
+                }
+                index += 1;
+            }
+            if line(index).starts_with(b"\\") {
+                index += 1;
+            }
+        }
+        // Each side of a `GIT binary patch` ends with a blank line.
+        while line(index).starts_with(b"literal ") || line(index).starts_with(b"delta ") {
+            while index < lines.len() {
+                index += 1;
+                if line(index - 1).trim().is_empty() {
+                    break;
+                }
+            }
+        }
+        files.push(header.into_file_diff(py, lines[start..index].concat())?);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Patch;
+
+    use gix::bstr::ByteSlice;
+    use pyo3::prelude::*;
+    use pyo3::types::PyBytes;
+
+    const EMAIL: &[u8] =
+        b"From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001\n\
+        Subject: [PATCH] Change the files\n\
+        \n\
+        ---\n \
+        a.txt | 2 +-\n\
+        \n";
+
+    const FILES: &[u8] = b"diff --git a/a.txt b/a.txt\n\
+        index 1234567..89abcde 100644\n\
+        --- a/a.txt\n\
+        +++ b/a.txt\n\
+        @@ -1,3 +1,3 @@ fn main\n \
+        one\n\
+        -two\n\
+        +2\n \
+        three\n\
+        diff --git \"a/caf\\303\\251.txt\" b/cafe.txt\n\
+        similarity index 90%\n\
+        rename from \"caf\\303\\251.txt\"\n\
+        rename to cafe.txt\n\
+        diff --git a/image.png b/image.png\n\
+        deleted file mode 100755\n\
+        index 1234567..0000000\n\
+        Binary files a/image.png and /dev/null differ\n\
+        diff --git a/last.txt b/last.txt\n\
+        new file mode 100644\n\
+        index 0000000..1234567\n\
+        --- /dev/null\n\
+        +++ b/last.txt\n\
+        @@ -0,0 +1 @@\n\
+        +last\n\
+        \\ No newline at end of file\n";
+
+    #[test]
+    fn round_trip() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let text = [EMAIL, FILES, b"-- \ngitch 0.1.0\n\n"].concat();
+            let patch = Patch::parse(py, PyBytes::new(py, &text).as_any()).unwrap();
+
+            let files = patch
+                .files
+                .iter()
+                .map(|file| {
+                    let file = file.borrow(py);
+                    (file.kind, file.previous_path.clone(), file.path.clone())
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                files,
+                [
+                    ("modified", "a.txt".into(), "a.txt".into()),
+                    ("renamed", "café.txt".into(), "cafe.txt".into()),
+                    ("deleted", "image.png".into(), "image.png".into()),
+                    ("added", "last.txt".into(), "last.txt".into()),
+                ]
+            );
+
+            let modified = patch.files[0].borrow(py);
+            assert_eq!(
+                (modified.previous_id.as_str(), modified.id.as_str()),
+                ("1234567", "89abcde")
+            );
+            assert_eq!(modified.mode.as_deref(), Some("100644"));
+            let hunk = modified.hunks[0].borrow(py);
+            assert_eq!(
+                (
+                    hunk.old_start,
+                    hunk.old_lines,
+                    hunk.new_start,
+                    hunk.new_lines
+                ),
+                (1, 3, 1, 3)
+            );
+            assert_eq!(patch.files[1].borrow(py).similarity, Some(90));
+            assert!(patch.files[2].borrow(py).binary);
+
+            // Without what's around the files.
+            assert_eq!(patch.__bytes__(py).as_bstr(), FILES.as_bstr());
+        });
+    }
+}
@@ -0,0 +1,78 @@
+//! Patches computed on a worker thread ahead of the commit being iterated
+//! to, so diffing overlaps with whatever the caller does with the previous
+//! ones.
+
+use pyo3::prelude::*;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, mpsc};
+
+use crate::{Commit, DiffContext, diff, unified_diff};
+
+/// An iterator over `(commit, patch)` pairs of the commits in the order they
+/// were given, the patch `None` for commits that change nothing.
+///
+/// The patches come from a worker thread that stays at most `lookahead`
+/// commits ahead, it stops once the iterator is exhausted or dropped.
+#[pyclass]
+pub struct DiffPrefetch {
+    commits: VecDeque<Py<Commit>>,
+    diffs: Mutex<mpsc::Receiver<PyResult<Option<Vec<u8>>>>>,
+}
+
+#[pymethods]
+impl DiffPrefetch {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<(Py<Commit>, Option<String>)>> {
+        let Some(commit) = self.commits.pop_front() else {
+            return Ok(None);
+        };
+        let diffs = self.diffs.get_mut().unwrap();
+        let diff = py
+            .allow_threads(move || diffs.recv())
+            .expect("the worker sends a patch for every commit")
+            // The worker stops at the first error.
+            .inspect_err(|_| self.commits.clear())?;
+        Ok(Some((
+            commit,
+            diff.map(|diff| String::from_utf8_lossy(&diff).into_owned()),
+        )))
+    }
+}
+
+pub(crate) fn prefetch_diffs(
+    py: Python<'_>,
+    repository: &Arc<gix::ThreadSafeRepository>,
+    context: DiffContext,
+    commits: Vec<Py<Commit>>,
+    algorithm: gix::diff::blob::Algorithm,
+    format: unified_diff::PatchFormat,
+    lookahead: usize,
+) -> DiffPrefetch {
+    let inners = commits
+        .iter()
+        .map(|commit| commit.borrow(py).inner.clone())
+        .collect::<Vec<_>>();
+    let (sender, receiver) = mpsc::sync_channel(lookahead.max(1));
+    let repository = repository.clone();
+    std::thread::spawn(move || {
+        let repository = repository.to_thread_local();
+        let mut resource_cache = diff::resource_cache(&repository);
+        for inner in inners {
+            let commit = inner.attach(&repository).into_commit();
+            let diff = context.diff(&mut resource_cache, &commit, 0, algorithm, &format, &[]);
+            let failed = diff.is_err();
+            // Nobody is iterating anymore.
+            if sender.send(diff).is_err() || failed {
+                break;
+            }
+        }
+    });
+    DiffPrefetch {
+        commits: commits.into(),
+        diffs: Mutex::new(receiver),
+    }
+}
@@ -0,0 +1,453 @@
+use gix::diff::blob::Sink;
+use gix::diff::blob::intern::InternedInput;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::Range;
+
+use crate::{IntoPyResult, signature_time, tree};
+
+/// The number of alphanumeric characters a block of lines needs to count as
+/// copied from another file, git's default for `blame -C`.
+const COPY_SCORE: usize = 40;
+
+/// Where a line of a file came from.
+#[pyclass(frozen)]
+pub struct LineProvenance {
+    /// The line number in the file that was asked about, starting at 1.
+    #[pyo3(get)]
+    pub line: usize,
+    /// The commit that introduced the line.
+    #[pyo3(get)]
+    pub commit_id: String,
+    /// The file the line was introduced in.
+    #[pyo3(get)]
+    pub path: String,
+    /// The line number in `path` as of `commit_id`.
+    #[pyo3(get)]
+    pub original_line: usize,
+    /// The commits that moved or copied the line into another file as
+    /// `(commit ID, from path, to path)`, newest first.
+    #[pyo3(get)]
+    pub transfers: Vec<(String, String, String)>,
+}
+
+/// A line that still has to be traced further back.
+struct Pending {
+    line: usize,
+    path: String,
+    /// The index of the line in `path` as of the commit it is queued at.
+    index: u32,
+    transfers: Vec<(String, String, String)>,
+}
+
+/// Collects the changed ranges of a line diff.
+struct Changes(Vec<(Range<u32>, Range<u32>)>);
+
+impl Sink for Changes {
+    type Out = Vec<(Range<u32>, Range<u32>)>;
+
+    fn process_change(&mut self, before: Range<u32>, after: Range<u32>) {
+        self.0.push((before, after));
+    }
+
+    fn finish(self) -> Self::Out {
+        self.0
+    }
+}
+
+/// The blocks of lines `new` has in common with `old`, as the range of lines
+/// in `new`, where they start in `old`, and how many alphanumeric characters
+/// they have.
+fn matching_blocks(old: &[u8], new: &[u8]) -> Vec<(Range<u32>, u32, usize)> {
+    let input = InternedInput::new(
+        gix::diff::blob::sources::byte_lines_with_terminator(old),
+        gix::diff::blob::sources::byte_lines_with_terminator(new),
+    );
+    let mut changes = gix::diff::blob::diff(
+        gix::diff::blob::Algorithm::Myers,
+        &input,
+        Changes(Vec::new()),
+    );
+    slide_down(&mut changes, &input.before, &input.after);
+    let mut blocks = Vec::new();
+    let (mut before, mut after) = (0, 0);
+    // The lines after the last change are unchanged as well.
+    let end = (
+        input.before.len() as u32..input.before.len() as u32,
+        input.after.len() as u32..input.after.len() as u32,
+    );
+    for (changed_before, changed_after) in changes.into_iter().chain([end]) {
+        if after < changed_after.start {
+            let score = input.after[after as usize..changed_after.start as usize]
+                .iter()
+                .map(|&token| {
+                    input.interner[token]
+                        .iter()
+                        .filter(|byte| byte.is_ascii_alphanumeric())
+                        .count()
+                })
+                .sum();
+            blocks.push((after..changed_after.start, before, score));
+        }
+        before = changed_before.end;
+        after = changed_after.end;
+    }
+    blocks
+}
+
+/// Move lines that were only removed or only added as far down as they can
+/// go like git does, e.g. when a function is removed after another one it is
+/// its own closing brace that goes and not the one of the function before.
+/// Otherwise that brace would make the lines before it a separate block,
+/// too short to count as copied.
+fn slide_down<T: PartialEq>(changes: &mut [(Range<u32>, Range<u32>)], before: &[T], after: &[T]) {
+    for index in 0..changes.len() {
+        let (next_before, next_after) = changes.get(index + 1).map_or(
+            (before.len() as u32, after.len() as u32),
+            |(before, after)| (before.start, after.start),
+        );
+        let (removed, added) = &mut changes[index];
+        let lines = match (removed.start == removed.end, added.start == added.end) {
+            (false, true) => &before[removed.start as usize..next_before as usize],
+            (true, false) => &after[added.start as usize..next_after as usize],
+            _ => continue,
+        };
+        let len = (removed.end - removed.start).max(added.end - added.start) as usize;
+        // The line after the change has to stay unchanged on both sides.
+        let room = (next_before - removed.end).min(next_after - added.end) as usize;
+        let by = (0..room)
+            .take_while(|&by| lines[by] == lines[len + by])
+            .count() as u32;
+        *removed = removed.start + by..removed.end + by;
+        *added = added.start + by..added.end + by;
+    }
+}
+
+/// Pass the lines found in `old` on to `path` at `parent`, keeping the rest
+/// in `pending`. Blocks scoring less than `min_score` aren't considered.
+fn pass_blame(
+    old: &[u8],
+    new: &[u8],
+    min_score: usize,
+    pending: &mut Vec<Pending>,
+    mut passed: impl FnMut(Pending, u32),
+) {
+    if pending.is_empty() {
+        return;
+    }
+    let blocks = matching_blocks(old, new);
+    let mut remaining = Vec::new();
+    for line in pending.drain(..) {
+        match blocks
+            .iter()
+            .find(|(lines, _, score)| lines.contains(&line.index) && *score >= min_score)
+        {
+            Some((lines, start, _)) => {
+                let index = start + line.index - lines.start;
+                passed(line, index);
+            }
+            None => remaining.push(line),
+        }
+    }
+    *pending = remaining;
+}
+
+fn blob(tree: &gix::Tree<'_>, path: &str) -> PyResult<Option<Vec<u8>>> {
+    let Some(entry) = tree.lookup_entry_by_path(path).into_py_result()? else {
+        return Ok(None);
+    };
+    if !entry.mode().is_blob() {
+        return Ok(None);
+    }
+    Ok(Some(entry.object().into_py_result()?.detach().data))
+}
+
+/// Trace each line of `path` (or of `line_range`, 1-based and inclusive)
+/// back to the commit that introduced it, like `git blame -C -C`.
+///
+/// Lines are passed on to the same file in a parent if they are unchanged
+/// there. What is left is looked up in the previous versions of the other
+/// files changed by the commit and, for files it creates, in all files of
+/// its first parent; that way lines follow renames, moves, and copies into
+/// other files. Blocks of copied lines need some substance to count (see
+/// `COPY_SCORE`) so that e.g. lone closing braces aren't taken for copies.
+pub(crate) fn code_provenance(
+    repository: &gix::Repository,
+    path: &str,
+    line_range: Option<(usize, usize)>,
+) -> PyResult<Vec<LineProvenance>> {
+    let head = repository.head_commit().into_py_result()?;
+    let Some(data) = blob(&head.tree().into_py_result()?, path)? else {
+        return Err(PyValueError::new_err(format!(
+            "{path} is not a file in HEAD"
+        )));
+    };
+    let line_count = gix::diff::blob::sources::byte_lines_with_terminator(&data).count();
+    let (first, last) = line_range.unwrap_or((1, line_count));
+    if first == 0 || first > last || last > line_count {
+        return Err(PyValueError::new_err(format!(
+            "{path} has {line_count} lines, {first}-{last} is out of range"
+        )));
+    }
+
+    // Like git, visit commits newest first so a commit reached through
+    // several children is only looked at once with all lines queued at it.
+    let mut queue = BinaryHeap::new();
+    let mut queued: HashMap<gix::ObjectId, Vec<Pending>> = HashMap::new();
+    let time = |id: gix::ObjectId| -> PyResult<i64> {
+        let commit = repository.find_object(id).into_py_result()?;
+        Ok(signature_time(&commit.data, b"committer").map_or(0, |(seconds, _)| seconds))
+    };
+    queue.push((time(head.id)?, head.id));
+    queued.insert(
+        head.id,
+        (first..=last)
+            .map(|line| Pending {
+                line,
+                path: path.to_string(),
+                index: line as u32 - 1,
+                transfers: Vec::new(),
+            })
+            .collect(),
+    );
+
+    let mut found = Vec::new();
+    while let Some((_, id)) = queue.pop() {
+        let Some(lines) = queued.remove(&id) else {
+            continue;
+        };
+        let commit = repository.find_commit(id).into_py_result()?;
+        let tree = commit.tree().into_py_result()?;
+        // Parents missing from a shallow clone are treated like there are none.
+        let parents = commit
+            .parent_ids()
+            .filter_map(|parent| parent.object().ok()?.peel_to_commit().ok())
+            .collect::<Vec<_>>();
+
+        let mut by_path: HashMap<String, Vec<Pending>> = HashMap::new();
+        for line in lines {
+            by_path.entry(line.path.clone()).or_default().push(line);
+        }
+        let mut passed: HashMap<gix::ObjectId, Vec<Pending>> = HashMap::new();
+        for (path, mut pending) in by_path {
+            let Some(data) = blob(&tree, &path)? else {
+                continue;
+            };
+            let mut in_parent = false;
+            for parent in &parents {
+                let Some(old) = blob(&parent.tree().into_py_result()?, &path)? else {
+                    continue;
+                };
+                in_parent = true;
+                pass_blame(&old, &data, 0, &mut pending, |mut line, index| {
+                    line.index = index;
+                    passed.entry(parent.id).or_default().push(line);
+                });
+            }
+
+            if let Some(parent) = parents.first()
+                && !pending.is_empty()
+            {
+                let parent_tree = parent.tree().into_py_result()?;
+                let mut candidates = repository
+                    .diff_tree_to_tree(Some(&parent_tree), Some(&tree), None)
+                    .into_py_result()?
+                    .into_iter()
+                    .filter_map(|change| match change {
+                        gix::object::tree::diff::ChangeDetached::Deletion {
+                            location,
+                            entry_mode,
+                            id,
+                            ..
+                        }
+                        | gix::object::tree::diff::ChangeDetached::Modification {
+                            location,
+                            previous_entry_mode: entry_mode,
+                            previous_id: id,
+                            ..
+                        }
+                        | gix::object::tree::diff::ChangeDetached::Rewrite {
+                            source_location: location,
+                            source_entry_mode: entry_mode,
+                            source_id: id,
+                            ..
+                        } if entry_mode.is_blob() => Some((location.to_string(), id)),
+                        _ => None,
+                    })
+                    .filter(|(location, _)| *location != path)
+                    .collect::<Vec<_>>();
+                if !in_parent {
+                    for entry in tree::ls_tree(repository, parent_tree.id, true, None)? {
+                        if entry.mode.starts_with("100")
+                            && !candidates
+                                .iter()
+                                .any(|(location, _)| *location == entry.path)
+                        {
+                            candidates.push((
+                                entry.path,
+                                gix::ObjectId::from_hex(entry.id.as_bytes()).into_py_result()?,
+                            ));
+                        }
+                    }
+                }
+
+                for (location, blob_id) in candidates {
+                    if pending.is_empty() {
+                        break;
+                    }
+                    let old = repository.find_object(blob_id).into_py_result()?;
+                    // Binary files don't have lines to copy.
+                    if old.data.contains(&0) {
+                        continue;
+                    }
+                    pass_blame(
+                        &old.data,
+                        &data,
+                        COPY_SCORE,
+                        &mut pending,
+                        |mut line, index| {
+                            line.transfers.push((
+                                id.to_string(),
+                                location.clone(),
+                                line.path.clone(),
+                            ));
+                            line.path = location.clone();
+                            line.index = index;
+                            passed.entry(parent.id).or_default().push(line);
+                        },
+                    );
+                }
+            }
+
+            found.extend(pending.into_iter().map(|line| LineProvenance {
+                line: line.line,
+                commit_id: id.to_string(),
+                path: line.path,
+                original_line: line.index as usize + 1,
+                transfers: line.transfers,
+            }));
+        }
+
+        for (parent, lines) in passed {
+            if !queued.contains_key(&parent) {
+                queue.push((time(parent)?, parent));
+            }
+            queued.entry(parent).or_default().extend(lines);
+        }
+    }
+
+    found.sort_by_key(|line| line.line);
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::code_provenance;
+    use crate::tests::git;
+
+    use std::path::Path;
+
+    fn write(directory: &Path, name: &str, lines: &[&str]) {
+        let content = lines
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        std::fs::write(directory.join(name), content).unwrap();
+    }
+
+    /// `(commit ID, path, original line)` of each line of `git blame -C -C
+    /// --porcelain`.
+    fn blame(directory: &Path, path: &str) -> Vec<(String, String, usize)> {
+        let output = git(directory, &["blame", "-C", "-C", "--porcelain", path], b"");
+        let mut lines = Vec::new();
+        let (mut header, mut filename) = (None, String::new());
+        for line in String::from_utf8(output).unwrap().lines() {
+            if let Some(name) = line.strip_prefix("filename ") {
+                filename = name.to_string();
+            } else if line.starts_with('\t') {
+                let (id, original_line): (String, usize) = header.take().unwrap();
+                lines.push((id, filename.clone(), original_line));
+            } else if let [id, original_line, _, ..] =
+                line.split(' ').collect::<Vec<_>>().as_slice()
+                && id.len() == 40
+                && id.bytes().all(|byte| byte.is_ascii_hexdigit())
+            {
+                header = Some((id.to_string(), original_line.parse().unwrap()));
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn follows_lines_between_files_like_git_blame() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q", "-b", "main"], b"");
+        let parse = [
+            "fn parse(input: &str) -> Vec<String> {",
+            "    input.split_whitespace().map(str::to_string).collect()",
+            "}",
+        ];
+        let render = [
+            "fn render(words: &[String]) -> String {",
+            "    words.iter().map(|word| format!(\"<{word}>\")).collect()",
+            "}",
+        ];
+        write(path, "lib.rs", &[&parse[..], &[""], &render[..]].concat());
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "parse and render"], b"");
+
+        // Move `render` to a new file and `parse` to another existing one,
+        // in the same commit.
+        write(path, "main.rs", &["fn main() {}"]);
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "main"], b"");
+        write(path, "lib.rs", &["mod render;"]);
+        write(
+            path,
+            "render.rs",
+            &["use std::fmt;", "", &render.join("\n")],
+        );
+        write(path, "main.rs", &["fn main() {}", "", &parse.join("\n")]);
+        git(path, &["add", "."], b"");
+        git(path, &["commit", "-q", "-m", "split"], b"");
+        let first = String::from_utf8(git(path, &["rev-parse", "HEAD~2"], b"")).unwrap();
+        let split = String::from_utf8(git(path, &["rev-parse", "HEAD"], b"")).unwrap();
+        let (first, split) = (first.trim(), split.trim());
+
+        let repository = gix::open(path).unwrap();
+        for file in ["render.rs", "main.rs"] {
+            let provenance = code_provenance(&repository, file, None).unwrap();
+            let found = provenance
+                .iter()
+                .map(|line| {
+                    (
+                        line.commit_id.clone(),
+                        line.path.clone(),
+                        line.original_line,
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(found, blame(path, file), "{file}");
+            for line in &provenance {
+                let transfers = if line.commit_id == first {
+                    vec![(split.to_string(), "lib.rs".to_string(), file.to_string())]
+                } else {
+                    Vec::new()
+                };
+                assert_eq!(line.transfers, transfers, "{file}:{}", line.line);
+            }
+        }
+        let moved = code_provenance(&repository, "render.rs", Some((3, 5))).unwrap();
+        assert!(moved.iter().all(|line| line.path == "lib.rs"));
+        assert_eq!(
+            moved
+                .iter()
+                .map(|line| line.original_line)
+                .collect::<Vec<_>>(),
+            [5, 6, 7]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// The references recognized when no patterns are given, as `(kind, prefix)`.
+const DEFAULT_PATTERNS: &[(&str, &str)] = &[("issue", "#"), ("github", "GH-"), ("jira", "JIRA-")];
+
+/// Words that mark the references following them as resolved by the commit,
+/// as in `Fixes #123` or a `Closes: GH-45` trailer.
+const CLOSING_KEYWORDS: &[&str] = &[
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+/// A reference to an issue or ticket in a commit message.
+#[pyclass(frozen)]
+pub struct Reference {
+    /// The kind of the pattern that matched, e.g. `issue` or `jira`.
+    #[pyo3(get)]
+    pub kind: String,
+    /// The number following the prefix, e.g. `123` for `GH-123`.
+    #[pyo3(get)]
+    pub id: String,
+    /// Whether the reference follows a closing keyword such as `Fixes`.
+    #[pyo3(get)]
+    pub closes: bool,
+}
+
+/// The kind and number of a word that is a prefix followed by digits.
+fn reference<'a>(patterns: &'a [(String, String)], word: &'a str) -> Option<(&'a str, &'a str)> {
+    patterns.iter().find_map(|(kind, prefix)| {
+        let id = word
+            .get(..prefix.len())
+            .filter(|start| start.eq_ignore_ascii_case(prefix))
+            .map(|_| &word[prefix.len()..])?;
+        (!id.is_empty() && id.bytes().all(|byte| byte.is_ascii_digit()))
+            .then_some((kind.as_str(), id))
+    })
+}
+
+/// Find the references in `message` (summary, body, and trailers alike), in
+/// order of first appearance.
+///
+/// `patterns` are `(kind, prefix)` pairs, a reference is a word made of the
+/// (case-insensitive) prefix followed by digits.
+pub(crate) fn references(
+    message: &str,
+    patterns: Option<Vec<(String, String)>>,
+) -> PyResult<Vec<Reference>> {
+    let mut patterns = match patterns {
+        Some(patterns) => patterns,
+        None => DEFAULT_PATTERNS
+            .iter()
+            .map(|&(kind, prefix)| (kind.to_string(), prefix.to_string()))
+            .collect(),
+    };
+    if let Some((kind, _)) = patterns.iter().find(|(_, prefix)| prefix.is_empty()) {
+        return Err(PyValueError::new_err(format!(
+            "the prefix of pattern {kind:?} is empty"
+        )));
+    }
+    // The longest prefix wins when several match.
+    patterns.sort_by_key(|(_, prefix)| std::cmp::Reverse(prefix.len()));
+
+    let mut found = Vec::<Reference>::new();
+    for line in message.lines() {
+        // A closing keyword applies to the list of references after it, e.g.
+        // `Fixes #1, #2 and #3`.
+        let mut closing = false;
+        for word in line.split_whitespace() {
+            let word = word.trim_matches(|char: char| matches!(char, ',' | '.' | ';' | '(' | ')'));
+            let keyword = word.strip_suffix(':').unwrap_or(word);
+            if CLOSING_KEYWORDS
+                .iter()
+                .any(|closing| closing.eq_ignore_ascii_case(keyword))
+            {
+                closing = true;
+                continue;
+            }
+            let Some((kind, id)) = reference(&patterns, word) else {
+                closing &= word.eq_ignore_ascii_case("and") || word.is_empty();
+                continue;
+            };
+            match found
+                .iter_mut()
+                .find(|reference| reference.kind == kind && reference.id == id)
+            {
+                Some(reference) => reference.closes |= closing,
+                None => found.push(Reference {
+                    kind: kind.to_string(),
+                    id: id.to_string(),
+                    closes: closing,
+                }),
+            }
+        }
+    }
+    Ok(found)
+}
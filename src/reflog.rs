@@ -0,0 +1,52 @@
+use pyo3::prelude::*;
+
+use crate::{IntoPyResult, identity_time, signature_time, zoned};
+
+/// The time of a reflog line, `<old> <new> <name> <<email>> <seconds>
+/// <offset>\t<message>`.
+fn line_time(line: &[u8]) -> Option<(i64, i32)> {
+    identity_time(line.split(|&byte| byte == b'\t').next()?)
+}
+
+/// When a ref was created, in order of preference:
+///
+/// 1. the first entry of its reflog (tags only have one if
+///    `core.logAllRefUpdates=always`),
+/// 2. the tagger time of annotated tags,
+/// 3. the commit time of the commit it points to.
+pub(crate) fn ref_created_at(
+    repository: &gix::Repository,
+    name: &str,
+) -> PyResult<Option<jiff::Zoned>> {
+    let mut reference = repository.find_reference(name).into_py_result()?;
+    let full_name = reference.name().as_bstr().to_string();
+
+    // Per-worktree refs like HEAD keep their reflog in the worktree's git
+    // directory, everything else in the common one.
+    let logs = if full_name.starts_with("refs/") {
+        repository.common_dir().join("logs")
+    } else {
+        repository.git_dir().join("logs")
+    };
+    if let Ok(log) = std::fs::read(logs.join(&full_name))
+        && let Some(time) = log
+            .split(|&byte| byte == b'\n')
+            .find(|line| !line.is_empty())
+            .and_then(line_time)
+    {
+        return Ok(Some(zoned(time)));
+    }
+
+    if let Some(id) = reference.target().try_id()
+        && let Ok(object) = repository.find_object(id)
+        && object.kind == gix::object::Kind::Tag
+        && let Some(time) = signature_time(&object.data, b"tagger")
+    {
+        return Ok(Some(zoned(time)));
+    }
+
+    let Ok(commit) = reference.peel_to_commit() else {
+        return Ok(None);
+    };
+    Ok(signature_time(&commit.data, b"committer").map(zoned))
+}
@@ -0,0 +1,605 @@
+use gitch_engine::message;
+use pyo3::exceptions::{PyOSError, PyRuntimeError};
+use pyo3::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Commit, IntoPyResult};
+
+#[pyclass]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Capitalization {
+    Upper,
+    Lower,
+}
+
+/// Rules used to derive the fixed-up message of each commit in a rewrite
+/// plan.
+#[pyclass]
+#[derive(Clone)]
+pub struct RewriteRules {
+    /// Force the first letter of the summary to this capitalization.
+    #[pyo3(get, set)]
+    pub first_word_capitalization: Option<Capitalization>,
+    /// Remove a single trailing period from the summary.
+    #[pyo3(get, set)]
+    pub strip_summary_period: bool,
+    /// Rename trailer keys (e.g. `Commit-Type` to `Commit-type`), matched
+    /// ignoring ASCII case like git does.
+    #[pyo3(get, set)]
+    pub trailer_renames: HashMap<String, String>,
+    /// Explicit replacement messages keyed by full commit ID, these take
+    /// priority over all other rules.
+    #[pyo3(get, set)]
+    pub messages: HashMap<String, String>,
+}
+
+#[pymethods]
+impl RewriteRules {
+    #[new]
+    #[pyo3(
+        signature=(
+            first_word_capitalization=None,
+            strip_summary_period=false,
+            trailer_renames=None,
+            messages=None
+        ))]
+    fn new(
+        first_word_capitalization: Option<Capitalization>,
+        strip_summary_period: bool,
+        trailer_renames: Option<HashMap<String, String>>,
+        messages: Option<HashMap<String, String>>,
+    ) -> Self {
+        Self {
+            first_word_capitalization,
+            strip_summary_period,
+            trailer_renames: trailer_renames.unwrap_or_default(),
+            messages: messages.unwrap_or_default(),
+        }
+    }
+}
+
+impl RewriteRules {
+    fn apply(&self, id: &str, message: &str) -> String {
+        if let Some(message) = self.messages.get(id) {
+            return message.clone();
+        }
+
+        // Trailers only ever live in the last paragraph of the message, and
+        // only if it is a trailer block by the same parsing as everywhere
+        // else.
+        let mut message = message.to_string();
+        if !self.trailer_renames.is_empty()
+            && !message::parse(&message).trailer_list.is_empty()
+            && let Some(start) = message
+                .rfind("\n\n")
+                .map(|position| position + 2)
+                .or_else(|| message.rfind("\r\n\r\n").map(|position| position + 4))
+        {
+            let (head, trailers) = message.split_at(start);
+            let trailers = trailers
+                .split_inclusive('\n')
+                .map(|line| {
+                    if let Some((token, value)) = line.split_once(':')
+                        && value.starts_with(' ')
+                        && let Some(to) = self
+                            .trailer_renames
+                            .iter()
+                            .find_map(|(from, to)| from.eq_ignore_ascii_case(token).then_some(to))
+                    {
+                        return format!("{to}:{value}");
+                    }
+                    line.to_string()
+                })
+                .collect::<String>();
+            message = format!("{head}{trailers}");
+        }
+
+        let (summary, rest) = match message.split_once('\n') {
+            Some((summary, rest)) => (summary.to_string(), Some(rest)),
+            None => (message.clone(), None),
+        };
+        let mut summary = summary;
+        if self.strip_summary_period && summary.ends_with('.') && !summary.ends_with("..") {
+            summary.pop();
+        }
+        if let Some(capitalization) = self.first_word_capitalization {
+            let mut chars = summary.chars();
+            if let Some(first) = chars.next() {
+                summary = match capitalization {
+                    Capitalization::Upper => first.to_uppercase().chain(chars).collect(),
+                    Capitalization::Lower => first.to_lowercase().chain(chars).collect(),
+                };
+            }
+        }
+
+        match rest {
+            Some(rest) => format!("{summary}\n{rest}"),
+            None => summary,
+        }
+    }
+}
+
+/// A single commit whose message changes as part of a rewrite.
+#[pyclass]
+pub struct RewriteEntry {
+    #[pyo3(get)]
+    pub id: String,
+    #[pyo3(get)]
+    pub old_message: String,
+    /// Can be adjusted before executing the plan.
+    #[pyo3(get, set)]
+    pub new_message: String,
+}
+
+/// The set of message changes to apply to history. Trees, authorship, and
+/// timestamps are never changed.
+#[pyclass]
+pub struct RewritePlan {
+    /// The commit HEAD pointed to when the plan was made, executing the plan
+    /// is refused if this no longer matches.
+    #[pyo3(get)]
+    pub head: String,
+    #[pyo3(get)]
+    pub entries: Vec<Py<RewriteEntry>>,
+}
+
+pub(crate) fn plan(
+    py: Python<'_>,
+    repository: &gix::Repository,
+    commits: &[Commit],
+    rules: &RewriteRules,
+) -> PyResult<RewritePlan> {
+    let head = repository.head_id().into_py_result()?.to_string();
+
+    let mut entries = Vec::new();
+    for commit in commits {
+        let decoded = gix::objs::CommitRef::from_bytes(&commit.inner.data).into_py_result()?;
+        // Non UTF-8 messages are only rewritten when explicitly requested.
+        let Ok(old_message) = std::str::from_utf8(decoded.message) else {
+            if let Some(message) = rules.messages.get(&commit.id) {
+                entries.push(Py::new(
+                    py,
+                    RewriteEntry {
+                        id: commit.id.clone(),
+                        old_message: decoded.message.to_string(),
+                        new_message: message.clone(),
+                    },
+                )?);
+            }
+            continue;
+        };
+
+        let new_message = rules.apply(&commit.id, old_message);
+        if new_message != old_message {
+            entries.push(Py::new(
+                py,
+                RewriteEntry {
+                    id: commit.id.clone(),
+                    old_message: old_message.to_string(),
+                    new_message,
+                },
+            )?);
+        }
+    }
+
+    Ok(RewritePlan { head, entries })
+}
+
+/// Rewrite history so that every commit in the plan gets its new message.
+///
+/// All descendants of a rewritten commit are recreated with remapped parents
+/// and local branches, lightweight tags, and a detached HEAD that pointed into
+/// the rewritten history are moved in a single ref transaction. Annotated tags
+/// are left untouched. Signatures of recreated commits are dropped as they
+/// would no longer be valid, and so is the `encoding` of commits whose message
+/// is replaced as the new one is UTF-8.
+///
+/// Returns the mapping of every rewritten commit ID to its replacement.
+pub(crate) fn execute(
+    py: Python<'_>,
+    repository: &gix::Repository,
+    plan: &RewritePlan,
+) -> PyResult<HashMap<gix::ObjectId, gix::ObjectId>> {
+    let head = repository.head_id().into_py_result()?.detach();
+    if head.to_string() != plan.head {
+        return Err(PyRuntimeError::new_err(format!(
+            "HEAD moved from {} to {head} since the rewrite plan was made",
+            plan.head
+        )));
+    }
+
+    // Moving the refs writes to the reflog which needs an identity, check for
+    // it up front rather than failing after all objects have been written.
+    if repository
+        .committer()
+        .transpose()
+        .into_py_result()?
+        .is_none()
+    {
+        return Err(PyRuntimeError::new_err(
+            "no committer identity configured, set `user.name` and `user.email` first",
+        ));
+    }
+
+    let mut messages = HashMap::new();
+    for entry in &plan.entries {
+        let entry = entry.borrow(py);
+        let id = gix::ObjectId::from_hex(entry.id.as_bytes()).into_py_result()?;
+        messages.insert(id, entry.new_message.clone());
+    }
+    if messages.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Every ref that may need to be moved along with the rewritten history.
+    let mut tips = Vec::new();
+    let references = repository.references().into_py_result()?;
+    for reference in references
+        .local_branches()
+        .into_py_result()?
+        .chain(references.tags().into_py_result()?)
+    {
+        let reference = reference.map_err(|e| PyOSError::new_err(e.to_string()))?;
+        if let gix::refs::TargetRef::Object(id) = reference.target()
+            && repository.find_header(id).into_py_result()?.kind() == gix::object::Kind::Commit
+        {
+            tips.push((reference.name().to_owned(), id.to_owned()));
+        }
+    }
+    let head_reference = repository.head().into_py_result()?;
+    if head_reference.is_detached() {
+        tips.push(("HEAD".try_into().into_py_result()?, head));
+    }
+
+    // Collect the commit graph reachable from all tips.
+    let mut parents = HashMap::new();
+    let mut walk_order = Vec::new();
+    for info in repository
+        .rev_walk(tips.iter().map(|(_, id)| *id))
+        .all()
+        .into_py_result()?
+    {
+        let info = info.into_py_result()?;
+        walk_order.push(info.id);
+        parents.insert(info.id, info.parent_ids.to_vec());
+    }
+    for id in messages.keys() {
+        if !parents.contains_key(id) {
+            return Err(PyRuntimeError::new_err(format!(
+                "commit {id} is not reachable from any branch or tag"
+            )));
+        }
+    }
+
+    // Order the commits so that parents are always visited before children.
+    let mut topological_order = Vec::with_capacity(walk_order.len());
+    let mut visited = HashSet::new();
+    for start in walk_order {
+        let mut stack = vec![(start, false)];
+        while let Some((id, expanded)) = stack.pop() {
+            if expanded {
+                topological_order.push(id);
+                continue;
+            }
+            if !visited.insert(id) {
+                continue;
+            }
+            stack.push((id, true));
+            for parent_id in &parents[&id] {
+                if parents.contains_key(parent_id) && !visited.contains(parent_id) {
+                    stack.push((*parent_id, false));
+                }
+            }
+        }
+    }
+
+    let mut mapping = HashMap::new();
+    for id in topological_order {
+        let new_parents = parents[&id]
+            .iter()
+            .map(|parent_id| *mapping.get(parent_id).unwrap_or(parent_id))
+            .collect::<Vec<_>>();
+        let new_message = messages.get(&id);
+        if new_message.is_none() && new_parents == parents[&id] {
+            continue;
+        }
+
+        let object = repository.find_object(id).into_py_result()?;
+        let mut commit = gix::objs::CommitRef::from_bytes(&object.data)
+            .into_py_result()?
+            .into_owned();
+        commit.parents = new_parents.into_iter().collect();
+        if let Some(message) = new_message {
+            commit.message = message.as_str().into();
+            // The new message is UTF-8 whatever the old one was encoded in.
+            commit.encoding = None;
+        }
+        commit
+            .extra_headers
+            .retain(|(name, _)| name != "gpgsig" && name != "gpgsig-sha256");
+
+        let new_id = repository.write_object(&commit).into_py_result()?.detach();
+        mapping.insert(id, new_id);
+    }
+
+    let edits = tips
+        .into_iter()
+        .filter_map(|(name, id)| {
+            mapping
+                .get(&id)
+                .map(|new_id| gix::refs::transaction::RefEdit {
+                    change: gix::refs::transaction::Change::Update {
+                        log: gix::refs::transaction::LogChange {
+                            message: "gitch: rewrite commit messages".into(),
+                            ..Default::default()
+                        },
+                        expected: gix::refs::transaction::PreviousValue::MustExistAndMatch(
+                            gix::refs::Target::Object(id),
+                        ),
+                        new: gix::refs::Target::Object(*new_id),
+                    },
+                    name,
+                    deref: false,
+                })
+        })
+        .collect::<Vec<_>>();
+    repository
+        .edit_references(edits)
+        .map_err(crate::lock::into_py_err)?;
+
+    Ok(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RewriteRules, execute, plan};
+    use crate::Commit;
+    use crate::tests::git;
+
+    use pyo3::prelude::*;
+
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    fn rules(messages: &[(&str, &str)]) -> RewriteRules {
+        RewriteRules {
+            first_word_capitalization: None,
+            strip_summary_period: false,
+            trailer_renames: HashMap::new(),
+            messages: messages
+                .iter()
+                .map(|(id, message)| (id.to_string(), message.to_string()))
+                .collect(),
+        }
+    }
+
+    fn rev_parse(directory: &Path, rev: &str) -> String {
+        String::from_utf8(git(directory, &["rev-parse", rev], b""))
+            .unwrap()
+            .trim()
+            .to_owned()
+    }
+
+    fn commit(directory: &Path, message: &str) -> String {
+        git(
+            directory,
+            &["commit", "-q", "--allow-empty", "-m", message],
+            b"",
+        );
+        rev_parse(directory, "HEAD")
+    }
+
+    /// A new repository with an identity to write the reflog with.
+    fn repository() -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q", "-b", "main"], b"");
+        git(path, &["config", "user.name", "Committer"], b"");
+        git(
+            path,
+            &["config", "user.email", "committer@example.com"],
+            b"",
+        );
+        directory
+    }
+
+    /// Write `data` as a commit object and point `main` at it.
+    fn write_commit(directory: &Path, data: &[u8]) -> String {
+        let id = git(
+            directory,
+            &["hash-object", "-t", "commit", "-w", "--stdin"],
+            data,
+        );
+        let id = String::from_utf8(id).unwrap().trim().to_owned();
+        git(directory, &["update-ref", "refs/heads/main", &id], b"");
+        id
+    }
+
+    /// Plan the rewrite of the commits in `ids` with `rules` and execute it.
+    fn rewrite(
+        directory: &Path,
+        ids: &[&str],
+        rules: &RewriteRules,
+    ) -> PyResult<HashMap<String, String>> {
+        pyo3::prepare_freethreaded_python();
+        let repository = gix::open(directory).unwrap();
+        let commits = ids
+            .iter()
+            .map(|id| {
+                let id = gix::ObjectId::from_hex(id.as_bytes()).unwrap();
+                Commit::try_from(repository.find_object(id).unwrap().detach()).unwrap()
+            })
+            .collect::<Vec<_>>();
+        Python::with_gil(|py| {
+            let plan = plan(py, &repository, &commits, rules)?;
+            let mapping = execute(py, &repository, &plan)?;
+            Ok(mapping
+                .into_iter()
+                .map(|(old, new)| (old.to_string(), new.to_string()))
+                .collect())
+        })
+    }
+
+    #[test]
+    fn trailers_are_renamed_only_in_the_trailer_block() {
+        let rules = RewriteRules {
+            trailer_renames: HashMap::from([(
+                "Commit-Type".to_string(),
+                "Commit-type".to_string(),
+            )]),
+            ..rules(&[])
+        };
+
+        assert_eq!(
+            rules.apply(
+                "",
+                "Summary\n\nCommit-Type: in the body\n\nSigned-off-by: A <a@example.com>\ncommit-TYPE: feature\n",
+            ),
+            "Summary\n\nCommit-Type: in the body\n\nSigned-off-by: A <a@example.com>\nCommit-type: feature\n",
+        );
+        // Not a trailer block, so nothing in it is a trailer.
+        let message = "Summary\n\nCommit-Type:feature is what\nthis paragraph is about\n";
+        assert_eq!(rules.apply("", message), message);
+    }
+
+    #[test]
+    fn merges_and_lightweight_tags_follow_the_rewrite() {
+        let directory = repository();
+        let path = directory.path();
+        let a = commit(path, "a");
+        let b = commit(path, "b");
+        git(path, &["checkout", "-q", "-b", "side", &a], b"");
+        let d = commit(path, "d");
+        git(path, &["checkout", "-q", "main"], b"");
+        let c = commit(path, "c");
+        git(
+            path,
+            &["merge", "-q", "--no-ff", "-m", "merge", "side"],
+            b"",
+        );
+        let m = rev_parse(path, "HEAD");
+        git(path, &["tag", "light", &c], b"");
+        git(
+            path,
+            &["tag", "-a", "-m", "annotated", "annotated", &c],
+            b"",
+        );
+
+        let mapping = rewrite(path, &[&b], &rules(&[(&b, "B\n")])).unwrap();
+
+        // Only `b` and its descendants are recreated, the side branch isn't.
+        let mut rewritten = mapping.keys().cloned().collect::<Vec<_>>();
+        rewritten.sort();
+        let mut expected = vec![b.clone(), c.clone(), m.clone()];
+        expected.sort();
+        assert_eq!(rewritten, expected);
+        assert_eq!(rev_parse(path, "main"), mapping[&m]);
+        assert_eq!(rev_parse(path, "main^1"), mapping[&c]);
+        assert_eq!(rev_parse(path, "main^2"), d);
+        assert_eq!(rev_parse(path, "main^1^"), mapping[&b]);
+        assert_eq!(rev_parse(path, "main^1^^"), a);
+        assert_eq!(
+            git(path, &["log", "-1", "--format=%B", &mapping[&b]], b""),
+            b"B\n\n"
+        );
+
+        assert_eq!(rev_parse(path, "light"), mapping[&c]);
+        assert_eq!(rev_parse(path, "annotated^{commit}"), c);
+    }
+
+    #[test]
+    fn detached_head_follows_the_rewrite() {
+        let directory = repository();
+        let path = directory.path();
+        let a = commit(path, "a");
+        let b = commit(path, "b");
+        git(path, &["checkout", "-q", "--detach", "HEAD"], b"");
+        let c = commit(path, "c");
+
+        let mapping = rewrite(path, &[&a], &rules(&[(&a, "A\n")])).unwrap();
+
+        assert_eq!(mapping.len(), 3);
+        assert_eq!(rev_parse(path, "main"), mapping[&b]);
+        assert_eq!(rev_parse(path, "HEAD"), mapping[&c]);
+        assert!(gix::open(path).unwrap().head().unwrap().is_detached());
+    }
+
+    #[test]
+    fn refused_when_head_moved() {
+        let directory = repository();
+        let path = directory.path();
+        let a = commit(path, "a");
+        commit(path, "b");
+
+        pyo3::prepare_freethreaded_python();
+        let repository = gix::open(path).unwrap();
+        let id = gix::ObjectId::from_hex(a.as_bytes()).unwrap();
+        let commits = [Commit::try_from(repository.find_object(id).unwrap().detach()).unwrap()];
+        let error = Python::with_gil(|py| {
+            let plan = plan(py, &repository, &commits, &rules(&[(&a, "A\n")]))?;
+            commit(path, "c");
+            execute(py, &repository, &plan).map(|_| ())
+        })
+        .unwrap_err();
+
+        assert!(error.to_string().contains("HEAD moved"), "{error}");
+        assert_eq!(
+            git(path, &["log", "--format=%s", "--reverse"], b""),
+            b"a\nb\nc\n"
+        );
+    }
+
+    #[test]
+    fn signatures_of_recreated_commits_are_dropped() {
+        let directory = repository();
+        let path = directory.path();
+        let a = commit(path, "a");
+        let tree = rev_parse(path, "HEAD^{tree}");
+        let signed = write_commit(
+            path,
+            format!(
+                "tree {tree}\n\
+                 parent {a}\n\
+                 author Author <author@example.com> 1700000000 +0100\n\
+                 committer Committer <committer@example.com> 1700000000 +0100\n\
+                 gpgsig -----BEGIN PGP SIGNATURE-----\n \n signature\n -----END PGP SIGNATURE-----\n\
+                 \n\
+                 signed\n"
+            )
+            .as_bytes(),
+        );
+
+        let mapping = rewrite(path, &[&a], &rules(&[(&a, "A\n")])).unwrap();
+
+        let data = git(path, &["cat-file", "commit", &mapping[&signed]], b"");
+        let data = String::from_utf8(data).unwrap();
+        assert!(!data.contains("gpgsig"), "{data}");
+        assert!(data.ends_with("\nsigned\n"), "{data}");
+    }
+
+    #[test]
+    fn replaced_message_drops_its_encoding() {
+        let directory = repository();
+        let path = directory.path();
+        commit(path, "a");
+        let tree = rev_parse(path, "HEAD^{tree}");
+        let mut data = format!(
+            "tree {tree}\n\
+             author Author <author@example.com> 1700000000 +0100\n\
+             committer Committer <committer@example.com> 1700000000 +0100\n\
+             encoding ISO-8859-1\n\
+             \n"
+        )
+        .into_bytes();
+        data.extend_from_slice(b"caf\xe9\n");
+        let encoded = write_commit(path, &data);
+
+        let mapping = rewrite(path, &[&encoded], &rules(&[(&encoded, "café\n")])).unwrap();
+
+        let data = git(path, &["cat-file", "commit", &mapping[&encoded]], b"");
+        let data = String::from_utf8(data).unwrap();
+        assert!(!data.contains("encoding"), "{data}");
+        assert!(data.ends_with("\n\ncafé\n"), "{data}");
+    }
+}
@@ -0,0 +1,181 @@
+//! Plain data representations of the core types for reports and web APIs.
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use serde_json::Value;
+
+use crate::paged::FileDiff;
+use crate::{Commit, Signature};
+
+/// A time as an RFC 3339 timestamp in the offset it was recorded in.
+fn time(time: &jiff::Zoned) -> String {
+    time.timestamp()
+        .display_with_offset(time.offset())
+        .to_string()
+}
+
+pub(crate) fn signature(signature: &Signature) -> Value {
+    serde_json::json!({
+        "name": signature.name,
+        "email": signature.email,
+        "utc_offset": signature.utc_offset,
+    })
+}
+
+pub(crate) fn commit(commit: &Commit) -> Value {
+    serde_json::json!({
+        "id": commit.id,
+        "parent_ids": commit.parent_ids,
+        "summary": commit.summary(),
+        "body": commit.body(),
+        "author": signature(&commit.author),
+        "author_time": time(&commit.author_time),
+        "committer": signature(&commit.committer),
+        "commit_time": time(&commit.commit_time),
+        "trailers": commit.trailer_list(),
+        "encoding": commit.encoding,
+        "extra_headers": commit.extra_headers,
+        "rewritten_parent_ids": commit.rewritten_parent_ids,
+        "is_boundary": commit.is_boundary,
+        "is_shallow": commit.is_shallow,
+        "calendar": commit.calendar.as_ref().map(|day| serde_json::json!({
+            "region": day.region,
+            "weekday": day.weekday,
+            "is_weekend": day.is_weekend,
+            "holiday": day.holiday,
+        })),
+    })
+}
+
+/// The name and version of the schema of `diff`.
+const DIFF_FORMAT: &str = "gitch-diff";
+const DIFF_VERSION: u64 = 1;
+
+/// The changed files of a diff with their hunks and lines:
+///
+/// - `format`, `version`: `"gitch-diff"` and `1`, the version goes up with
+///   any change that isn't just an added key.
+/// - `stats`: `files_changed`, `insertions`, and `deletions` of all files.
+/// - `files`: for every file the keys of `FileDiff` (`kind`, `path`,
+///   `previous_path`, `mode`, `previous_mode`, `id`, `previous_id`,
+///   `similarity`, `binary`, `suppressed_size`) with its own `insertions`
+///   and `deletions` and its `hunks`.
+/// - `hunks`: `header` (the `@@` line), `old_start`, `old_lines`,
+///   `new_start`, `new_lines`, and `lines`.
+/// - `lines`: `origin` (`" "`, `"-"`, or `"+"`), `old_lineno`,
+///   `new_lineno`, `content` without its line terminator,
+///   `changed_ranges`, and `moved`.
+///
+/// Paths and content not in UTF-8 have the invalid bytes replaced (with
+/// U+FFFD), `changed_ranges` are byte offsets into the original content.
+pub(crate) fn diff(py: Python<'_>, files: &[FileDiff]) -> Value {
+    let (mut insertions, mut deletions) = (0, 0);
+    let files: Vec<_> = files
+        .iter()
+        .map(|file| {
+            let (mut file_insertions, mut file_deletions) = (0, 0);
+            let hunks: Vec<_> = file
+                .hunks
+                .iter()
+                .map(|hunk| {
+                    let hunk = hunk.borrow(py);
+                    let header = hunk.content.split(|&byte| byte == b'\n').next();
+                    let lines: Vec<_> = hunk
+                        .lines()
+                        .into_iter()
+                        .map(|line| {
+                            match line.origin {
+                                '+' => file_insertions += 1,
+                                '-' => file_deletions += 1,
+                                _ => {}
+                            }
+                            let content = line.content.strip_suffix(b"\n").unwrap_or(&line.content);
+                            serde_json::json!({
+                                "origin": line.origin,
+                                "old_lineno": line.old_lineno,
+                                "new_lineno": line.new_lineno,
+                                "content": String::from_utf8_lossy(content),
+                                "changed_ranges": line.changed_ranges,
+                                "moved": line.moved,
+                            })
+                        })
+                        .collect();
+                    serde_json::json!({
+                        "header": String::from_utf8_lossy(header.unwrap_or_default()),
+                        "old_start": hunk.old_start,
+                        "old_lines": hunk.old_lines,
+                        "new_start": hunk.new_start,
+                        "new_lines": hunk.new_lines,
+                        "lines": lines,
+                    })
+                })
+                .collect();
+            insertions += file_insertions;
+            deletions += file_deletions;
+            serde_json::json!({
+                "kind": file.kind,
+                "path": file.path,
+                "previous_path": file.previous_path,
+                "mode": file.mode,
+                "previous_mode": file.previous_mode,
+                "id": file.id,
+                "previous_id": file.previous_id,
+                "similarity": file.similarity,
+                "binary": file.binary,
+                "suppressed_size": file.suppressed_size,
+                "insertions": file_insertions,
+                "deletions": file_deletions,
+                "hunks": hunks,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "format": DIFF_FORMAT,
+        "version": DIFF_VERSION,
+        "stats": {
+            "files_changed": files.len(),
+            "insertions": insertions,
+            "deletions": deletions,
+        },
+        "files": files,
+    })
+}
+
+/// Convert to the Python objects `json.loads` would produce.
+pub(crate) fn to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(value) => value.into_pyobject(py)?.to_owned().into_any(),
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(number), _) => number.into_pyobject(py)?.into_any(),
+            (None, Some(number)) => number.into_pyobject(py)?.into_any(),
+            (None, None) => number.as_f64().into_pyobject(py)?.into_any(),
+        },
+        Value::String(value) => value.into_pyobject(py)?.into_any(),
+        Value::Array(values) => PyList::new(
+            py,
+            values
+                .iter()
+                .map(|value| to_py(py, value))
+                .collect::<PyResult<Vec<_>>>()?,
+        )?
+        .into_any(),
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+/// Render as JSON, indented by two spaces if `pretty`.
+pub(crate) fn to_json(value: &Value, pretty: bool) -> String {
+    if pretty {
+        serde_json::to_string_pretty(value).unwrap()
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,340 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::IntoPyResult;
+
+/// Chunk boundaries are where the low bits of the rolling hash are zero,
+/// which makes chunks 1 KiB on average.
+const BOUNDARY_MASK: u64 = (1 << 10) - 1;
+const MIN_CHUNK: usize = 256;
+const MAX_CHUNK: usize = 8 * 1024;
+
+/// The number of values of a MinHash signature.
+const SIGNATURE: usize = 128;
+/// The probability with which a pair of blobs exactly as similar as the
+/// threshold becomes a candidate, more similar ones are more likely.
+const RECALL: f64 = 0.99;
+/// Below this threshold banding lets so many pairs through that it isn't
+/// worth it, every pair is compared.
+const EXHAUSTIVE_BELOW: f64 = 0.5;
+
+/// Two blobs with mostly the same content.
+#[pyclass(frozen)]
+pub struct SimilarBlobs {
+    #[pyo3(get)]
+    pub first: String,
+    #[pyo3(get)]
+    pub first_size: u64,
+    #[pyo3(get)]
+    pub second: String,
+    #[pyo3(get)]
+    pub second_size: u64,
+    /// The share of distinct chunks the blobs have in common (their Jaccard
+    /// index), between 0 and 1.
+    #[pyo3(get)]
+    pub similarity: f64,
+}
+
+/// The SplitMix64 finalizer, a cheap but well mixing 64-bit hash.
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// The hashes of the distinct content-defined chunks of `data`.
+///
+/// Boundaries come from a gear hash of the preceding bytes, so an insertion
+/// or deletion only changes the chunks around it and the rest still match.
+fn chunks(data: &[u8]) -> Vec<u64> {
+    let gear: [u64; 256] = std::array::from_fn(|byte| mix(byte as u64));
+    let mut hashes = Vec::new();
+    let mut start = 0;
+    let mut rolling = 0u64;
+    for (index, &byte) in data.iter().enumerate() {
+        rolling = (rolling << 1).wrapping_add(gear[usize::from(byte)]);
+        let len = index + 1 - start;
+        if (len >= MIN_CHUNK && rolling & BOUNDARY_MASK == 0) || len >= MAX_CHUNK {
+            hashes.push(fnv1a(&data[start..=index]));
+            start = index + 1;
+            rolling = 0;
+        }
+    }
+    if start < data.len() {
+        hashes.push(fnv1a(&data[start..]));
+    }
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn minhash(chunks: &[u64]) -> [u64; SIGNATURE] {
+    std::array::from_fn(|slot| {
+        let seed = mix(slot as u64);
+        chunks
+            .iter()
+            .map(|&chunk| mix(chunk ^ seed))
+            .min()
+            .unwrap_or(u64::MAX)
+    })
+}
+
+/// How to split a signature into bands of rows for `threshold`: blobs
+/// agreeing on all values of any band are compared exactly.
+///
+/// A pair of similarity `s` agrees on a band with probability `s^rows`, so it
+/// becomes a candidate with `1 - (1 - s^rows)^bands`. These are the most rows
+/// (which let the fewest dissimilar pairs through) for which that is at least
+/// `RECALL` at the threshold.
+fn banding(threshold: f64) -> (usize, usize) {
+    (1..=SIGNATURE)
+        .rev()
+        .map(|rows| (SIGNATURE / rows, rows))
+        .find(|&(bands, rows)| candidate_probability(threshold, bands, rows) >= RECALL)
+        .unwrap_or((SIGNATURE, 1))
+}
+
+/// The probability that a pair of similarity `similarity` agrees on at least
+/// one of `bands` bands of `rows` values.
+fn candidate_probability(similarity: f64, bands: usize, rows: usize) -> f64 {
+    1.0 - (1.0 - similarity.powi(rows as i32)).powi(bands as i32)
+}
+
+/// The Jaccard index of two sorted sets.
+fn jaccard(a: &[u64], b: &[u64]) -> f64 {
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    let union = a.len() + b.len() - common;
+    if union == 0 {
+        0.0
+    } else {
+        common as f64 / union as f64
+    }
+}
+
+/// Find pairs of blobs of at least `min_size` bytes anywhere in the object
+/// database whose content is at least `threshold` similar.
+///
+/// Blobs are split into content-defined chunks. From a `threshold` of 0.5
+/// candidate pairs are found with MinHash and locality-sensitive hashing,
+/// banded for the threshold (see `banding`) so not every pair of blobs has
+/// to be compared, below it every pair is a candidate. Candidates are then
+/// compared exactly, which means pairs can be missed (at most 1% of those
+/// right at `threshold`, fewer of the more similar ones) but no dissimilar
+/// ones are reported.
+pub(crate) fn similar_blobs(
+    repository: &gix::ThreadSafeRepository,
+    threshold: f64,
+    min_size: u64,
+) -> PyResult<Vec<SimilarBlobs>> {
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(PyValueError::new_err(format!(
+            "threshold must be between 0 and 1, got {threshold}"
+        )));
+    }
+    let local = repository.to_thread_local();
+    let mut blobs = Vec::new();
+    let mut seen = HashSet::new();
+    for id in local.objects.iter().into_py_result()? {
+        let id = id.into_py_result()?;
+        // The same object can be stored in several packs or loose as well.
+        if !seen.insert(id) {
+            continue;
+        }
+        let header = local.find_header(id).into_py_result()?;
+        if header.kind() == gix::object::Kind::Blob && header.size() >= min_size {
+            blobs.push((id, header.size()));
+        }
+    }
+
+    // Chunking reads every blob, spread that over all cores.
+    let workers = std::thread::available_parallelism()
+        .map(usize::from)
+        .unwrap_or(1);
+    let batch = blobs.len().div_ceil(workers).max(1);
+    let hashed = std::thread::scope(|scope| {
+        let handles = blobs
+            .chunks(batch)
+            .map(|batch| {
+                scope.spawn(move || -> PyResult<Vec<_>> {
+                    let repository = repository.to_thread_local();
+                    batch
+                        .iter()
+                        .map(|&(id, size)| {
+                            let blob = repository.find_object(id).into_py_result()?;
+                            let chunks = chunks(&blob.data);
+                            let signature = minhash(&chunks);
+                            Ok((id, size, chunks, signature))
+                        })
+                        .collect()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<PyResult<Vec<_>>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    let mut candidates = HashSet::new();
+    if threshold < EXHAUSTIVE_BELOW {
+        for first in 0..hashed.len() {
+            candidates.extend((first + 1..hashed.len()).map(|second| (first, second)));
+        }
+    } else {
+        let (bands, rows) = banding(threshold);
+        for band in 0..bands {
+            let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+            for (index, (.., signature)) in hashed.iter().enumerate() {
+                buckets
+                    .entry(&signature[band * rows..(band + 1) * rows])
+                    .or_default()
+                    .push(index);
+            }
+            for bucket in buckets.values() {
+                for (position, &first) in bucket.iter().enumerate() {
+                    for &second in &bucket[position + 1..] {
+                        candidates.insert((first, second));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut similar = candidates
+        .into_iter()
+        .filter_map(|(first, second)| {
+            let (first_id, first_size, first_chunks, _) = &hashed[first];
+            let (second_id, second_size, second_chunks, _) = &hashed[second];
+            let similarity = jaccard(first_chunks, second_chunks);
+            let ((first, first_size), (second, second_size)) = if first_id < second_id {
+                ((first_id, first_size), (second_id, second_size))
+            } else {
+                ((second_id, second_size), (first_id, first_size))
+            };
+            (similarity >= threshold).then(|| SimilarBlobs {
+                first: first.to_string(),
+                first_size: *first_size,
+                second: second.to_string(),
+                second_size: *second_size,
+                similarity,
+            })
+        })
+        .collect::<Vec<_>>();
+    similar.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| (&a.first, &a.second).cmp(&(&b.first, &b.second)))
+    });
+    Ok(similar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EXHAUSTIVE_BELOW, RECALL, SIGNATURE, banding, candidate_probability, chunks, jaccard, mix,
+        similar_blobs,
+    };
+    use crate::tests::git;
+
+    use std::path::Path;
+
+    /// `len` bytes of noise that only repeat for the same `seed`.
+    fn noise(seed: u64, len: usize) -> Vec<u8> {
+        (0..len as u64)
+            .map(|index| mix(seed << 32 | index) as u8)
+            .collect()
+    }
+
+    fn store(directory: &Path, content: &[u8]) -> String {
+        let id = git(directory, &["hash-object", "-w", "--stdin"], content);
+        String::from_utf8(id).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn banding_catches_pairs_at_the_threshold() {
+        let mut previous_rows = SIGNATURE;
+        for threshold in [1.0, 0.95, 0.9, 0.8, 0.7, 0.6, EXHAUSTIVE_BELOW] {
+            let (bands, rows) = banding(threshold);
+            assert!(bands * rows <= SIGNATURE);
+            assert!(candidate_probability(threshold, bands, rows) >= RECALL);
+            // Lower thresholds need shorter bands to still agree on one.
+            assert!(rows <= previous_rows, "{threshold}: {rows} rows");
+            previous_rows = rows;
+        }
+        // The fixed 32 bands of 4 rows missed most pairs at 0.5.
+        assert!(candidate_probability(0.5, 32, 4) < 0.9);
+    }
+
+    #[test]
+    fn finds_pairs_below_half_similar() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q"], b"");
+        let first = noise(1, 64 * 1024);
+        let mut second = first[..32 * 1024].to_vec();
+        second.extend(noise(2, 32 * 1024));
+        let unrelated = noise(3, 64 * 1024);
+        let first_id = store(path, &first);
+        let second_id = store(path, &second);
+        store(path, &unrelated);
+
+        let expected = jaccard(&chunks(&first), &chunks(&second));
+        assert!((0.2..EXHAUSTIVE_BELOW).contains(&expected), "{expected}");
+        let repository = gix::ThreadSafeRepository::open(path).unwrap();
+        let similar = similar_blobs(&repository, 0.2, 1024).unwrap();
+        assert_eq!(similar.len(), 1);
+        let pair = &similar[0];
+        let mut ids = [pair.first.clone(), pair.second.clone()];
+        ids.sort();
+        let mut expected_ids = [first_id, second_id];
+        expected_ids.sort();
+        assert_eq!(ids, expected_ids);
+        assert_eq!(pair.similarity, expected);
+
+        assert!(similar_blobs(&repository, 0.9, 1024).unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_near_copies_with_banding() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        git(path, &["init", "-q"], b"");
+        let first = noise(1, 256 * 1024);
+        let mut second = first.clone();
+        second.splice(100 * 1024..100 * 1024, noise(2, 4 * 1024));
+        store(path, &first);
+        store(path, &second);
+        store(path, &noise(3, 256 * 1024));
+
+        let expected = jaccard(&chunks(&first), &chunks(&second));
+        assert!(expected >= 0.9, "{expected}");
+        let repository = gix::ThreadSafeRepository::open(path).unwrap();
+        let similar = similar_blobs(&repository, 0.9, 1024).unwrap();
+        assert_eq!(similar.len(), 1);
+        assert_eq!(similar[0].similarity, expected);
+        assert!(similar_blobs(&repository, 1.5, 1024).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use pyo3::prelude::*;
+
+use crate::IntoPyResult;
+
+/// The state of the filtered paths in a commit, `None` for paths that don't
+/// exist in it.
+type PathState = Vec<Option<(gix::ObjectId, gix::objs::tree::EntryMode)>>;
+
+/// Commits mapped to their (rewritten) parents.
+type Parents = HashMap<gix::ObjectId, Vec<gix::ObjectId>>;
+
+struct PathStates<'a> {
+    repository: &'a gix::Repository,
+    paths: &'a [PathBuf],
+    states: HashMap<gix::ObjectId, PathState>,
+}
+
+impl PathStates<'_> {
+    fn get(&mut self, id: gix::ObjectId) -> PyResult<&PathState> {
+        if !self.states.contains_key(&id) {
+            let tree = self
+                .repository
+                .find_commit(id)
+                .into_py_result()?
+                .tree()
+                .into_py_result()?;
+            let mut state = Vec::with_capacity(self.paths.len());
+            for path in self.paths {
+                state.push(
+                    tree.lookup_entry_by_path(path)
+                        .into_py_result()?
+                        .map(|entry| (entry.oid().to_owned(), entry.mode())),
+                );
+            }
+            self.states.insert(id, state);
+        }
+        Ok(&self.states[&id])
+    }
+
+    /// Whether `id` has the same content as `other` at all filtered paths
+    /// (git calls this TREESAME).
+    fn same(&mut self, id: gix::ObjectId, other: gix::ObjectId) -> PyResult<bool> {
+        let state = self.get(id)?.clone();
+        Ok(*self.get(other)? == state)
+    }
+}
+
+/// Apply git's default history simplification for `paths` to the walked
+/// commits.
+///
+/// A commit is kept if it differs at the filtered paths from every one of its
+/// parents (or, for root commits, if any of the paths exist). A commit that is
+/// the same as one of its parents is dropped and history is followed through
+/// that parent only, commits that are then no longer reachable are dropped as
+/// well. Without paths every commit is kept.
+///
+/// Returns the kept commits mapped to their rewritten parents: the nearest kept
+/// ancestors along the followed history. Parents that fall outside of the
+/// walked commits are left out so the resulting graph only ever refers to
+/// commits that are part of it.
+pub(crate) fn by_paths(
+    repository: &gix::Repository,
+    infos: &[gix::revision::walk::Info<'_>],
+    paths: &[PathBuf],
+) -> PyResult<Parents> {
+    Ok(by_paths_with_dropped(repository, infos, paths)?.0)
+}
+
+/// Like `by_paths`, also returning the dropped commits mapped to the kept
+/// commit they were simplified to, `None` if history ends before one.
+pub(crate) fn by_paths_with_dropped(
+    repository: &gix::Repository,
+    infos: &[gix::revision::walk::Info<'_>],
+    paths: &[PathBuf],
+) -> PyResult<(Parents, HashMap<gix::ObjectId, Option<gix::ObjectId>>)> {
+    let mut states = PathStates {
+        repository,
+        paths,
+        states: HashMap::new(),
+    };
+
+    // Decide which commits are kept and which parent the others follow.
+    let mut kept = HashSet::new();
+    let mut follow = HashMap::new();
+    for info in infos {
+        if paths.is_empty() {
+            kept.insert(info.id);
+            continue;
+        }
+        let mut same_parent = None;
+        for parent_id in &info.parent_ids {
+            if states.same(info.id, *parent_id)? {
+                same_parent = Some(*parent_id);
+                break;
+            }
+        }
+        match same_parent {
+            Some(parent_id) => {
+                follow.insert(info.id, Some(parent_id));
+            }
+            None if info.parent_ids.is_empty()
+                && states.get(info.id)?.iter().all(Option::is_none) =>
+            {
+                follow.insert(info.id, None);
+            }
+            None => {
+                kept.insert(info.id);
+            }
+        }
+    }
+
+    // Commits only reachable through parents that weren't followed are
+    // simplified away too.
+    let walked = infos
+        .iter()
+        .map(|info| (info.id, &info.parent_ids))
+        .collect::<HashMap<_, _>>();
+    let mut reachable = HashSet::new();
+    let mut stack = infos
+        .first()
+        .map(|info| info.id)
+        .into_iter()
+        .collect::<Vec<_>>();
+    while let Some(id) = stack.pop() {
+        let Some(parent_ids) = walked.get(&id) else {
+            continue;
+        };
+        if !reachable.insert(id) {
+            continue;
+        }
+        match follow.get(&id) {
+            Some(parent_id) => stack.extend(*parent_id),
+            None => stack.extend(parent_ids.iter().copied()),
+        }
+    }
+    kept.retain(|id| reachable.contains(id));
+
+    let resolve = |mut id: gix::ObjectId| loop {
+        if kept.contains(&id) {
+            return Some(id);
+        }
+        id = (*follow.get(&id)?)?;
+    };
+
+    let kept_parents = infos
+        .iter()
+        .filter(|info| kept.contains(&info.id))
+        .map(|info| {
+            let mut parents = Vec::new();
+            for parent_id in info.parent_ids.iter().filter_map(|id| resolve(*id)) {
+                if !parents.contains(&parent_id) {
+                    parents.push(parent_id);
+                }
+            }
+            (info.id, parents)
+        })
+        .collect();
+    let dropped = infos
+        .iter()
+        .filter(|info| !kept.contains(&info.id))
+        .map(|info| (info.id, resolve(info.id)))
+        .collect();
+    Ok((kept_parents, dropped))
+}
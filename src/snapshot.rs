@@ -0,0 +1,131 @@
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::IntoPyResult;
+
+/// How far back `RepositorySnapshot.recent_contributors` looks.
+const RECENT_SECONDS: i64 = 90 * 24 * 60 * 60;
+
+/// Repository-level statistics gathered in a single pass.
+#[pyclass]
+pub struct RepositorySnapshot {
+    /// The number of refs by kind: `branches`, `remote_branches`, `tags`, and
+    /// `other` (e.g. notes or stashes).
+    #[pyo3(get)]
+    pub ref_counts: HashMap<String, usize>,
+    /// The commit HEAD points to, `None` for an unborn HEAD.
+    #[pyo3(get)]
+    pub head_id: Option<String>,
+    /// The branch HEAD points to, `None` if it is detached.
+    #[pyo3(get)]
+    pub head_branch: Option<String>,
+    /// The most recent tag reachable from HEAD.
+    #[pyo3(get)]
+    pub latest_tag: Option<String>,
+    /// The number of commits reachable from HEAD.
+    #[pyo3(get)]
+    pub total_commits: usize,
+    /// The number of distinct author emails of commits made in the last 90
+    /// days that are reachable from HEAD.
+    #[pyo3(get)]
+    pub recent_contributors: usize,
+    /// The size of the object database on disk in bytes.
+    #[pyo3(get)]
+    pub size_bytes: u64,
+}
+
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            directory_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+pub(crate) fn snapshot(repository: &gix::Repository) -> PyResult<RepositorySnapshot> {
+    let mut ref_counts = HashMap::from(
+        ["branches", "remote_branches", "tags", "other"].map(|kind| (kind.to_string(), 0)),
+    );
+    // Tagged commits and the names of their tags.
+    let mut tagged = HashMap::<gix::ObjectId, Vec<String>>::new();
+    for reference in repository
+        .references()
+        .into_py_result()?
+        .all()
+        .into_py_result()?
+    {
+        let mut reference = reference.map_err(|e| PyOSError::new_err(e.to_string()))?;
+        let kind = match reference.name().category() {
+            Some(gix::refs::Category::LocalBranch) => "branches",
+            Some(gix::refs::Category::RemoteBranch) => "remote_branches",
+            Some(gix::refs::Category::Tag) => "tags",
+            _ => "other",
+        };
+        *ref_counts.get_mut(kind).unwrap() += 1;
+
+        if kind == "tags"
+            && let Ok(id) = reference.peel_to_id_in_place()
+        {
+            tagged
+                .entry(id.detach())
+                .or_default()
+                .push(reference.name().shorten().to_string());
+        }
+    }
+
+    let head = repository.head().into_py_result()?;
+    let head_branch = head
+        .referent_name()
+        .filter(|_| !head.is_detached())
+        .map(|name| name.shorten().to_string());
+    let head_id = repository.head_id().ok().map(|id| id.detach());
+
+    let mut total_commits = 0;
+    let mut latest_tag = None;
+    let mut contributors = HashSet::new();
+    if let Some(head_id) = head_id {
+        let recent = jiff::Timestamp::now().as_second() - RECENT_SECONDS;
+        // A single walk (sped up by the commit-graph if there is one) is enough
+        // for all history based statistics.
+        for info in repository
+            .rev_walk([head_id])
+            .sorting(gix::revision::walk::Sorting::ByCommitTime(
+                gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+            ))
+            .all()
+            .into_py_result()?
+        {
+            let info = info.into_py_result()?;
+            total_commits += 1;
+            if latest_tag.is_none()
+                && let Some(tags) = tagged.get(&info.id)
+            {
+                latest_tag = tags.iter().max().cloned();
+            }
+            if info.commit_time.is_some_and(|time| time >= recent) {
+                let object = info.object().into_py_result()?;
+                let commit = gix::objs::CommitRef::from_bytes(&object.data).into_py_result()?;
+                contributors.insert(commit.author().email.to_string());
+            }
+        }
+    }
+
+    Ok(RepositorySnapshot {
+        ref_counts,
+        head_id: head_id.map(|id| id.to_string()),
+        head_branch,
+        latest_tag,
+        total_commits,
+        recent_contributors: contributors.len(),
+        size_bytes: directory_size(&repository.common_dir().join("objects")).into_py_result()?,
+    })
+}
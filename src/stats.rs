@@ -0,0 +1,643 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use gix::diff::blob::intern::InternedInput;
+use gix::diff::blob::platform::resource::Data;
+use gix::object::tree::diff::ChangeDetached;
+
+use crate::{IntoPyResult, binary, diff};
+
+/// What `Repository.diff` returns.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// The patch.
+    Patch,
+    /// The lines of `git diff --numstat`.
+    Numstat,
+    /// The histogram of `git diff --stat`.
+    Stat,
+    /// The files, hunks, and lines as JSON, see `serialize::diff`.
+    Json,
+    /// The files, hunks, and lines as HTML, see `html`.
+    Html,
+}
+
+impl OutputFormat {
+    pub(crate) fn parse(format: &str) -> PyResult<Self> {
+        match format {
+            "patch" => Ok(Self::Patch),
+            "numstat" => Ok(Self::Numstat),
+            "stat" => Ok(Self::Stat),
+            "json" => Ok(Self::Json),
+            "html" => Ok(Self::Html),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown format {format:?}, expected one of patch, numstat, stat, json, or html"
+            ))),
+        }
+    }
+}
+
+/// The size of a commit's changes like `git diff --shortstat`.
+#[pyclass(frozen)]
+pub struct DiffStat {
+    #[pyo3(get)]
+    pub files_changed: usize,
+    #[pyo3(get)]
+    pub insertions: usize,
+    #[pyo3(get)]
+    pub deletions: usize,
+}
+
+/// Count the changes of `commit` against its first parent.
+///
+/// Only the lines are diffed, no patch is formatted. Like git, binary files,
+/// pure renames, and mode changes count as changed files without any lines,
+/// and submodules as files of a `Subproject commit <id>` line.
+pub(crate) fn stats(
+    commit: &gix::Commit<'_>,
+    algorithm: gix::diff::blob::Algorithm,
+) -> PyResult<DiffStat> {
+    let mut resource_cache = diff::resource_cache(commit.repo);
+    let mut stat = DiffStat {
+        files_changed: 0,
+        insertions: 0,
+        deletions: 0,
+    };
+    for change in diff::changes_with_parent(commit) {
+        let null = gix::index::hash::Kind::Sha1.null();
+        let counted =
+            |mode: &gix::object::tree::EntryMode| mode.is_blob_or_symlink() || mode.is_commit();
+        let (old_location, old_id, new_location, new_id) = match &change {
+            ChangeDetached::Addition {
+                location,
+                entry_mode,
+                id,
+                ..
+            } if counted(entry_mode) => (location, null, location, *id),
+            ChangeDetached::Deletion {
+                location,
+                entry_mode,
+                id,
+                ..
+            } if counted(entry_mode) => (location, *id, location, null),
+            ChangeDetached::Modification {
+                location,
+                previous_entry_mode,
+                entry_mode,
+                previous_id,
+                id,
+            } if counted(previous_entry_mode) && counted(entry_mode) => {
+                (location, *previous_id, location, *id)
+            }
+            ChangeDetached::Rewrite {
+                source_location,
+                source_entry_mode,
+                source_id,
+                location,
+                entry_mode,
+                id,
+                ..
+            } if source_entry_mode.is_blob_or_symlink() && entry_mode.is_blob_or_symlink() => {
+                (source_location, *source_id, location, *id)
+            }
+            _ => continue,
+        };
+        stat.files_changed += 1;
+        if old_id == new_id {
+            continue;
+        }
+        let (source_mode, mode) = (
+            change.source_entry_mode_and_id().0,
+            change.entry_mode_and_id().0,
+        );
+        if source_mode.is_commit() || mode.is_commit() {
+            let text = |mode: gix::object::tree::EntryMode, id: gix::ObjectId| -> Vec<u8> {
+                if id.is_null() {
+                    Vec::new()
+                } else if mode.is_commit() {
+                    format!("Subproject commit {id}\n").into_bytes()
+                } else {
+                    binary::read(&commit.repo.objects, &id, &mut Vec::new()).to_vec()
+                }
+            };
+            let (old, new) = (text(source_mode, old_id), text(mode, new_id));
+            let counter = count(algorithm, &old, &new);
+            stat.insertions += counter.insertions as usize;
+            stat.deletions += counter.removals as usize;
+            continue;
+        }
+
+        resource_cache
+            .set_resource(
+                old_id,
+                gix::object::tree::EntryKind::Blob,
+                old_location.as_ref(),
+                gix::diff::blob::ResourceKind::OldOrSource,
+                &commit.repo.objects,
+            )
+            .into_py_result()?;
+        resource_cache
+            .set_resource(
+                new_id,
+                gix::object::tree::EntryKind::Blob,
+                new_location.as_ref(),
+                gix::diff::blob::ResourceKind::NewOrDestination,
+                &commit.repo.objects,
+            )
+            .into_py_result()?;
+        let outcome = resource_cache.prepare_diff().into_py_result()?;
+        // Binary data has no lines to count.
+        if [outcome.old.data, outcome.new.data]
+            .iter()
+            .any(|data| matches!(data, Data::Binary { .. }))
+        {
+            continue;
+        }
+        let old = outcome.old.data.as_slice().unwrap_or_default();
+        let new = outcome.new.data.as_slice().unwrap_or_default();
+        let counter = count(algorithm, old, new);
+        stat.insertions += counter.insertions as usize;
+        stat.deletions += counter.removals as usize;
+    }
+    Ok(stat)
+}
+
+/// The inserted and removed lines from `old` to `new`.
+fn count(
+    algorithm: gix::diff::blob::Algorithm,
+    old: &[u8],
+    new: &[u8],
+) -> gix::diff::blob::sink::Counter<()> {
+    let input = InternedInput::new(
+        gix::diff::blob::sources::byte_lines_with_terminator(old),
+        gix::diff::blob::sources::byte_lines_with_terminator(new),
+    );
+    gix::diff::blob::diff(algorithm, &input, gix::diff::blob::sink::Counter::default())
+}
+
+/// A count of `git diff --numstat`, `-` for the lines of binary files.
+#[derive(IntoPyObject)]
+pub(crate) enum Count {
+    Lines(usize),
+    Binary(&'static str),
+}
+
+/// A renamed or copied file's path the way `--stat` and `--numstat` show it,
+/// with the parts both paths share around the change in braces like
+/// `src/{old => new}/lib.rs` (`pprint_rename` of git).
+fn rename_path(old: &[u8], new: &[u8]) -> Vec<u8> {
+    // The common prefix up to and including its last slash.
+    let mut prefix = 0;
+    for (index, (a, b)) in old.iter().zip(new).enumerate() {
+        if a != b {
+            break;
+        }
+        if *a == b'/' {
+            prefix = index + 1;
+        }
+    }
+    // The common suffix from its first slash, which may overlap the slash
+    // ending the prefix but no more.
+    let overlap = usize::from(prefix > 0);
+    let mut suffix = 0;
+    for (len, (a, b)) in old.iter().rev().zip(new.iter().rev()).enumerate() {
+        if a != b || old.len() - len <= prefix - overlap || new.len() - len <= prefix - overlap {
+            break;
+        }
+        if *a == b'/' {
+            suffix = len + 1;
+        }
+    }
+
+    let middle = |path: &[u8]| path[prefix..path.len().saturating_sub(suffix).max(prefix)].to_vec();
+    let mut out = Vec::new();
+    if prefix + suffix > 0 {
+        out.extend_from_slice(&old[..prefix]);
+        out.push(b'{');
+    }
+    out.extend_from_slice(&middle(old));
+    out.extend_from_slice(b" => ");
+    out.extend_from_slice(&middle(new));
+    if prefix + suffix > 0 {
+        out.push(b'}');
+        out.extend_from_slice(&old[old.len() - suffix..]);
+    }
+    out
+}
+
+/// A file of a patch as `--stat` and `--numstat` count it.
+pub(crate) struct FileStat {
+    /// The path as shown, see `rename_path` for renames and copies.
+    name: String,
+    /// The path after the change.
+    path: String,
+    insertions: usize,
+    deletions: usize,
+    /// The sizes in bytes before and after of a binary file, both 0 like git
+    /// if its content didn't change.
+    binary: Option<(u64, u64)>,
+    /// The mode of a created file.
+    created: Option<String>,
+    /// The mode of a deleted file.
+    deleted: Option<String>,
+    /// The modes before and after if they differ.
+    mode_change: Option<(String, String)>,
+    /// `rename` or `copy` and the similarity in percent of a renamed or
+    /// copied file.
+    rewrite: Option<(&'static str, u8)>,
+}
+
+/// The files of `patch` (with git's extended header and the default `a/` and
+/// `b/` prefixes) with their inserted and deleted lines. Files whose diff was
+/// suppressed count as binary.
+///
+/// The patch doesn't tell whether a file with an unchanged blob (only
+/// renamed or its mode changed) is binary, `is_binary` is asked for its new
+/// path instead. `size` gives the size of the blob at a path on either side
+/// of the patch.
+pub(crate) fn file_stats(
+    patch: &[u8],
+    mut is_binary: impl FnMut(&[u8]) -> bool,
+    mut size: impl FnMut(gix::diff::blob::ResourceKind, &[u8]) -> u64,
+) -> Vec<FileStat> {
+    let mut files: Vec<FileStat> = Vec::new();
+    let mut previous_deleted = false;
+    let mut lines = patch.split(|&byte| byte == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        let Some(paths) = line.strip_prefix(b"diff --git a/") else {
+            continue;
+        };
+        // Unless renamed or copied both paths are the same, `<path> b/<path>`.
+        let mut path = paths[..paths.len().saturating_sub(3) / 2].to_vec();
+        let mut source = None;
+        let (mut created, mut deleted, mut binary) = (None, None, false);
+        let (mut old_mode, mut new_mode, mut rewrite, mut percent) = (None, None, None, 0);
+        let (mut insertions, mut deletions) = (0, 0);
+        let mut in_hunk = false;
+        while let Some(line) = lines.next_if(|line| !line.starts_with(b"diff --git ")) {
+            if in_hunk || line.starts_with(b"@@ ") {
+                in_hunk = true;
+                match line.first() {
+                    Some(b'+') => insertions += 1,
+                    Some(b'-') => deletions += 1,
+                    _ => {}
+                }
+            } else if let Some(from) = line.strip_prefix(b"rename from ") {
+                source = Some(from.to_vec());
+                rewrite = Some("rename");
+            } else if let Some(from) = line.strip_prefix(b"copy from ") {
+                source = Some(from.to_vec());
+                rewrite = Some("copy");
+            } else if let Some(to) = line
+                .strip_prefix(b"rename to ")
+                .or_else(|| line.strip_prefix(b"copy to "))
+            {
+                path = to.to_vec();
+            } else if let Some(similarity) = line.strip_prefix(b"similarity index ") {
+                let similarity = similarity.strip_suffix(b"%").unwrap_or(similarity);
+                percent = std::str::from_utf8(similarity)
+                    .ok()
+                    .and_then(|similarity| similarity.parse().ok())
+                    .unwrap_or_default();
+            } else if let Some(mode) = line.strip_prefix(b"new file mode ") {
+                created = Some(String::from_utf8_lossy(mode).into_owned());
+            } else if let Some(mode) = line.strip_prefix(b"deleted file mode ") {
+                deleted = Some(String::from_utf8_lossy(mode).into_owned());
+            } else if let Some(mode) = line.strip_prefix(b"old mode ") {
+                old_mode = Some(String::from_utf8_lossy(mode).into_owned());
+            } else if let Some(mode) = line.strip_prefix(b"new mode ") {
+                new_mode = Some(String::from_utf8_lossy(mode).into_owned());
+            } else if line.starts_with(b"Binary files ")
+                || line.starts_with(b"diff suppressed (")
+                || line == b"GIT binary patch"
+            {
+                binary = true;
+            }
+        }
+        let (added, deleted_file) = (created.is_some(), deleted.is_some());
+
+        let binary = if binary {
+            let source = source.as_deref().unwrap_or(&path);
+            Some((
+                if added {
+                    0
+                } else {
+                    size(gix::diff::blob::ResourceKind::OldOrSource, source)
+                },
+                if deleted_file {
+                    0
+                } else {
+                    size(gix::diff::blob::ResourceKind::NewOrDestination, &path)
+                },
+            ))
+        } else {
+            (!in_hunk && is_binary(&path)).then_some((0, 0))
+        };
+        let name = match source {
+            Some(source) => rename_path(&source, &path),
+            None => path.clone(),
+        };
+        // A file replaced by a submodule (or the other way around) is
+        // deleted and added in the patch, but counts as one changed file.
+        if let Some(file) = files
+            .last_mut()
+            .filter(|file| added && previous_deleted && file.path.as_bytes() == path)
+        {
+            file.insertions += insertions;
+            file.deletions += deletions;
+            file.binary = file.binary.or(binary);
+            // Its type changed, as a change of its mode.
+            file.mode_change = file.deleted.take().zip(created);
+            previous_deleted = false;
+            continue;
+        }
+        previous_deleted = deleted_file;
+        files.push(FileStat {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            path: String::from_utf8_lossy(&path).into_owned(),
+            insertions,
+            deletions,
+            binary,
+            created,
+            deleted,
+            mode_change: old_mode.zip(new_mode),
+            rewrite: rewrite.map(|rewrite| (rewrite, percent)),
+        });
+    }
+    files
+}
+
+/// The files like the lines of `git diff --numstat`: inserted and deleted
+/// lines, `-` for both if the file is binary, and the path.
+pub(crate) fn numstat(files: Vec<FileStat>) -> Vec<(Count, Count, String)> {
+    files
+        .into_iter()
+        .map(|file| match file.binary {
+            Some(_) => (Count::Binary("-"), Count::Binary("-"), file.name),
+            None => (
+                Count::Lines(file.insertions),
+                Count::Lines(file.deletions),
+                file.name,
+            ),
+        })
+        .collect()
+}
+
+/// `it` changes scaled to a histogram `width` columns wide for at most
+/// `max_change` changes, at least one column for any change.
+fn scale_linear(it: usize, width: usize, max_change: usize) -> usize {
+    if it == 0 {
+        return 0;
+    }
+    1 + it * (width - 1) / max_change
+}
+
+/// The files like `git diff --stat` lays them out in `width` columns: the
+/// name, number of changed lines, and a histogram of each file followed by
+/// a summary line, `None` if there are no files.
+///
+/// Names that don't fit are shortened from the front, binary files show
+/// their sizes instead (`Bin 1024 -> 2048 bytes`).
+pub(crate) fn stat(files: &[FileStat], width: usize) -> Option<String> {
+    use std::fmt::Write;
+
+    if files.is_empty() {
+        return None;
+    }
+    let decimal_width = |number: u64| number.to_string().len() as i64;
+
+    let (mut max_len, mut max_change, mut bin_width, mut number_width) = (0, 0, 0, 0);
+    for file in files {
+        max_len = max_len.max(file.name.chars().count() as i64);
+        match file.binary {
+            // `Bin XXX -> YYY bytes`, with the counts aligned with `Bin`.
+            Some((old, new)) => {
+                bin_width = bin_width.max(14 + decimal_width(old) + decimal_width(new));
+                number_width = 3;
+            }
+            None => max_change = max_change.max(file.insertions + file.deletions),
+        }
+    }
+    let number_width = number_width.max(decimal_width(max_change as u64));
+
+    // The name and graph take what they need if it fits, otherwise 5/8 of
+    // the width are left for the name and the rest for the graph, which
+    // gets at least 6 columns. The 6 are for ` | ` around the count and the
+    // spaces before the name and after the count.
+    let width = (width as i64).max(16 + 6 + number_width);
+    let mut graph_width = if max_change as i64 + 4 > bin_width {
+        max_change as i64
+    } else {
+        bin_width - 4
+    };
+    let mut name_width = max_len;
+    if name_width + number_width + 6 + graph_width > width {
+        if graph_width > width * 3 / 8 - number_width - 6 {
+            graph_width = (width * 3 / 8 - number_width - 6).max(6);
+        }
+        if name_width > width - number_width - 6 - graph_width {
+            name_width = width - number_width - 6 - graph_width;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let mut out = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for file in files {
+        let (mut prefix, mut name) = ("", file.name.as_str());
+        let mut len = name_width;
+        let name_len = name.chars().count() as i64;
+        if name_len > name_width {
+            prefix = "...";
+            len = (len - 3).max(0);
+            let skip = (name_len - len).max(0) as usize;
+            name = &name[name
+                .char_indices()
+                .nth(skip)
+                .map_or(name.len(), |(at, _)| at)..];
+            if let Some(slash) = name.find('/') {
+                name = &name[slash..];
+            }
+        }
+        let padding = (len - name.chars().count() as i64).max(0) as usize;
+        let number_width = number_width as usize;
+
+        if let Some((old, new)) = file.binary {
+            write!(
+                out,
+                " {prefix}{name}{:padding$} | {:>number_width$}",
+                "", "Bin"
+            )
+            .unwrap();
+            if old != 0 || new != 0 {
+                write!(out, " {old} -> {new} bytes").unwrap();
+            }
+            out.push('\n');
+            continue;
+        }
+
+        let (added, removed) = (file.insertions, file.deletions);
+        insertions += added;
+        deletions += removed;
+        let (mut add, mut del) = (added, removed);
+        if graph_width <= max_change as i64 {
+            let graph_width = graph_width as usize;
+            let mut total = scale_linear(add + del, graph_width, max_change);
+            if total < 2 && add > 0 && del > 0 {
+                total = 2;
+            }
+            if add < del {
+                add = scale_linear(add, graph_width, max_change);
+                del = total - add;
+            } else {
+                del = scale_linear(del, graph_width, max_change);
+                add = total - del;
+            }
+        }
+        let changed = added + removed;
+        writeln!(
+            out,
+            " {prefix}{name}{:padding$} | {changed:>number_width$}{}{}{}",
+            "",
+            if changed > 0 { " " } else { "" },
+            "+".repeat(add),
+            "-".repeat(del),
+        )
+        .unwrap();
+    }
+
+    let plural = |count: usize, singular: &str, plural: &str| {
+        format!("{count} {}", if count == 1 { singular } else { plural })
+    };
+    write!(out, " {} changed", plural(files.len(), "file", "files")).unwrap();
+    if insertions > 0 || deletions == 0 {
+        write!(
+            out,
+            ", {}",
+            plural(insertions, "insertion(+)", "insertions(+)")
+        )
+        .unwrap();
+    }
+    if deletions > 0 || insertions == 0 {
+        write!(
+            out,
+            ", {}",
+            plural(deletions, "deletion(-)", "deletions(-)")
+        )
+        .unwrap();
+    }
+    out.push('\n');
+    Some(out)
+}
+
+/// The created, deleted, renamed, and copied files and changed modes like
+/// `git diff --summary` lists them, one line each.
+pub(crate) fn summary(files: &[FileStat]) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    for file in files {
+        if let Some(mode) = &file.created {
+            writeln!(out, " create mode {mode} {}", file.path).unwrap();
+        } else if let Some(mode) = &file.deleted {
+            writeln!(out, " delete mode {mode} {}", file.path).unwrap();
+        } else if let Some((kind, similarity)) = file.rewrite {
+            writeln!(out, " {kind} {} ({similarity}%)", file.name).unwrap();
+            // The rename line already names the file.
+            if let Some((old, new)) = &file.mode_change {
+                writeln!(out, " mode change {old} => {new}").unwrap();
+            }
+        } else if let Some((old, new)) = &file.mode_change {
+            writeln!(out, " mode change {old} => {new} {}", file.path).unwrap();
+        }
+    }
+    out
+}
+
+/// A file's share of all changed lines for `dirstat`.
+struct Damage<'a> {
+    path: &'a str,
+    changed: u64,
+}
+
+/// Sum up the changes of the files below `base` (with its trailing slash),
+/// which start `files` and are taken from it, pushing every directory with
+/// at least `permille` of the `total` changes (`gather_dirstat` of git).
+fn gather_dirstat(
+    out: &mut Vec<(f64, String)>,
+    files: &mut &[Damage<'_>],
+    total: u64,
+    base: &str,
+    permille: u64,
+    cumulative: bool,
+) -> u64 {
+    let (mut changed, mut sources) = (0, 0);
+    while let Some(file) = files.first() {
+        let Some(rest) = file.path.strip_prefix(base) else {
+            break;
+        };
+        match rest.find('/') {
+            Some(slash) => {
+                let directory = &file.path[..base.len() + slash + 1];
+                changed += gather_dirstat(out, files, total, directory, permille, cumulative);
+                sources += 1;
+            }
+            None => {
+                changed += file.changed;
+                *files = &files[1..];
+                sources += 2;
+            }
+        }
+    }
+
+    // Neither the top level nor a directory whose changes all come from a
+    // single directory below it are reported.
+    if !base.is_empty() && sources != 1 && changed > 0 {
+        let share = changed * 1000 / total;
+        if share >= permille {
+            out.push((share as f64 / 10.0, base.to_owned()));
+            if !cumulative {
+                return 0;
+            }
+        }
+    }
+    changed
+}
+
+/// The directories with at least `limit` percent of the changed lines of
+/// `files` like `git diff --dirstat=lines`, as their percentage and path
+/// with a trailing slash.
+///
+/// Binary files count a line per 64 bytes of their sizes before and after.
+/// Changes are counted for the innermost directory only, a parent gets
+/// those reported for its subdirectories unless `cumulative`.
+pub(crate) fn dirstat(files: &[FileStat], limit: f64, cumulative: bool) -> Vec<(f64, String)> {
+    let mut damage: Vec<_> = files
+        .iter()
+        .map(|file| Damage {
+            path: &file.path,
+            changed: match file.binary {
+                Some((old, new)) => (old + new).div_ceil(64),
+                None => (file.insertions + file.deletions) as u64,
+            },
+        })
+        .collect();
+    let total = damage.iter().map(|file| file.changed).sum();
+    let mut out = Vec::new();
+    if total == 0 {
+        return out;
+    }
+    damage.sort_by(|a, b| a.path.cmp(b.path));
+    // Like git, only a tenth of a percent counts.
+    let permille = (limit * 10.0) as u64;
+    gather_dirstat(
+        &mut out,
+        &mut damage.as_slice(),
+        total,
+        "",
+        permille,
+        cumulative,
+    );
+    out
+}
@@ -0,0 +1,125 @@
+use pyo3::prelude::*;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{Commit, IntoPyResult};
+
+/// How closely the commits of one month follow the commit template.
+#[pyclass(frozen)]
+pub struct TemplateCompliance {
+    /// The month of the commit times, e.g. `2024-03`.
+    #[pyo3(get)]
+    pub period: String,
+    /// The number of (non-merge) commits in the month.
+    #[pyo3(get)]
+    pub commits: usize,
+    /// The share of commits that filled in each section of the template, in
+    /// template order.
+    #[pyo3(get)]
+    pub sections: Vec<(String, f64)>,
+}
+
+/// The name of a section heading such as `Why:`, `# Testing:`, or
+/// `Refs: <ticket>`.
+fn heading(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start_matches('#').trim();
+    let (name, rest) = line.split_once(':')?;
+    let valid = name.len() <= 40
+        && name.starts_with(|char: char| char.is_alphabetic())
+        && name
+            .chars()
+            .all(|char| char.is_alphanumeric() || matches!(char, ' ' | '-' | '_'));
+    (valid && (rest.is_empty() || rest.starts_with(char::is_whitespace)))
+        .then(|| (name.trim(), rest.trim()))
+}
+
+/// The section names of a template, in order.
+///
+/// Sections are the lines of the form `Name:` (optionally commented out and
+/// followed by placeholder text), the summary line isn't one.
+fn sections(template: &str) -> Vec<String> {
+    let mut sections = Vec::<String>::new();
+    for line in template.lines() {
+        if let Some((name, _)) = heading(line)
+            && !sections
+                .iter()
+                .any(|section| section.eq_ignore_ascii_case(name))
+        {
+            sections.push(name.to_string());
+        }
+    }
+    sections
+}
+
+/// The sections that have content in a message, either after the colon or on
+/// the lines up to the next section.
+fn filled(sections: &[String], message: &str) -> Vec<bool> {
+    let mut filled = vec![false; sections.len()];
+    let mut current = None;
+    // The summary can look like a heading (`fix: ...`) but is never one.
+    for line in message.lines().skip(1) {
+        let section = heading(line).and_then(|(name, rest)| {
+            let index = sections
+                .iter()
+                .position(|section| section.eq_ignore_ascii_case(name))?;
+            Some((index, rest))
+        });
+        match (section, current) {
+            (Some((index, rest)), _) => {
+                filled[index] |= !rest.is_empty();
+                current = Some(index);
+            }
+            (None, Some(index)) => filled[index] |= !line.trim().is_empty(),
+            (None, None) => {}
+        }
+    }
+    filled
+}
+
+/// Read the template at `path`, relative to the working tree (or from `HEAD`
+/// in bare repositories).
+pub(crate) fn read(repository: &gix::Repository, path: &Path) -> PyResult<String> {
+    let data = match repository.workdir() {
+        Some(root) => std::fs::read(root.join(path)).into_py_result()?,
+        None => {
+            let spec = format!("HEAD:{}", path.display());
+            let id = repository
+                .rev_parse_single(spec.as_str())
+                .into_py_result()?;
+            id.object().into_py_result()?.detach().data
+        }
+    };
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// The per-month share of commits that fill in each section of `template`.
+/// Merges are left out as their messages are usually generated.
+pub(crate) fn template_drift(template: &str, commits: &[Commit]) -> Vec<TemplateCompliance> {
+    let sections = sections(template);
+    let mut months = BTreeMap::<String, (usize, Vec<usize>)>::new();
+    for commit in commits.iter().filter(|commit| commit.parent_ids.len() <= 1) {
+        let (count, followed) = months
+            .entry(commit.commit_time.strftime("%Y-%m").to_string())
+            .or_insert_with(|| (0, vec![0; sections.len()]));
+        *count += 1;
+        for (followed, filled) in followed
+            .iter_mut()
+            .zip(filled(&sections, &commit.message()))
+        {
+            *followed += usize::from(filled);
+        }
+    }
+    months
+        .into_iter()
+        .map(|(period, (commits, followed))| TemplateCompliance {
+            period,
+            commits,
+            sections: sections
+                .iter()
+                .zip(followed)
+                .map(|(section, followed)| (section.clone(), followed as f64 / commits as f64))
+                .collect(),
+        })
+        .collect()
+}
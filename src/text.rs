@@ -0,0 +1,95 @@
+//! Message parsing, trailer validation, and text diffing that work on plain
+//! text without a repository or the filesystem.
+
+use pyo3::prelude::*;
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use crate::{Algorithm, message, unified_diff};
+
+/// A commit message split into its parts.
+#[pyclass(frozen)]
+pub struct Message {
+    #[pyo3(get)]
+    pub summary: String,
+    /// The message without summary and trailers, `None` if nothing is left.
+    #[pyo3(get)]
+    pub body: Option<String>,
+    /// The trailers in their original order including repeated ones.
+    #[pyo3(get)]
+    pub trailer_list: Vec<(String, String)>,
+}
+
+impl From<message::Message> for Message {
+    fn from(message: message::Message) -> Self {
+        Self {
+            summary: message.summary,
+            body: message.body,
+            trailer_list: message.trailer_list,
+        }
+    }
+}
+
+/// Parse a commit message (e.g. one being composed) exactly like the
+/// messages of `Commit`s.
+#[pyfunction]
+pub fn parse_message(message: &str) -> Message {
+    message::parse(message).into()
+}
+
+/// A rule of the `trailers` configuration, read from the attributes of any
+/// object (like `gitch.configuration.Trailer`).
+#[derive(FromPyObject)]
+pub struct TrailerRule {
+    mandatory: bool,
+    singular: bool,
+    values: BTreeSet<String>,
+}
+
+impl From<TrailerRule> for message::TrailerRule {
+    fn from(rule: TrailerRule) -> Self {
+        Self {
+            mandatory: rule.mandatory,
+            singular: rule.singular,
+            values: rule.values,
+        }
+    }
+}
+
+/// Everything wrong with the `(token, value)` pairs of `trailers` by the
+/// `(token, rule)` pairs of `rules`, as messages in the order of the rules.
+#[pyfunction]
+pub fn validate_trailers(
+    trailers: Vec<(String, String)>,
+    rules: Vec<(String, TrailerRule)>,
+) -> Vec<String> {
+    let rules = rules
+        .into_iter()
+        .map(|(token, rule)| (token, rule.into()))
+        .collect::<Vec<_>>();
+    message::validate_trailers(&trailers, &rules)
+}
+
+/// The unified diff of two texts as it would appear in a patch of a file
+/// changed from `old` to `new`, empty if they are the same. Like git, if
+/// either looks binary (has a NUL near the start) it's just a `Binary files
+/// ... differ` line.
+#[pyfunction]
+#[pyo3(signature=(old, new, algorithm=Algorithm::Myers, old_label="a", new_label="b"))]
+pub fn diff_text(
+    old: &[u8],
+    new: &[u8],
+    algorithm: Algorithm,
+    old_label: &str,
+    new_label: &str,
+) -> Cow<'static, [u8]> {
+    Cow::Owned(unified_diff::diff_text(
+        old,
+        new,
+        algorithm.into(),
+        old_label,
+        new_label,
+        None,
+    ))
+}
@@ -0,0 +1,45 @@
+//! Python functions as the `textconv` converters of diff drivers, see
+//! `gitch_engine::textconv`.
+
+use gitch_engine::textconv::{Converter, Converters};
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+use std::collections::HashMap;
+
+/// The converters of Python functions by the name of the diff driver they're
+/// for. They take the content as `bytes` and return the text as `str` or
+/// `bytes`.
+pub(crate) fn converters(converters: Option<HashMap<String, Py<PyAny>>>) -> Converters {
+    Converters::new(
+        converters
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(driver, converter)| {
+                let name = driver.clone();
+                let converter: Converter = Box::new(move |data| {
+                    convert(&name, &converter, data)
+                        .map_err(|error| gitch_engine::Error::Callback(Box::new(error)))
+                });
+                (driver, converter)
+            })
+            .collect(),
+    )
+}
+
+fn convert(driver: &str, converter: &Py<PyAny>, data: &[u8]) -> PyResult<Vec<u8>> {
+    Python::with_gil(|py| {
+        let text = converter.bind(py).call1((PyBytes::new(py, data),))?;
+        if let Ok(text) = text.downcast::<PyString>() {
+            Ok(text.to_str()?.as_bytes().to_vec())
+        } else if let Ok(text) = text.downcast::<PyBytes>() {
+            Ok(text.as_bytes().to_vec())
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "textconv of diff driver {driver} returned {}, expected str or bytes",
+                text.get_type().name()?
+            )))
+        }
+    })
+}
@@ -0,0 +1,135 @@
+use pyo3::prelude::*;
+
+use crate::IntoPyResult;
+
+/// A single entry of a tree listing, like a line of `git ls-tree -l`.
+#[pyclass]
+pub struct TreeEntry {
+    /// The path relative to the root of the repository.
+    #[pyo3(get)]
+    pub path: String,
+    /// The octal file mode (e.g. `100644` or `040000` for directories).
+    #[pyo3(get)]
+    pub mode: String,
+    #[pyo3(get)]
+    pub id: String,
+    /// The size of blobs in bytes, `None` for directories and submodules.
+    #[pyo3(get)]
+    pub size: Option<u64>,
+}
+
+fn entry(
+    repository: &gix::Repository,
+    path: String,
+    mode: gix::objs::tree::EntryMode,
+    id: &gix::oid,
+) -> PyResult<TreeEntry> {
+    let size = if mode.is_blob_or_symlink() {
+        Some(repository.find_header(id).into_py_result()?.size())
+    } else {
+        None
+    };
+    Ok(TreeEntry {
+        path,
+        // Padded like `git ls-tree` does for directories.
+        mode: format!("{:0>6}", mode.as_bytes(&mut [0; 6]).to_string()),
+        id: id.to_string(),
+        size,
+    })
+}
+
+fn list(
+    repository: &gix::Repository,
+    tree_id: gix::ObjectId,
+    prefix: &str,
+    recursive: bool,
+    out: &mut Vec<TreeEntry>,
+) -> PyResult<()> {
+    let tree = repository.find_object(tree_id).into_py_result()?;
+    for child in gix::objs::TreeRef::from_bytes(&tree.data)
+        .into_py_result()?
+        .entries
+    {
+        let path = format!("{prefix}{}", child.filename);
+        if recursive && child.mode.is_tree() {
+            list(
+                repository,
+                child.oid.to_owned(),
+                &format!("{path}/"),
+                recursive,
+                out,
+            )?;
+        } else {
+            out.push(entry(repository, path, child.mode, child.oid)?);
+        }
+    }
+    Ok(())
+}
+
+/// List the entries of a tree in git's order.
+///
+/// Like `git ls-tree` a `path` naming a directory lists its contents and one
+/// naming a file lists only that file, when `recursive` the contents of all
+/// subdirectories are listed instead of the directories themselves.
+pub(crate) fn ls_tree(
+    repository: &gix::Repository,
+    tree_id: gix::ObjectId,
+    recursive: bool,
+    path: Option<&str>,
+) -> PyResult<Vec<TreeEntry>> {
+    let mut out = Vec::new();
+    let path = path
+        .map(|path| path.trim_matches('/'))
+        .filter(|path| !path.is_empty());
+    let Some(path) = path else {
+        list(repository, tree_id, "", recursive, &mut out)?;
+        return Ok(out);
+    };
+
+    let tree = repository.find_tree(tree_id).into_py_result()?;
+    match tree.lookup_entry_by_path(path).into_py_result()? {
+        Some(found) if found.mode().is_tree() => {
+            list(
+                repository,
+                found.oid().to_owned(),
+                &format!("{path}/"),
+                recursive,
+                &mut out,
+            )?;
+        }
+        Some(found) => out.push(entry(
+            repository,
+            path.to_string(),
+            found.mode(),
+            found.oid(),
+        )?),
+        None => {}
+    }
+    Ok(out)
+}
+
+/// The content of the file at `path` in a tree, like `git show <rev>:<path>`
+/// (for symlinks that's the link target).
+pub(crate) fn show(
+    repository: &gix::Repository,
+    tree_id: gix::ObjectId,
+    path: &str,
+) -> PyResult<Vec<u8>> {
+    let tree = repository.find_tree(tree_id).into_py_result()?;
+    let found = tree
+        .lookup_entry_by_path(path.trim_matches('/'))
+        .into_py_result()?
+        .ok_or_else(|| {
+            pyo3::exceptions::PyFileNotFoundError::new_err(format!("{path} doesn't exist"))
+        })?;
+    if !found.mode().is_blob_or_symlink() {
+        return Err(pyo3::exceptions::PyIsADirectoryError::new_err(format!(
+            "{path} isn't a file"
+        )));
+    }
+    Ok(repository
+        .find_object(found.oid())
+        .into_py_result()?
+        .detach()
+        .data)
+}
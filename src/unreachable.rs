@@ -0,0 +1,146 @@
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::IntoPyResult;
+
+/// The objects of a repository that no ref reaches anymore, e.g. left behind
+/// by force pushes or expired reflog entries (similar to
+/// `git fsck --unreachable`).
+#[pyclass]
+pub struct UnreachableReport {
+    /// The number of unreachable objects by kind (`commit`, `tree`, `blob`,
+    /// and `tag`).
+    #[pyo3(get)]
+    pub counts: HashMap<String, usize>,
+    /// The uncompressed size in bytes of unreachable objects by kind.
+    #[pyo3(get)]
+    pub sizes: HashMap<String, u64>,
+    /// The IDs of the unreachable commits.
+    #[pyo3(get)]
+    pub commits: Vec<String>,
+}
+
+#[pymethods]
+impl UnreachableReport {
+    #[getter]
+    fn total_count(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    #[getter]
+    fn total_size(&self) -> u64 {
+        self.sizes.values().sum()
+    }
+}
+
+/// The IDs the reachability marking starts from: the targets of all refs and
+/// HEAD, optionally everything their reflogs mention, and the index.
+fn roots(repository: &gix::Repository, reflogs: bool) -> PyResult<Vec<gix::ObjectId>> {
+    let platform = repository.references().into_py_result()?;
+    let mut references = platform
+        .all()
+        .into_py_result()?
+        .map(|reference| reference.map_err(|e| PyOSError::new_err(e.to_string())))
+        .collect::<PyResult<Vec<_>>>()?;
+    if let Ok(Some(head)) = repository.try_find_reference("HEAD") {
+        references.push(head);
+    }
+
+    let mut roots = Vec::new();
+    for mut reference in references {
+        if reflogs {
+            let mut log = reference.log_iter();
+            if let Some(lines) = log.all().into_py_result()? {
+                for line in lines {
+                    let line = line.into_py_result()?;
+                    roots.extend([line.previous_oid(), line.new_oid()]);
+                }
+            }
+        }
+        // Symbolic refs to unborn branches don't point anywhere.
+        if let Ok(id) = reference.peel_to_id_in_place() {
+            roots.push(id.detach());
+        }
+    }
+
+    let index = repository.index_or_empty().into_py_result()?;
+    roots.extend(index.entries().iter().map(|entry| entry.id));
+    Ok(roots)
+}
+
+/// Mark all objects reachable from `roots`.
+fn mark(
+    repository: &gix::Repository,
+    roots: Vec<gix::ObjectId>,
+) -> PyResult<HashSet<gix::ObjectId>> {
+    let mut reachable = HashSet::new();
+    let mut pending = roots;
+    while let Some(id) = pending.pop() {
+        if id.is_null() || !reachable.insert(id) {
+            continue;
+        }
+        // Objects missing from a shallow or partial clone have nothing to mark.
+        let Ok(object) = repository.find_object(id) else {
+            continue;
+        };
+        match object.kind {
+            gix::object::Kind::Commit => {
+                let commit = gix::objs::CommitRef::from_bytes(&object.data).into_py_result()?;
+                pending.push(commit.tree());
+                pending.extend(commit.parents());
+            }
+            gix::object::Kind::Tree => {
+                let tree = gix::objs::TreeRef::from_bytes(&object.data).into_py_result()?;
+                pending.extend(
+                    tree.entries
+                        .iter()
+                        // Submodule commits live in another repository.
+                        .filter(|entry| !entry.mode.is_commit())
+                        .map(|entry| entry.oid.to_owned()),
+                );
+            }
+            gix::object::Kind::Tag => {
+                let tag = gix::objs::TagRef::from_bytes(&object.data).into_py_result()?;
+                pending.push(tag.target());
+            }
+            gix::object::Kind::Blob => {}
+        }
+    }
+    Ok(reachable)
+}
+
+pub(crate) fn unreachable_report(
+    repository: &gix::Repository,
+    reflogs: bool,
+) -> PyResult<UnreachableReport> {
+    let reachable = mark(repository, roots(repository, reflogs)?)?;
+
+    let kinds = ["commit", "tree", "blob", "tag"];
+    let mut counts = HashMap::from(kinds.map(|kind| (kind.to_string(), 0)));
+    let mut sizes = HashMap::from(kinds.map(|kind| (kind.to_string(), 0)));
+    let mut commits = Vec::new();
+    let mut seen = HashSet::new();
+    for id in repository.objects.iter().into_py_result()? {
+        let id = id.into_py_result()?;
+        // The same object can be stored in several packs or loose as well.
+        if reachable.contains(&id) || !seen.insert(id) {
+            continue;
+        }
+        let header = repository.find_header(id).into_py_result()?;
+        let kind = header.kind().to_string();
+        *counts.entry(kind.clone()).or_default() += 1;
+        *sizes.entry(kind).or_default() += header.size();
+        if header.kind() == gix::object::Kind::Commit {
+            commits.push(id.to_string());
+        }
+    }
+    commits.sort();
+
+    Ok(UnreachableReport {
+        counts,
+        sizes,
+        commits,
+    })
+}
@@ -0,0 +1,367 @@
+use pyo3::prelude::*;
+use pyo3::types::PyBool;
+
+use std::borrow::Cow;
+
+use crate::{Commit, IntoPyResult, Repository, renames, word_diff};
+
+/// A single changed file passed to `on_file` of a delta visitor.
+#[pyclass]
+pub struct ChangeInfo {
+    /// One of `added`, `deleted`, `modified`, `renamed`, or `copied`.
+    #[pyo3(get)]
+    pub kind: &'static str,
+    #[pyo3(get)]
+    pub path: String,
+    /// The path before a rename or copy, otherwise the same as `path`.
+    #[pyo3(get)]
+    pub previous_path: String,
+    /// The octal file mode (e.g. `100644`), `None` for deletions.
+    #[pyo3(get)]
+    pub mode: Option<String>,
+    /// The octal file mode before the change, `None` for additions.
+    #[pyo3(get)]
+    pub previous_mode: Option<String>,
+    #[pyo3(get)]
+    pub id: String,
+    #[pyo3(get)]
+    pub previous_id: String,
+    /// How similar in percent a renamed or copied file is to the original,
+    /// the `similarity index` of `git diff -M`. `None` for other changes.
+    #[pyo3(get)]
+    pub similarity: Option<u8>,
+}
+
+/// A single hunk passed to `on_hunk` of a delta visitor.
+#[pyclass]
+pub struct Hunk {
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub old_start: u32,
+    #[pyo3(get)]
+    pub old_lines: u32,
+    #[pyo3(get)]
+    pub new_start: u32,
+    #[pyo3(get)]
+    pub new_lines: u32,
+    pub(crate) content: Vec<u8>,
+    /// `Line.moved` of every line, empty if moves weren't looked for.
+    pub(crate) moved: Vec<Option<u32>>,
+}
+
+/// A single line of a hunk.
+#[pyclass(frozen)]
+pub struct Line {
+    /// `' '` for context, `'-'` for removed, and `'+'` for added lines.
+    #[pyo3(get)]
+    pub origin: char,
+    /// The line number in the old file, `None` for added lines.
+    #[pyo3(get)]
+    pub old_lineno: Option<u32>,
+    /// The line number in the new file, `None` for removed lines.
+    #[pyo3(get)]
+    pub new_lineno: Option<u32>,
+    /// The `(start, end)` byte ranges of `content` that changed, for removed
+    /// and added lines paired up with one another: the first removed line of
+    /// a change with its first added line and so on. Empty for other lines,
+    /// and for pairs without anything in common.
+    #[pyo3(get)]
+    pub changed_ranges: Vec<(usize, usize)>,
+    /// For removed and added lines moved verbatim from or to elsewhere in
+    /// the diff (like git's `--color-moved`), the number of the block of
+    /// moved lines they belong to, counting from 0, so that adjacent blocks
+    /// can be told apart. `None` for other lines, for blocks of fewer than 20
+    /// letters and digits, and for the hunks of delta visitors.
+    #[pyo3(get)]
+    pub moved: Option<u32>,
+    pub(crate) content: Vec<u8>,
+}
+
+#[pymethods]
+impl Line {
+    /// The line including its terminator, if it has one.
+    #[getter]
+    fn content(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.content)
+    }
+}
+
+#[pymethods]
+impl Hunk {
+    /// The hunk in unified diff format including its `@@` header.
+    #[getter]
+    fn content(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.content)
+    }
+
+    /// The lines of the hunk with their line numbers.
+    #[getter]
+    pub(crate) fn lines(&self) -> Vec<Line> {
+        let (mut old_lineno, mut new_lineno) = (self.old_start, self.new_start);
+        let mut lines = Vec::<Line>::new();
+        for line in self.content.split_inclusive(|&byte| byte == b'\n').skip(1) {
+            let Some((&origin, content)) = line.split_first() else {
+                continue;
+            };
+            let (old, new) = match origin {
+                b' ' => (Some(old_lineno), Some(new_lineno)),
+                b'-' => (Some(old_lineno), None),
+                b'+' => (None, Some(new_lineno)),
+                // `\ No newline at end of file` belongs to the previous line.
+                _ => {
+                    if let Some(previous) = lines.last_mut() {
+                        previous.content.pop();
+                    }
+                    continue;
+                }
+            };
+            old_lineno += u32::from(old.is_some());
+            new_lineno += u32::from(new.is_some());
+            lines.push(Line {
+                origin: char::from(origin),
+                old_lineno: old,
+                new_lineno: new,
+                changed_ranges: Vec::new(),
+                moved: self.moved.get(lines.len()).copied().flatten(),
+                content: content.to_vec(),
+            });
+        }
+
+        let mut index = 0;
+        while index < lines.len() {
+            let removed = lines[index..]
+                .iter()
+                .take_while(|line| line.origin == '-')
+                .count();
+            let added = lines[index + removed..]
+                .iter()
+                .take_while(|line| line.origin == '+')
+                .count();
+            for offset in 0..removed.min(added) {
+                let (old, new) = (index + offset, index + removed + offset);
+                (lines[old].changed_ranges, lines[new].changed_ranges) =
+                    word_diff::intraline(&lines[old].content, &lines[new].content);
+            }
+            index += (removed + added).max(1);
+        }
+        lines
+    }
+
+    /// The changes of the hunk by words, like `git diff --word-diff`.
+    #[getter]
+    fn words(&self) -> Vec<word_diff::Word> {
+        let lines = self
+            .content
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(&[][..], |end| &self.content[end + 1..]);
+        word_diff::words(lines)
+    }
+}
+
+/// Parse one side of a hunk header range (e.g. `12,3` or `12`).
+pub(crate) fn parse_range(range: &str) -> Option<(u32, u32)> {
+    match range.split_once(',') {
+        Some((start, lines)) => Some((start.parse().ok()?, lines.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Split rendered unified diff hunks back into individual hunks.
+pub(crate) fn split_hunks(path: &str, hunks: &[u8]) -> Vec<Hunk> {
+    let mut out: Vec<Hunk> = Vec::new();
+    for line in hunks.split_inclusive(|&byte| byte == b'\n') {
+        // Hunk lines always start with a prefix so only headers start with
+        // `@@`.
+        if line.starts_with(b"@@ -") {
+            let header = String::from_utf8_lossy(line);
+            let mut ranges = header.split_whitespace().skip(1);
+            let old = ranges
+                .next()
+                .and_then(|range| parse_range(range.strip_prefix('-')?));
+            let new = ranges
+                .next()
+                .and_then(|range| parse_range(range.strip_prefix('+')?));
+            let ((old_start, old_lines), (new_start, new_lines)) =
+                (old.unwrap_or_default(), new.unwrap_or_default());
+            out.push(Hunk {
+                path: path.to_string(),
+                old_start,
+                old_lines,
+                new_start,
+                new_lines,
+                content: Vec::new(),
+                moved: Vec::new(),
+            });
+        }
+        if let Some(hunk) = out.last_mut() {
+            hunk.content.extend_from_slice(line);
+        }
+    }
+    out
+}
+
+/// The entry (mode and ID) on one side of a change.
+pub(crate) type Entry = (gix::objs::tree::EntryMode, gix::ObjectId);
+
+/// Describe a change of a file along with the entries before and after it,
+/// `None` for directories as their contents change separately.
+pub(crate) fn change_info(
+    objects: &gix::OdbHandle,
+    delta: &gix::object::tree::diff::ChangeDetached,
+    null: gix::ObjectId,
+) -> Option<(ChangeInfo, Option<Entry>, Option<Entry>)> {
+    use gix::object::tree::diff::ChangeDetached;
+
+    let mut similarity = None;
+    let (kind, previous_path, path, previous_entry, entry) = match delta {
+        ChangeDetached::Addition {
+            location,
+            entry_mode,
+            id,
+            ..
+        } => ("added", location, location, None, Some((*entry_mode, *id))),
+        ChangeDetached::Deletion {
+            location,
+            entry_mode,
+            id,
+            ..
+        } => (
+            "deleted",
+            location,
+            location,
+            Some((*entry_mode, *id)),
+            None,
+        ),
+        ChangeDetached::Modification {
+            location,
+            previous_entry_mode,
+            entry_mode,
+            previous_id,
+            id,
+        } => (
+            "modified",
+            location,
+            location,
+            Some((*previous_entry_mode, *previous_id)),
+            Some((*entry_mode, *id)),
+        ),
+        ChangeDetached::Rewrite {
+            source_location,
+            location,
+            source_entry_mode,
+            entry_mode,
+            source_id,
+            id,
+            copy,
+            ..
+        } => {
+            similarity = renames::similarity(objects, source_id, id);
+            (
+                if *copy { "copied" } else { "renamed" },
+                source_location,
+                location,
+                Some((*source_entry_mode, *source_id)),
+                Some((*entry_mode, *id)),
+            )
+        }
+    };
+    // Directories aren't files, their contents are visited separately.
+    if previous_entry
+        .iter()
+        .chain(entry.iter())
+        .any(|(mode, _)| mode.is_tree())
+    {
+        return None;
+    }
+
+    let (backing, previous_backing) = (&mut [0; 6], &mut [0; 6]);
+    let info = ChangeInfo {
+        kind,
+        path: path.to_string(),
+        previous_path: previous_path.to_string(),
+        mode: entry.map(|(mode, _)| mode.as_bytes(backing).to_string()),
+        previous_mode: previous_entry.map(|(mode, _)| mode.as_bytes(previous_backing).to_string()),
+        id: entry.map_or(null, |(_, id)| id).to_string(),
+        previous_id: previous_entry.map_or(null, |(_, id)| id).to_string(),
+        similarity,
+    };
+    Some((info, previous_entry, entry))
+}
+
+/// Whether a visitor callback asked to stop by returning `False`.
+fn stop(result: &Bound<'_, PyAny>) -> PyResult<bool> {
+    Ok(result.is_instance_of::<PyBool>() && !result.extract::<bool>()?)
+}
+
+/// Visit the changes of `commit` against its first parent file by file.
+///
+/// Returns `false` if the visitor stopped the visit early.
+pub(crate) fn visit_deltas(
+    repository: &gix::Repository,
+    commit: &Commit,
+    visitor: &Bound<'_, PyAny>,
+    algorithm: gix::diff::blob::Algorithm,
+) -> PyResult<bool> {
+    let py = visitor.py();
+    let visit_hunks = visitor.hasattr("on_hunk")?;
+
+    let commit = commit.inner.clone().attach(repository).into_commit();
+    let tree = commit.tree().into_py_result()?;
+    let parent_tree = match commit.parent_ids().next() {
+        Some(parent_id) => parent_id
+            .object()
+            .into_py_result()?
+            .peel_to_tree()
+            .into_py_result()?,
+        None => repository.empty_tree(),
+    };
+    let deltas = repository
+        .diff_tree_to_tree(Some(&parent_tree), Some(&tree), None)
+        .into_py_result()?;
+
+    let mut resource_cache = repository
+        .diff_resource_cache(
+            gix::diff::blob::pipeline::Mode::ToGitUnlessBinaryToTextIsPresent,
+            Default::default(),
+        )
+        .into_py_result()?;
+    let null = gix::ObjectId::null(repository.object_hash());
+    for delta in deltas {
+        let Some((info, previous_entry, entry)) = change_info(&repository.objects, &delta, null)
+        else {
+            continue;
+        };
+        let (previous_path, path) = (info.previous_path.clone(), info.path.clone());
+        if stop(&visitor.call_method1("on_file", (Py::new(py, info)?,))?)? {
+            return Ok(false);
+        }
+
+        let blobs = previous_entry
+            .iter()
+            .chain(entry.iter())
+            .all(|(mode, _)| mode.is_blob_or_symlink());
+        if !visit_hunks || !blobs {
+            continue;
+        }
+        let hunks = Repository::unified_diff_files(
+            &mut resource_cache,
+            &repository.objects,
+            algorithm,
+            &Default::default(),
+            &previous_entry.map_or(null, |(_, id)| id),
+            previous_path.as_str().into(),
+            &entry.map_or(null, |(_, id)| id),
+            path.as_str().into(),
+        )?
+        .unwrap_or_default();
+        for hunk in split_hunks(&path, &hunks) {
+            if stop(&visitor.call_method1("on_hunk", (Py::new(py, hunk)?,))?)? {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
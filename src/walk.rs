@@ -0,0 +1,77 @@
+use pyo3::prelude::*;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::IntoPyResult;
+
+/// The options of `Repository.commits`, see there for what each does.
+#[derive(Default)]
+pub(crate) struct WalkOptions<'a> {
+    pub(crate) commit_start_cutoff: Option<&'a str>,
+    pub(crate) commit_end_cutoff: Option<&'a str>,
+    pub(crate) cutoff_start_timestamp: Option<&'a str>,
+    pub(crate) cutoff_end_timestamp: Option<&'a str>,
+    pub(crate) timezone: Option<&'a str>,
+    pub(crate) stable_order: bool,
+    pub(crate) simplify_by_decoration: bool,
+    pub(crate) paths: Option<Vec<PathBuf>>,
+    pub(crate) rewrite_parents: bool,
+    pub(crate) boundary: bool,
+    pub(crate) annotate_calendar: Option<&'a str>,
+    pub(crate) use_mailmap: bool,
+    pub(crate) eager: bool,
+}
+
+/// Resolve revision specs to commit IDs, splitting them into included and
+/// excluded tips. Specs prefixed with `^` are excluded like in `git rev-list`.
+pub(crate) fn resolve_tips(
+    repository: &gix::Repository,
+    specs: &[String],
+) -> PyResult<(Vec<gix::ObjectId>, Vec<gix::ObjectId>)> {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    for spec in specs {
+        let (tips, spec) = match spec.strip_prefix('^') {
+            Some(spec) => (&mut exclude, spec),
+            None => (&mut include, spec.as_str()),
+        };
+        let id = repository
+            .rev_parse_single(spec)
+            .into_py_result()?
+            .object()
+            .into_py_result()?
+            .peel_to_commit()
+            .into_py_result()?
+            .id;
+        tips.push(id);
+    }
+    Ok((include, exclude))
+}
+
+/// Walk the commits reachable from any of `include` but from none of
+/// `exclude`.
+///
+/// This is the building block for all walks, it stays lazy so callers can
+/// stop early.
+pub(crate) fn walk<'repo>(
+    repository: &'repo gix::Repository,
+    include: impl IntoIterator<Item = gix::ObjectId>,
+    exclude: impl IntoIterator<Item = gix::ObjectId>,
+    sorting: gix::revision::walk::Sorting,
+) -> PyResult<impl Iterator<Item = gix::revision::walk::Info<'repo>> + 'repo> {
+    let exclude = exclude.into_iter().collect::<Vec<_>>();
+    let mut hidden = HashSet::new();
+    if !exclude.is_empty() {
+        for info in repository.rev_walk(exclude).all().into_py_result()? {
+            hidden.insert(info.into_py_result()?.id);
+        }
+    }
+
+    Ok(repository
+        .rev_walk(include)
+        .sorting(sorting)
+        .selected(move |id| !hidden.contains(id))
+        .into_py_result()?
+        .flatten())
+}
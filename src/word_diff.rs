@@ -0,0 +1,265 @@
+use gix::diff::blob::intern::{InternedInput, Interner};
+use pyo3::prelude::*;
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::unified_diff::is_space;
+
+/// A piece of a word diff, like a line of `git diff --word-diff=porcelain`.
+#[pyclass(frozen)]
+pub struct Word {
+    /// `' '` for unchanged, `'-'` for removed, and `'+'` for added text, or
+    /// `'~'` for a line break.
+    #[pyo3(get)]
+    pub origin: char,
+    content: Vec<u8>,
+}
+
+#[pymethods]
+impl Word {
+    /// The text, `\n` for a line break.
+    #[getter]
+    fn content(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.content)
+    }
+}
+
+/// The removed and added lines of a change not yet diffed by words.
+#[derive(Default)]
+struct Pending {
+    minus: Vec<u8>,
+    plus: Vec<u8>,
+}
+
+/// The words of `text`, which like git's default are the runs of
+/// non-whitespace.
+fn split_words(text: &[u8]) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut index = 0;
+    while index < text.len() {
+        if is_space(text[index]) {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < text.len() && !is_space(text[index]) {
+            index += 1;
+        }
+        words.push(start..index);
+    }
+    words
+}
+
+/// The text the changed `words` span, an empty range after the preceding
+/// word if there are none.
+fn span(words: &[Range<usize>], changed: Range<u32>) -> Range<usize> {
+    let (start, end) = (changed.start as usize, changed.end as usize);
+    if start == end {
+        let at = start
+            .checked_sub(1)
+            .map_or(0, |previous| words[previous].end);
+        at..at
+    } else {
+        words[start].start..words[end - 1].end
+    }
+}
+
+/// Push `text` as words of `origin`, split at its line breaks.
+fn push(out: &mut Vec<Word>, origin: u8, text: &[u8]) {
+    for piece in text.split_inclusive(|&byte| byte == b'\n') {
+        let content = piece.strip_suffix(b"\n").unwrap_or(piece);
+        if !content.is_empty() {
+            out.push(Word {
+                origin: char::from(origin),
+                content: content.to_vec(),
+            });
+        }
+        if content.len() != piece.len() {
+            out.push(Word {
+                origin: '~',
+                content: b"\n".to_vec(),
+            });
+        }
+    }
+}
+
+impl Pending {
+    /// Diff the removed against the added lines by words, following
+    /// `diff_words_show` of git: unchanged text is taken from the added lines.
+    fn flush(&mut self, out: &mut Vec<Word>) {
+        let (minus, plus) = (
+            std::mem::take(&mut self.minus),
+            std::mem::take(&mut self.plus),
+        );
+        if plus.is_empty() {
+            push(out, b'-', &minus);
+            return;
+        }
+
+        let (minus_words, plus_words) = (split_words(&minus), split_words(&plus));
+        let mut input = InternedInput {
+            before: Vec::new(),
+            after: Vec::new(),
+            interner: Interner::new(minus_words.len() + plus_words.len()),
+        };
+        input.update_before(minus_words.iter().map(|word| &minus[word.clone()]));
+        input.update_after(plus_words.iter().map(|word| &plus[word.clone()]));
+
+        let mut changes = Vec::new();
+        gix::diff::blob::diff(
+            gix::diff::blob::Algorithm::Myers,
+            &input,
+            |before: Range<u32>, after: Range<u32>| changes.push((before, after)),
+        );
+
+        let mut current = 0;
+        for (before, after) in changes {
+            let (removed, added) = (span(&minus_words, before), span(&plus_words, after));
+            push(out, b' ', &plus[current..added.start]);
+            push(out, b'-', &minus[removed]);
+            push(out, b'+', &plus[added.clone()]);
+            current = added.end;
+        }
+        push(out, b' ', &plus[current..]);
+    }
+}
+
+/// The words of a hunk's lines (without its `@@` header).
+pub(crate) fn words(lines: &[u8]) -> Vec<Word> {
+    let mut out = Vec::new();
+    let mut pending = Pending::default();
+    for line in lines.split_inclusive(|&byte| byte == b'\n') {
+        let Some((&origin, content)) = line.split_first() else {
+            continue;
+        };
+        match origin {
+            b'-' => pending.minus.extend_from_slice(content),
+            b'+' => pending.plus.extend_from_slice(content),
+            // Like git, drop `\ No newline at end of file` as lines are
+            // shown with a line break anyway.
+            b'\\' => {}
+            _ => {
+                pending.flush(&mut out);
+                push(&mut out, b' ', content);
+            }
+        }
+    }
+    pending.flush(&mut out);
+    out
+}
+
+/// Render the words of a hunk's lines like `git diff --word-diff=plain`.
+fn render(out: &mut Vec<u8>, lines: &[u8]) {
+    for word in words(lines) {
+        let (prefix, suffix): (&[u8], &[u8]) = match word.origin {
+            '-' => (b"[-", b"-]"),
+            '+' => (b"{+", b"+}"),
+            _ => (b"", b""),
+        };
+        out.extend_from_slice(prefix);
+        out.extend_from_slice(&word.content);
+        out.extend_from_slice(suffix);
+    }
+}
+
+/// Render the hunks of `patch` like `git diff --word-diff=plain`, i.e. with
+/// removed words as `[-...-]` and added ones as `{+...+}` inline.
+pub(crate) fn word_diff(patch: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut hunk: Option<Vec<u8>> = None;
+    for line in patch.split_inclusive(|&byte| byte == b'\n') {
+        // Hunk lines always start with a prefix, so neither of these can be
+        // one.
+        if line.starts_with(b"diff --git ") || line.starts_with(b"@@ ") {
+            render(&mut out, &hunk.take().unwrap_or_default());
+            out.extend_from_slice(line);
+            if line.starts_with(b"@@ ") {
+                hunk = Some(Vec::new());
+            }
+        } else if let Some(hunk) = &mut hunk {
+            hunk.extend_from_slice(line);
+        } else {
+            out.extend_from_slice(line);
+        }
+    }
+    render(&mut out, &hunk.unwrap_or_default());
+    out
+}
+
+/// The tokens of a line for intraline changes: runs of word characters
+/// (counting non-ASCII bytes so UTF-8 characters stay whole), and every other
+/// byte on its own.
+fn tokens(line: &[u8]) -> Vec<Range<usize>> {
+    let is_word = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_' || !byte.is_ascii();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < line.len() {
+        let start = index;
+        index += 1;
+        if is_word(line[start]) {
+            while index < line.len() && is_word(line[index]) {
+                index += 1;
+            }
+        }
+        tokens.push(start..index);
+    }
+    tokens
+}
+
+/// Byte ranges as `(start, end)` pairs.
+type Ranges = Vec<(usize, usize)>;
+
+/// The byte ranges of `old` and `new` (without their terminators) that
+/// differ between the two lines, none if they have nothing in common.
+pub(crate) fn intraline(old: &[u8], new: &[u8]) -> (Ranges, Ranges) {
+    let content = |line: &[u8]| -> usize {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        line.strip_suffix(b"\r").unwrap_or(line).len()
+    };
+    let (old, new) = (&old[..content(old)], &new[..content(new)]);
+    let (old_tokens, new_tokens) = (tokens(old), tokens(new));
+    let mut input = InternedInput {
+        before: Vec::new(),
+        after: Vec::new(),
+        interner: Interner::new(old_tokens.len() + new_tokens.len()),
+    };
+    input.update_before(old_tokens.iter().map(|token| &old[token.clone()]));
+    input.update_after(new_tokens.iter().map(|token| &new[token.clone()]));
+
+    let (mut old_ranges, mut new_ranges) = (Vec::new(), Vec::new());
+    let is_text = |token: &Range<usize>| !is_space(old[token.start]);
+    let mut unchanged = old_tokens.iter().filter(|token| is_text(token)).count();
+    // Changed tokens next to each other make up one range.
+    let add = |ranges: &mut Ranges, tokens: &[Range<usize>], changed: Range<u32>| {
+        if changed.is_empty() {
+            return;
+        }
+        let (start, end) = (
+            tokens[changed.start as usize].start,
+            tokens[changed.end as usize - 1].end,
+        );
+        match ranges.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    };
+    gix::diff::blob::diff(
+        gix::diff::blob::Algorithm::Myers,
+        &input,
+        |before: Range<u32>, after: Range<u32>| {
+            unchanged -= old_tokens[before.start as usize..before.end as usize]
+                .iter()
+                .filter(|token| is_text(token))
+                .count();
+            add(&mut old_ranges, &old_tokens, before);
+            add(&mut new_ranges, &new_tokens, after);
+        },
+    );
+    // Like diff-highlight, lines without anything but whitespace in common
+    // are different lines rather than changed ones.
+    if unchanged == 0 {
+        return Default::default();
+    }
+    (old_ranges, new_ranges)
+}
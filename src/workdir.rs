@@ -0,0 +1,263 @@
+//! The working tree and the index as tree objects, so that uncommitted and
+//! staged changes diff like the changes of a commit.
+
+use gix::index::entry::{Flags, Mode, Stat};
+use gix::objs::tree::EntryKind;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use std::io::Read;
+use std::path::Path;
+
+use crate::IntoPyResult;
+
+/// Write the files of the working tree to `repository` and return the ID of
+/// a tree of them. `repository` should keep the objects it writes in memory.
+///
+/// Like `git diff <rev>` sees it, these are the files of the index as they
+/// are in the working tree, without those that are missing. Files whose
+/// stat information in the index still matches aren't read again. With
+/// `include_untracked` the files that are neither in the index nor ignored
+/// are added as well.
+pub(crate) fn tree(
+    repository: &gix::Repository,
+    include_untracked: bool,
+) -> PyResult<gix::ObjectId> {
+    let Some(root) = repository.workdir() else {
+        return Err(PyValueError::new_err(
+            "bare repositories have no working tree",
+        ));
+    };
+    let (mut pipeline, index) = repository.filter_pipeline(None).into_py_result()?;
+    let options = repository.stat_options().into_py_result()?;
+    let capabilities = repository.filesystem_options().into_py_result()?;
+    let mut editor = gix::objs::tree::Editor::new(
+        gix::objs::Tree::empty(),
+        &repository.objects,
+        repository.object_hash(),
+    );
+
+    let mut previous = None;
+    for entry in index.entries() {
+        let path = entry.path(&index);
+        // Conflicts have an entry for each side of the same path.
+        if previous.replace(path) == Some(path) {
+            continue;
+        }
+        let kind = kind(entry.mode);
+        // Neither of these are in the working tree to look at.
+        if entry.mode == Mode::DIR || entry.flags.contains(Flags::SKIP_WORKTREE) {
+            editor
+                .upsert(components(path), kind, entry.id)
+                .into_py_result()?;
+            continue;
+        }
+        let location = root.join(gix::path::from_bstr(path));
+        let Ok(metadata) = gix::index::fs::Metadata::from_path_no_follow(&location) else {
+            continue;
+        };
+        let (kind, id) = if entry.mode == Mode::COMMIT && metadata.is_dir() {
+            // The commit checked out in the submodule, if it has been cloned.
+            let id = gix::open(&location)
+                .ok()
+                .and_then(|submodule| submodule.head_id().ok().map(|id| id.detach()))
+                .unwrap_or(entry.id);
+            (kind, id)
+        } else if entry.mode != Mode::COMMIT
+            && (entry.flags.contains(Flags::ASSUME_VALID)
+                || !entry.stat.is_racy(index.timestamp(), options)
+                    && Stat::from_fs(&metadata)
+                        .is_ok_and(|stat| entry.stat.matches(&stat, options))
+                    // The stat information doesn't include the mode.
+                    && (!capabilities.symlink
+                        || metadata.is_symlink() == (entry.mode == Mode::SYMLINK))
+                    && (!capabilities.executable_bit
+                        || metadata.is_executable() == (entry.mode == Mode::FILE_EXECUTABLE)))
+        {
+            (kind, entry.id)
+        } else {
+            let Some((file_kind, id)) = blob(
+                repository,
+                &mut pipeline,
+                &index,
+                capabilities,
+                &location,
+                path,
+                &metadata,
+            )?
+            else {
+                continue;
+            };
+            (worktree_kind(kind, file_kind, capabilities), id)
+        };
+        editor.upsert(components(path), kind, id).into_py_result()?;
+    }
+
+    if include_untracked {
+        let mut excludes = repository
+            .excludes(&index, None, Default::default())
+            .into_py_result()?;
+        let mut directories = vec![root.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            for child in std::fs::read_dir(&directory).into_py_result()? {
+                let child = child.into_py_result()?;
+                if child.file_name() == ".git" {
+                    continue;
+                }
+                let location = child.path();
+                let relative = location
+                    .strip_prefix(root)
+                    .expect("could not get path relative to the working tree");
+                let Ok(metadata) = gix::index::fs::Metadata::from_path_no_follow(&location) else {
+                    continue;
+                };
+                let mode = if metadata.is_dir() {
+                    Mode::DIR
+                } else {
+                    Mode::FILE
+                };
+                if excludes
+                    .at_path(relative, Some(mode))
+                    .into_py_result()?
+                    .is_excluded()
+                {
+                    continue;
+                }
+                if metadata.is_dir() {
+                    // Other repositories inside the working tree, like
+                    // submodules, have files of their own.
+                    if !location.join(".git").exists() {
+                        directories.push(location);
+                    }
+                    continue;
+                }
+                let path = gix::path::to_unix_separators_on_windows(gix::path::into_bstr(relative));
+                if index.entry_by_path(path.as_ref()).is_some() {
+                    continue;
+                }
+                if let Some((kind, id)) = blob(
+                    repository,
+                    &mut pipeline,
+                    &index,
+                    capabilities,
+                    &location,
+                    path.as_ref(),
+                    &metadata,
+                )? {
+                    editor
+                        .upsert(components(path.as_ref()), kind, id)
+                        .into_py_result()?;
+                }
+            }
+        }
+    }
+
+    editor
+        .write(|tree| repository.write_object(tree).map(gix::Id::detach))
+        .into_py_result()
+}
+
+/// Write the tree of the files staged in the index to `repository` and
+/// return its ID, like `git write-tree` but with conflicts as on our side
+/// (i.e. as in `HEAD` during a merge) and without files only added with
+/// `git add --intent-to-add`.
+pub(crate) fn index_tree(repository: &gix::Repository) -> PyResult<gix::ObjectId> {
+    let index = repository.index_or_empty().into_py_result()?;
+    let mut editor = gix::objs::tree::Editor::new(
+        gix::objs::Tree::empty(),
+        &repository.objects,
+        repository.object_hash(),
+    );
+    for entry in index.entries() {
+        if !matches!(entry.stage_raw(), 0 | 2) || entry.flags.contains(Flags::INTENT_TO_ADD) {
+            continue;
+        }
+        editor
+            .upsert(components(entry.path(&index)), kind(entry.mode), entry.id)
+            .into_py_result()?;
+    }
+    editor
+        .write(|tree| repository.write_object(tree).map(gix::Id::detach))
+        .into_py_result()
+}
+
+/// The kind of tree entry an index entry with `mode` is.
+pub(crate) fn kind(mode: Mode) -> EntryKind {
+    mode.to_tree_entry_mode()
+        .map_or(EntryKind::Blob, |mode| mode.kind())
+}
+
+/// The kind of a file of the index as `kind` that is a `file_kind` in the
+/// working tree. Without an executable bit or symlinks in the file system
+/// the index keeps track of them.
+pub(crate) fn worktree_kind(
+    kind: EntryKind,
+    file_kind: EntryKind,
+    capabilities: gix::fs::Capabilities,
+) -> EntryKind {
+    let files = [EntryKind::Blob, EntryKind::BlobExecutable];
+    match kind {
+        _ if !files.contains(&file_kind) => file_kind,
+        EntryKind::Link if !capabilities.symlink => kind,
+        _ if files.contains(&kind) && !capabilities.executable_bit => kind,
+        _ => file_kind,
+    }
+}
+
+/// The components of a path in the index.
+pub(crate) fn components(path: &gix::bstr::BStr) -> impl Iterator<Item = &[u8]> {
+    path.split(|&byte| byte == b'/')
+}
+
+/// Write the file or symlink at `location` as a blob the way it would be
+/// committed (see `read`), `None` for anything else.
+fn blob(
+    repository: &gix::Repository,
+    pipeline: &mut gix::filter::Pipeline<'_>,
+    index: &gix::index::State,
+    capabilities: gix::fs::Capabilities,
+    location: &Path,
+    path: &gix::bstr::BStr,
+    metadata: &gix::index::fs::Metadata,
+) -> PyResult<Option<(EntryKind, gix::ObjectId)>> {
+    let Some((kind, data)) = read(pipeline, index, capabilities, location, path, metadata)? else {
+        return Ok(None);
+    };
+    let id = repository.write_blob(data).into_py_result()?;
+    Ok(Some((kind, id.detach())))
+}
+
+/// Read the file or symlink at `location` the way it would be committed
+/// (i.e. with the filters of its attributes applied), `None` for anything
+/// else.
+pub(crate) fn read(
+    pipeline: &mut gix::filter::Pipeline<'_>,
+    index: &gix::index::State,
+    capabilities: gix::fs::Capabilities,
+    location: &Path,
+    path: &gix::bstr::BStr,
+    metadata: &gix::index::fs::Metadata,
+) -> PyResult<Option<(EntryKind, Vec<u8>)>> {
+    let (kind, data) = if metadata.is_symlink() && capabilities.symlink {
+        let target = std::fs::read_link(location).into_py_result()?;
+        let target = gix::path::to_unix_separators_on_windows(gix::path::into_bstr(target));
+        (EntryKind::Link, target.to_vec())
+    } else if metadata.is_file() {
+        let file = std::fs::File::open(location).into_py_result()?;
+        let mut data = Vec::new();
+        pipeline
+            .convert_to_git(file, &gix::path::from_bstr(path), index)
+            .into_py_result()?
+            .read_to_end(&mut data)
+            .into_py_result()?;
+        let kind = if metadata.is_executable() {
+            EntryKind::BlobExecutable
+        } else {
+            EntryKind::Blob
+        };
+        (kind, data)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some((kind, data)))
+}
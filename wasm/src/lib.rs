@@ -0,0 +1,104 @@
+//! The message, trailer, and text diff logic of `gitch-engine` for
+//! JavaScript, so a web UI can preview what gitch makes of a message or a
+//! change with the same code.
+//!
+//! Built for `wasm32-unknown-unknown` (e.g. with `wasm-pack build wasm`).
+//! The engine is used without its `repository` feature, so nothing here
+//! touches a repository or the filesystem.
+
+use gitch_engine::{funcname, message, unified_diff};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use std::collections::BTreeSet;
+
+/// A commit message split into its parts, as `parseMessage` returns it.
+#[derive(Serialize)]
+struct Message {
+    summary: String,
+    /// The message without summary and trailers, `null` if nothing is left.
+    body: Option<String>,
+    /// The trailers as `[token, value]` pairs in their original order
+    /// including repeated ones.
+    trailers: Vec<(String, String)>,
+}
+
+/// A rule of the `trailers` configuration, with the same defaults.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct TrailerRule {
+    mandatory: bool,
+    singular: bool,
+    values: BTreeSet<String>,
+}
+
+/// Split a commit message into `{summary, body, trailers}` exactly like
+/// gitch does.
+#[wasm_bindgen(js_name = parseMessage)]
+pub fn parse_message(message: &str) -> Result<JsValue, JsError> {
+    let message = message::parse(message);
+    let message = Message {
+        summary: message.summary,
+        body: message.body,
+        trailers: message.trailer_list,
+    };
+    Ok(serde_wasm_bindgen::to_value(&message)?)
+}
+
+/// Everything wrong with `trailers` (`[token, value]` pairs) by `rules`
+/// (`[token, {mandatory, singular, values}]` pairs, e.g.
+/// `Object.entries(configuration.trailers)`), in the order of the rules.
+#[wasm_bindgen(js_name = validateTrailers)]
+pub fn validate_trailers(trailers: JsValue, rules: JsValue) -> Result<Vec<String>, JsError> {
+    let trailers: Vec<(String, String)> = serde_wasm_bindgen::from_value(trailers)?;
+    let rules = serde_wasm_bindgen::from_value::<Vec<(String, TrailerRule)>>(rules)?
+        .into_iter()
+        .map(|(token, rule)| {
+            let rule = message::TrailerRule {
+                mandatory: rule.mandatory,
+                singular: rule.singular,
+                values: rule.values,
+            };
+            (token, rule)
+        })
+        .collect::<Vec<_>>();
+    Ok(message::validate_trailers(&trailers, &rules))
+}
+
+/// The unified diff of two texts as it would appear in a patch, empty if
+/// they are the same.
+///
+/// `algorithm` is one of git's `myers` (the default), `minimal`, or
+/// `histogram`. With the name of one of git's builtin diff drivers (e.g.
+/// `rust`) as `driver`, hunk headers name their function by its patterns.
+#[wasm_bindgen(js_name = diffText)]
+pub fn diff_text(
+    old: &[u8],
+    new: &[u8],
+    algorithm: Option<String>,
+    old_label: Option<String>,
+    new_label: Option<String>,
+    driver: Option<String>,
+) -> Result<Vec<u8>, JsError> {
+    let algorithm = match algorithm.as_deref().unwrap_or("myers") {
+        "myers" => unified_diff::Algorithm::Myers,
+        "minimal" => unified_diff::Algorithm::MyersMinimal,
+        "histogram" => unified_diff::Algorithm::Histogram,
+        other => return Err(JsError::new(&format!("unknown diff algorithm {other:?}"))),
+    };
+    let funcname = match driver.as_deref() {
+        Some(driver) => Some(
+            funcname::builtin(driver)
+                .ok_or_else(|| JsError::new(&format!("unknown diff driver {driver:?}")))?,
+        ),
+        None => None,
+    };
+    Ok(unified_diff::diff_text(
+        old,
+        new,
+        algorithm,
+        old_label.as_deref().unwrap_or("a"),
+        new_label.as_deref().unwrap_or("b"),
+        funcname,
+    ))
+}